@@ -1,387 +1,1066 @@
-use ::rand::{thread_rng, Rng};
+use std::io::Write;
+use std::path::Path;
+use std::sync::mpsc::{self, Sender};
+use std::time::Duration;
+
 use macroquad::prelude::*;
 use tracing::info;
-use tracing_subscriber;
-
-// Include the compute module from the parent project
-use life::compute::{MEM_SIZE, VM};
-
-// Memory-mapped I/O addresses (using the last bytes of address space)
-const MOVE_LEFT_ADDR: usize = MEM_SIZE - 4; // 252: Left movement strength
-const MOVE_RIGHT_ADDR: usize = MEM_SIZE - 3; // 253: Right movement strength  
-const MOVE_UP_ADDR: usize = MEM_SIZE - 2; // 254: Up movement strength
-const MOVE_DOWN_ADDR: usize = MEM_SIZE - 1; // 255: Down movement strength
-
-// Sensory input addresses (using addresses before movement commands)
-const FOOD_DISTANCE_X_ADDR: usize = MEM_SIZE - 6; // 250: X distance to nearest food (signed)
-const FOOD_DISTANCE_Y_ADDR: usize = MEM_SIZE - 5; // 251: Y distance to nearest food (signed)
-
-// Simulation constants
-const INITIAL_POPULATION: usize = 20;
-const MAX_ENERGY: f32 = 200.0;
-const ENERGY_DRAIN_PER_FRAME: f32 = 0.1;
-const MOVEMENT_ENERGY_COST: f32 = 0.2;
-const MOVEMENT_SPEED: f32 = 1.0;
-const EATING_RADIUS: f32 = 12.0;
-const FOOD_SPAWN_INTERVAL: f64 = 2.0;
-const MIN_FOOD_COUNT: usize = 10;
-const INITIAL_FOOD_COUNT: usize = 15;
-const FOOD_DISTRIBUTION_STD: f32 = 150.0;
-const MAP_BOUNDARY: f32 = 400.0;
+
+// Include the simulation modules from the parent project
+use life::autosave::{self, Autosaver, DEFAULT_AUTOSAVE_DIR, DEFAULT_AUTOSAVE_SLOTS};
+use life::bundle::PopulationBundle;
+use life::compute::{
+    CostTable, FLAG_CARRY, FLAG_NEGATIVE, FLAG_TRAP, FLAG_ZERO, LoopDetector, Thread,
+    TimerInterrupt, VM, grid_shape,
+};
+use life::display::{DISPLAY_SIZE, Display};
+use life::isa;
+use life::keyboard::{
+    DEFAULT_KEYBOARD_ADDR, JOY_DOWN, JOY_FIRE, JOY_LEFT, JOY_RIGHT, JOY_UP, Keyboard, KeyboardMode,
+};
+use life::memory_map::{MemoryMap, RegionKind};
+use life::migration::MigrationLink;
+use life::mmio::{OverLimit, RateLimit};
+use life::replay::{Playback, Replay};
+use life::rng::SimRng;
+use life::sandbox::Sandbox;
+use life::world::{
+    Chunking, Coarsening, Curriculum, DROPPED_FOOD_ENERGY, FOOD_DISTANCE_X_ADDR,
+    FOOD_DISTANCE_Y_ADDR, FOOD_GROWTH_THRESHOLD, Food, FoodController, FoodModel, Intervention,
+    Invasions, Lifeform, MOVE_DOWN_ADDR, MOVE_LEFT_ADDR, MOVE_RIGHT_ADDR, MOVE_UP_ADDR,
+    NUTRIENT_CELL_SIZE, NoiseKind, NutrientField, ReproductionMode, SELF_INSTRUCTIONS_ADDR,
+    SPECIES_HISTORY, STEP_BUDGET_ADDR, SensorNoise, Speciation, Species, SpeciesExtinction,
+    Subsystems, TELEMETRY_HISTORY, Tag, Telemetry, Viewport, World, WorldConfig, WorldSnapshot,
+    lifeform_memory_map, load_invaders,
+};
+
+// Rendering constants
 const LIFEFORM_SIZE: f32 = 8.0;
 
-// Sensory system constants
-const MAX_FOOD_DETECTION_RANGE: f32 = 100.0; // Maximum range for food detection
-const SENSORY_SCALE_FACTOR: f32 = 2.0; // Scale factor to convert world distance to memory value
+// Population bundle written by F5 and read back by F9
+const POPULATION_BUNDLE_PATH: &str = "population.cbor";
+
+// User tags
+const TAG_COLORS: [[f32; 3]; 6] = [
+    [1.0, 0.3, 0.3],
+    [1.0, 0.8, 0.2],
+    [0.3, 1.0, 0.4],
+    [0.3, 0.8, 1.0],
+    [0.7, 0.4, 1.0],
+    [1.0, 1.0, 1.0],
+];
+const LEADERBOARD_SIZE: usize = 8; // Oldest lifeforms listed in the HUD
+const TRACE_LINES_SHOWN: usize = 12; // Sampled trace entries listed in the VM inspector
+const SPECIES_PANELS: usize = 4; // Most populous species given a panel
+const EXTINCTION_ALERT_SECONDS: f64 = 6.0; // How long a species extinction stays announced
+
+// Camera bookmarks recalled with 1-9 and saved with Ctrl+1-9
+const BOOKMARKS_PATH: &str = "camera_bookmarks.txt";
+const BOOKMARK_KEYS: [KeyCode; 9] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+];
+
+// Parameter presets A and B, swapped with P and saved with Ctrl+P
+const PRESETS_PATH: &str = "parameter_presets.txt";
+const PRESET_LABELS: [char; 2] = ['A', 'B'];
+const LIVE_MUTATION_RATE: f64 = 0.05; // Starting point for [ and ] when no rate is set
+const MUTATION_RATE_RANGE: (f64, f64) = (1.0 / 256.0, 1.0); // One byte up to the whole genome
+const FOOD_RATE_RANGE: (f64, f64) = (0.05, 20.0);
+
+// Migration between worlds started with --listen/--connect
+const MIGRATION_INTERVAL: f64 = 10.0; // Seconds between emigrant batches
+const EMIGRANTS_PER_BATCH: usize = 2;
+
+// Unattended demo mode started with --demo or G
+const DEMO_SUBJECT_SECONDS: f64 = 12.0; // How long the camera stays on each subject
+const DEMO_ZOOM: f32 = 2.5;
+const DEMO_CAMERA_EASE: f32 = 0.05; // Fraction of the remaining distance covered per frame
+const DEMO_CLUSTER_RADIUS: f32 = 60.0; // Neighbourhood counted when looking for crowds
+
+/// Apply a god-mode action, logging it to the replay being recorded, if any
+fn intervene(world: &mut World, recording: &mut Option<(Replay, String)>, action: Intervention) {
+    match recording {
+        Some((replay, path)) => {
+            replay.record(world, action);
+            // Saved after every action so a crash loses nothing
+            if let Err(e) = replay.save(&*path) {
+                tracing::error!("Failed to save replay {}: {}", path, e);
+            }
+        }
+        None => {
+            world.intervene(&action);
+        }
+    }
+}
 
-/// Food that provides energy to lifeforms
-#[derive(Debug, Clone)]
-pub struct Food {
-    pub x: f32,
-    pub y: f32,
-    pub energy_value: f32,
+/// Append snapshots to a CBOR sequence file from a background thread, so writing
+/// never holds up the simulation
+fn spawn_observation_writer(path: &str) -> std::io::Result<Sender<Vec<WorldSnapshot>>> {
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    let (sender, receiver) = mpsc::channel::<Vec<WorldSnapshot>>();
+    let path = path.to_string();
+    std::thread::spawn(move || {
+        for batch in receiver {
+            let written = batch
+                .iter()
+                .try_for_each(|snapshot| ciborium::into_writer(snapshot, &mut file))
+                .map_err(|e| e.to_string())
+                .and_then(|()| file.flush().map_err(|e| e.to_string()));
+            if let Err(e) = written {
+                tracing::error!("Failed to write observations to {}: {}", path, e);
+                return;
+            }
+        }
+    });
+    Ok(sender)
 }
 
-impl Food {
-    pub fn new(x: f32, y: f32, energy_value: f32) -> Self {
-        Self { x, y, energy_value }
+/// Value following `flag` on the command line, e.g. `--import <path>`
+fn arg_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    let pos = args.iter().position(|a| a == flag)?;
+    args.get(pos + 1).map(String::as_str)
+}
+
+fn world_to_screen(x: f32, y: f32, camera_x: f32, camera_y: f32, zoom: f32) -> (f32, f32) {
+    let screen_x = (x - camera_x) * zoom + screen_width() / 2.0;
+    let screen_y = (y - camera_y) * zoom + screen_height() / 2.0;
+    (screen_x, screen_y)
+}
+
+/// Shade each nutrient cell by how close it is to sprouting food
+fn draw_nutrients(field: &NutrientField, camera_x: f32, camera_y: f32, zoom: f32) {
+    let size = NUTRIENT_CELL_SIZE * zoom;
+    for (index, amount) in field.cells.iter().enumerate() {
+        if *amount <= 0.0 {
+            continue;
+        }
+        let (x, y) = field.cell_origin(index);
+        let (screen_x, screen_y) = world_to_screen(x, y, camera_x, camera_y, zoom);
+        let alpha = (amount / FOOD_GROWTH_THRESHOLD).min(1.0) * 0.25;
+        draw_rectangle(
+            screen_x,
+            screen_y,
+            size,
+            size,
+            Color::new(0.5, 0.35, 0.1, alpha),
+        );
     }
+}
 
-    /// Create food with random energy value in a reasonable range
-    pub fn new_random(x: f32, y: f32, rng: &mut impl Rng) -> Self {
-        let energy_value = rng.gen_range(20.0..=50.0);
-        Self::new(x, y, energy_value)
+fn draw_food(food: &Food, camera_x: f32, camera_y: f32, zoom: f32) {
+    let screen_pos = world_to_screen(food.x, food.y, camera_x, camera_y, zoom);
+
+    // Only draw if on screen
+    let margin = 10.0 * zoom;
+    if screen_pos.0 < -margin
+        || screen_pos.0 > screen_width() + margin
+        || screen_pos.1 < -margin
+        || screen_pos.1 > screen_height() + margin
+    {
+        return;
     }
 
-    pub fn draw(&self, camera_x: f32, camera_y: f32, zoom: f32) {
-        let screen_pos = self.world_to_screen(camera_x, camera_y, zoom);
-        
-        // Only draw if on screen
-        if !self.is_on_screen(screen_pos, zoom) {
-            return;
-        }
+    let size = (4.0 + food.energy_value / 10.0) * zoom;
 
-        let size = (4.0 + self.energy_value / 10.0) * zoom;
+    // Draw food as a green circle with brightness based on energy value, toxins in purple
+    let brightness = (food.energy_value / 50.0).clamp(0.3, 1.0);
+    let food_color = if food.toxic {
+        Color::new(brightness * 0.7, 0.1, brightness, 1.0)
+    } else {
+        Color::new(0.2, brightness, 0.3, 1.0)
+    };
 
-        // Draw food as a green circle with brightness based on energy value
-        let brightness = (self.energy_value / 50.0).clamp(0.3, 1.0);
-        let food_color = Color::new(0.2, brightness, 0.3, 1.0);
+    draw_circle(screen_pos.0, screen_pos.1, size, food_color);
 
-        draw_circle(screen_pos.0, screen_pos.1, size, food_color);
+    // Add a small white center for visibility
+    if size > 2.0 {
+        draw_circle(screen_pos.0, screen_pos.1, size * 0.3, WHITE);
+    }
+}
 
-        // Add a small white center for visibility
-        if size > 2.0 {
-            draw_circle(screen_pos.0, screen_pos.1, size * 0.3, WHITE);
+fn draw_lifeform(
+    lifeform: &Lifeform,
+    display: Option<Display>,
+    camera_x: f32,
+    camera_y: f32,
+    zoom: f32,
+) {
+    let (screen_x, screen_y) = world_to_screen(lifeform.x, lifeform.y, camera_x, camera_y, zoom);
+
+    // Only draw if on screen
+    if screen_x >= -10.0
+        && screen_x <= screen_width() + 10.0
+        && screen_y >= -10.0
+        && screen_y <= screen_height() + 10.0
+    {
+        let size = LIFEFORM_SIZE * zoom;
+
+        // Draw the lifeform as a circle
+        let brightness = (lifeform.energy / 100.0).clamp(0.2, 1.0);
+        let [r, g, b] = lifeform.color;
+        let final_color = Color::new(r * brightness, g * brightness, b * brightness, 1.0);
+
+        draw_circle(screen_x, screen_y, size, final_color);
+
+        // Genome-driven 3x3 skin inside the body, once it is big enough to make out
+        if size > 6.0 {
+            let skin = lifeform.skin();
+            let [r, g, b] = skin.accent;
+            let accent = Color::new(r, g, b, 1.0);
+            let pixel = size * 1.2 / 3.0;
+            let left = screen_x - pixel * 1.5;
+            let top = screen_y - pixel * 1.5;
+            for row in 0..3 {
+                for col in 0..3 {
+                    if skin.is_lit(row, col) {
+                        let x = left + col as f32 * pixel;
+                        let y = top + row as f32 * pixel;
+                        draw_rectangle(x, y, pixel, pixel, accent);
+                    }
+                }
+            }
         }
-    }
 
-    fn world_to_screen(&self, camera_x: f32, camera_y: f32, zoom: f32) -> (f32, f32) {
-        let screen_x = (self.x - camera_x) * zoom + screen_width() / 2.0;
-        let screen_y = (self.y - camera_y) * zoom + screen_height() / 2.0;
-        (screen_x, screen_y)
-    }
+        // Flag tagged lifeforms with a ring and their name
+        if let Some(tag) = &lifeform.tag {
+            let [r, g, b] = tag.color;
+            let tag_color = Color::new(r, g, b, 1.0);
+            draw_circle_lines(screen_x, screen_y, size + 4.0, 2.0, tag_color);
+            draw_text(
+                &tag.name,
+                screen_x + size + 6.0,
+                screen_y - size - 6.0,
+                16.0,
+                tag_color,
+            );
+        }
 
-    fn is_on_screen(&self, screen_pos: (f32, f32), zoom: f32) -> bool {
-        let margin = 10.0 * zoom;
-        screen_pos.0 >= -margin
-            && screen_pos.0 <= screen_width() + margin
-            && screen_pos.1 >= -margin
-            && screen_pos.1 <= screen_height() + margin
+        // Draw energy bar above the creature
+        if size > 4.0 {
+            let bar_width = size * 2.0;
+            let bar_height = 2.0;
+            let bar_x = screen_x - bar_width / 2.0;
+            let bar_y = screen_y - size - 8.0;
+
+            // Background
+            draw_rectangle(bar_x, bar_y, bar_width, bar_height, DARKGRAY);
+            // Energy level
+            let energy_width = bar_width * (lifeform.energy / 100.0).clamp(0.0, 1.0);
+            draw_rectangle(bar_x, bar_y, energy_width, bar_height, GREEN);
+
+            // Draw PC value below the energy bar
+            let pc_text = format!("PC:{}", lifeform.vm.pc);
+            let font_size = 20.0 * zoom; //.max(8.0).min(12.0); // Scale with zoom but keep readable
+            let text_x = screen_x - (pc_text.len() as f32 * font_size * 0.3); // Center text roughly
+            let text_y = bar_y + bar_height + font_size + 2.0;
+            draw_text(&pc_text, text_x, text_y, font_size, WHITE);
+
+            // Draw the program's display above the energy bar, once it has lit a pixel
+            if let Some(display) = display
+                && !display.is_blank(lifeform.vm.data())
+            {
+                let pixel = bar_width / DISPLAY_SIZE as f32;
+                let left = bar_x;
+                let top = bar_y - 2.0 - bar_width;
+                draw_rectangle(left, top, bar_width, bar_width, BLACK);
+                for y in 0..DISPLAY_SIZE {
+                    for x in 0..DISPLAY_SIZE {
+                        if display.pixel(lifeform.vm.data(), x, y) {
+                            let px = left + x as f32 * pixel;
+                            let py = top + y as f32 * pixel;
+                            draw_rectangle(px, py, pixel, pixel, WHITE);
+                        }
+                    }
+                }
+            }
+        }
     }
 }
 
-/// Generate a normally distributed random number using Box-Muller transform
-/// This is more efficient than the previous version and avoids potential edge cases
-fn normal_random(mean: f32, std_dev: f32, rng: &mut impl Rng) -> f32 {
-    // Box-Muller transform - generate two independent uniform random numbers
-    let u1: f32 = rng.gen_range(f32::EPSILON..1.0); // Avoid exactly 0.0
-    let u2: f32 = rng.gen_range(0.0..1.0);
-    
-    // Box-Muller transform
-    let z0 = (-2.0 * u1.ln()).sqrt() * (2.0 * std::f32::consts::PI * u2).cos();
-    
-    z0 * std_dev + mean
+/// A register of every thread, separated by slashes, the one running next marked
+/// with `*` and halted ones with `.`; just the value without a coroutine
+fn thread_registers(vm: &VM, register: impl Fn(&Thread) -> usize) -> String {
+    let threads = vm.threads();
+    if threads.len() == 1 {
+        return register(&threads[0]).to_string();
+    }
+    threads
+        .iter()
+        .enumerate()
+        .map(|(i, t)| {
+            let mark = if i == vm.current_thread() {
+                "*"
+            } else if t.halted {
+                "."
+            } else {
+                ""
+            };
+            format!("{}{}", register(t), mark)
+        })
+        .collect::<Vec<_>>()
+        .join("/")
 }
 
-/// Clamp coordinates to map boundaries
-fn clamp_to_map_bounds(coord: f32) -> f32 {
-    coord.clamp(-MAP_BOUNDARY, MAP_BOUNDARY)
+/// Flags register as letters, with `-` for each clear flag
+fn flags_text(flags: u8) -> String {
+    [
+        (FLAG_ZERO, 'Z'),
+        (FLAG_CARRY, 'C'),
+        (FLAG_NEGATIVE, 'N'),
+        (FLAG_TRAP, 'T'),
+    ]
+    .iter()
+    .map(|&(bit, letter)| if flags & bit != 0 { letter } else { '-' })
+    .collect()
 }
 
-/// A simulated bacteria/lifeform controlled by a VM
-#[derive(Debug, Clone)]
-pub struct Lifeform {
-    pub vm: VM,
-    pub x: f32,
-    pub y: f32,
-    pub color: Color,
-    pub energy: f32,
-    pub age: u32,
+/// Outline color used for a memory region in the grid view
+fn region_color(kind: RegionKind) -> Color {
+    match kind {
+        RegionKind::Code => Color::new(0.5, 0.5, 0.5, 0.6),
+        RegionKind::Scratch => Color::new(0.8, 0.8, 0.8, 0.6),
+        RegionKind::Stack => Color::new(0.9, 0.5, 0.9, 0.8),
+        RegionKind::Sensors => SKYBLUE,
+        RegionKind::Actuators => YELLOW,
+        RegionKind::Display => GREEN,
+    }
 }
 
-impl Lifeform {
-    pub fn new(x: f32, y: f32) -> Self {
-        let mut vm = VM::new();
-        let mut rng = thread_rng();
-        vm.randomize(&mut rng);
+/// Color of a memory cell holding `value`, running through the rainbow to white
+fn cell_color(value: u8) -> Color {
+    let t = value as f32 / 255.0;
+    if t < 0.15 {
+        Color::new(1.0, t * 6.0, 0.0, 1.0)
+    } else if t < 0.30 {
+        Color::new(1.0, 0.5 + (t - 0.15) * 3.33, 0.0, 1.0)
+    } else if t < 0.45 {
+        Color::new(1.0, 1.0, (t - 0.30) * 6.66, 1.0)
+    } else if t < 0.60 {
+        Color::new(1.0 - (t - 0.45) * 6.66, 1.0, 0.0, 1.0)
+    } else if t < 0.75 {
+        Color::new(0.0, 1.0, (t - 0.60) * 6.66, 1.0)
+    } else if t < 0.90 {
+        Color::new(0.0, 1.0 - (t - 0.75) * 6.66, 1.0, 1.0)
+    } else if t < 0.98 {
+        Color::new((t - 0.90) * 12.5, 0.0, 1.0, 1.0)
+    } else {
+        Color::new(1.0, 1.0, 1.0, 1.0)
+    }
+}
 
-        Self {
-            vm,
-            x,
-            y,
-            color: Self::random_color(&mut rng),
-            energy: 100.0,
-            age: 0,
+/// Draw a single VM's memory as a grid at the given offset
+fn draw_vm<const N: usize>(
+    vm: &VM<N>,
+    memory_map: &MemoryMap,
+    offset_x: f32,
+    offset_y: f32,
+    grid_size: f32,
+    padding: f32,
+) {
+    // Draw the VM grid centered in its pane
+    let (cols, rows) = grid_shape(N);
+    let square_width = (grid_size - (cols as f32 - 1.0) * padding) / cols as f32;
+    let square_height = (grid_size - (rows as f32 - 1.0) * padding) / rows as f32;
+    // Where the threads that are not running next stand, outlined apart from the PC
+    let waiting: Vec<usize> = vm
+        .threads()
+        .iter()
+        .enumerate()
+        .filter(|&(i, t)| i != vm.current_thread() && !t.halted)
+        .map(|(_, t)| t.pc)
+        .collect();
+    for row in 0..rows {
+        for col in 0..cols {
+            let x = offset_x + col as f32 * (square_width + padding);
+            let y = offset_y + row as f32 * (square_height + padding);
+            let idx = row * cols + col;
+            let Some(&value) = vm.memory.get(idx) else {
+                break;
+            };
+            draw_rectangle(x, y, square_width, square_height, cell_color(value));
+            // In Harvard mode the lower half of each cell shows the data bank
+            if vm.is_harvard() {
+                let half = square_height / 2.0;
+                let data = cell_color(vm.data()[idx]);
+                draw_rectangle(x, y + half, square_width, half, data);
+            }
+            if idx == vm.pc {
+                draw_rectangle_lines(x, y, square_width, square_height, 5.0, WHITE);
+            } else if waiting.contains(&idx) {
+                draw_rectangle_lines(x, y, square_width, square_height, 5.0, ORANGE);
+            }
         }
     }
 
-    pub fn from_vm(vm: VM, x: f32, y: f32) -> Self {
-        let mut rng = thread_rng();
-        Self {
-            vm,
-            x,
-            y,
-            color: Self::random_color(&mut rng),
-            energy: 100.0,
-            age: 0,
+    // Outline region boundaries and label the first cell of each region
+    let cell_kind = |idx: usize| memory_map.region_at(idx).map(|r| r.kind);
+    for idx in 0..N {
+        let Some(kind) = cell_kind(idx) else {
+            continue;
+        };
+        let (row, col) = (idx / cols, idx % cols);
+        let x = offset_x + col as f32 * (square_width + padding);
+        let y = offset_y + row as f32 * (square_height + padding);
+        let color = region_color(kind);
+        if col > 0 && cell_kind(idx - 1) != Some(kind) {
+            draw_line(x, y, x, y + square_height, 2.0, color);
+        }
+        if col + 1 < cols && cell_kind(idx + 1) != Some(kind) {
+            draw_line(
+                x + square_width,
+                y,
+                x + square_width,
+                y + square_height,
+                2.0,
+                color,
+            );
+        }
+        if row > 0 && cell_kind(idx - cols) != Some(kind) {
+            draw_line(x, y, x + square_width, y, 2.0, color);
+        }
+        if row + 1 < rows && cell_kind(idx + cols) != Some(kind) {
+            draw_line(
+                x,
+                y + square_height,
+                x + square_width,
+                y + square_height,
+                2.0,
+                color,
+            );
         }
     }
+    for region in memory_map.regions() {
+        if region.range.is_empty() {
+            continue;
+        }
+        let (row, col) = (region.range.start / cols, region.range.start % cols);
+        let x = offset_x + col as f32 * (square_width + padding);
+        let y = offset_y + row as f32 * (square_height + padding);
+        draw_text(
+            &region.kind.to_string(),
+            x + 1.0,
+            y + 8.0,
+            9.0,
+            region_color(region.kind),
+        );
+    }
 
-    fn random_color(rng: &mut impl Rng) -> Color {
-        Color::new(
-            rng.gen_range(0.0..1.0),
-            rng.gen_range(0.0..1.0),
-            rng.gen_range(0.0..1.0),
-            1.0,
-        )
+    // Draw the current number of steps centered and large
+    let steps_text = format!("{}", vm.total_steps_count);
+    let text_size = grid_size * 0.3;
+    let text_dimensions = measure_text(&steps_text, None, text_size as u16, 1.0);
+    let text_x = offset_x + (grid_size - text_dimensions.width) / 2.0;
+    let text_y = offset_y + (grid_size + text_dimensions.height) / 2.0;
+    draw_text(&steps_text, text_x, text_y, text_size, WHITE);
+
+    // Draw the log view to the right of the VM grid (no background, white text)
+    let _log_width = grid_size * 1.2;
+    let log_height = grid_size;
+    let log_x = offset_x + grid_size + padding * 2.0;
+    let log_y = offset_y;
+    let log_font_size = (grid_size / 18.0).max(12.0);
+    let mut y = log_y + log_font_size + 4.0;
+    let max_lines = (log_height / (log_font_size + 2.0)).floor() as usize;
+    let start_idx = vm.recent.len().saturating_sub(max_lines);
+    for line in vm.recent.lines().skip(start_idx) {
+        draw_text(&line, log_x + 8.0, y, log_font_size, WHITE);
+        y += log_font_size + 2.0;
     }
 
-    /// Update the lifeform - run VM step and process movement commands
-    pub fn update(&mut self, food_items: &[Food]) {
-        self.update_sensory_input(food_items);
-        self.restart_vm_if_halted();
-        self.vm.step();
-        self.process_movement_commands();
-        self.age_and_consume_energy();
-    }
-
-    /// Update sensory input by finding the nearest food and writing distance to memory
-    fn update_sensory_input(&mut self, food_items: &[Food]) {
-        if let Some((distance_x, distance_y)) = self.find_nearest_food_distance(food_items) {
-            // Convert world coordinates to memory values (scaled and clamped to u8 range)
-            let memory_x = self.distance_to_memory_value(distance_x);
-            let memory_y = self.distance_to_memory_value(distance_y);
-            
-            self.vm.memory[FOOD_DISTANCE_X_ADDR] = memory_x;
-            self.vm.memory[FOOD_DISTANCE_Y_ADDR] = memory_y;
-        } else {
-            // No food detected within range - write neutral values
-            self.vm.memory[FOOD_DISTANCE_X_ADDR] = 128; // Neutral (middle value)
-            self.vm.memory[FOOD_DISTANCE_Y_ADDR] = 128; // Neutral (middle value)
-        }
+    // Sampled long-horizon trace under the log, newest last
+    let mut y = log_y + log_height + log_font_size + 8.0;
+    draw_text(
+        &format!("Sampled trace ({} steps):", vm.trace.steps()),
+        log_x + 8.0,
+        y,
+        log_font_size,
+        YELLOW,
+    );
+    let skip = vm.trace.entries.len().saturating_sub(TRACE_LINES_SHOWN);
+    for entry in vm.trace.entries.iter().skip(skip) {
+        y += log_font_size + 2.0;
+        draw_text(&entry.to_string(), log_x + 8.0, y, log_font_size, LIGHTGRAY);
+    }
+}
+
+/// Draw a series of samples as a line plot scaled to its own min/max, with room
+/// for `capacity` samples
+fn draw_sparkline(
+    values: &[f32],
+    capacity: usize,
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    color: Color,
+) {
+    draw_rectangle_lines(x, y, width, height, 1.0, DARKGRAY);
+    if values.len() < 2 {
+        return;
     }
 
-    /// Find the nearest food within detection range and return relative distance
-    fn find_nearest_food_distance(&self, food_items: &[Food]) -> Option<(f32, f32)> {
-        let mut nearest_distance_squared = MAX_FOOD_DETECTION_RANGE * MAX_FOOD_DETECTION_RANGE;
-        let mut nearest_food_pos: Option<(f32, f32)> = None;
+    let min = values.iter().copied().fold(f32::INFINITY, f32::min);
+    let max = values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let range = (max - min).max(f32::EPSILON);
+    let step_x = width / (capacity.max(2) - 1) as f32;
+    // Right-align so the newest sample always sits at the right edge
+    let start_x = x + width - step_x * (values.len() - 1) as f32;
+
+    for (i, pair) in values.windows(2).enumerate() {
+        let x0 = start_x + i as f32 * step_x;
+        let y0 = y + height - (pair[0] - min) / range * height;
+        let y1 = y + height - (pair[1] - min) / range * height;
+        draw_line(x0, y0, x0 + step_x, y1, 1.0, color);
+    }
+}
 
-        for food in food_items {
-            let dx = food.x - self.x;
-            let dy = food.y - self.y;
-            let distance_squared = dx * dx + dy * dy;
+/// Draw labelled sparklines for a lifeform's telemetry history
+fn draw_telemetry(telemetry: &Telemetry, x: f32, y: f32, width: f32) {
+    let height = 24.0;
+    let row = height + 16.0;
+
+    let energy: Vec<f32> = telemetry.energy.iter().copied().collect();
+    let pos_x: Vec<f32> = telemetry.position.iter().map(|p| p.0).collect();
+    let pos_y: Vec<f32> = telemetry.position.iter().map(|p| p.1).collect();
+    let instructions: Vec<f32> = telemetry.instructions.iter().map(|&n| n as f32).collect();
+
+    draw_text("Energy", x, y + 10.0, 12.0, LIGHTGRAY);
+    draw_sparkline(
+        &energy,
+        TELEMETRY_HISTORY,
+        x,
+        y + 14.0,
+        width,
+        height,
+        GREEN,
+    );
+    draw_text("Position (x, y)", x, y + row + 10.0, 12.0, LIGHTGRAY);
+    draw_sparkline(
+        &pos_x,
+        TELEMETRY_HISTORY,
+        x,
+        y + row + 14.0,
+        width,
+        height,
+        ORANGE,
+    );
+    draw_sparkline(
+        &pos_y,
+        TELEMETRY_HISTORY,
+        x,
+        y + row + 14.0,
+        width,
+        height,
+        PINK,
+    );
+    draw_text(
+        "Instructions/tick",
+        x,
+        y + row * 2.0 + 10.0,
+        12.0,
+        LIGHTGRAY,
+    );
+    draw_sparkline(
+        &instructions,
+        TELEMETRY_HISTORY,
+        x,
+        y + row * 2.0 + 14.0,
+        width,
+        height,
+        SKYBLUE,
+    );
+}
 
-            if distance_squared < nearest_distance_squared {
-                nearest_distance_squared = distance_squared;
-                nearest_food_pos = Some((dx, dy));
-            }
-        }
+/// A color of its own for each species, spread around the hue circle
+fn species_color(id: u32) -> Color {
+    let hue = (id as f32 * 0.618_034).fract();
+    macroquad::color::hsl_to_rgb(hue, 0.7, 0.6)
+}
 
-        nearest_food_pos
+/// Live panels for the most populous species in the bottom right corner
+fn draw_species_panels(species: &[Species]) {
+    let width = 230.0;
+    let height = 62.0;
+    let x = screen_width() - width - 20.0;
+    let mut ranked: Vec<&Species> = species.iter().collect();
+    ranked.sort_by(|a, b| b.population.cmp(&a.population).then(a.id.cmp(&b.id)));
+
+    let shown = ranked.len().min(SPECIES_PANELS);
+    let top = screen_height() - 20.0 - shown as f32 * (height + 6.0) - 20.0;
+    draw_text(
+        &format!("Species: {} living", species.len()),
+        x,
+        top + 12.0,
+        16.0,
+        YELLOW,
+    );
+    for (i, species) in ranked.into_iter().take(SPECIES_PANELS).enumerate() {
+        let y = top + 20.0 + i as f32 * (height + 6.0);
+        let color = species_color(species.id);
+        draw_rectangle(x, y, width, height, Color::new(0.0, 0.0, 0.0, 0.7));
+        draw_rectangle(x, y, 4.0, height, color);
+        draw_text(
+            &format!(
+                "Species #{} (generation {})",
+                species.id, species.founding_generation
+            ),
+            x + 10.0,
+            y + 14.0,
+            14.0,
+            color,
+        );
+        draw_text(
+            &format!(
+                "Population {} (peak {}) | fitness {:.1} s",
+                species.population, species.peak_population, species.mean_fitness
+            ),
+            x + 10.0,
+            y + 28.0,
+            12.0,
+            WHITE,
+        );
+        let history: Vec<f32> = species.history.iter().map(|&n| n as f32).collect();
+        draw_sparkline(
+            &history,
+            SPECIES_HISTORY,
+            x + 10.0,
+            y + 34.0,
+            width - 20.0,
+            22.0,
+            color,
+        );
     }
+}
 
-    /// Convert a world distance to a memory value (0-255)
-    /// Positive distances map to 128-255, negative to 0-127, with 128 being neutral
-    fn distance_to_memory_value(&self, distance: f32) -> u8 {
-        let scaled_distance = distance * SENSORY_SCALE_FACTOR;
-        let clamped = scaled_distance.clamp(-128.0, 127.0);
-        ((clamped + 128.0) as u8)
+/// Extinctions recorded since the last call, announced for `EXTINCTION_ALERT_SECONDS`
+#[derive(Default)]
+struct ExtinctionAlert {
+    /// Last extinction already announced, to find the new ones in the world's log
+    seen: Option<SpeciesExtinction>,
+    message: Option<(String, f64)>,
+}
+
+impl ExtinctionAlert {
+    fn update(&mut self, log: &[SpeciesExtinction], now: f64) {
+        let start = self
+            .seen
+            .and_then(|seen| log.iter().rposition(|e| *e == seen))
+            .map_or(0, |i| i + 1);
+        let new = &log[start..];
+        let Some(&latest) = new.last() else {
+            return;
+        };
+        self.seen = Some(latest);
+        let message = match new.len() {
+            1 => latest.to_string(),
+            n => format!("{} (and {} more)", latest, n - 1),
+        };
+        self.message = Some((message, now + EXTINCTION_ALERT_SECONDS));
     }
 
-    fn restart_vm_if_halted(&mut self) {
-        if self.vm.halted {
-            self.vm.halted = false;
-            self.vm.pc = 0; // Restart from beginning
+    fn draw(&self, now: f64) {
+        let Some((message, until)) = &self.message else {
+            return;
+        };
+        if now > *until {
+            return;
         }
+        let dims = measure_text(message, None, 18, 1.0);
+        let x = (screen_width() - dims.width) / 2.0;
+        let y = 40.0;
+        draw_rectangle(
+            x - 12.0,
+            y - 22.0,
+            dims.width + 24.0,
+            32.0,
+            Color::new(0.3, 0.0, 0.0, 0.85),
+        );
+        draw_text(message, x, y, 18.0, RED);
     }
+}
 
-    fn age_and_consume_energy(&mut self) {
-        self.age += 1;
-        self.energy -= ENERGY_DRAIN_PER_FRAME;
+/// Opcode reference drawn over the world, generated from the instruction set
+fn draw_isa_help() {
+    let reference = isa::reference();
+    let width = 560.0;
+    let height = 60.0 + reference.len() as f32 * 16.0;
+    let x = (screen_width() - width) / 2.0;
+    let y = (screen_height() - height) / 2.0;
+
+    draw_rectangle(x, y, width, height, Color::new(0.0, 0.0, 0.0, 0.9));
+    draw_rectangle_lines(x, y, width, height, 2.0, WHITE);
+    draw_text(
+        "Instruction set (H to close)",
+        x + 10.0,
+        y + 22.0,
+        18.0,
+        YELLOW,
+    );
+    // The default font is proportional, so each column gets its own x offset
+    let columns = [0.0, 60.0, 130.0, 180.0, 230.0, 290.0];
+    let headings = ["Name", "Code", "Size", "Steps", "Flags", "Description"];
+    for (offset, heading) in columns.iter().zip(headings) {
+        draw_text(heading, x + 10.0 + offset, y + 44.0, 16.0, LIGHTGRAY);
     }
-
-    fn process_movement_commands(&mut self) {
-        // Compare values to determine movement direction
-        let movement_values = [
-            self.vm.memory[MOVE_LEFT_ADDR],
-            self.vm.memory[MOVE_RIGHT_ADDR],
-            self.vm.memory[MOVE_UP_ADDR],
-            self.vm.memory[MOVE_DOWN_ADDR],
+    for (i, info) in reference.iter().enumerate() {
+        let row_y = y + 62.0 + i as f32 * 16.0;
+        let cells = [
+            info.mnemonic.to_string(),
+            info.encoding.clone(),
+            info.size.to_string(),
+            info.steps.to_string(),
+            info.flags.to_string(),
+            info.summary.to_string(),
         ];
-
-        // Horizontal movement: move in direction of larger value
-        if movement_values[0] > movement_values[1] {
-            self.move_and_consume_energy(-MOVEMENT_SPEED, 0.0);
-        } else if movement_values[1] > movement_values[0] {
-            self.move_and_consume_energy(MOVEMENT_SPEED, 0.0);
+        for (offset, cell) in columns.iter().zip(&cells) {
+            draw_text(cell, x + 10.0 + offset, row_y, 16.0, WHITE);
         }
+    }
+}
+
+/// What a text entry wants done after a frame of input
+enum TagEditOutcome {
+    Editing,
+    Commit,
+    Cancel,
+}
+
+/// Text entry for naming and annotating the selected lifeform
+struct TagEditor {
+    target: usize,
+    tag: Tag,
+    color_index: usize,
+    editing_note: bool,
+}
+
+impl TagEditor {
+    fn new(target: usize, existing: Option<&Tag>) -> Self {
+        // Drop keystrokes typed before the editor opened
+        while get_char_pressed().is_some() {}
 
-        // Vertical movement: move in direction of larger value
-        if movement_values[2] > movement_values[3] {
-            self.move_and_consume_energy(0.0, -MOVEMENT_SPEED);
-        } else if movement_values[3] > movement_values[2] {
-            self.move_and_consume_energy(0.0, MOVEMENT_SPEED);
+        let tag = existing.cloned().unwrap_or_else(|| Tag {
+            color: TAG_COLORS[0],
+            ..Tag::default()
+        });
+        let color_index = TAG_COLORS.iter().position(|c| *c == tag.color).unwrap_or(0);
+        Self {
+            target,
+            tag,
+            color_index,
+            editing_note: false,
         }
     }
 
-    fn move_and_consume_energy(&mut self, dx: f32, dy: f32) {
-        self.x += dx;
-        self.y += dy;
-        self.energy -= MOVEMENT_ENERGY_COST;
-    }
+    /// Tab switches between name and note, Up/Down cycles the flag color
+    fn update(&mut self) -> TagEditOutcome {
+        if is_key_pressed(KeyCode::Escape) {
+            return TagEditOutcome::Cancel;
+        }
+        if is_key_pressed(KeyCode::Enter) {
+            return TagEditOutcome::Commit;
+        }
+        if is_key_pressed(KeyCode::Tab) {
+            self.editing_note = !self.editing_note;
+        }
+        if is_key_pressed(KeyCode::Up) {
+            self.color_index = (self.color_index + 1) % TAG_COLORS.len();
+        }
+        if is_key_pressed(KeyCode::Down) {
+            self.color_index = (self.color_index + TAG_COLORS.len() - 1) % TAG_COLORS.len();
+        }
+        self.tag.color = TAG_COLORS[self.color_index];
 
-    pub fn draw(&self, camera_x: f32, camera_y: f32, zoom: f32) {
-        let screen_x = (self.x - camera_x) * zoom + screen_width() / 2.0;
-        let screen_y = (self.y - camera_y) * zoom + screen_height() / 2.0;
+        let field = if self.editing_note {
+            &mut self.tag.note
+        } else {
+            &mut self.tag.name
+        };
+        if is_key_pressed(KeyCode::Backspace) {
+            field.pop();
+        }
+        while let Some(c) = get_char_pressed() {
+            if !c.is_control() {
+                field.push(c);
+            }
+        }
+        TagEditOutcome::Editing
+    }
 
-        // Only draw if on screen
-        if screen_x >= -10.0
-            && screen_x <= screen_width() + 10.0
-            && screen_y >= -10.0
-            && screen_y <= screen_height() + 10.0
-        {
-            let size = LIFEFORM_SIZE * zoom;
-
-            // Draw the lifeform as a circle
-            let brightness = (self.energy / 100.0).clamp(0.2, 1.0);
-            let final_color = Color::new(
-                self.color.r * brightness,
-                self.color.g * brightness,
-                self.color.b * brightness,
-                self.color.a,
-            );
+    fn draw(&self) {
+        let width = 420.0;
+        let x = (screen_width() - width) / 2.0;
+        let y = screen_height() / 2.0 - 50.0;
+        let [r, g, b] = self.tag.color;
 
-            draw_circle(screen_x, screen_y, size, final_color);
+        draw_rectangle(x, y, width, 100.0, Color::new(0.0, 0.0, 0.0, 0.9));
+        draw_rectangle_lines(x, y, width, 100.0, 2.0, Color::new(r, g, b, 1.0));
+        draw_text(
+            &format!("Tag lifeform #{}", self.target),
+            x + 10.0,
+            y + 20.0,
+            18.0,
+            YELLOW,
+        );
+        let cursor = |active: bool| if active { "_" } else { "" };
+        draw_text(
+            &format!("Name: {}{}", self.tag.name, cursor(!self.editing_note)),
+            x + 10.0,
+            y + 42.0,
+            16.0,
+            WHITE,
+        );
+        draw_text(
+            &format!("Note: {}{}", self.tag.note, cursor(self.editing_note)),
+            x + 10.0,
+            y + 62.0,
+            16.0,
+            WHITE,
+        );
+        draw_text(
+            "Tab = Name/Note, Up/Down = Color, Enter = Save, Esc = Cancel",
+            x + 10.0,
+            y + 88.0,
+            12.0,
+            LIGHTGRAY,
+        );
+    }
+}
 
-            // Draw energy bar above the creature
-            if size > 4.0 {
-                let bar_width = size * 2.0;
-                let bar_height = 2.0;
-                let bar_x = screen_x - bar_width / 2.0;
-                let bar_y = screen_y - size - 8.0;
+/// A saved camera position and zoom level
+#[derive(Debug, Clone)]
+struct Bookmark {
+    name: String,
+    x: f32,
+    y: f32,
+    zoom: f32,
+}
 
-                // Background
-                draw_rectangle(bar_x, bar_y, bar_width, bar_height, DARKGRAY);
-                // Energy level
-                let energy_width = bar_width * (self.energy / 100.0).clamp(0.0, 1.0);
-                draw_rectangle(bar_x, bar_y, energy_width, bar_height, GREEN);
+/// Nine bookmark slots, stored one per line as `slot x y zoom name`
+#[derive(Debug, Default)]
+struct Bookmarks {
+    slots: [Option<Bookmark>; 9],
+}
 
-                // Draw PC value below the energy bar
-                let pc_text = format!("PC:{}", self.vm.pc);
-                let font_size = (20.0 * zoom); //.max(8.0).min(12.0); // Scale with zoom but keep readable
-                let text_x = screen_x - (pc_text.len() as f32 * font_size * 0.3); // Center text roughly
-                let text_y = bar_y + bar_height + font_size + 2.0;
-                draw_text(&pc_text, text_x, text_y, font_size, WHITE);
+impl Bookmarks {
+    /// Read saved bookmarks, skipping malformed lines; a missing file means no bookmarks
+    fn load(path: &str) -> Self {
+        let mut bookmarks = Self::default();
+        let Ok(contents) = std::fs::read_to_string(path) else {
+            return bookmarks;
+        };
+        for line in contents.lines() {
+            let mut fields = line.splitn(5, ' ');
+            let Some(slot) = fields.next().and_then(|f| f.parse::<usize>().ok()) else {
+                continue;
+            };
+            let mut number = || fields.next().and_then(|f| f.parse::<f32>().ok());
+            let (Some(x), Some(y), Some(zoom)) = (number(), number(), number()) else {
+                continue;
+            };
+            let name = fields.next().unwrap_or_default().to_string();
+            if let Some(entry) = slot.checked_sub(1).and_then(|i| bookmarks.slots.get_mut(i)) {
+                *entry = Some(Bookmark { name, x, y, zoom });
             }
         }
+        bookmarks
     }
 
-    pub fn is_alive(&self) -> bool {
-        self.energy > 0.0 // Only check energy, not VM halt status
+    fn save(&self, path: &str) -> std::io::Result<()> {
+        let mut contents = String::new();
+        for (i, slot) in self.slots.iter().enumerate() {
+            if let Some(b) = slot {
+                contents.push_str(&format!(
+                    "{} {} {} {} {}\n",
+                    i + 1,
+                    b.x,
+                    b.y,
+                    b.zoom,
+                    b.name
+                ));
+            }
+        }
+        std::fs::write(path, contents)
     }
+}
 
-    /// Check if this lifeform collides with food (within eating distance)
-    pub fn can_eat_food(&self, food: &Food) -> bool {
-        let distance_squared = (self.x - food.x).powi(2) + (self.y - food.y).powi(2);
-        distance_squared <= EATING_RADIUS * EATING_RADIUS
+/// Live parameters saved as a whole, to flip between two settings and watch the population respond
+#[derive(Debug, Clone, PartialEq)]
+struct Preset {
+    name: String,
+    step_delay_ms: f64,
+    /// `None` keeps the world's default 1-10% per offspring
+    mutation_rate: Option<f64>,
+    food_rate: f64,
+}
+
+impl Preset {
+    fn capture(name: String, step_delay_ms: f64, world: &World) -> Self {
+        Self {
+            name,
+            step_delay_ms,
+            mutation_rate: world.config.mutation_rate,
+            food_rate: world.food_rate(),
+        }
     }
 
-    /// Consume food and gain energy
-    pub fn eat_food(&mut self, food: &Food) {
-        self.energy = (self.energy + food.energy_value).min(MAX_ENERGY);
+    fn apply(&self, step_delay_ms: &mut f64, world: &mut World) {
+        *step_delay_ms = self.step_delay_ms;
+        world.config.mutation_rate = self.mutation_rate;
+        world.set_food_rate(self.food_rate);
     }
 }
 
-/// Draw a single VM's memory as a grid at the given offset
-fn draw_vm(vm: &VM, offset_x: f32, offset_y: f32, grid_size: f32, padding: f32) {
-    // Draw the VM grid centered in its pane
-    let cols = 16;
-    let rows = 16;
-    let square_width = (grid_size - (cols as f32 - 1.0) * padding) / cols as f32;
-    let square_height = (grid_size - (rows as f32 - 1.0) * padding) / rows as f32;
-    for row in 0..rows {
-        for col in 0..cols {
-            let x = offset_x + col as f32 * (square_width + padding);
-            let y = offset_y + row as f32 * (square_height + padding);
-            let idx = row * cols + col;
-            let value = vm.memory[idx];
-            let t = value as f32 / 255.0;
-            let color = if t < 0.15 {
-                Color::new(1.0, t * 6.0, 0.0, 1.0)
-            } else if t < 0.30 {
-                Color::new(1.0, 0.5 + (t - 0.15) * 3.33, 0.0, 1.0)
-            } else if t < 0.45 {
-                Color::new(1.0, 1.0, (t - 0.30) * 6.66, 1.0)
-            } else if t < 0.60 {
-                Color::new(1.0 - (t - 0.45) * 6.66, 1.0, 0.0, 1.0)
-            } else if t < 0.75 {
-                Color::new(0.0, 1.0, (t - 0.60) * 6.66, 1.0)
-            } else if t < 0.90 {
-                Color::new(0.0, 1.0 - (t - 0.75) * 6.66, 1.0, 1.0)
-            } else if t < 0.98 {
-                Color::new((t - 0.90) * 12.5, 0.0, 1.0, 1.0)
-            } else {
-                Color::new(1.0, 1.0, 1.0, 1.0)
+/// Two preset slots, stored one per line as `slot delay mutation food name`,
+/// with `-` for the default mutation
+#[derive(Debug, Default)]
+struct Presets {
+    slots: [Option<Preset>; 2],
+    /// Slot the live parameters last came from or went to
+    active: usize,
+}
+
+impl Presets {
+    /// Read saved presets, skipping malformed lines; a missing file means none
+    fn load(path: &str) -> Self {
+        let mut presets = Self::default();
+        let Ok(contents) = std::fs::read_to_string(path) else {
+            return presets;
+        };
+        for line in contents.lines() {
+            let mut fields = line.splitn(5, ' ');
+            let Some(slot) = fields
+                .next()
+                .and_then(|f| f.chars().next())
+                .and_then(|c| PRESET_LABELS.iter().position(|l| *l == c))
+            else {
+                continue;
             };
-            draw_rectangle(x, y, square_width, square_height, color);
-            if idx == vm.pc {
-                draw_rectangle_lines(x, y, square_width, square_height, 5.0, WHITE);
+            let step_delay_ms = fields.next().and_then(|f| f.parse().ok());
+            let mutation_rate = match fields.next() {
+                Some("-") => Some(None),
+                field => field.and_then(|f| f.parse().ok()).map(Some),
+            };
+            let food_rate = fields.next().and_then(|f| f.parse().ok());
+            let (Some(step_delay_ms), Some(mutation_rate), Some(food_rate)) =
+                (step_delay_ms, mutation_rate, food_rate)
+            else {
+                continue;
+            };
+            let name = fields.next().unwrap_or_default().to_string();
+            presets.slots[slot] = Some(Preset {
+                name,
+                step_delay_ms,
+                mutation_rate,
+                food_rate,
+            });
+        }
+        presets
+    }
+
+    fn save(&self, path: &str) -> std::io::Result<()> {
+        let mut contents = String::new();
+        for (label, slot) in PRESET_LABELS.iter().zip(&self.slots) {
+            if let Some(p) = slot {
+                let mutation = p
+                    .mutation_rate
+                    .map_or_else(|| "-".to_string(), |r| r.to_string());
+                contents.push_str(&format!(
+                    "{} {} {} {} {}\n",
+                    label, p.step_delay_ms, mutation, p.food_rate, p.name
+                ));
             }
+        }
+        std::fs::write(path, contents)
+    }
 
-            // Highlight memory-mapped I/O addresses
-            if idx >= MOVE_LEFT_ADDR && idx <= MOVE_DOWN_ADDR {
-                draw_rectangle_lines(x, y, square_width, square_height, 2.0, YELLOW);
+    /// Switch to the other slot, applying it if something was saved there
+    fn toggle(&mut self, step_delay_ms: &mut f64, world: &mut World) {
+        self.active = 1 - self.active;
+        let label = PRESET_LABELS[self.active];
+        match &self.slots[self.active] {
+            Some(preset) => {
+                preset.apply(step_delay_ms, world);
+                info!("Switched to preset {} ({})", label, preset.name);
             }
-            // Highlight sensory input addresses
-            if idx == FOOD_DISTANCE_X_ADDR || idx == FOOD_DISTANCE_Y_ADDR {
-                draw_rectangle_lines(x, y, square_width, square_height, 2.0, SKYBLUE);
+            None => info!(
+                "Preset {} is empty; Ctrl+P saves the current parameters to it",
+                label
+            ),
+        }
+    }
+}
+
+/// Text entry naming the preset the live parameters are being saved to
+struct PresetNamer {
+    slot: usize,
+    name: String,
+}
+
+impl PresetNamer {
+    fn new(slot: usize, existing: Option<&Preset>) -> Self {
+        // Drop keystrokes typed before the prompt opened
+        while get_char_pressed().is_some() {}
+
+        Self {
+            slot,
+            name: existing.map_or_else(String::new, |p| p.name.clone()),
+        }
+    }
+
+    fn update(&mut self) -> TagEditOutcome {
+        if is_key_pressed(KeyCode::Escape) {
+            return TagEditOutcome::Cancel;
+        }
+        if is_key_pressed(KeyCode::Enter) {
+            return TagEditOutcome::Commit;
+        }
+        if is_key_pressed(KeyCode::Backspace) {
+            self.name.pop();
+        }
+        while let Some(c) = get_char_pressed() {
+            if !c.is_control() {
+                self.name.push(c);
             }
         }
+        TagEditOutcome::Editing
     }
-    // Draw the current number of steps centered and large
-    let steps_text = format!("{}", vm.total_steps_count);
-    let text_size = grid_size * 0.3;
-    let text_dimensions = measure_text(&steps_text, None, text_size as u16, 1.0);
-    let text_x = offset_x + (grid_size - text_dimensions.width) / 2.0;
-    let text_y = offset_y + (grid_size + text_dimensions.height) / 2.0;
-    draw_text(&steps_text, text_x, text_y, text_size, WHITE);
 
-    // Draw the log view to the right of the VM grid (no background, white text)
-    let _log_width = grid_size * 1.2;
-    let log_height = grid_size;
-    let log_x = offset_x + grid_size + padding * 2.0;
-    let log_y = offset_y;
-    let log_font_size = (grid_size / 18.0).max(12.0);
-    let mut y = log_y + log_font_size + 4.0;
-    let max_lines = (log_height / (log_font_size + 2.0)).floor() as usize;
-    let start_idx = if vm.recent_instructions.len() > max_lines {
-        vm.recent_instructions.len() - max_lines
-    } else {
-        0
-    };
-    for line in vm.recent_instructions.iter().skip(start_idx) {
-        draw_text(line, log_x + 8.0, y, log_font_size, WHITE);
-        y += log_font_size + 2.0;
+    fn draw(&self) {
+        let width = 420.0;
+        let x = (screen_width() - width) / 2.0;
+        let y = screen_height() / 2.0 - 40.0;
+
+        draw_rectangle(x, y, width, 80.0, Color::new(0.0, 0.0, 0.0, 0.9));
+        draw_rectangle_lines(x, y, width, 80.0, 2.0, YELLOW);
+        draw_text(
+            &format!("Save parameters as preset {}", PRESET_LABELS[self.slot]),
+            x + 10.0,
+            y + 20.0,
+            18.0,
+            YELLOW,
+        );
+        draw_text(
+            &format!("Name: {}_", self.name),
+            x + 10.0,
+            y + 42.0,
+            16.0,
+            WHITE,
+        );
+        draw_text(
+            "Enter = Save, Esc = Cancel",
+            x + 10.0,
+            y + 68.0,
+            12.0,
+            LIGHTGRAY,
+        );
     }
 }
 
@@ -395,6 +1074,12 @@ pub struct Camera {
     pub zoom_speed: f32,
 }
 
+impl Default for Camera {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Camera {
     pub fn new() -> Self {
         Self {
@@ -406,6 +1091,32 @@ impl Camera {
         }
     }
 
+    fn jump_to(&mut self, bookmark: &Bookmark) {
+        self.x = bookmark.x;
+        self.y = bookmark.y;
+        self.zoom = bookmark.zoom;
+    }
+
+    /// World coordinates of a point on screen
+    pub fn screen_to_world(&self, screen_x: f32, screen_y: f32) -> (f32, f32) {
+        (
+            (screen_x - screen_width() / 2.0) / self.zoom + self.x,
+            (screen_y - screen_height() / 2.0) / self.zoom + self.y,
+        )
+    }
+
+    /// World-space rectangle currently visible on screen
+    pub fn viewport(&self) -> Viewport {
+        let half_width = screen_width() / 2.0 / self.zoom;
+        let half_height = screen_height() / 2.0 / self.zoom;
+        Viewport {
+            min_x: self.x - half_width,
+            min_y: self.y - half_height,
+            max_x: self.x + half_width,
+            max_y: self.y + half_height,
+        }
+    }
+
     pub fn update(&mut self) {
         // Camera movement with WASD keys only (arrows reserved for speed control)
         let move_speed = 5.0; // Fixed pixels per frame
@@ -423,28 +1134,154 @@ impl Camera {
             self.x += move_speed / self.zoom;
         }
 
-        // Zoom with Q/E or scroll wheel
-        let zoom_factor = 1.02; // Fixed zoom per frame
-
-        if is_key_down(KeyCode::Q) {
-            self.zoom /= zoom_factor;
+        // Zoom with Q/E or scroll wheel
+        let zoom_factor = 1.02; // Fixed zoom per frame
+
+        if is_key_down(KeyCode::Q) {
+            self.zoom /= zoom_factor;
+        }
+        if is_key_down(KeyCode::E) {
+            self.zoom *= zoom_factor;
+        }
+
+        // Handle mouse wheel for zooming
+        let (_x, wheel_y) = mouse_wheel();
+        if wheel_y != 0.0 {
+            if wheel_y > 0.0 {
+                self.zoom *= 1.1;
+            } else {
+                self.zoom /= 1.1;
+            }
+        }
+
+        // Clamp zoom
+        self.zoom = self.zoom.clamp(0.1, 10.0);
+    }
+}
+
+/// Kinds of subject the demo camera cycles through
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DemoSubject {
+    NewestBirth,
+    BestForager,
+    DensestCluster,
+}
+
+impl DemoSubject {
+    fn next(self) -> Self {
+        match self {
+            DemoSubject::NewestBirth => DemoSubject::BestForager,
+            DemoSubject::BestForager => DemoSubject::DensestCluster,
+            DemoSubject::DensestCluster => DemoSubject::NewestBirth,
+        }
+    }
+}
+
+/// Camera autopilot for running the simulation on a display without anyone at the keyboard
+struct DemoMode {
+    subject: DemoSubject,
+    started: f64,
+    /// World position the camera is heading for
+    focus: (f32, f32),
+    /// Whether `focus` follows a lifeform rather than a fixed spot
+    following: bool,
+    caption: String,
+}
+
+impl DemoMode {
+    fn new(world: &World, now: f64) -> Self {
+        let mut demo = Self {
+            subject: DemoSubject::DensestCluster,
+            started: now,
+            focus: (0.0, 0.0),
+            following: false,
+            caption: String::new(),
+        };
+        demo.choose(world, DemoSubject::NewestBirth, now);
+        demo
+    }
+
+    /// Pick the best example of `subject` in the world right now
+    fn choose(&mut self, world: &World, subject: DemoSubject, now: f64) {
+        self.subject = subject;
+        self.started = now;
+        let seconds = |ticks: u32| ticks as f64 * world.config.tick_seconds;
+        let lifeforms = &world.lifeforms;
+        match subject {
+            DemoSubject::NewestBirth => {
+                if let Some(l) = lifeforms.iter().min_by_key(|l| l.age) {
+                    self.focus = (l.x, l.y);
+                    self.following = true;
+                    self.caption = format!("Newest arrival, {:.1} s old", seconds(l.age));
+                }
+            }
+            DemoSubject::BestForager => {
+                if let Some(l) = lifeforms
+                    .iter()
+                    .max_by(|a, b| a.energy.total_cmp(&b.energy))
+                {
+                    self.focus = (l.x, l.y);
+                    self.following = true;
+                    self.caption = format!(
+                        "Best forager, {:.0} energy after {:.1} s",
+                        l.energy,
+                        seconds(l.age)
+                    );
+                }
+            }
+            DemoSubject::DensestCluster => {
+                let neighbours = |l: &Lifeform| {
+                    lifeforms
+                        .iter()
+                        .filter(|o| (o.x - l.x).hypot(o.y - l.y) <= DEMO_CLUSTER_RADIUS)
+                        .collect::<Vec<_>>()
+                };
+                if let Some(cluster) = lifeforms.iter().map(neighbours).max_by_key(Vec::len) {
+                    let n = cluster.len() as f32;
+                    let x = cluster.iter().map(|l| l.x).sum::<f32>() / n;
+                    let y = cluster.iter().map(|l| l.y).sum::<f32>() / n;
+                    self.focus = (x, y);
+                    self.following = false;
+                    self.caption = format!("Densest cluster, {} lifeforms", cluster.len());
+                }
+            }
+        }
+    }
+
+    /// Move on to the next subject when its time is up and steer the camera
+    fn update(&mut self, world: &World, camera: &mut Camera, now: f64) {
+        if now - self.started >= DEMO_SUBJECT_SECONDS {
+            self.choose(world, self.subject.next(), now);
         }
-        if is_key_down(KeyCode::E) {
-            self.zoom *= zoom_factor;
-        }
-
-        // Handle mouse wheel for zooming
-        let (_x, wheel_y) = mouse_wheel();
-        if wheel_y != 0.0 {
-            if wheel_y > 0.0 {
-                self.zoom *= 1.1;
-            } else {
-                self.zoom /= 1.1;
-            }
+        // Lifeforms have no stable identity, so follow whoever is closest to the last spot
+        if self.following
+            && let Some(l) = world.lifeforms.iter().min_by(|a, b| {
+                let (fx, fy) = self.focus;
+                (a.x - fx)
+                    .hypot(a.y - fy)
+                    .total_cmp(&(b.x - fx).hypot(b.y - fy))
+            })
+        {
+            self.focus = (l.x, l.y);
         }
+        camera.x += (self.focus.0 - camera.x) * DEMO_CAMERA_EASE;
+        camera.y += (self.focus.1 - camera.y) * DEMO_CAMERA_EASE;
+        camera.zoom += (DEMO_ZOOM - camera.zoom) * DEMO_CAMERA_EASE;
+    }
 
-        // Clamp zoom
-        self.zoom = self.zoom.clamp(0.1, 10.0);
+    fn draw(&self) {
+        let size = 32.0;
+        let dims = measure_text(&self.caption, None, size as u16, 1.0);
+        let x = (screen_width() - dims.width) / 2.0;
+        let y = screen_height() - 60.0;
+        draw_rectangle(
+            x - 16.0,
+            y - dims.height - 12.0,
+            dims.width + 32.0,
+            dims.height + 24.0,
+            Color::new(0.0, 0.0, 0.0, 0.7),
+        );
+        draw_text(&self.caption, x, y, size, WHITE);
     }
 }
 
@@ -465,59 +1302,475 @@ async fn main() {
     info!("Starting bacteria simulation");
 
     let mut camera = Camera::new();
-    let mut lifeforms: Vec<Lifeform> = Vec::new();
-    let mut generation = 0;
-    let mut last_spawn_time = get_time();
+    let mut bookmarks = Bookmarks::load(BOOKMARKS_PATH);
+    let mut presets = Presets::load(PRESETS_PATH);
+    let mut memory_map = lifeform_memory_map();
+    let args: Vec<String> = std::env::args().collect();
+
+    // World tuning from the command line: `--steps-per-tick <n>`, `--tick-budget <ms>`
+    let mut world_config = WorldConfig::default();
+    if let Some(steps) = arg_value(&args, "--steps-per-tick").and_then(|v| v.parse().ok()) {
+        world_config.steps_per_tick = steps;
+    }
+    // `--reproduction <off|fission|conjugation|mating|self-copy>`
+    world_config.reproduction = match arg_value(&args, "--reproduction") {
+        Some("fission") => ReproductionMode::Fission,
+        Some("conjugation") => ReproductionMode::FissionConjugation,
+        Some("mating") => ReproductionMode::Mating,
+        Some("self-copy") => ReproductionMode::SelfCopy,
+        _ => ReproductionMode::Off,
+    };
+    // `--knockout food,reproduction,mutation` starts with those subsystems switched off
+    for name in arg_value(&args, "--knockout").unwrap_or("").split(',') {
+        let subsystems = &mut world_config.subsystems;
+        match name {
+            "food" => subsystems.food_spawning = false,
+            "reproduction" => subsystems.reproduction = false,
+            "mutation" => subsystems.mutation = false,
+            "" => {}
+            other => tracing::warn!("Unknown subsystem {:?}", other),
+        }
+    }
+    // `--rate-limit 252:4,253:4:0.5` accepts one write per 4 ticks to each listed
+    // address; extra writes are ignored, or charged the given energy when a cost is set
+    for spec in arg_value(&args, "--rate-limit").unwrap_or("").split(',') {
+        let parts: Vec<&str> = spec.split(':').collect();
+        let limit = match parts.as_slice() {
+            [addr, period] => addr
+                .parse()
+                .ok()
+                .zip(period.parse().ok())
+                .map(|(addr, period)| RateLimit {
+                    addr,
+                    period,
+                    over_limit: OverLimit::Ignore,
+                }),
+            [addr, period, cost] => addr
+                .parse()
+                .ok()
+                .zip(period.parse().ok())
+                .zip(cost.parse().ok())
+                .map(|((addr, period), cost)| RateLimit {
+                    addr,
+                    period,
+                    over_limit: OverLimit::Charge(cost),
+                }),
+            _ => None,
+        };
+        match limit {
+            Some(limit) => world_config.actuator_limits.push(limit),
+            None if spec.is_empty() => {}
+            None => tracing::warn!("Invalid rate limit {:?}", spec),
+        }
+    }
+    // `--interrupt 64:200` makes every VM jump to address 200 every 64 steps
+    if let Some(spec) = arg_value(&args, "--interrupt") {
+        match spec
+            .split_once(':')
+            .and_then(|(period, vector)| period.parse().ok().zip(vector.parse().ok()))
+        {
+            Some((period, vector)) => {
+                world_config.timer_interrupt = Some(TimerInterrupt { period, vector })
+            }
+            None => tracing::warn!("Invalid interrupt {:?}", spec),
+        }
+    }
+    // `--coroutine 128` starts a second thread at address 128 in every VM
+    if let Some(spec) = arg_value(&args, "--coroutine") {
+        match spec.parse() {
+            Ok(entry) => world_config.coroutine = Some(entry),
+            Err(_) => tracing::warn!("Invalid coroutine entry {:?}", spec),
+        }
+    }
+    // `--loop-detector 16:1024` samples state every 16 steps and finds cycles up to
+    // 1024 samples long; `--loop-detector off` lets stuck programs spin
+    if let Some(spec) = arg_value(&args, "--loop-detector") {
+        let (interval, max_period) = spec.split_once(':').unwrap_or((spec, ""));
+        let default = LoopDetector::default();
+        match (interval.parse(), max_period.parse()) {
+            _ if spec == "off" => world_config.loop_detector = None,
+            (Ok(interval), Ok(max_period)) => {
+                world_config.loop_detector = Some(LoopDetector {
+                    interval,
+                    max_period,
+                    ..default
+                })
+            }
+            (Ok(interval), Err(_)) if max_period.is_empty() => {
+                world_config.loop_detector = Some(LoopDetector {
+                    interval,
+                    ..default
+                })
+            }
+            _ => tracing::warn!("Invalid loop detector {:?}", spec),
+        }
+    }
+    // `--display [addr]` maps an 8x8 display at `addr` (default 212) that programs draw on
+    if args.iter().any(|a| a == "--display") {
+        world_config.display = Some(
+            arg_value(&args, "--display")
+                .and_then(|v| v.parse().ok())
+                .map_or_else(Display::default, Display::new),
+        );
+    }
+    // `--keyboard <joystick|keys>[:addr]` picks what J feeds a steered lifeform: held
+    // arrows and Space as `JOY_*` bits, or the last typed key, at `addr` (default 220)
+    let mut keyboard = Keyboard::new(DEFAULT_KEYBOARD_ADDR, KeyboardMode::Joystick);
+    if let Some(spec) = arg_value(&args, "--keyboard") {
+        let (mode, addr) = spec.split_once(':').unwrap_or((spec, ""));
+        match mode {
+            "joystick" => {}
+            "keys" => keyboard.mode = KeyboardMode::LastKey,
+            _ => tracing::warn!("Invalid keyboard mode {:?}", mode),
+        }
+        if let Ok(addr) = addr.parse() {
+            keyboard.addr = addr;
+        }
+    }
+    // `--sandbox 16:1,2` lets imported, immigrant and invading genomes run 16 steps per
+    // tick and reach only ports 1 and 2; `--sandbox off` trusts every genome
+    if let Some(spec) = arg_value(&args, "--sandbox") {
+        let (steps, ports) = spec.split_once(':').unwrap_or((spec, ""));
+        let ports: Result<Vec<u8>, _> = ports
+            .split(',')
+            .filter(|p| !p.is_empty())
+            .map(str::parse)
+            .collect();
+        match (steps.parse(), ports) {
+            _ if spec == "off" => world_config.sandbox = None,
+            (Ok(max_steps_per_tick), Ok(allowed_ports)) => {
+                world_config.sandbox = Some(Sandbox {
+                    max_steps_per_tick,
+                    allowed_ports,
+                    ..Sandbox::default()
+                })
+            }
+            _ => tracing::warn!("Invalid sandbox {:?}", spec),
+        }
+    }
+    // `--species 0.2` starts a new species when a newborn's genome differs from every
+    // species founder in over 20% of its bytes; `--species off` tracks no species
+    if let Some(spec) = arg_value(&args, "--species") {
+        match spec.parse() {
+            _ if spec == "off" => world_config.speciation = None,
+            Ok(distance) => {
+                world_config.speciation = Some(Speciation {
+                    distance,
+                    ..Speciation::default()
+                })
+            }
+            Err(_) => tracing::warn!("Invalid species distance {:?}", spec),
+        }
+    }
+    // `--food-model nutrients` grows food from excreted waste instead of spawning it
+    if arg_value(&args, "--food-model") == Some("nutrients") {
+        world_config.food_model = FoodModel::NutrientCycling;
+    }
+    // `--curriculum` makes the world harsher in stages as the population gets fitter
+    if args.iter().any(|a| a == "--curriculum") {
+        world_config.curriculum = Some(Curriculum::default());
+    }
+    // `--observe <path>` streams a snapshot every `--observe-interval <seconds>` (default 1)
+    let observer = arg_value(&args, "--observe").and_then(|path| {
+        world_config.observation_interval = Some(
+            arg_value(&args, "--observe-interval")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(1.0),
+        );
+        spawn_observation_writer(path)
+            .inspect(|_| info!("Writing observations to {}", path))
+            .inspect_err(|e| tracing::error!("Failed to create {}: {}", path, e))
+            .ok()
+    });
+    // `--tick-rate <hz>` sets how many ticks make up one simulated second
+    if let Some(hz) = arg_value(&args, "--tick-rate").and_then(|v| v.parse::<f64>().ok()) {
+        world_config.tick_seconds = 1.0 / hz.max(1.0);
+    }
+    world_config.tick_budget_ms = arg_value(&args, "--tick-budget").and_then(|v| v.parse().ok());
+    // `--coarsen <period>` approximates far off-screen lifeforms every `period` ticks
+    if let Some(period) = arg_value(&args, "--coarsen").and_then(|v| v.parse().ok()) {
+        world_config.coarsening = Some(Coarsening {
+            period,
+            ..Coarsening::default()
+        });
+    }
+    // `--target-population <n>` adjusts the food rate to hold the population near `n`
+    if let Some(target) = arg_value(&args, "--target-population").and_then(|v| v.parse().ok()) {
+        world_config.food_controller = Some(FoodController {
+            target_population: target,
+            ..FoodController::default()
+        });
+    }
+    // `--actuator-latency <n>` carries out movement commands `n` updates after they are written
+    if let Some(latency) = arg_value(&args, "--actuator-latency").and_then(|v| v.parse().ok()) {
+        world_config.actuator_latency = latency;
+    }
+    // `--sensor-noise gaussian|uniform[:amount[:dropout]]`, e.g. `uniform:8:0.1`
+    if let Some(spec) = arg_value(&args, "--sensor-noise") {
+        let parts: Vec<&str> = spec.split(':').collect();
+        let mut noise = SensorNoise::default();
+        match parts[0] {
+            "gaussian" => noise.kind = NoiseKind::Gaussian,
+            "uniform" => noise.kind = NoiseKind::Uniform,
+            other => tracing::warn!("Unknown sensor noise {:?}, using gaussian", other),
+        }
+        if let Some(amount) = parts.get(1).and_then(|v| v.parse().ok()) {
+            noise.amount = amount;
+        }
+        if let Some(dropout) = parts.get(2).and_then(|v| v.parse().ok()) {
+            noise.dropout = dropout;
+        }
+        world_config.sensor_noise = Some(noise);
+    }
+    // `--instruction-cost <energy>` charges every executed instruction that much energy
+    if let Some(cost) = arg_value(&args, "--instruction-cost").and_then(|v| v.parse().ok()) {
+        world_config.instruction_costs = Some(CostTable::uniform(cost));
+    }
+    // `--harvard` keeps programs in read-only code memory, away from the data they write
+    world_config.harvard = args.iter().any(|a| a == "--harvard");
+    // `--chunks <distance>` ticks the map chunk by chunk in parallel and freezes chunks
+    // farther than `distance` from the view into `chunks/`
+    if let Some(distance) = arg_value(&args, "--chunks").and_then(|v| v.parse().ok()) {
+        world_config.chunking = Some(Chunking {
+            stream_distance: Some(distance),
+            ..Chunking::default()
+        });
+    }
+    // `--seed <n>` re-runs an earlier session; without it a fresh seed is drawn, so
+    // every run can be reproduced from the seed it logs
+    world_config.seed = Some(
+        arg_value(&args, "--seed")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or_else(SimRng::entropy_seed),
+    );
+    // `--record <path>` logs god-mode actions to a replay file, which keeps the seed so
+    // `--replay <path>` can later re-run the session exactly
+    let mut recording = arg_value(&args, "--record").map(|path| {
+        let replay = Replay::new(world_config.clone());
+        if let Err(e) = replay.save(path) {
+            tracing::error!("Failed to save replay {}: {}", path, e);
+        }
+        info!("Recording interventions to {}", path);
+        (replay, path.to_string())
+    });
+    let mut playback = arg_value(&args, "--replay").and_then(|path| {
+        Replay::load(path)
+            .inspect_err(|e| tracing::error!("Failed to load replay {}: {}", path, e))
+            .ok()
+            .map(Playback::new)
+    });
+    // A replay brings its own config, overriding the command line
+    let mut world = match &playback {
+        Some(playback) => playback.world(),
+        None => World::new(world_config),
+    };
+    info!(
+        "World seed {} (rerun with --seed {})",
+        world.rng.seed(),
+        world.rng.seed()
+    );
+    if let Some(display) = world.config.display {
+        memory_map.declare("display", RegionKind::Display, display.range());
+    }
+    // `--invaders <bundle|dir|genome>` occasionally lands a batch of those genomes
+    if let Some(path) = arg_value(&args, "--invaders") {
+        match load_invaders(Path::new(path)) {
+            Ok(pool) => {
+                info!("Loaded {} invader genomes from {}", pool.len(), path);
+                world.invasion_pool = pool;
+                world
+                    .config
+                    .invasions
+                    .get_or_insert_with(Invasions::default);
+            }
+            Err(e) => tracing::error!("Failed to load invaders from {}: {}", path, e),
+        }
+    }
+    // `--audit` flags energy that appears or vanishes without a recorded cause
+    if args.iter().any(|a| a == "--audit") {
+        world.enable_audit();
+    }
+
+    // Optionally seed the world from a bundle: `--import <path>`
+    if let Some(path) = arg_value(&args, "--import") {
+        match PopulationBundle::load(path) {
+            Ok(bundle) => {
+                bundle.apply_to(&mut world);
+                info!("Imported {} lifeforms from {}", world.lifeforms.len(), path);
+            }
+            Err(e) => tracing::error!("Failed to import {}: {}", path, e),
+        }
+    }
+
+    // After a crash, offer to pick up from the newest autosave
+    if let Some(path) = autosave::recoverable(DEFAULT_AUTOSAVE_DIR)
+        && ask_resume(&path).await
+    {
+        match PopulationBundle::load(&path) {
+            Ok(bundle) => {
+                bundle.apply_to(&mut world);
+                info!(
+                    "Resumed {} lifeforms from {}",
+                    world.lifeforms.len(),
+                    path.display()
+                );
+            }
+            Err(e) => tracing::error!("Failed to resume {}: {}", path.display(), e),
+        }
+    }
+    // `--autosave <seconds>` saves the population in the background at that interval
+    let mut autosaver = arg_value(&args, "--autosave")
+        .and_then(|v| v.parse::<f64>().ok())
+        .and_then(|seconds| {
+            Autosaver::start(
+                DEFAULT_AUTOSAVE_DIR,
+                Duration::from_secs_f64(seconds.max(1.0)),
+                DEFAULT_AUTOSAVE_SLOTS,
+            )
+            .inspect_err(|e| tracing::error!("Failed to start autosave: {}", e))
+            .ok()
+        });
+
+    // Optionally link to another running world: `--listen <addr>` or `--connect <addr>`
+    let migration = if let Some(addr) = arg_value(&args, "--listen") {
+        MigrationLink::listen(addr)
+            .inspect_err(|e| tracing::error!("Failed to listen on {}: {}", addr, e))
+            .ok()
+    } else if let Some(addr) = arg_value(&args, "--connect") {
+        MigrationLink::connect(addr)
+            .inspect_err(|e| tracing::error!("Failed to connect to {}: {}", addr, e))
+            .ok()
+    } else {
+        None
+    };
+    let mut last_migration_time = get_time();
     let mut selected_lifeform: Option<usize> = None;
+    let mut tag_editor: Option<TagEditor> = None;
+    let mut preset_namer: Option<PresetNamer> = None;
+    let mut show_isa_help = false;
+    let mut show_species = true;
+    // Extinctions from before this session, e.g. in an imported world, are not announced
+    let mut extinction_alert = ExtinctionAlert {
+        seen: world.extinction_log.last().copied(),
+        ..ExtinctionAlert::default()
+    };
+    let mut demo = args
+        .iter()
+        .any(|a| a == "--demo")
+        .then(|| DemoMode::new(&world, get_time()));
 
     // Speed control variables
     let mut paused = false;
     let mut step_delay_ms: f64 = 16.0; // Default ~60 FPS
     let mut last_update_time = get_time();
 
-    // Food system variables
-    let mut food_items: Vec<Food> = Vec::new();
-    let mut last_food_spawn_time = get_time();
-    let map_center_x = 0.0;
-    let map_center_y = 0.0;
-
-    // Spawn initial population
-    let mut rng = thread_rng();
-    for _ in 0..INITIAL_POPULATION {
-        let x = rng.gen_range(-200.0..200.0);
-        let y = rng.gen_range(-200.0..200.0);
-        lifeforms.push(Lifeform::new(x, y));
-    }
-
-    // Spawn initial food to ensure minimum count
-    for _ in 0..INITIAL_FOOD_COUNT {
-        let food_x = clamp_to_map_bounds(normal_random(map_center_x, FOOD_DISTRIBUTION_STD, &mut rng));
-        let food_y = clamp_to_map_bounds(normal_random(map_center_y, FOOD_DISTRIBUTION_STD, &mut rng));
-        let food = Food::new_random(food_x, food_y, &mut rng);
-        food_items.push(food);
-    }
-
     loop {
         clear_background(BLACK);
 
-        // Update camera
-        camera.update();
+        // While a tag or preset is being named the keyboard belongs to the editor
+        let editing = tag_editor.is_some() || preset_namer.is_some();
+        if let Some(namer) = &mut preset_namer {
+            match namer.update() {
+                TagEditOutcome::Editing => {}
+                TagEditOutcome::Commit => {
+                    let preset = Preset::capture(namer.name.clone(), step_delay_ms, &world);
+                    presets.slots[namer.slot] = Some(preset);
+                    presets.active = namer.slot;
+                    match presets.save(PRESETS_PATH) {
+                        Ok(()) => info!(
+                            "Saved preset {} ({})",
+                            PRESET_LABELS[namer.slot], namer.name
+                        ),
+                        Err(e) => tracing::error!("Failed to save presets: {}", e),
+                    }
+                    preset_namer = None;
+                }
+                TagEditOutcome::Cancel => preset_namer = None,
+            }
+        }
+        if let Some(editor) = &mut tag_editor {
+            match editor.update() {
+                TagEditOutcome::Editing => {}
+                TagEditOutcome::Commit => {
+                    if let Some(lifeform) = world.lifeforms.get_mut(editor.target) {
+                        info!("Tagged lifeform {} as {:?}", editor.target, editor.tag.name);
+                        lifeform.tag = Some(editor.tag.clone());
+                    }
+                    tag_editor = None;
+                }
+                TagEditOutcome::Cancel => tag_editor = None,
+            }
+        }
+
+        // J hands the keyboard to the selected lifeform's program; J or Esc takes it back
+        let steering = world.lifeforms.iter().any(|l| l.keyboard.is_some());
+        if steering && (is_key_pressed(KeyCode::J) || is_key_pressed(KeyCode::Escape)) {
+            world.lifeforms.iter_mut().for_each(|l| l.keyboard = None);
+            info!("Keyboard released");
+        } else if !editing
+            && !steering
+            && is_key_pressed(KeyCode::J)
+            && let Some(idx) = selected_lifeform
+            && let Some(lifeform) = world.lifeforms.get_mut(idx)
+        {
+            // Drop the J itself so it does not reach the program as a key press
+            while get_char_pressed().is_some() {}
+            lifeform.keyboard = Some(keyboard.clone());
+            info!(
+                "Steering lifeform {} ({:?} at {})",
+                idx, keyboard.mode, keyboard.addr
+            );
+        }
+        // While steering, the arrows, Space and typed keys belong to the program
+        let keys_captured = editing || steering;
+        if let Some(lifeform) = world.lifeforms.iter_mut().find(|l| l.keyboard.is_some())
+            && let Some(input) = &mut lifeform.keyboard
+        {
+            let held = [
+                (KeyCode::Left, JOY_LEFT),
+                (KeyCode::Right, JOY_RIGHT),
+                (KeyCode::Up, JOY_UP),
+                (KeyCode::Down, JOY_DOWN),
+                (KeyCode::Space, JOY_FIRE),
+            ]
+            .iter()
+            .filter(|(key, _)| is_key_down(*key))
+            .fold(0, |bits, (_, bit)| bits | bit);
+            input.set_joystick(held);
+            while let Some(c) = get_char_pressed() {
+                if c.is_ascii() {
+                    input.press(c as u8);
+                }
+            }
+        }
+
+        // Update camera, keeping a steered lifeform in view
+        if let Some(lifeform) = world.lifeforms.iter().find(|l| l.keyboard.is_some()) {
+            camera.x = lifeform.x;
+            camera.y = lifeform.y;
+        } else if !editing {
+            match &mut demo {
+                Some(demo) => demo.update(&world, &mut camera, get_time()),
+                None => camera.update(),
+            }
+        }
+        world.viewport = Some(camera.viewport());
 
         // Speed control with arrow keys and pause functionality
-        if is_key_pressed(KeyCode::Space) {
+        if !keys_captured && is_key_pressed(KeyCode::Space) {
             paused = !paused;
             info!("Simulation {}", if paused { "paused" } else { "running" });
         }
 
         // Adjust step_delay_ms with left/right arrows
-        if is_key_pressed(KeyCode::Right) {
+        if !keys_captured && is_key_pressed(KeyCode::Right) {
             step_delay_ms = (step_delay_ms * 2.0).min(2000.0); // Max 2 seconds between steps
             info!(
                 "Simulation speed decreased: {} ms between steps",
                 step_delay_ms
             );
         }
-        if is_key_pressed(KeyCode::Left) {
+        if !keys_captured && is_key_pressed(KeyCode::Left) {
             step_delay_ms = (step_delay_ms / 2.0).max(1.0); // Min 1ms between steps
             info!(
                 "Simulation speed increased: {} ms between steps",
@@ -525,9 +1778,53 @@ async fn main() {
             );
         }
 
+        // Live mutation rate with [ and ], food rate with - and =
+        if !keys_captured {
+            let (min_mutation, max_mutation) = MUTATION_RATE_RANGE;
+            let mutation = world.config.mutation_rate.unwrap_or(LIVE_MUTATION_RATE);
+            let scaled_mutation = if is_key_pressed(KeyCode::LeftBracket) {
+                Some(mutation / 2.0)
+            } else if is_key_pressed(KeyCode::RightBracket) {
+                Some(mutation * 2.0)
+            } else {
+                None
+            };
+            if let Some(rate) = scaled_mutation {
+                let rate = rate.clamp(min_mutation, max_mutation);
+                world.config.mutation_rate = Some(rate);
+                info!("Mutation rate: {:.1}% of bytes", rate * 100.0);
+            }
+
+            let (min_food, max_food) = FOOD_RATE_RANGE;
+            let scaled_food = if is_key_pressed(KeyCode::Minus) {
+                Some(world.food_rate() / 2.0)
+            } else if is_key_pressed(KeyCode::Equal) {
+                Some(world.food_rate() * 2.0)
+            } else {
+                None
+            };
+            if let Some(rate) = scaled_food {
+                world.set_food_rate(rate.clamp(min_food, max_food));
+                info!("Food rate: x{:.2}", world.food_rate());
+            }
+        }
+
+        // P flips between presets A and B; Ctrl+P names and saves the live parameters
+        if !keys_captured && is_key_pressed(KeyCode::P) {
+            if is_key_down(KeyCode::LeftControl) || is_key_down(KeyCode::RightControl) {
+                let slot = presets.active;
+                preset_namer = Some(PresetNamer::new(slot, presets.slots[slot].as_ref()));
+            } else {
+                presets.toggle(&mut step_delay_ms, &mut world);
+            }
+        }
+
         // Update simulation based on timing and pause state
         let current_time = get_time();
-        let should_update = if paused {
+        // The world holds still while tagging so the target index stays valid
+        let should_update = if editing {
+            false
+        } else if paused {
             // When paused, only update on 's' key press (single step)
             is_key_pressed(KeyCode::S)
         } else {
@@ -537,9 +1834,7 @@ async fn main() {
 
         if should_update {
             // Update all lifeforms with sensory input
-            for lifeform in &mut lifeforms {
-                lifeform.update(&food_items);
-            }
+            world.tick();
             last_update_time = current_time;
 
             if paused && is_key_pressed(KeyCode::S) {
@@ -547,91 +1842,194 @@ async fn main() {
             }
         }
 
-        // Food spawning (ensure minimum food count and spawn periodically using normal distribution)
-        let current_time = get_time();
-
-        // Check if we need to spawn food (either time-based or to maintain minimum count)
-        let should_spawn_food = (current_time - last_food_spawn_time >= FOOD_SPAWN_INTERVAL)
-            || (food_items.len() < MIN_FOOD_COUNT);
+        // Recall a camera bookmark, or save one with Ctrl held
+        if !keys_captured {
+            let saving = is_key_down(KeyCode::LeftControl) || is_key_down(KeyCode::RightControl);
+            for (slot, key) in BOOKMARK_KEYS.iter().enumerate() {
+                if !is_key_pressed(*key) {
+                    continue;
+                }
+                if saving {
+                    // Name the spot after the tagged lifeform being watched, if any
+                    let name = selected_lifeform
+                        .and_then(|idx| world.lifeforms.get(idx))
+                        .and_then(|l| l.tag.as_ref())
+                        .map_or_else(|| format!("Bookmark {}", slot + 1), |t| t.name.clone());
+                    bookmarks.slots[slot] = Some(Bookmark {
+                        name,
+                        x: camera.x,
+                        y: camera.y,
+                        zoom: camera.zoom,
+                    });
+                    match bookmarks.save(BOOKMARKS_PATH) {
+                        Ok(()) => info!("Saved camera bookmark {}", slot + 1),
+                        Err(e) => tracing::error!("Failed to save bookmarks: {}", e),
+                    }
+                } else if let Some(bookmark) = &bookmarks.slots[slot] {
+                    camera.jump_to(bookmark);
+                    info!("Jumped to bookmark {} ({})", slot + 1, bookmark.name);
+                }
+            }
+        }
 
-        if should_spawn_food {
-            // Calculate how many food items to spawn
-            let food_count = if food_items.len() < MIN_FOOD_COUNT {
-                // Spawn enough to reach minimum count, plus 1-3 extra
-                (MIN_FOOD_COUNT - food_items.len()) + rng.gen_range(1..=3)
-            } else {
-                // Regular spawning: 1-3 food items
-                rng.gen_range(1..=3)
+        if !keys_captured && is_key_pressed(KeyCode::G) {
+            demo = match demo {
+                Some(_) => None,
+                None => Some(DemoMode::new(&world, get_time())),
             };
+            info!("Demo mode {}", if demo.is_some() { "on" } else { "off" });
+        }
 
-            for _ in 0..food_count {
-                let food_x = clamp_to_map_bounds(normal_random(map_center_x, FOOD_DISTRIBUTION_STD, &mut rng));
-                let food_y = clamp_to_map_bounds(normal_random(map_center_y, FOOD_DISTRIBUTION_STD, &mut rng));
-                let food = Food::new_random(food_x, food_y, &mut rng);
-                food_items.push(food);
-            }
-            last_food_spawn_time = current_time;
+        if !keys_captured && is_key_pressed(KeyCode::H) {
+            show_isa_help = !show_isa_help;
         }
 
-        // Food consumption (check collisions between lifeforms and food)
-        for lifeform in &mut lifeforms {
-            let mut eaten_food_indices = Vec::new();
+        if !keys_captured && is_key_pressed(KeyCode::L) {
+            show_species = !show_species;
+        }
 
-            for (i, food) in food_items.iter().enumerate() {
-                if lifeform.can_eat_food(food) {
-                    lifeform.eat_food(food);
-                    eaten_food_indices.push(i);
-                    // Optional: log food consumption for debugging
-                    // info!("Lifeform at ({:.1}, {:.1}) ate food worth {:.1} energy",
-                    //       lifeform.x, lifeform.y, food.energy_value);
-                }
-            }
+        // Tag the selected lifeform
+        if !keys_captured
+            && is_key_pressed(KeyCode::T)
+            && let Some(idx) = selected_lifeform
+            && let Some(lifeform) = world.lifeforms.get(idx)
+        {
+            tag_editor = Some(TagEditor::new(idx, lifeform.tag.as_ref()));
+        }
 
-            // Remove eaten food (in reverse order to maintain indices)
-            for &i in eaten_food_indices.iter().rev() {
-                food_items.remove(i);
+        // Knock out individual subsystems
+        if !keys_captured {
+            let subsystems = &mut world.config.subsystems;
+            for (key, name, enabled) in [
+                (KeyCode::F1, "Food spawning", &mut subsystems.food_spawning),
+                (KeyCode::F2, "Reproduction", &mut subsystems.reproduction),
+                (KeyCode::F3, "Mutation", &mut subsystems.mutation),
+            ] {
+                if is_key_pressed(key) {
+                    *enabled = !*enabled;
+                    info!("{} {}", name, if *enabled { "enabled" } else { "disabled" });
+                }
             }
         }
 
-        // Remove dead lifeforms
-        let alive_count = lifeforms.len();
-        lifeforms.retain(|l| l.is_alive());
-        let died_count = alive_count - lifeforms.len();
-
-        if died_count > 0 {
-            info!("Generation {}: {} lifeforms died", generation, died_count);
+        // Cycle through reproduction modes
+        if !keys_captured && is_key_pressed(KeyCode::M) {
+            world.config.reproduction = match world.config.reproduction {
+                ReproductionMode::Off => ReproductionMode::Fission,
+                ReproductionMode::Fission => ReproductionMode::FissionConjugation,
+                ReproductionMode::FissionConjugation => ReproductionMode::Mating,
+                ReproductionMode::Mating => ReproductionMode::SelfCopy,
+                ReproductionMode::SelfCopy => ReproductionMode::Off,
+            };
+            info!("Reproduction mode: {}", world.config.reproduction);
         }
 
-        // Spawn new lifeforms periodically or when population is low
-        let current_time = get_time();
-        if (current_time - last_spawn_time > 5.0 && lifeforms.len() < 10) || lifeforms.is_empty() {
-            if lifeforms.is_empty() {
-                generation += 1;
-                info!("Starting generation {}", generation);
+        // Export/import the population bundle
+        if !keys_captured && is_key_pressed(KeyCode::F5) {
+            match PopulationBundle::from_world(&world).save(POPULATION_BUNDLE_PATH) {
+                Ok(()) => info!(
+                    "Exported {} lifeforms to {}",
+                    world.lifeforms.len(),
+                    POPULATION_BUNDLE_PATH
+                ),
+                Err(e) => tracing::error!("Failed to export population: {}", e),
+            }
+        }
+        if !keys_captured && is_key_pressed(KeyCode::F9) {
+            match PopulationBundle::load(POPULATION_BUNDLE_PATH) {
+                Ok(bundle) => {
+                    bundle.apply_to(&mut world);
+                    selected_lifeform = None;
+                    info!(
+                        "Imported {} lifeforms from {}",
+                        world.lifeforms.len(),
+                        POPULATION_BUNDLE_PATH
+                    );
+                }
+                Err(e) => tracing::error!("Failed to import population: {}", e),
             }
+        }
 
-            // Spawn new random lifeforms
-            for _ in 0..5 {
-                let x = rng.gen_range(-MAP_BOUNDARY..MAP_BOUNDARY);
-                let y = rng.gen_range(-MAP_BOUNDARY..MAP_BOUNDARY);
-                lifeforms.push(Lifeform::new(x, y));
+        // Exchange creatures with the linked world
+        if !editing && let Some(link) = &migration {
+            let immigrants = link.receive_immigrants();
+            if !immigrants.is_empty() {
+                info!("{} immigrants arrived", immigrants.len());
+                world.admit_immigrants(&immigrants);
+            }
+            while let Some(e) = link.take_error() {
+                tracing::warn!("Migration: {}", e);
+            }
+            if link.is_connected() && get_time() - last_migration_time >= MIGRATION_INTERVAL {
+                let emigrants = world.take_emigrants(EMIGRANTS_PER_BATCH);
+                if !emigrants.is_empty() {
+                    info!("{} lifeforms emigrated", emigrants.len());
+                    // Emigration reorders the population, so indices are no longer valid
+                    selected_lifeform = None;
+                }
+                if let Err(e) = link.send_emigrants(emigrants) {
+                    tracing::warn!("Emigrants lost: {}", e);
+                }
+                last_migration_time = get_time();
             }
+        }
 
-            last_spawn_time = current_time;
+        // God mode: right click drops food, N spawns a lifeform at the cursor,
+        // K kills the selected one
+        if !editing {
+            let (mouse_x, mouse_y) = mouse_position();
+            let (world_x, world_y) = camera.screen_to_world(mouse_x, mouse_y);
+            if is_mouse_button_pressed(MouseButton::Right) {
+                let action = Intervention::DropFood {
+                    x: world_x,
+                    y: world_y,
+                    energy: DROPPED_FOOD_ENERGY,
+                };
+                intervene(&mut world, &mut recording, action);
+            }
+            if !keys_captured && is_key_pressed(KeyCode::N) {
+                let action = Intervention::Spawn {
+                    x: world_x,
+                    y: world_y,
+                };
+                intervene(&mut world, &mut recording, action);
+            }
+            if !keys_captured
+                && is_key_pressed(KeyCode::K)
+                && let Some(index) = selected_lifeform.take()
+            {
+                intervene(&mut world, &mut recording, Intervention::Kill { index });
+            }
+        }
+        // Food, eating, deaths and respawns are paced by simulated time; replayed
+        // actions land on the same side of the ecology update as when recorded
+        if let Some(playback) = &mut playback {
+            playback.apply_due(&mut world);
+        }
+        if !editing {
+            world.update_ecology();
+        }
+        extinction_alert.update(&world.extinction_log, get_time());
+        if let Some(playback) = &mut playback {
+            playback.apply_due(&mut world);
+        }
+        if let Some(observer) = &observer {
+            let snapshots = world.drain_observations();
+            if !snapshots.is_empty() {
+                let _ = observer.send(snapshots);
+            }
         }
 
         // Handle mouse clicks to select lifeforms
-        if is_mouse_button_pressed(MouseButton::Left) {
+        if !editing && is_mouse_button_pressed(MouseButton::Left) {
             let (mouse_x, mouse_y) = mouse_position();
             selected_lifeform = None;
 
-            // Convert mouse position to world coordinates
-            let world_x = (mouse_x - screen_width() / 2.0) / camera.zoom + camera.x;
-            let world_y = (mouse_y - screen_height() / 2.0) / camera.zoom + camera.y;
+            let (world_x, world_y) = camera.screen_to_world(mouse_x, mouse_y);
 
             // Find the closest lifeform within clicking distance
             let click_radius = 20.0 / camera.zoom; // Adjust click radius based on zoom
-            for (idx, lifeform) in lifeforms.iter().enumerate() {
+            for (idx, lifeform) in world.lifeforms.iter().enumerate() {
                 let dx = lifeform.x - world_x;
                 let dy = lifeform.y - world_y;
                 let distance = (dx * dx + dy * dy).sqrt();
@@ -647,9 +2045,19 @@ async fn main() {
             }
         }
 
+        if world.config.food_model == FoodModel::NutrientCycling {
+            draw_nutrients(&world.nutrients, camera.x, camera.y, camera.zoom);
+        }
+
         // Draw all lifeforms
-        for (idx, lifeform) in lifeforms.iter().enumerate() {
-            lifeform.draw(camera.x, camera.y, camera.zoom);
+        for (idx, lifeform) in world.lifeforms.iter().enumerate() {
+            draw_lifeform(
+                lifeform,
+                world.config.display,
+                camera.x,
+                camera.y,
+                camera.zoom,
+            );
 
             // Highlight selected lifeform
             if Some(idx) == selected_lifeform {
@@ -661,8 +2069,8 @@ async fn main() {
         }
 
         // Draw all food items
-        for food in &food_items {
-            food.draw(camera.x, camera.y, camera.zoom);
+        for food in &world.food_items {
+            draw_food(food, camera.x, camera.y, camera.zoom);
         }
 
         // Draw world bounds
@@ -684,21 +2092,76 @@ async fn main() {
 
         // Draw UI
         draw_text(
-            &format!("Generation: {}", generation),
+            &format!("Generation: {}", world.generation),
             10.0,
             30.0,
             20.0,
             WHITE,
         );
+        if let Some(audit) = &world.audit {
+            draw_text(
+                &match audit.leaks.last() {
+                    Some(leak) => format!(
+                        "Energy audit: {} leaks, last {:+.2} at tick {}",
+                        audit.leak_count,
+                        leak.amount(),
+                        leak.tick
+                    ),
+                    None => format!("Energy audit: {} checks, no leaks", audit.checks),
+                },
+                200.0,
+                30.0,
+                16.0,
+                if audit.leak_count > 0 { RED } else { GREEN },
+            );
+        }
         draw_text(
-            &format!("Lifeforms: {}", lifeforms.len()),
+            &format!("Lifeforms: {}", world.lifeforms.len()),
             10.0,
             50.0,
             20.0,
             WHITE,
         );
+        if let Some(curriculum) = &world.config.curriculum {
+            let difficulty = world.difficulty();
+            draw_text(
+                &format!(
+                    "Curriculum: stage {}/{} | fitness {:.1} s | scarcity x{:.1}, range x{:.2}, toxins {:.0}%",
+                    world.curriculum_stage(),
+                    curriculum.stages.len(),
+                    world.fitness(),
+                    difficulty.food_scarcity,
+                    difficulty.detection_range,
+                    difficulty.toxin_chance * 100.0
+                ),
+                200.0,
+                50.0,
+                16.0,
+                SKYBLUE,
+            );
+        }
+        if let Some(controller) = &world.config.food_controller {
+            draw_text(
+                &format!(
+                    "Food rate: x{:.2} (target population {})",
+                    world.food_rate(),
+                    controller.target_population
+                ),
+                300.0,
+                70.0,
+                16.0,
+                GREEN,
+            );
+        }
         draw_text(
-            &format!("Food: {}", food_items.len()),
+            &match world.config.food_model {
+                FoodModel::GaussianSpawner => format!("Food: {}", world.food_items.len()),
+                FoodModel::NutrientCycling => format!(
+                    "Food: {} | Nutrients: {:.0}",
+                    world.food_items.len(),
+                    world.nutrients.total()
+                ),
+            },
             10.0,
             70.0,
             20.0,
@@ -726,47 +2189,154 @@ async fn main() {
             status_color,
         );
         draw_text(
-            &format!("Speed: {:.1} ms/step", step_delay_ms),
+            &if world.config.tick_budget_ms.is_some() {
+                let throttle = world.throttle();
+                format!(
+                    "Speed: {:.1} ms/step | {} VM steps/tick{}",
+                    step_delay_ms,
+                    throttle.steps,
+                    if throttle.defer_offscreen {
+                        " (deferring off-screen)"
+                    } else {
+                        ""
+                    }
+                )
+            } else {
+                format!("Speed: {:.1} ms/step", step_delay_ms)
+            },
             10.0,
             130.0,
             16.0,
             WHITE,
         );
 
-        draw_text("Controls:", 10.0, 150.0, 16.0, YELLOW);
+        let stats = world.reproduction_stats;
         draw_text(
-            "WASD = Camera, Q/E/Scroll = Zoom",
+            &format!(
+                "Reproduction: {} | fissions {}, conjugations {}, matings {}, self-copies {}",
+                world.config.reproduction,
+                stats.fissions,
+                stats.conjugations,
+                stats.matings,
+                stats.replications
+            ),
             10.0,
-            170.0,
-            14.0,
-            LIGHTGRAY,
+            150.0,
+            16.0,
+            WHITE,
         );
+
+        let subsystems = world.config.subsystems;
+        let on_off = |enabled: bool| if enabled { "on" } else { "OFF" };
         draw_text(
-            "SPACE = Pause/Unpause, S = Single Step",
+            &format!(
+                "Food spawning: {} | Reproduction: {} | Mutation: {}",
+                on_off(subsystems.food_spawning),
+                on_off(subsystems.reproduction),
+                on_off(subsystems.mutation)
+            ),
             10.0,
-            185.0,
-            14.0,
-            LIGHTGRAY,
+            170.0,
+            16.0,
+            if subsystems == Subsystems::default() {
+                WHITE
+            } else {
+                ORANGE
+            },
         );
+
+        let preset_label = PRESET_LABELS[presets.active];
         draw_text(
-            "Left/Right Arrows = Speed Control",
+            &format!(
+                "Mutation: {} | Food rate: x{:.2} | Preset {}{}",
+                world
+                    .config
+                    .mutation_rate
+                    .map_or_else(|| "1-10%".to_string(), |r| format!("{:.1}%", r * 100.0)),
+                world.food_rate(),
+                preset_label,
+                presets.slots[presets.active]
+                    .as_ref()
+                    .map_or_else(|| " (unsaved)".to_string(), |p| format!(": {}", p.name))
+            ),
             10.0,
-            200.0,
-            14.0,
-            LIGHTGRAY,
+            190.0,
+            16.0,
+            WHITE,
         );
-        draw_text(
+
+        draw_text("Controls:", 10.0, 210.0, 16.0, YELLOW);
+        let controls = [
+            "WASD = Camera, Q/E/Scroll = Zoom",
+            "SPACE = Pause/Unpause, S = Single Step",
+            "Left/Right Arrows = Speed Control",
+            "[/] = Mutation rate, -/= = Food rate",
+            "P = Switch preset A/B, Ctrl+P = Save preset",
+            "M = Cycle reproduction mode",
+            "T = Tag selected lifeform, H = Instruction set help, G = Demo mode",
+            "L = Toggle species panels",
+            "1-9 = Jump to camera bookmark, Ctrl+1-9 = Save bookmark",
+            "F1/F2/F3 = Toggle food spawning/reproduction/mutation",
+            "F5 = Export population, F9 = Import population",
+            "Right click = Drop food, N = Spawn lifeform, K = Kill selected",
+            "J = Steer selected lifeform with arrows/Space, J/Esc = Release",
             "Click on a lifeform to inspect its VM",
-            10.0,
-            215.0,
-            14.0,
-            LIGHTGRAY,
-        );
+        ];
+        for (i, line) in controls.iter().enumerate() {
+            draw_text(line, 10.0, 230.0 + i as f32 * 15.0, 14.0, LIGHTGRAY);
+        }
+
+        // Oldest lifeforms, with their tags so followed individuals stand out
+        let leaderboard_y = 240.0 + controls.len() as f32 * 15.0;
+        draw_text("Oldest lifeforms:", 10.0, leaderboard_y, 16.0, YELLOW);
+        for (rank, idx) in world.leaderboard(LEADERBOARD_SIZE).into_iter().enumerate() {
+            let lifeform = &world.lifeforms[idx];
+            let (label, color) = match &lifeform.tag {
+                Some(tag) => {
+                    let [r, g, b] = tag.color;
+                    (format!(" {}", tag.name), Color::new(r, g, b, 1.0))
+                }
+                None => (String::new(), LIGHTGRAY),
+            };
+            draw_text(
+                &format!(
+                    "{}. #{} age {} energy {:.0}{}",
+                    rank + 1,
+                    idx,
+                    lifeform.age,
+                    lifeform.energy,
+                    label
+                ),
+                10.0,
+                leaderboard_y + 20.0 + rank as f32 * 15.0,
+                14.0,
+                color,
+            );
+        }
+
+        // Saved camera bookmarks below the leaderboard
+        let bookmarks_y = leaderboard_y + 30.0 + LEADERBOARD_SIZE as f32 * 15.0;
+        draw_text("Bookmarks:", 10.0, bookmarks_y, 16.0, YELLOW);
+        for (row, (slot, bookmark)) in bookmarks
+            .slots
+            .iter()
+            .enumerate()
+            .filter_map(|(i, b)| b.as_ref().map(|b| (i, b)))
+            .enumerate()
+        {
+            draw_text(
+                &format!("{}. {}", slot + 1, bookmark.name),
+                10.0,
+                bookmarks_y + 20.0 + row as f32 * 15.0,
+                14.0,
+                LIGHTGRAY,
+            );
+        }
 
         // Draw VM inspector panel if a lifeform is selected
         if let Some(selected_idx) = selected_lifeform {
-            if selected_idx < lifeforms.len() {
-                let lifeform = &lifeforms[selected_idx];
+            if selected_idx < world.lifeforms.len() {
+                let lifeform = &world.lifeforms[selected_idx];
 
                 // Draw VM panel background
                 let panel_size = 300.0;
@@ -777,21 +2347,29 @@ async fn main() {
                     panel_x - 10.0,
                     panel_y - 10.0,
                     panel_size + 20.0,
-                    panel_size + 140.0, // Increased height for sensory info
+                    panel_size + 280.0, // Room for sensory info, telemetry and tag
                     Color::new(0.0, 0.0, 0.0, 0.8),
                 );
                 draw_rectangle_lines(
                     panel_x - 10.0,
                     panel_y - 10.0,
                     panel_size + 20.0,
-                    panel_size + 140.0, // Increased height for sensory info
+                    panel_size + 280.0, // Room for sensory info, telemetry and tag
                     2.0,
                     WHITE,
                 );
 
                 // Draw lifeform info
+                let sandbox_text = if lifeform.sandboxed {
+                    " (sandboxed)"
+                } else {
+                    ""
+                };
+                let species_text = lifeform
+                    .species
+                    .map_or_else(String::new, |id| format!(" | species #{}", id));
                 draw_text(
-                    &format!("Lifeform #{}", selected_idx),
+                    &format!("Lifeform #{}{}{}", selected_idx, species_text, sandbox_text),
                     panel_x,
                     panel_y - 5.0,
                     18.0,
@@ -804,8 +2382,16 @@ async fn main() {
                     14.0,
                     WHITE,
                 );
+                let keyboard_text = match &lifeform.keyboard {
+                    Some(input) => format!(
+                        " | Keyboard @{}: 0x{:02X}",
+                        input.addr,
+                        lifeform.vm.data().get(input.addr).copied().unwrap_or(0)
+                    ),
+                    None => String::new(),
+                };
                 draw_text(
-                    &format!("Age: {}", lifeform.age),
+                    &format!("Age: {}{}", lifeform.age, keyboard_text),
                     panel_x,
                     panel_y + 30.0,
                     14.0,
@@ -819,14 +2405,28 @@ async fn main() {
                     WHITE,
                 );
                 draw_text(
-                    &format!("VM Steps: {}", lifeform.vm.total_steps_count),
+                    &format!(
+                        "VM Steps: {} | ACC: {} | X: {} | Y: {}",
+                        lifeform.vm.total_steps_count,
+                        thread_registers(&lifeform.vm, |t| t.acc as usize),
+                        thread_registers(&lifeform.vm, |t| t.x as usize),
+                        thread_registers(&lifeform.vm, |t| t.y as usize)
+                    ),
                     panel_x,
                     panel_y + 60.0,
                     14.0,
                     WHITE,
                 );
                 draw_text(
-                    &format!("PC: {}", lifeform.vm.pc),
+                    &format!(
+                        "PC: {} | SP: {} | DSP: {} | Flags: {} | Last tick: {} instr | Budget: {}",
+                        thread_registers(&lifeform.vm, |t| t.pc),
+                        lifeform.vm.sp,
+                        lifeform.vm.dsp,
+                        flags_text(lifeform.vm.flags),
+                        lifeform.vm.data()[SELF_INSTRUCTIONS_ADDR],
+                        lifeform.vm.data()[STEP_BUDGET_ADDR]
+                    ),
                     panel_x,
                     panel_y + 75.0,
                     14.0,
@@ -834,23 +2434,37 @@ async fn main() {
                 );
 
                 // Display sensory input values
-                let food_x_value = lifeform.vm.memory[FOOD_DISTANCE_X_ADDR];
-                let food_y_value = lifeform.vm.memory[FOOD_DISTANCE_Y_ADDR];
+                let food_x_value = lifeform.vm.data()[FOOD_DISTANCE_X_ADDR];
+                let food_y_value = lifeform.vm.data()[FOOD_DISTANCE_Y_ADDR];
                 draw_text(
-                    &format!("Food Sense X: {} ({})", food_x_value, 
-                        if food_x_value < 128 { "Left" } 
-                        else if food_x_value > 128 { "Right" } 
-                        else { "Neutral" }),
+                    &format!(
+                        "Food Sense X: {} ({})",
+                        food_x_value,
+                        if food_x_value < 128 {
+                            "Left"
+                        } else if food_x_value > 128 {
+                            "Right"
+                        } else {
+                            "Neutral"
+                        }
+                    ),
                     panel_x,
                     panel_y + 90.0,
                     12.0,
                     SKYBLUE,
                 );
                 draw_text(
-                    &format!("Food Sense Y: {} ({})", food_y_value,
-                        if food_y_value < 128 { "Up" } 
-                        else if food_y_value > 128 { "Down" } 
-                        else { "Neutral" }),
+                    &format!(
+                        "Food Sense Y: {} ({})",
+                        food_y_value,
+                        if food_y_value < 128 {
+                            "Up"
+                        } else if food_y_value > 128 {
+                            "Down"
+                        } else {
+                            "Neutral"
+                        }
+                    ),
                     panel_x,
                     panel_y + 105.0,
                     12.0,
@@ -858,7 +2472,33 @@ async fn main() {
                 );
 
                 // Draw the VM memory grid
-                draw_vm(&lifeform.vm, panel_x, panel_y + 120.0, panel_size, 1.0);
+                draw_vm(
+                    &lifeform.vm,
+                    &memory_map,
+                    panel_x,
+                    panel_y + 120.0,
+                    panel_size,
+                    1.0,
+                );
+
+                // Draw recent telemetry history below the grid
+                draw_telemetry(
+                    &lifeform.telemetry,
+                    panel_x,
+                    panel_y + panel_size + 125.0,
+                    panel_size,
+                );
+
+                if let Some(tag) = &lifeform.tag {
+                    let [r, g, b] = tag.color;
+                    draw_text(
+                        &format!("Tag: {} - {}", tag.name, tag.note),
+                        panel_x,
+                        panel_y + panel_size + 260.0,
+                        14.0,
+                        Color::new(r, g, b, 1.0),
+                    );
+                }
             } else {
                 // Selected lifeform no longer exists (probably died)
                 selected_lifeform = None;
@@ -869,14 +2509,14 @@ async fn main() {
         draw_text(
             "Memory-Mapped I/O:",
             10.0,
-            screen_height() - 120.0,
+            screen_height() - 135.0,
             16.0,
             YELLOW,
         );
         draw_text(
             "Movement (Comparative):",
             10.0,
-            screen_height() - 100.0,
+            screen_height() - 115.0,
             14.0,
             YELLOW,
         );
@@ -886,21 +2526,21 @@ async fn main() {
                 MOVE_LEFT_ADDR, MOVE_RIGHT_ADDR
             ),
             10.0,
-            screen_height() - 80.0,
+            screen_height() - 95.0,
             12.0,
             LIGHTGRAY,
         );
         draw_text(
             &format!("Up: addr {} | Down: addr {}", MOVE_UP_ADDR, MOVE_DOWN_ADDR),
             10.0,
-            screen_height() - 65.0,
+            screen_height() - 80.0,
             12.0,
             LIGHTGRAY,
         );
         draw_text(
             "Sensory Input:",
             10.0,
-            screen_height() - 45.0,
+            screen_height() - 60.0,
             14.0,
             SKYBLUE,
         );
@@ -910,6 +2550,16 @@ async fn main() {
                 FOOD_DISTANCE_X_ADDR, FOOD_DISTANCE_Y_ADDR
             ),
             10.0,
+            screen_height() - 40.0,
+            12.0,
+            LIGHTGRAY,
+        );
+        draw_text(
+            &format!(
+                "Self: instr last tick addr {} | Step budget addr {}",
+                SELF_INSTRUCTIONS_ADDR, STEP_BUDGET_ADDR
+            ),
+            10.0,
             screen_height() - 25.0,
             12.0,
             LIGHTGRAY,
@@ -922,11 +2572,64 @@ async fn main() {
             LIGHTGRAY,
         );
 
+        // The VM inspector takes over the right side while a lifeform is selected
+        if show_species && selected_lifeform.is_none() && world.config.speciation.is_some() {
+            draw_species_panels(world.species());
+        }
+        extinction_alert.draw(get_time());
+        if let Some(demo) = &demo {
+            demo.draw();
+        }
+        if show_isa_help {
+            draw_isa_help();
+        }
+        if let Some(editor) = &tag_editor {
+            editor.draw();
+        }
+        if let Some(namer) = &preset_namer {
+            namer.draw();
+        }
+
+        if let Some(autosaver) = &mut autosaver {
+            autosaver.tick(&world);
+        }
+
         // ESC to quit
-        if is_key_pressed(KeyCode::Escape) {
+        if !keys_captured && is_key_pressed(KeyCode::Escape) {
             break;
         }
 
         next_frame().await
     }
+    if let Some(autosaver) = autosaver {
+        autosaver.finish();
+    }
+}
+
+/// Ask whether to resume from the autosave left by a session that did not exit cleanly
+async fn ask_resume(path: &Path) -> bool {
+    loop {
+        clear_background(BLACK);
+        draw_text(
+            "The last session did not exit cleanly.",
+            40.0,
+            80.0,
+            28.0,
+            WHITE,
+        );
+        draw_text(
+            &format!("Resume from {}? [Y/N]", path.display()),
+            40.0,
+            116.0,
+            28.0,
+            YELLOW,
+        );
+        if is_key_pressed(KeyCode::Y) || is_key_pressed(KeyCode::Enter) {
+            return true;
+        }
+        if is_key_pressed(KeyCode::N) || is_key_pressed(KeyCode::Escape) {
+            return false;
+        }
+        next_frame().await
+    }
 }
@@ -0,0 +1,510 @@
+// analysis.rs
+
+// Static analysis of genomes: which bytes can ever run, which never did, and which run without doing anything
+
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::ops::Range;
+
+#[cfg(feature = "heatmap")]
+use crate::compute::VM;
+use crate::compute::{Instruction, MEM_SIZE, relative_target};
+use crate::disasm::{self, DecodedInstruction};
+use crate::isa::IsaVersion;
+
+// Analysis constants
+pub const MIN_NOP_SLED: usize = 4; // Consecutive reachable NOPs reported as a sled
+
+/// Decode the instruction at `addr` as a VM running `isa` would: bytes outside the
+/// version decode as data, and run as HLT
+pub fn decode_for(program: &[u8], addr: usize, isa: IsaVersion) -> DecodedInstruction {
+    let entry = disasm::decode_at(program, addr);
+    if entry.instruction.is_some() && isa.decode(entry.opcode).is_none() {
+        return DecodedInstruction {
+            instruction: None,
+            operand: None,
+            ..entry
+        };
+    }
+    entry
+}
+
+/// How control passes from one instruction to the next
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EdgeKind {
+    /// On to the following instruction, including a CALL's return and an untaken branch
+    FallThrough,
+    /// A jump to its target, conditional or not
+    Jump,
+    /// CALL into a subroutine
+    Call,
+    /// SKE or SKNE passing over the next instruction
+    Skip,
+}
+
+impl EdgeKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EdgeKind::FallThrough => "fall through",
+            EdgeKind::Jump => "jump",
+            EdgeKind::Call => "call",
+            EdgeKind::Skip => "skip",
+        }
+    }
+}
+
+/// Addresses execution may continue at after `entry`, and how it gets there.
+///
+/// Conditional branches and skips go both ways and CALL also returns to the next
+/// instruction. RET, HLT and bytes that are not instructions lead nowhere; so do
+/// targets past the end, where the VM halts.
+pub fn exits(
+    program: &[u8],
+    entry: &DecodedInstruction,
+    isa: IsaVersion,
+) -> Vec<(usize, EdgeKind)> {
+    let len = program.len().min(MEM_SIZE);
+    let next = (entry.addr + entry.size(), EdgeKind::FallThrough);
+    let operand = entry.operand.unwrap_or(0);
+    let jump = (operand as usize, EdgeKind::Jump);
+    let relative = (
+        relative_target(entry.addr, operand as i8, MEM_SIZE),
+        EdgeKind::Jump,
+    );
+    let exits = match entry.instruction {
+        None | Some(Instruction::HLT | Instruction::RET) => vec![],
+        Some(Instruction::JMP) => vec![jump],
+        Some(
+            Instruction::JZ
+            | Instruction::JNZ
+            | Instruction::JC
+            | Instruction::JNC
+            | Instruction::JN,
+        ) => vec![next, jump],
+        Some(Instruction::CALL) => vec![next, (jump.0, EdgeKind::Call)],
+        Some(Instruction::JR) => vec![relative],
+        Some(Instruction::JRZ) => vec![next, relative],
+        Some(Instruction::SKE | Instruction::SKNE) => {
+            let skipped = next.0 + decode_for(program, next.0, isa).size();
+            vec![next, (skipped, EdgeKind::Skip)]
+        }
+        Some(_) => vec![next],
+    };
+    exits.into_iter().filter(|&(addr, _)| addr < len).collect()
+}
+
+/// Addresses execution may continue at after `entry`; see `exits`
+pub fn successors(program: &[u8], entry: &DecodedInstruction, isa: IsaVersion) -> Vec<usize> {
+    exits(program, entry, isa)
+        .into_iter()
+        .map(|(addr, _)| addr)
+        .collect()
+}
+
+/// Whether control can leave `entry` other than by running on to the next instruction
+fn transfers_control(entry: &DecodedInstruction) -> bool {
+    match entry.instruction {
+        None => true,
+        Some(instruction) => matches!(
+            instruction,
+            Instruction::HLT
+                | Instruction::RET
+                | Instruction::JMP
+                | Instruction::JZ
+                | Instruction::JNZ
+                | Instruction::JC
+                | Instruction::JNC
+                | Instruction::JN
+                | Instruction::CALL
+                | Instruction::JR
+                | Instruction::JRZ
+                | Instruction::SKE
+                | Instruction::SKNE
+        ),
+    }
+}
+
+/// Instructions reachable from `entries`, in address order, following every branch
+/// both ways. The program is taken as the whole memory image, and self-modifying
+/// stores are not followed.
+pub fn reachable(program: &[u8], isa: IsaVersion, entries: &[usize]) -> Vec<DecodedInstruction> {
+    let len = program.len().min(MEM_SIZE);
+    let mut visited = vec![false; len];
+    let mut pending: Vec<usize> = entries.iter().copied().filter(|&a| a < len).collect();
+    let mut found = Vec::new();
+    while let Some(addr) = pending.pop() {
+        if std::mem::replace(&mut visited[addr], true) {
+            continue;
+        }
+        let entry = decode_for(program, addr, isa);
+        pending.extend(successors(program, &entry, isa));
+        found.push(entry);
+    }
+    found.sort_by_key(|entry| entry.addr);
+    found
+}
+
+/// Parts of a genome that contribute nothing to what it does
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DeadCode {
+    /// Bytes no instruction reachable from the entry points covers, opcode or operand
+    pub unreachable: Vec<Range<usize>>,
+    /// Reachable instructions that never ran; empty for a purely static analysis
+    pub never_executed: Vec<Range<usize>>,
+    /// Runs of at least `MIN_NOP_SLED` reachable NOPs, which execute but do nothing
+    pub nop_sleds: Vec<Range<usize>>,
+}
+
+impl DeadCode {
+    /// Dead code found from the program alone, starting from address 0
+    pub fn analyze(program: &[u8], isa: IsaVersion) -> Self {
+        Self::with_execution(program, isa, &[0], |_| true)
+    }
+
+    /// Dead code given the entry points and which instruction starts `executed`
+    pub fn with_execution(
+        program: &[u8],
+        isa: IsaVersion,
+        entries: &[usize],
+        executed: impl Fn(usize) -> bool,
+    ) -> Self {
+        let len = program.len().min(MEM_SIZE);
+        let reached = reachable(program, isa, entries);
+        let mut covered = vec![false; len];
+        for entry in &reached {
+            covered[entry.addr..(entry.addr + entry.size()).min(len)].fill(true);
+        }
+
+        let never_executed = ranges(
+            reached
+                .iter()
+                .filter(|entry| !executed(entry.addr))
+                .map(|entry| entry.addr..(entry.addr + entry.size()).min(len)),
+        );
+        let nop_sleds = ranges(
+            reached
+                .iter()
+                .filter(|entry| entry.instruction == Some(Instruction::NOP))
+                .map(|entry| entry.addr..entry.addr + 1),
+        )
+        .into_iter()
+        .filter(|sled| sled.len() >= MIN_NOP_SLED)
+        .collect();
+
+        DeadCode {
+            unreachable: ranges((0..len).filter(|&a| !covered[a]).map(|a| a..a + 1)),
+            never_executed,
+            nop_sleds,
+        }
+    }
+
+    /// Bytes that could be dropped without changing the run: unreachable or never executed
+    pub fn dead_bytes(&self) -> usize {
+        self.unreachable
+            .iter()
+            .chain(&self.never_executed)
+            .map(Range::len)
+            .sum()
+    }
+}
+
+/// Static reachability from address 0 and the timer interrupt vector, combined with
+/// the heatmap of what ran since the VM was last reset. Analyzes the genome as loaded,
+/// `initial_state`, so code the program wrote over itself shows as never executed.
+#[cfg(feature = "heatmap")]
+pub fn find_dead_code(vm: &VM) -> DeadCode {
+    let mut entries = vec![0];
+    if let Some(timer) = vm.interrupt {
+        entries.push(timer.vector as usize);
+    }
+    DeadCode::with_execution(&vm.initial_state, vm.isa, &entries, |addr| {
+        vm.heatmap.is_executed(addr)
+    })
+}
+
+/// `program` with everything that cannot affect its run zeroed, so genomes that
+/// behave identically compare and hash equal.
+///
+/// The image is padded with zeros (NOPs) or cut to memory size, like a loaded VM's.
+/// Bytes survive if a reachable instruction covers them or reads them as data; the
+/// rest become zero. Programs whose reads or jumps are not fixed in the code are
+/// only padded: ones using either stack (CALL, RET, PUSH, POP, whose pointers move
+/// at run time), COPY, which reads and writes wherever X and Y point, and ones
+/// storing into their own reachable code. Only address 0 is
+/// taken as an entry point, so timer interrupt handlers are not kept.
+pub fn canonicalize(program: &[u8], isa: IsaVersion) -> Vec<u8> {
+    let mut image = program.to_vec();
+    image.resize(MEM_SIZE, 0);
+    let reached = reachable(&image, isa, &[0]);
+    let mut keep = vec![false; MEM_SIZE];
+    for entry in &reached {
+        keep[entry.addr..(entry.addr + entry.size()).min(MEM_SIZE)].fill(true);
+    }
+
+    for entry in &reached {
+        let Some(instruction) = entry.instruction else {
+            continue;
+        };
+        let addr = entry.operand.unwrap_or(0) as usize;
+        match instruction {
+            Instruction::CALL
+            | Instruction::RET
+            | Instruction::PUSH
+            | Instruction::POP
+            | Instruction::COPY => {
+                return image;
+            }
+            Instruction::STA | Instruction::SWP if keep[addr] => return image,
+            _ => {}
+        }
+    }
+    for entry in &reached {
+        if let Some(instruction) = entry.instruction
+            && instruction.has_address_operand()
+        {
+            keep[entry.operand.unwrap_or(0) as usize] = true;
+        }
+    }
+
+    for (byte, keep) in image.iter_mut().zip(keep) {
+        if !keep {
+            *byte = 0;
+        }
+    }
+    image
+}
+
+/// Hash of `canonicalize(program, isa)` and the instruction set, equal for genomes
+/// that run identically
+pub fn canonical_hash(program: &[u8], isa: IsaVersion) -> u64 {
+    use std::hash::{DefaultHasher, Hash, Hasher};
+    let mut hasher = DefaultHasher::new();
+    (canonicalize(program, isa), isa).hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The canonical hashes of the last `capacity` genomes evaluated, so an evolution
+/// loop can skip children that would only repeat an earlier run
+#[derive(Debug, Clone, Default)]
+pub struct DuplicateFilter {
+    capacity: usize,
+    order: VecDeque<u64>,
+    seen: HashSet<u64>,
+}
+
+impl DuplicateFilter {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            ..Self::default()
+        }
+    }
+
+    /// Whether a genome with this canonical hash was evaluated recently
+    pub fn contains(&self, hash: u64) -> bool {
+        self.seen.contains(&hash)
+    }
+
+    /// Remember an evaluated genome, forgetting the oldest past `capacity`; `false`
+    /// if it was already remembered
+    pub fn insert(&mut self, hash: u64) -> bool {
+        if self.capacity == 0 || !self.seen.insert(hash) {
+            return false;
+        }
+        if self.order.len() == self.capacity
+            && let Some(oldest) = self.order.pop_front()
+        {
+            self.seen.remove(&oldest);
+        }
+        self.order.push_back(hash);
+        true
+    }
+
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+}
+
+impl fmt::Display for DeadCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kinds = [
+            ("unreachable", &self.unreachable),
+            ("never executed", &self.never_executed),
+            ("NOP sleds", &self.nop_sleds),
+        ];
+        for (i, (kind, regions)) in kinds.into_iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            let bytes: usize = regions.iter().map(Range::len).sum();
+            write!(f, "{} {} bytes", kind, bytes)?;
+            if !regions.is_empty() {
+                let listed: Vec<String> = regions
+                    .iter()
+                    .map(|r| format!("{}..{}", r.start, r.end))
+                    .collect();
+                write!(f, " ({})", listed.join(", "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Merge ascending ranges that touch into maximal ones
+fn ranges(parts: impl IntoIterator<Item = Range<usize>>) -> Vec<Range<usize>> {
+    let mut merged: Vec<Range<usize>> = Vec::new();
+    for part in parts {
+        match merged.last_mut() {
+            Some(last) if last.end >= part.start => last.end = last.end.max(part.end),
+            _ => merged.push(part),
+        }
+    }
+    merged
+}
+
+/// A straight run of instructions entered only at its first and left only after its last
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlock {
+    /// In execution order; never empty
+    pub instructions: Vec<DecodedInstruction>,
+}
+
+impl BasicBlock {
+    pub fn start(&self) -> usize {
+        self.instructions[0].addr
+    }
+
+    /// One past the block's last byte
+    pub fn end(&self) -> usize {
+        self.last().addr + self.last().size()
+    }
+
+    pub fn last(&self) -> &DecodedInstruction {
+        &self.instructions[self.instructions.len() - 1]
+    }
+}
+
+/// A transfer of control between two blocks, given by index into `ControlFlowGraph::blocks`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub kind: EdgeKind,
+}
+
+/// Basic blocks of the code reachable from address 0 and the edges between them
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ControlFlowGraph {
+    /// Ordered by start address; the block at address 0, if any, comes first
+    pub blocks: Vec<BasicBlock>,
+    /// Grouped by `from`, in the order `exits` lists them
+    pub edges: Vec<Edge>,
+}
+
+/// The control-flow graph of `program` under the latest instruction set
+pub fn cfg(program: &[u8]) -> ControlFlowGraph {
+    ControlFlowGraph::build(program, IsaVersion::LATEST)
+}
+
+impl ControlFlowGraph {
+    /// Split the code reachable from address 0 into blocks. A block starts at the entry,
+    /// at every branch target and after every branch, and wherever two paths meet, so
+    /// overlapping decodes of the same bytes land in separate blocks.
+    pub fn build(program: &[u8], isa: IsaVersion) -> Self {
+        let len = program.len().min(MEM_SIZE);
+        let reached = reachable(program, isa, &[0]);
+        let mut at = vec![None; len];
+        let mut predecessors = vec![0usize; len];
+        let mut leader = vec![false; len];
+        if len > 0 {
+            leader[0] = true;
+        }
+        for entry in &reached {
+            at[entry.addr] = Some(*entry);
+            for addr in successors(program, entry, isa) {
+                predecessors[addr] += 1;
+                leader[addr] |= transfers_control(entry);
+            }
+        }
+        for addr in 0..len {
+            leader[addr] |= predecessors[addr] > 1;
+        }
+
+        let mut blocks = Vec::new();
+        for entry in reached.iter().filter(|e| leader[e.addr]) {
+            let mut instructions = vec![*entry];
+            let mut current = *entry;
+            while !transfers_control(&current) {
+                let next = current.addr + current.size();
+                match at.get(next).copied().flatten() {
+                    Some(following) if !leader[next] => {
+                        instructions.push(following);
+                        current = following;
+                    }
+                    _ => break,
+                }
+            }
+            blocks.push(BasicBlock { instructions });
+        }
+
+        let mut graph = ControlFlowGraph {
+            blocks,
+            edges: Vec::new(),
+        };
+        for (from, block) in graph.blocks.iter().enumerate() {
+            for (addr, kind) in exits(program, block.last(), isa) {
+                // Every exit of a block's last instruction starts a block
+                if let Some(to) = graph.block_at(addr) {
+                    graph.edges.push(Edge { from, to, kind });
+                }
+            }
+        }
+        graph
+    }
+
+    /// Index of the block starting at `addr`
+    pub fn block_at(&self, addr: usize) -> Option<usize> {
+        self.blocks.iter().position(|b| b.start() == addr)
+    }
+
+    /// Edges leaving block `index`
+    pub fn successors(&self, index: usize) -> impl Iterator<Item = &Edge> {
+        self.edges.iter().filter(move |e| e.from == index)
+    }
+
+    /// Edges entering block `index`
+    pub fn predecessors(&self, index: usize) -> impl Iterator<Item = &Edge> {
+        self.edges.iter().filter(move |e| e.to == index)
+    }
+}
+
+impl fmt::Display for ControlFlowGraph {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, block) in self.blocks.iter().enumerate() {
+            writeln!(
+                f,
+                "block {}: {:04}..{:04}",
+                index,
+                block.start(),
+                block.end()
+            )?;
+            for entry in &block.instructions {
+                writeln!(f, "  {:04}: {}", entry.addr, entry)?;
+            }
+            let exits: Vec<String> = self
+                .successors(index)
+                .map(|e| format!("block {} ({})", e.to, e.kind.name()))
+                .collect();
+            if exits.is_empty() {
+                writeln!(f, "  -> exit")?;
+            } else {
+                writeln!(f, "  -> {}", exits.join(", "))?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,673 @@
+// asm.rs
+
+// Text assembler turning mnemonics, labels, named constants and macros into a program image
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::compute::{Instruction, MEM_SIZE, STACK_TOP};
+use crate::isa::IsaVersion;
+use crate::lint::{self, Diagnostic};
+use crate::world::{
+    DAUGHTER_LEN, DAUGHTER_START_ADDR, FOOD_DISTANCE_X_ADDR, FOOD_DISTANCE_Y_ADDR, MOVE_DOWN_ADDR,
+    MOVE_LEFT_ADDR, MOVE_RIGHT_ADDR, MOVE_UP_ADDR, SCRATCH_START_ADDR, SELF_INSTRUCTIONS_ADDR,
+    SKIN_ACCENT_ADDR, SKIN_PATTERN_ADDR, STACK_START_ADDR, STEP_BUDGET_ADDR,
+};
+
+// Assembler constants
+pub const MAX_EXPANSION_DEPTH: usize = 32; // Deepest nesting of includes, macros and repeats
+
+/// Names of the world's sensor, actuator and layout addresses, predefined in every
+/// source along with `daughter_len`
+pub const ADDRESS_NAMES: [(&str, usize); 14] = [
+    ("food_x", FOOD_DISTANCE_X_ADDR),
+    ("food_y", FOOD_DISTANCE_Y_ADDR),
+    ("move_left", MOVE_LEFT_ADDR),
+    ("move_right", MOVE_RIGHT_ADDR),
+    ("move_up", MOVE_UP_ADDR),
+    ("move_down", MOVE_DOWN_ADDR),
+    ("self_instructions", SELF_INSTRUCTIONS_ADDR),
+    ("step_budget", STEP_BUDGET_ADDR),
+    ("scratch", SCRATCH_START_ADDR),
+    ("data_stack", STACK_START_ADDR),
+    ("stack_top", STACK_TOP as usize),
+    ("skin_pattern", SKIN_PATTERN_ADDR),
+    ("skin_accent", SKIN_ACCENT_ADDR),
+    ("daughter", DAUGHTER_START_ADDR),
+];
+
+/// The predefined name of `addr`, if it has one
+pub fn address_name(addr: usize) -> Option<&'static str> {
+    ADDRESS_NAMES
+        .iter()
+        .find(|&&(_, value)| value == addr)
+        .map(|&(name, _)| name)
+}
+
+/// What is wrong with a line of assembly
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsmErrorKind {
+    /// Neither an instruction nor a directive
+    UnknownMnemonic(String),
+    /// The instruction exists but the target instruction set lacks it
+    NotInIsa(Instruction, IsaVersion),
+    UnknownSymbol(String),
+    /// A label or constant defined twice, or shadowing a built-in constant
+    DuplicateSymbol(String),
+    /// Not a number, a symbol or `symbol+n`/`symbol-n`
+    BadOperand(String),
+    MissingOperand,
+    /// An operand given to an instruction that takes none, or a second one
+    UnexpectedOperand,
+    /// A value that does not fit the byte it is encoded in
+    OutOfRange(i64),
+    /// The program grew past the end of memory
+    TooLong,
+    /// A source or include file could not be read
+    Io(String),
+    /// `.macro` or `.rept` without its `.endm` or `.endr`
+    Unterminated(&'static str),
+    /// `.endm` or `.endr` with no block open
+    UnmatchedEnd(&'static str),
+    /// A macro called with a different number of arguments than it declares
+    ArgumentCount {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    /// Includes, macro calls or repeats nested more than `MAX_EXPANSION_DEPTH` deep,
+    /// usually by recursion
+    TooDeep,
+}
+
+/// A line of a source file; `file` is `None` for text passed to `assemble`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub file: Option<PathBuf>,
+    /// 1-based, or 0 for the file as a whole
+    pub line: usize,
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.file, self.line) {
+            (Some(file), 0) => write!(f, "{}", file.display()),
+            (Some(file), line) => write!(f, "{}:{}", file.display(), line),
+            (None, line) => write!(f, "line {}", line),
+        }
+    }
+}
+
+/// An assembly failure, where it happened and, inside a macro, the call that
+/// expanded it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub span: Span,
+    pub expanded_from: Option<Span>,
+    pub kind: AsmErrorKind,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.span)?;
+        if let Some(call) = &self.expanded_from {
+            write!(f, " (in macro called at {})", call)?;
+        }
+        write!(f, ": ")?;
+        match &self.kind {
+            AsmErrorKind::UnknownMnemonic(name) => write!(f, "unknown mnemonic `{}`", name),
+            AsmErrorKind::NotInIsa(instruction, isa) => {
+                write!(f, "{} is not part of ISA {}", instruction, isa)
+            }
+            AsmErrorKind::UnknownSymbol(name) => write!(f, "undefined symbol `{}`", name),
+            AsmErrorKind::DuplicateSymbol(name) => write!(f, "`{}` is already defined", name),
+            AsmErrorKind::BadOperand(text) => write!(f, "cannot read operand `{}`", text),
+            AsmErrorKind::MissingOperand => write!(f, "missing operand"),
+            AsmErrorKind::UnexpectedOperand => write!(f, "unexpected operand"),
+            AsmErrorKind::OutOfRange(value) => write!(f, "{} does not fit in a byte", value),
+            AsmErrorKind::TooLong => write!(f, "program is longer than {} bytes", MEM_SIZE),
+            AsmErrorKind::Io(e) => write!(f, "cannot read: {}", e),
+            AsmErrorKind::Unterminated(directive) => write!(f, "{} is never closed", directive),
+            AsmErrorKind::UnmatchedEnd(directive) => {
+                write!(f, "{} without a block to close", directive)
+            }
+            AsmErrorKind::ArgumentCount {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "macro `{}` takes {} arguments, not {}",
+                name, expected, found
+            ),
+            AsmErrorKind::TooDeep => {
+                write!(f, "expansion nested more than {} deep", MAX_EXPANSION_DEPTH)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+/// An assembled program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assembly {
+    /// The memory image, zero-padded to memory size, ready for `VM::load_program`
+    pub image: [u8; MEM_SIZE],
+    /// Bytes the source emitted, from address 0
+    pub len: usize,
+    /// Every label and the address it marks
+    pub labels: BTreeMap<String, usize>,
+    /// Where each statement's bytes start and the source line it came from
+    pub sources: BTreeMap<usize, Span>,
+}
+
+impl Assembly {
+    /// The bytes the source emitted, without the zero padding
+    pub fn code(&self) -> &[u8] {
+        &self.image[..self.len]
+    }
+
+    /// The source line that emitted the byte at `addr`
+    pub fn span_at(&self, addr: usize) -> Option<&Span> {
+        if addr >= self.len {
+            return None;
+        }
+        self.sources
+            .range(..=addr)
+            .next_back()
+            .map(|(_, span)| span)
+    }
+
+    /// Lint the assembled image; see `lint::lint`
+    pub fn lint(&self, isa: IsaVersion) -> Vec<Diagnostic> {
+        lint::lint(&self.image, isa)
+    }
+}
+
+/// A statement waiting for the second pass, when every label is known
+enum Item<'a> {
+    Instruction {
+        instruction: Instruction,
+        operand: Option<&'a str>,
+    },
+    Bytes(Vec<&'a str>),
+}
+
+/// Assemble `source` into a program image for `isa`.
+///
+/// One statement per line, `;` starting a comment. A line may begin with any
+/// number of `name:` labels. Statements are an instruction mnemonic with its
+/// operand, in any case; `.byte a, b, ...` emitting raw bytes; or
+/// `.equ name value` defining a constant from what is defined above it.
+/// Operands are decimal, `0x` hex or `0b` binary numbers, negative ones
+/// encoding as two's complement, or symbols with an optional `+n`/`-n`: labels,
+/// constants and the predefined names. Immediates may be written with a leading
+/// `#`. JR and JRZ take either a label, assembled into the offset that reaches
+/// it, or the signed offset itself.
+///
+/// Before assembly, `.include "path"` is replaced by the file's lines,
+/// `.rept n` ... `.endr` by `n` copies of its body, and `.macro name a, b` ...
+/// `.endm` defines a macro that `name x, y` expands, with `\a` and `\b` in the
+/// body replaced by the arguments and `\@` by a number unique to each
+/// expansion, for labels. Includes in `source` resolve against the current
+/// directory.
+pub fn assemble(source: &str, isa: IsaVersion) -> Result<Assembly, AsmError> {
+    let lines = Preprocessor::default().run(source, None, 0, None)?;
+    assemble_lines(&lines, isa)
+}
+
+/// Assemble the file at `path` like `assemble`, resolving includes against the
+/// directory of the file that names them
+pub fn assemble_file(path: impl AsRef<Path>, isa: IsaVersion) -> Result<Assembly, AsmError> {
+    let path = path.as_ref();
+    let source = std::fs::read_to_string(path).map_err(|e| AsmError {
+        span: Span {
+            file: Some(path.to_path_buf()),
+            line: 0,
+        },
+        expanded_from: None,
+        kind: AsmErrorKind::Io(e.to_string()),
+    })?;
+    let lines = Preprocessor::default().run(&source, Some(path), 0, None)?;
+    assemble_lines(&lines, isa)
+}
+
+/// A line left for assembly once includes, repeats and macros are expanded
+struct SourceLine {
+    text: String,
+    span: Span,
+    expanded_from: Option<Span>,
+}
+
+impl SourceLine {
+    fn error(&self, kind: AsmErrorKind) -> AsmError {
+        AsmError {
+            span: self.span.clone(),
+            expanded_from: self.expanded_from.clone(),
+            kind,
+        }
+    }
+}
+
+/// A `.macro` definition
+struct Macro {
+    params: Vec<String>,
+    body: Vec<SourceLine>,
+}
+
+/// Expands includes, repeats and macros, remembering macros across files
+#[derive(Default)]
+struct Preprocessor {
+    macros: BTreeMap<String, Macro>,
+    expansions: usize,
+}
+
+impl Preprocessor {
+    /// Split the text of `file` into lines and expand them
+    fn run(
+        &mut self,
+        text: &str,
+        file: Option<&Path>,
+        depth: usize,
+        expanded_from: Option<&Span>,
+    ) -> Result<Vec<SourceLine>, AsmError> {
+        let lines: Vec<_> = text
+            .lines()
+            .enumerate()
+            .map(|(index, text)| SourceLine {
+                text: text.to_string(),
+                span: Span {
+                    file: file.map(Path::to_path_buf),
+                    line: index + 1,
+                },
+                expanded_from: expanded_from.cloned(),
+            })
+            .collect();
+        let mut out = Vec::new();
+        self.expand(&lines, depth, &mut out)?;
+        Ok(out)
+    }
+
+    fn expand(
+        &mut self,
+        lines: &[SourceLine],
+        depth: usize,
+        out: &mut Vec<SourceLine>,
+    ) -> Result<(), AsmError> {
+        let mut index = 0;
+        while index < lines.len() {
+            let line = &lines[index];
+            index += 1;
+            let code = line.text.split(';').next().unwrap_or("");
+            let (labels, statement) = split_labels(code);
+            let (word, args) = statement
+                .split_once(char::is_whitespace)
+                .map_or((statement, ""), |(w, a)| (w, a.trim()));
+            let directive = word.to_ascii_lowercase();
+            let nested = || {
+                (depth < MAX_EXPANSION_DEPTH)
+                    .then_some(depth + 1)
+                    .ok_or_else(|| line.error(AsmErrorKind::TooDeep))
+            };
+            // Labels in front of a directive or macro call mark where its expansion starts
+            let keep_labels = |out: &mut Vec<SourceLine>| {
+                if !labels.is_empty() {
+                    out.push(SourceLine {
+                        text: labels.to_string(),
+                        span: line.span.clone(),
+                        expanded_from: line.expanded_from.clone(),
+                    });
+                }
+            };
+
+            match directive.as_str() {
+                ".macro" => {
+                    let mut header = args.split([' ', '\t', ',']).filter(|a| !a.is_empty());
+                    let name = header
+                        .next()
+                        .ok_or_else(|| line.error(AsmErrorKind::MissingOperand))?;
+                    if !is_symbol(name) {
+                        return Err(line.error(AsmErrorKind::BadOperand(name.to_string())));
+                    }
+                    let params = header.map(str::to_string).collect();
+                    let end = block_end(lines, index, ".macro", ".endm")
+                        .ok_or_else(|| line.error(AsmErrorKind::Unterminated(".macro")))?;
+                    let body = lines[index..end]
+                        .iter()
+                        .map(|l| SourceLine {
+                            text: l.text.clone(),
+                            span: l.span.clone(),
+                            expanded_from: None,
+                        })
+                        .collect();
+                    if self
+                        .macros
+                        .insert(name.to_string(), Macro { params, body })
+                        .is_some()
+                    {
+                        return Err(line.error(AsmErrorKind::DuplicateSymbol(name.to_string())));
+                    }
+                    keep_labels(out);
+                    index = end + 1;
+                }
+                ".rept" => {
+                    let count = parse_number(args)
+                        .and_then(|n| usize::try_from(n).ok())
+                        .ok_or_else(|| line.error(AsmErrorKind::BadOperand(args.to_string())))?;
+                    let end = block_end(lines, index, ".rept", ".endr")
+                        .ok_or_else(|| line.error(AsmErrorKind::Unterminated(".rept")))?;
+                    let depth = nested()?;
+                    keep_labels(out);
+                    for _ in 0..count {
+                        self.expand(&lines[index..end], depth, out)?;
+                    }
+                    index = end + 1;
+                }
+                ".endm" | ".endr" => {
+                    let directive = if directive == ".endm" {
+                        ".endm"
+                    } else {
+                        ".endr"
+                    };
+                    return Err(line.error(AsmErrorKind::UnmatchedEnd(directive)));
+                }
+                ".include" => {
+                    let name = args.trim_matches('"');
+                    if name.is_empty() {
+                        return Err(line.error(AsmErrorKind::MissingOperand));
+                    }
+                    let path = match &line.span.file {
+                        Some(file) => file.parent().unwrap_or(Path::new("")).join(name),
+                        None => PathBuf::from(name),
+                    };
+                    let text = std::fs::read_to_string(&path).map_err(|e| {
+                        line.error(AsmErrorKind::Io(format!("{}: {}", path.display(), e)))
+                    })?;
+                    let depth = nested()?;
+                    keep_labels(out);
+                    out.extend(self.run(&text, Some(&path), depth, line.expanded_from.as_ref())?);
+                }
+                _ if self.macros.contains_key(word) => {
+                    let depth = nested()?;
+                    let body = self.call(line, word, args)?;
+                    keep_labels(out);
+                    self.expand(&body, depth, out)?;
+                }
+                _ => out.push(SourceLine {
+                    text: line.text.clone(),
+                    span: line.span.clone(),
+                    expanded_from: line.expanded_from.clone(),
+                }),
+            }
+        }
+        Ok(())
+    }
+
+    /// The body of macro `name` with the arguments of the call on `line` substituted
+    fn call(
+        &mut self,
+        line: &SourceLine,
+        name: &str,
+        args: &str,
+    ) -> Result<Vec<SourceLine>, AsmError> {
+        let args: Vec<&str> = match args {
+            "" => Vec::new(),
+            args => args.split(',').map(str::trim).collect(),
+        };
+        let definition = &self.macros[name];
+        if args.len() != definition.params.len() {
+            return Err(line.error(AsmErrorKind::ArgumentCount {
+                name: name.to_string(),
+                expected: definition.params.len(),
+                found: args.len(),
+            }));
+        }
+        self.expansions += 1;
+        let unique = self.expansions.to_string();
+        // Longest names first, so `\ab` is not taken for `\a` followed by `b`
+        let mut params: Vec<_> = definition.params.iter().zip(&args).collect();
+        params.sort_by_key(|(param, _)| std::cmp::Reverse(param.len()));
+        let call = line
+            .expanded_from
+            .clone()
+            .unwrap_or_else(|| line.span.clone());
+        Ok(definition
+            .body
+            .iter()
+            .map(|body| {
+                let mut text = body.text.replace("\\@", &unique);
+                for (param, arg) in &params {
+                    text = text.replace(&format!("\\{}", param), arg);
+                }
+                SourceLine {
+                    text,
+                    span: body.span.clone(),
+                    expanded_from: Some(call.clone()),
+                }
+            })
+            .collect())
+    }
+}
+
+/// Index of the line closing the block whose body starts at `start`, skipping
+/// nested blocks of the same kind
+fn block_end(lines: &[SourceLine], start: usize, open: &str, close: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (index, line) in lines.iter().enumerate().skip(start) {
+        let code = line.text.split(';').next().unwrap_or("");
+        let word = split_labels(code).1.split_whitespace().next().unwrap_or("");
+        if word.eq_ignore_ascii_case(open) {
+            depth += 1;
+        } else if word.eq_ignore_ascii_case(close) {
+            if depth == 0 {
+                return Some(index);
+            }
+            depth -= 1;
+        }
+    }
+    None
+}
+
+/// Split the leading `name:` labels off a line of code, both parts trimmed
+fn split_labels(code: &str) -> (&str, &str) {
+    let code = code.trim();
+    let mut rest = code;
+    while let Some((label, after)) = rest.split_once(':')
+        && is_symbol(label.trim())
+    {
+        rest = after.trim_start();
+    }
+    (code[..code.len() - rest.len()].trim_end(), rest.trim())
+}
+
+/// Assemble lines left by the preprocessor
+fn assemble_lines(lines: &[SourceLine], isa: IsaVersion) -> Result<Assembly, AsmError> {
+    let mut symbols: BTreeMap<&str, i64> = ADDRESS_NAMES
+        .iter()
+        .chain([&("daughter_len", DAUGHTER_LEN)])
+        .map(|&(name, value)| (name, value as i64))
+        .collect();
+    let mut labels = BTreeMap::new();
+    let mut items = Vec::new();
+    let mut addr = 0;
+
+    // First pass: place every statement and collect the labels
+    for line in lines {
+        let error = |kind| line.error(kind);
+        let mut rest = line.text.split(';').next().unwrap_or("").trim();
+        while let Some((label, after)) = rest.split_once(':')
+            && is_symbol(label.trim())
+        {
+            let label = label.trim();
+            if symbols.insert(label, addr as i64).is_some() {
+                return Err(error(AsmErrorKind::DuplicateSymbol(label.to_string())));
+            }
+            labels.insert(label.to_string(), addr);
+            rest = after.trim();
+        }
+        if rest.is_empty() {
+            continue;
+        }
+
+        let (mnemonic, operands) = rest
+            .split_once(char::is_whitespace)
+            .map_or((rest, ""), |(m, o)| (m, o.trim()));
+        let item = match mnemonic.to_ascii_lowercase().as_str() {
+            ".equ" => {
+                let (name, value) = operands
+                    .split_once([' ', '\t', ','])
+                    .ok_or(error(AsmErrorKind::MissingOperand))?;
+                let value = evaluate(value.trim(), &symbols).map_err(error)?;
+                if !is_symbol(name) {
+                    return Err(error(AsmErrorKind::BadOperand(name.to_string())));
+                }
+                if symbols.insert(name, value).is_some() {
+                    return Err(error(AsmErrorKind::DuplicateSymbol(name.to_string())));
+                }
+                continue;
+            }
+            ".byte" => {
+                let bytes: Vec<_> = operands.split(',').map(str::trim).collect();
+                if bytes.iter().any(|b| b.is_empty()) {
+                    return Err(error(AsmErrorKind::MissingOperand));
+                }
+                Item::Bytes(bytes)
+            }
+            _ => {
+                let instruction = Instruction::all()
+                    .find(|i| i.mnemonic().eq_ignore_ascii_case(mnemonic))
+                    .ok_or_else(|| error(AsmErrorKind::UnknownMnemonic(mnemonic.to_string())))?;
+                if isa.decode(instruction as u8) != Some(instruction) {
+                    return Err(error(AsmErrorKind::NotInIsa(instruction, isa)));
+                }
+                let operand = (!operands.is_empty()).then_some(operands);
+                match (instruction.size(), operand) {
+                    (1, Some(_)) => return Err(error(AsmErrorKind::UnexpectedOperand)),
+                    (2, None) => return Err(error(AsmErrorKind::MissingOperand)),
+                    _ => {}
+                }
+                Item::Instruction {
+                    instruction,
+                    operand,
+                }
+            }
+        };
+        let size = match &item {
+            Item::Instruction { instruction, .. } => instruction.size(),
+            Item::Bytes(bytes) => bytes.len(),
+        };
+        if addr + size > MEM_SIZE {
+            return Err(error(AsmErrorKind::TooLong));
+        }
+        items.push((line, addr, item));
+        addr += size;
+    }
+
+    // Second pass: encode with every symbol known
+    let mut image = [0; MEM_SIZE];
+    let mut sources = BTreeMap::new();
+    for (line, at, item) in items {
+        let error = |kind| line.error(kind);
+        sources.insert(at, line.span.clone());
+        let bytes = match item {
+            Item::Instruction {
+                instruction,
+                operand,
+            } => {
+                let mut bytes = vec![instruction as u8];
+                if let Some(text) = operand {
+                    let text = match text.strip_prefix('#') {
+                        Some(value) if instruction.is_immediate() => value.trim(),
+                        _ => text,
+                    };
+                    if text.contains(',') {
+                        return Err(error(AsmErrorKind::UnexpectedOperand));
+                    }
+                    let value = evaluate(text, &symbols).map_err(error)?;
+                    let byte = if instruction.is_relative() && !is_number(text) {
+                        // A label: the offset from this instruction that lands on it
+                        (value - at as i64).rem_euclid(MEM_SIZE as i64) as u8
+                    } else if instruction.is_relative() {
+                        i8::try_from(value).map_err(|_| error(AsmErrorKind::OutOfRange(value)))?
+                            as u8
+                    } else {
+                        to_byte(value).map_err(error)?
+                    };
+                    bytes.push(byte);
+                }
+                bytes
+            }
+            Item::Bytes(values) => values
+                .into_iter()
+                .map(|text| evaluate(text, &symbols).and_then(to_byte).map_err(error))
+                .collect::<Result<_, _>>()?,
+        };
+        image[at..at + bytes.len()].copy_from_slice(&bytes);
+    }
+
+    Ok(Assembly {
+        image,
+        len: addr,
+        labels,
+        sources,
+    })
+}
+
+/// Whether `text` can name a label or constant
+fn is_symbol(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && text.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn is_number(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '+')
+}
+
+/// The value of a number, or of a symbol with an optional `+n`/`-n` offset
+fn evaluate(text: &str, symbols: &BTreeMap<&str, i64>) -> Result<i64, AsmErrorKind> {
+    if is_number(text) {
+        return parse_number(text).ok_or_else(|| AsmErrorKind::BadOperand(text.to_string()));
+    }
+    let (name, offset) = match text.find(['+', '-']) {
+        Some(split) => {
+            let offset = parse_number(text[split..].trim())
+                .ok_or_else(|| AsmErrorKind::BadOperand(text.to_string()))?;
+            (text[..split].trim(), offset)
+        }
+        None => (text, 0),
+    };
+    if !is_symbol(name) {
+        return Err(AsmErrorKind::BadOperand(text.to_string()));
+    }
+    symbols
+        .get(name)
+        .map(|value| value + offset)
+        .ok_or_else(|| AsmErrorKind::UnknownSymbol(name.to_string()))
+}
+
+fn parse_number(text: &str) -> Option<i64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let digits = digits.trim();
+    let value = if let Some(hex) = digits.strip_prefix("0x").or(digits.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(binary) = digits.strip_prefix("0b").or(digits.strip_prefix("0B")) {
+        i64::from_str_radix(binary, 2).ok()?
+    } else {
+        digits.parse().ok()?
+    };
+    Some(if negative { -value } else { value })
+}
+
+/// A byte operand: 0 to 255, or -128 to -1 as two's complement
+fn to_byte(value: i64) -> Result<u8, AsmErrorKind> {
+    match value {
+        0..=255 => Ok(value as u8),
+        -128..=-1 => Ok(value as i8 as u8),
+        _ => Err(AsmErrorKind::OutOfRange(value)),
+    }
+}
@@ -0,0 +1,130 @@
+// autosave.rs
+
+// Periodic population bundles written on a background thread, and recovery after a crash
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::bundle::PopulationBundle;
+use crate::world::World;
+
+// Autosave constants
+pub const DEFAULT_AUTOSAVE_DIR: &str = "autosave";
+pub const DEFAULT_AUTOSAVE_SLOTS: usize = 3; // Files rotated through, so a torn write never costs every save
+const RUNNING_MARKER: &str = "running"; // Names the newest save; left behind by an unclean exit
+
+/// Writes the world as a population bundle every `interval`, cycling through `slots` files.
+///
+/// Encoding and file I/O happen on a background thread; the simulation only hands
+/// over a snapshot. A marker file naming the newest save exists for as long as the
+/// autosaver runs, so a marker found at startup means the previous session did not
+/// shut down cleanly.
+pub struct Autosaver {
+    dir: PathBuf,
+    interval: Duration,
+    slots: usize,
+    next_slot: usize,
+    last_save: Instant,
+    outgoing: Option<Sender<(PathBuf, PopulationBundle)>>,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl Autosaver {
+    pub fn start(
+        dir: impl Into<PathBuf>,
+        interval: Duration,
+        slots: usize,
+    ) -> std::io::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        let marker = dir.join(RUNNING_MARKER);
+        std::fs::write(&marker, "")?;
+
+        let (tx, rx) = mpsc::channel::<(PathBuf, PopulationBundle)>();
+        let writer = thread::spawn(move || {
+            for (path, bundle) in rx {
+                // Write beside the slot and rename, so a crash mid-write keeps the old file
+                let partial = path.with_extension("partial");
+                let result = bundle
+                    .save(&partial)
+                    .map_err(|e| e.to_string())
+                    .and_then(|_| std::fs::rename(&partial, &path).map_err(|e| e.to_string()))
+                    .and_then(|_| {
+                        let name = path.file_name().unwrap_or_default().to_string_lossy();
+                        std::fs::write(&marker, name.as_bytes()).map_err(|e| e.to_string())
+                    });
+                match result {
+                    Ok(()) => tracing::debug!("Autosaved to {}", path.display()),
+                    Err(e) => tracing::error!("Autosave to {} failed: {}", path.display(), e),
+                }
+            }
+        });
+
+        Ok(Self {
+            dir,
+            interval,
+            slots: slots.max(1),
+            next_slot: 0,
+            last_save: Instant::now(),
+            outgoing: Some(tx),
+            writer: Some(writer),
+        })
+    }
+
+    /// Queue a save if `interval` has passed since the last one
+    pub fn tick(&mut self, world: &World) {
+        if self.last_save.elapsed() >= self.interval {
+            self.save_now(world);
+        }
+    }
+
+    /// Queue a save into the next slot regardless of the interval
+    pub fn save_now(&mut self, world: &World) {
+        self.last_save = Instant::now();
+        let path = slot_path(&self.dir, self.next_slot);
+        self.next_slot = (self.next_slot + 1) % self.slots;
+        if let Some(outgoing) = &self.outgoing {
+            let _ = outgoing.send((path, PopulationBundle::from_world(world)));
+        }
+    }
+
+    /// Wait for pending saves and mark the session as cleanly finished
+    pub fn finish(mut self) {
+        self.shut_down();
+    }
+
+    fn shut_down(&mut self) {
+        self.outgoing = None;
+        if let Some(writer) = self.writer.take()
+            && writer.join().is_err()
+        {
+            tracing::error!("Autosave writer panicked");
+        }
+        if let Err(e) = std::fs::remove_file(self.dir.join(RUNNING_MARKER)) {
+            tracing::warn!("Failed to remove autosave marker: {}", e);
+        }
+    }
+}
+
+impl Drop for Autosaver {
+    fn drop(&mut self) {
+        // Dropped during a panic: leave the marker so the next start offers recovery
+        if self.writer.is_some() && !thread::panicking() {
+            self.shut_down();
+        }
+    }
+}
+
+/// Newest autosave in `dir` if the last session there exited uncleanly
+pub fn recoverable(dir: impl AsRef<Path>) -> Option<PathBuf> {
+    let dir = dir.as_ref();
+    let newest = std::fs::read_to_string(dir.join(RUNNING_MARKER)).ok()?;
+    let path = dir.join(newest.trim());
+    (!newest.trim().is_empty() && path.is_file()).then_some(path)
+}
+
+fn slot_path(dir: &Path, slot: usize) -> PathBuf {
+    dir.join(format!("autosave_{}.cbor", slot))
+}
@@ -0,0 +1,894 @@
+// Command-line tools for inspecting and analyzing VM genomes
+
+use std::error::Error;
+use std::io::Write;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use ::rand::Rng;
+use life::analysis::{self, ControlFlowGraph, DeadCode};
+use life::asm;
+use life::bundle::{CreatureRecord, PopulationBundle, PopulationStats};
+use life::compute::{MEM_SIZE, ProgramFile};
+use life::debugger::Debugger;
+use life::disasm;
+use life::divergence::{Divergence, SensorStream};
+use life::evaluator::{
+    DEFAULT_EVAL_COPIES, DEFAULT_EVAL_STEPS, DEFAULT_EVAL_TICKS, EvalTarget, Evaluator,
+};
+use life::fitness::{FitnessConfig, FitnessPipeline};
+use life::isa::{self, Program};
+use life::lint::{self, Severity};
+use life::notify::{Milestone, MilestoneTracker, Notifier, NotifyTarget};
+use life::peephole;
+use life::provenance::Provenance;
+use life::reach::Reach;
+use life::relocation::{self, RelocationReport};
+use life::replay::{Playback, Replay};
+use life::rng::SimRng;
+use life::scenario::{Scenario, Template};
+use life::selftest;
+use life::world::{MetricsFlush, World, WorldConfig};
+
+const DEFAULT_CHECKPOINT_INTERVAL: u64 = 10_000; // Ticks between `run --checkpoint` saves
+const DEFAULT_METRICS_INTERVAL: u64 = 1_000; // Ticks between `run --metrics` flushes
+const DEFAULT_VERIFY_STEPS: usize = 10_000; // Steps `optimize --verify` replays each version for
+#[cfg(feature = "heatmap")]
+const DEAD_CODE_TRIAL_STEPS: usize = 10_000; // Steps `analyze dead-code` runs each genome for
+#[cfg(feature = "rhai")]
+const DEFAULT_HOSTCALL_STEPS: usize = 1000; // Steps `hostcall` runs a genome for
+
+const USAGE: &str = "Usage:
+  bacteria analyze dead-code <dir>                  List unreachable code and NOP sleds in every genome in a directory;
+                                                    with --features heatmap, also code a 10000-step trial run never executed
+  bacteria analyze diff <dir>                       Disassemble and diff every genome in a run directory
+  bacteria analyze relocation <dir>                 Score how position-independent each genome's control flow is
+  bacteria asm <source> [options]                   Assemble a text program into a genome file
+      --out <path>        Where to write it (default: the source with a .bin extension)
+      --isa <version>     Instruction set to assemble for and tag the file with (default: latest)
+  bacteria cfg <genome>                             Print a genome's control-flow graph: basic blocks and the edges between them
+  bacteria debug <genome>                           Step a genome interactively: breakpoints, watchpoints, peek, poke
+                                                    and tracing, one command per line (`help` lists them)
+  bacteria disasm <genome>                          Print an annotated listing of a genome: code, likely data and jump targets
+  bacteria export-population <dir> <bundle> [--seed <n>]
+                                                    Pack the genomes in a directory into a population bundle,
+                                                    placing them with the seed (default 24301)
+  bacteria hostcall <script> <genome> [--steps <n>]  Run a genome with HOSTCALL (0x2A) handled by a Rhai script,
+                                                    printing every call (default 1000 steps; needs --features rhai)
+  bacteria import-population <bundle> <dir>         Unpack a population bundle into watermarked genome files
+  bacteria isa [v1|v2|v3|v4]                        Print the opcode reference for an instruction set (default: latest)
+  bacteria lint <genome>...                         Flag misaligned jumps, stores to sensors, unreachable HLTs,
+                                                    operands past the end of memory and genomes that never move;
+                                                    fails on any warning
+  bacteria new-scenario <template> [dir]            Scaffold a scenario directory (world.toml, fitness.toml, seeds/)
+                                                    from `foraging`, `replication-arena` or `soup` (default dir: the template name)
+  bacteria optimize <genome> [options]              Strip NOPs, INC/DEC pairs, jumps to the next instruction and
+                                                    redundant loads from a genome's code, listing each removal
+      --out <path>        Write the optimized genome here
+      --verify            Replay both versions and fail if their stores or final registers differ
+      --steps <n>         Steps each replay runs for (default 10000)
+  bacteria provenance <genome>...                   Verify and show where genome files came from
+  bacteria reach <genome>...                        Prove which addresses a genome can ever execute, jump to and store to
+  bacteria replay <file> [--ticks <n>]              Re-run a recorded session headless (default: to its last intervention)
+  bacteria run [options]                            Evolve a seeded world headless, reporting milestones
+      --ticks <n>            Ticks to run (default: until interrupted)
+      --seed <n>             World seed (default 24301)
+      --bundle <path>        Start from a bundle's population and config
+      --scenario <dir>       Start from a scenario's config and seed genomes
+      --checkpoint <path>    Save the population to this bundle every --checkpoint-every ticks
+      --checkpoint-every <n> Ticks between checkpoints (default 10000)
+      --notify <target>      Report new bests, extinctions and checkpoints to `desktop` or
+                             an http:// webhook URL; repeatable
+      --metrics <path>       Append population, fitness, food, energy and birth aggregates
+                             to this CSV file every --metrics-every ticks
+      --metrics-every <n>    Ticks between metrics flushes (default 1000)
+  bacteria trace-diff <genome> <genome> [options]   Run two genomes on the same sensor stream and show where they diverge
+      --ticks <n>          Sensor frames to feed (default 200)
+      --steps-per-tick <n> VM steps per frame (default 1)
+      --seed <n>           Seed for the sensor stream and RND (default 24301)
+  bacteria serve-eval [options]                     Score hex genomes, one per line, from stdin or POST /eval,
+                                                    answering with a JSON line each
+      --listen <addr>     Serve HTTP on this address, e.g. 127.0.0.1:8080 (default: stdin)
+      --steps <n>         VM step budget per genome (default 10000)
+      --world <bundle>    Score survival in the bundle's world instead; a scenario directory
+                          for its world and fitness shapers, `default` for the default world
+      --ticks <n>         World ticks per genome (default 2000)
+      --copies <n>        Copies of the genome dropped into the world (default 5)
+      --seed <n>          World seed, the same for every genome (default 24301)
+      --fitness <path>    Fitness shapers applied to the raw score (TOML)
+  bacteria selftest [options]                       Run short seeded worlds and check invariants
+      --seed <n>          Seed for every phase (default 24301)
+      --ticks <n>         Ticks per phase (default 1000)
+      --bundle <path>     Start from a bundle's population and config
+      --expect-hash <hex> Fail unless the last phase ends with this trace hash";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    let result = match args.as_slice() {
+        ["analyze", "dead-code", dir] => analyze_dead_code(Path::new(dir)),
+        ["analyze", "diff", dir] => analyze_diff(Path::new(dir)),
+        ["analyze", "relocation", dir] => analyze_relocation(Path::new(dir)),
+        ["asm", source, options @ ..] => assemble(Path::new(source), options),
+        ["cfg", genome] => print_cfg(Path::new(genome)),
+        ["debug", genome] => debug(Path::new(genome)),
+        ["disasm", genome] => print_listing(Path::new(genome)),
+        ["export-population", dir, bundle, options @ ..] => {
+            export_population(Path::new(dir), Path::new(bundle), options)
+        }
+        #[cfg(feature = "rhai")]
+        ["hostcall", script, genome, options @ ..] => {
+            hostcall(Path::new(script), Path::new(genome), options)
+        }
+        #[cfg(not(feature = "rhai"))]
+        ["hostcall", ..] => Err("built without the `rhai` feature".into()),
+        ["import-population", bundle, dir] => import_population(Path::new(bundle), Path::new(dir)),
+        ["isa"] => print_isa(&isa::IsaVersion::LATEST.to_string()),
+        ["isa", version] => print_isa(version),
+        ["lint", paths @ ..] if !paths.is_empty() => lint_genomes(paths),
+        ["new-scenario", template] => new_scenario(template, Path::new(template)),
+        ["new-scenario", template, dir] => new_scenario(template, Path::new(dir)),
+        ["optimize", genome, options @ ..] => optimize(Path::new(genome), options),
+        ["provenance", paths @ ..] if !paths.is_empty() => show_provenance(paths),
+        ["reach", paths @ ..] if !paths.is_empty() => print_reach(paths),
+        ["replay", path, options @ ..] => replay(Path::new(path), options),
+        ["run", options @ ..] => run(options),
+        ["selftest", options @ ..] => selftest(options),
+        ["serve-eval", options @ ..] => serve_eval(options),
+        ["trace-diff", a, b, options @ ..] => trace_diff(Path::new(a), Path::new(b), options),
+        _ => {
+            eprintln!("{}", USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Read a genome file and the instruction set it was saved under, padding short
+/// files with zeros like `VM::load_program`.
+///
+/// Any provenance trailer is verified, reported as a warning if invalid, and dropped.
+fn read_genome(path: &Path) -> std::io::Result<Program> {
+    let mut program = ProgramFile::load(path)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?
+        .program;
+    if let Err(e) = Provenance::read(&program.genome) {
+        eprintln!("warning: {}: {}", path.display(), e);
+    }
+    program.genome.resize(MEM_SIZE, 0);
+    Ok(program)
+}
+
+/// Collect `*.bin` genomes in a directory, ordered by file name
+fn genome_paths(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "bin"))
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn analyze_dead_code(dir: &Path) -> Result<(), Box<dyn Error>> {
+    let paths = genome_paths(dir)?;
+    if paths.is_empty() {
+        println!("No genomes (*.bin) found in {}", dir.display());
+        return Ok(());
+    }
+    let names: Vec<String> = paths.iter().map(|p| file_name(p)).collect();
+    let width = names.iter().map(String::len).max().unwrap_or(0);
+    for (name, path) in names.iter().zip(&paths) {
+        let dead = dead_code(&read_genome(path)?);
+        println!(
+            "{:width$}  {:>3} dead bytes  {}",
+            name,
+            dead.dead_bytes(),
+            dead
+        );
+    }
+    Ok(())
+}
+
+/// Dead code in `program`, including what a trial run from a fresh VM never executed
+#[cfg(feature = "heatmap")]
+fn dead_code(program: &Program) -> DeadCode {
+    let mut vm: life::compute::VM = life::compute::VM::new();
+    vm.isa = program.isa;
+    vm.load_program(&program.genome);
+    vm.run_for(DEAD_CODE_TRIAL_STEPS);
+    life::analysis::find_dead_code(&vm)
+}
+
+/// Dead code in `program` found statically
+#[cfg(not(feature = "heatmap"))]
+fn dead_code(program: &Program) -> DeadCode {
+    DeadCode::analyze(&program.genome, program.isa)
+}
+
+fn analyze_diff(dir: &Path) -> Result<(), Box<dyn Error>> {
+    let paths = genome_paths(dir)?;
+    if paths.is_empty() {
+        println!("No genomes (*.bin) found in {}", dir.display());
+        return Ok(());
+    }
+    let genomes = paths
+        .iter()
+        .map(|p| read_genome(p).map(|program| program.genome))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    let names: Vec<String> = paths.iter().map(|p| file_name(p)).collect();
+
+    println!("== Consecutive generations ==");
+    for i in 1..genomes.len() {
+        let changes = disasm::diff(&genomes[i - 1], &genomes[i]);
+        println!(
+            "{} -> {}: {} bytes, {} instructions changed",
+            names[i - 1],
+            names[i],
+            disasm::byte_distance(&genomes[i - 1], &genomes[i]),
+            changes.len()
+        );
+        for change in changes {
+            let show = |d: Option<disasm::DecodedInstruction>| {
+                d.map_or_else(|| "-".to_string(), |d| d.to_string())
+            };
+            println!(
+                "  {:04}: {:<12} -> {}",
+                change.addr,
+                show(change.before),
+                show(change.after)
+            );
+        }
+    }
+
+    println!();
+    println!("== Pairwise byte distance ==");
+    let width = names.iter().map(String::len).max().unwrap_or(0);
+    print!("{:width$}", "");
+    for i in 0..genomes.len() {
+        print!(" {:>4}", i);
+    }
+    println!();
+    for (i, a) in genomes.iter().enumerate() {
+        print!("{:width$}", names[i]);
+        for b in &genomes {
+            print!(" {:>4}", disasm::byte_distance(a, b));
+        }
+        println!();
+    }
+    Ok(())
+}
+
+fn analyze_relocation(dir: &Path) -> Result<(), Box<dyn Error>> {
+    let paths = genome_paths(dir)?;
+    if paths.is_empty() {
+        println!("No genomes (*.bin) found in {}", dir.display());
+        return Ok(());
+    }
+    let genomes = paths
+        .iter()
+        .map(|p| read_genome(p).map(|program| program.genome))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    let names: Vec<String> = paths.iter().map(|p| file_name(p)).collect();
+
+    let width = names.iter().map(String::len).max().unwrap_or(0);
+    for (name, genome) in names.iter().zip(&genomes) {
+        println!("{:width$}  {}", name, RelocationReport::analyze(genome));
+    }
+    println!();
+    println!(
+        "Mean relative branch share: {:.0}%",
+        relocation::mean_relative_fraction(genomes.iter().map(Vec::as_slice)) * 100.0
+    );
+    Ok(())
+}
+
+fn print_cfg(path: &Path) -> Result<(), Box<dyn Error>> {
+    let program = read_genome(path)?;
+    let graph = ControlFlowGraph::build(&program.genome, program.isa);
+    println!(
+        "{}: ISA {}, {} blocks, {} edges",
+        path.display(),
+        program.isa,
+        graph.blocks.len(),
+        graph.edges.len()
+    );
+    print!("{}", graph);
+    Ok(())
+}
+
+fn assemble(source: &Path, options: &[&str]) -> Result<(), Box<dyn Error>> {
+    let isa = match option_value(options, "--isa") {
+        Some(version) => version.parse()?,
+        None => isa::IsaVersion::LATEST,
+    };
+    let assembly = asm::assemble_file(source, isa)?;
+    for diagnostic in assembly.lint(isa) {
+        let place = match assembly.span_at(diagnostic.addr) {
+            Some(span) => span.to_string(),
+            None => format!("address {}", diagnostic.addr),
+        };
+        eprintln!("{}: {}: {}", diagnostic.severity(), place, diagnostic);
+    }
+    let out =
+        option_value(options, "--out").map_or_else(|| source.with_extension("bin"), PathBuf::from);
+    let program = Program {
+        genome: assembly.image.to_vec(),
+        isa,
+    };
+    std::fs::write(&out, program.to_file_bytes())?;
+    println!(
+        "Wrote {}: {} bytes, {} labels, ISA {}",
+        out.display(),
+        assembly.len,
+        assembly.labels.len(),
+        isa
+    );
+    Ok(())
+}
+
+fn lint_genomes(paths: &[&str]) -> Result<(), Box<dyn Error>> {
+    let mut warnings = 0;
+    for path in paths {
+        let program = read_genome(Path::new(path))?;
+        for diagnostic in lint::lint(&program.genome, program.isa) {
+            println!(
+                "{}:{}: {}: {}",
+                path,
+                diagnostic.addr,
+                diagnostic.severity(),
+                diagnostic
+            );
+            if diagnostic.severity() == Severity::Warning {
+                warnings += 1;
+            }
+        }
+    }
+    if warnings > 0 {
+        return Err(format!("{} warnings", warnings).into());
+    }
+    Ok(())
+}
+
+fn print_reach(paths: &[&str]) -> Result<(), Box<dyn Error>> {
+    for path in paths {
+        let program = read_genome(Path::new(path))?;
+        println!("{}:", path);
+        print!("{}", Reach::for_lifeform(&program.genome, program.isa));
+    }
+    Ok(())
+}
+
+fn optimize(path: &Path, options: &[&str]) -> Result<(), Box<dyn Error>> {
+    let program = read_genome(path)?;
+    let optimized = peephole::optimize(&program.genome, program.isa)?;
+    for removal in &optimized.removals {
+        println!(
+            "{:3}: removed {} ({} bytes)",
+            removal.addr,
+            removal.rewrite.name(),
+            removal.bytes
+        );
+    }
+    let code_bytes = |genome: &[u8]| -> usize {
+        analysis::reachable(genome, program.isa, &[0])
+            .iter()
+            .map(|entry| entry.size())
+            .sum()
+    };
+    println!(
+        "{}: {} -> {} code bytes",
+        path.display(),
+        code_bytes(&program.genome),
+        code_bytes(&optimized.program)
+    );
+
+    if options.contains(&"--verify") {
+        let steps =
+            option_value(options, "--steps").map_or(Ok(DEFAULT_VERIFY_STEPS), str::parse)?;
+        peephole::verify(&program.genome, &optimized.program, program.isa, steps)?;
+        println!("Verified over {} steps", steps);
+    }
+    if let Some(out) = option_value(options, "--out") {
+        let optimized = Program {
+            genome: optimized.program,
+            isa: program.isa,
+        };
+        std::fs::write(out, optimized.to_file_bytes())?;
+        println!("Wrote {}", out);
+    }
+    Ok(())
+}
+
+fn export_population(
+    dir: &Path,
+    bundle_path: &Path,
+    options: &[&str],
+) -> Result<(), Box<dyn Error>> {
+    let seed = option_value(options, "--seed").map_or(Ok(selftest::DEFAULT_SEED), str::parse)?;
+    let mut rng = SimRng::new(seed);
+    let mut creatures = Vec::new();
+    for path in genome_paths(dir)? {
+        let program = read_genome(&path)?;
+        let x = rng.random_range(-200.0..200.0);
+        let y = rng.random_range(-200.0..200.0);
+        creatures.push(CreatureRecord::from_program(&program, x, y, &mut rng));
+    }
+
+    let bundle = PopulationBundle {
+        format_version: life::bundle::BUNDLE_FORMAT_VERSION,
+        config: WorldConfig::default(),
+        stats: PopulationStats {
+            population: creatures.len(),
+            total_energy: creatures.iter().map(|c| c.energy).sum(),
+            relative_branches: relocation::mean_relative_fraction(
+                creatures.iter().map(|c| c.genome.as_slice()),
+            ),
+            ..PopulationStats::default()
+        },
+        creatures,
+    };
+    bundle.save(bundle_path)?;
+    println!(
+        "Exported {} genomes to {}",
+        bundle.creatures.len(),
+        bundle_path.display()
+    );
+    Ok(())
+}
+
+fn import_population(bundle_path: &Path, dir: &Path) -> Result<(), Box<dyn Error>> {
+    let bundle = PopulationBundle::load(bundle_path)?;
+    std::fs::create_dir_all(dir)?;
+    let provenance = Provenance::for_world(&bundle.config, bundle.stats.generation);
+    for (i, creature) in bundle.creatures.iter().enumerate() {
+        let mut file = provenance.watermark(&creature.genome);
+        file.extend_from_slice(&creature.isa.tag());
+        std::fs::write(dir.join(format!("genome_{:04}.bin", i)), file)?;
+    }
+
+    let stats = &bundle.stats;
+    println!(
+        "Imported {} genomes into {}",
+        bundle.creatures.len(),
+        dir.display()
+    );
+    println!("  provenance:  {}", provenance);
+    println!("  config:      {:?}", bundle.config);
+    println!("  generation:  {}", stats.generation);
+    println!("  population:  {}", stats.population);
+    println!("  food:        {}", stats.food_count);
+    println!("  energy:      {:.1}", stats.total_energy);
+    println!("  mean age:    {:.1}", stats.mean_age);
+    println!(
+        "  relative:    {:.0}% of branches",
+        stats.relative_branches * 100.0
+    );
+    println!(
+        "  births:      {} fissions, {} matings, {} self-copies ({} conjugations)",
+        stats.reproduction.fissions,
+        stats.reproduction.matings,
+        stats.reproduction.replications,
+        stats.reproduction.conjugations
+    );
+    Ok(())
+}
+
+fn debug(path: &Path) -> Result<(), Box<dyn Error>> {
+    use std::io::{BufRead, IsTerminal};
+
+    let mut debugger = Debugger::new(read_genome(path)?);
+    let interactive = std::io::stdin().is_terminal();
+    if interactive {
+        println!("Debugging {}; `help` lists the commands", path.display());
+    }
+    print!("{}", debugger.status());
+    let mut lines = std::io::stdin().lock().lines();
+    loop {
+        if interactive {
+            print!("(debug) ");
+            std::io::stdout().flush()?;
+        }
+        let Some(line) = lines.next().transpose()? else {
+            return Ok(());
+        };
+        match debugger.execute_line(&line) {
+            Ok(Some(output)) => print!("{}", output),
+            Ok(None) => return Ok(()),
+            Err(e) => eprintln!("error: {}", e),
+        }
+    }
+}
+
+fn print_listing(path: &Path) -> Result<(), Box<dyn Error>> {
+    let program = read_genome(path)?;
+    print!("{}", disasm::Listing::new(&program.genome, program.isa));
+    Ok(())
+}
+
+fn print_isa(version: &str) -> Result<(), Box<dyn Error>> {
+    let version: isa::IsaVersion = version.parse()?;
+    println!("{}", isa::REFERENCE_HEADER);
+    for instruction in version.instructions() {
+        println!("{}", isa::OpcodeInfo::new(instruction));
+    }
+    Ok(())
+}
+
+fn show_provenance(paths: &[&str]) -> Result<(), Box<dyn Error>> {
+    let mut all_valid = true;
+    for path in paths {
+        let file = ProgramFile::load(path)?;
+        if !file.legacy {
+            let metadata = file.metadata;
+            println!(
+                "{}: seed {}, generation {}, fitness {:.1}",
+                path, metadata.seed, metadata.generation, metadata.fitness
+            );
+        }
+        let program = file.program;
+        match Provenance::read(&program.genome) {
+            Ok(Some(provenance)) => println!("{}: {}, ISA {}", path, provenance, program.isa),
+            Ok(None) => println!("{}: no provenance, ISA {}", path, program.isa),
+            Err(e) => {
+                println!("{}: {}", path, e);
+                all_valid = false;
+            }
+        }
+    }
+    if !all_valid {
+        return Err("some genome files failed verification".into());
+    }
+    Ok(())
+}
+
+/// Value following `flag` among the command options, e.g. `--seed 7`
+fn option_value<'a>(options: &[&'a str], flag: &str) -> Option<&'a str> {
+    let pos = options.iter().position(|o| *o == flag)?;
+    options.get(pos + 1).copied()
+}
+
+fn selftest(options: &[&str]) -> Result<(), Box<dyn Error>> {
+    let seed = option_value(options, "--seed").map_or(Ok(selftest::DEFAULT_SEED), str::parse)?;
+    let ticks = option_value(options, "--ticks").map_or(Ok(selftest::DEFAULT_TICKS), str::parse)?;
+    let expected_hash = option_value(options, "--expect-hash")
+        .map(|h| u64::from_str_radix(h, 16))
+        .transpose()?;
+    let bundle = option_value(options, "--bundle")
+        .map(PopulationBundle::load)
+        .transpose()?;
+    let config = bundle
+        .as_ref()
+        .map_or_else(WorldConfig::default, |b| b.config.clone());
+
+    println!("selftest: seed {}, {} ticks per phase", seed, ticks);
+    let reports = selftest::run(&config, bundle.as_ref(), seed, ticks)?;
+    for report in &reports {
+        println!(
+            "  {:<10} ok  population {:>4}, food {:>4}, births {:>5}, trace {:016x}",
+            report.name, report.population, report.food_count, report.births, report.trace_hash
+        );
+    }
+
+    if let (Some(expected), Some(last)) = (expected_hash, reports.last())
+        && last.trace_hash != expected
+    {
+        return Err(format!(
+            "trace hash {:016x} does not match expected {:016x}",
+            last.trace_hash, expected
+        )
+        .into());
+    }
+    println!("selftest passed");
+    Ok(())
+}
+
+fn run(options: &[&str]) -> Result<(), Box<dyn Error>> {
+    let ticks = option_value(options, "--ticks")
+        .map(str::parse::<u64>)
+        .transpose()?;
+    let seed = option_value(options, "--seed").map_or(Ok(selftest::DEFAULT_SEED), str::parse)?;
+    let checkpoint = option_value(options, "--checkpoint").map(PathBuf::from);
+    let checkpoint_every = option_value(options, "--checkpoint-every")
+        .map_or(Ok(DEFAULT_CHECKPOINT_INTERVAL), str::parse)?
+        .max(1);
+    let targets = options
+        .windows(2)
+        .filter(|w| w[0] == "--notify")
+        .map(|w| NotifyTarget::parse(w[1]))
+        .collect::<Result<Vec<_>, _>>()?;
+    let bundle = option_value(options, "--bundle")
+        .map(PopulationBundle::load)
+        .transpose()?;
+    let scenario = option_value(options, "--scenario")
+        .map(Scenario::load)
+        .transpose()?;
+    let mut metrics = option_value(options, "--metrics")
+        .map(MetricsExport::open)
+        .transpose()?;
+    let metrics_every = option_value(options, "--metrics-every")
+        .map_or(Ok(DEFAULT_METRICS_INTERVAL), str::parse)?
+        .max(1);
+
+    let config = match (&bundle, &scenario) {
+        (Some(bundle), _) => bundle.config.clone(),
+        (None, Some(scenario)) => scenario.config.clone(),
+        (None, None) => WorldConfig::default(),
+    };
+    let mut world = World::new(WorldConfig {
+        seed: Some(seed),
+        ..config
+    });
+    if let Some(bundle) = &bundle {
+        bundle.apply_to(&mut world);
+    } else if let Some(scenario) = &scenario {
+        scenario.apply_to(&mut world);
+    }
+    match ticks {
+        Some(ticks) => println!("run: seed {}, {} ticks", seed, ticks),
+        None => println!("run: seed {}, until interrupted", seed),
+    }
+
+    // Failed notifications are logged as warnings; the world's own chatter stays quiet
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::WARN)
+        .with_writer(std::io::stderr)
+        .init();
+    let notifier = Notifier::start(targets);
+    let mut milestones = MilestoneTracker::default();
+    while ticks.is_none_or(|ticks| world.tick_count() < ticks) {
+        world.tick();
+        world.update_ecology();
+        if let Some(milestone) = milestones.observe(&world) {
+            println!("{}", milestone);
+            notifier.notify(milestone);
+        }
+        if let Some(path) = &checkpoint
+            && world.tick_count().is_multiple_of(checkpoint_every)
+        {
+            PopulationBundle::from_world(&world).save(path)?;
+            let milestone = Milestone::Checkpoint {
+                path: path.clone(),
+                tick: world.tick_count(),
+            };
+            println!("{}", milestone);
+            notifier.notify(milestone);
+        }
+        if let Some(metrics) = &mut metrics
+            && world.tick_count().is_multiple_of(metrics_every)
+        {
+            metrics.write(&world.flush_metrics())?;
+        }
+    }
+    notifier.finish();
+    if let Some(metrics) = &mut metrics
+        && world.metrics.population.window.count() > 0
+    {
+        metrics.write(&world.flush_metrics())?;
+    }
+
+    println!(
+        "tick {}: population {}, food {}, generation {}, fitness {:.1} s",
+        world.tick_count(),
+        world.lifeforms.len(),
+        world.food_items.len(),
+        world.generation,
+        world.fitness()
+    );
+    Ok(())
+}
+
+/// CSV file `run --metrics` appends flushed aggregates to
+struct MetricsExport {
+    file: std::fs::File,
+}
+
+impl MetricsExport {
+    /// Open for appending, writing the header if the file is new or empty
+    fn open(path: &str) -> std::io::Result<Self> {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        if file.metadata()?.len() == 0 {
+            writeln!(file, "{}", MetricsFlush::CSV_HEADER)?;
+        }
+        Ok(Self { file })
+    }
+
+    fn write(&mut self, flush: &MetricsFlush) -> std::io::Result<()> {
+        self.file.write_all(flush.csv_rows().as_bytes())
+    }
+}
+
+fn replay(path: &Path, options: &[&str]) -> Result<(), Box<dyn Error>> {
+    let replay = Replay::load(path)?;
+    let last_tick = replay.interventions.last().map_or(0, |r| r.tick);
+    let ticks = option_value(options, "--ticks").map_or(Ok(last_tick), str::parse)?;
+    match replay.config.seed {
+        Some(seed) => println!("replay: seed {}, {} ticks", seed, ticks),
+        None => println!("replay: unseeded, so the run will not match the recording"),
+    }
+    for recorded in &replay.interventions {
+        println!("  tick {:>6}: {}", recorded.tick, recorded.intervention);
+    }
+
+    let mut playback = Playback::new(replay);
+    let mut world = playback.world();
+    loop {
+        playback.apply_due(&mut world);
+        world.update_ecology();
+        playback.apply_due(&mut world);
+        if world.tick_count() >= ticks {
+            break;
+        }
+        world.tick();
+    }
+
+    println!(
+        "tick {}: population {}, food {}, trace {:016x}",
+        world.tick_count(),
+        world.lifeforms.len(),
+        world.food_items.len(),
+        selftest::trace_hash(&world)
+    );
+    if !playback.finished() {
+        println!(
+            "some interventions lie beyond tick {} and were not applied",
+            ticks
+        );
+    }
+    Ok(())
+}
+
+fn new_scenario(template: &str, dir: &Path) -> Result<(), Box<dyn Error>> {
+    let template = Template::parse(template).ok_or_else(|| {
+        let names: Vec<_> = Template::ALL.iter().map(Template::name).collect();
+        format!(
+            "unknown template `{}`; expected one of: {}",
+            template,
+            names.join(", ")
+        )
+    })?;
+    let scenario = template.scenario();
+    scenario.save(dir)?;
+    println!("{}: {}", template.name(), template.description());
+    println!(
+        "wrote {} with {} seed genomes; start it with `bacteria run --scenario {}`",
+        dir.display(),
+        scenario.seeds.len(),
+        dir.display()
+    );
+    Ok(())
+}
+
+fn serve_eval(options: &[&str]) -> Result<(), Box<dyn Error>> {
+    let mut scenario_fitness = None;
+    let target = match option_value(options, "--world") {
+        Some(world) => {
+            let config = match world {
+                "default" => WorldConfig::default(),
+                path if Path::new(path).is_dir() => {
+                    let scenario = Scenario::load(path)?;
+                    scenario_fitness = Some(scenario.fitness);
+                    scenario.config
+                }
+                path => PopulationBundle::load(path)?.config,
+            };
+            let seed =
+                option_value(options, "--seed").map_or(Ok(selftest::DEFAULT_SEED), str::parse)?;
+            let ticks =
+                option_value(options, "--ticks").map_or(Ok(DEFAULT_EVAL_TICKS), str::parse)?;
+            let copies =
+                option_value(options, "--copies").map_or(Ok(DEFAULT_EVAL_COPIES), str::parse)?;
+            EvalTarget::world(config, seed, ticks, copies)
+        }
+        None => EvalTarget::Vm {
+            steps: option_value(options, "--steps").map_or(Ok(DEFAULT_EVAL_STEPS), str::parse)?,
+        },
+    };
+    // An explicit --fitness replaces the scenario's shapers
+    let fitness = option_value(options, "--fitness")
+        .map(FitnessConfig::load)
+        .transpose()?
+        .or(scenario_fitness)
+        .unwrap_or_default();
+    let mut evaluator = Evaluator::new(target, FitnessPipeline::new(fitness));
+
+    // Failed requests are logged as warnings; the world's own chatter stays quiet
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::WARN)
+        .with_writer(std::io::stderr)
+        .init();
+    match option_value(options, "--listen") {
+        Some(addr) => {
+            let listener = TcpListener::bind(addr)?;
+            eprintln!(
+                "serve-eval: listening on http://{}/eval",
+                listener.local_addr()?
+            );
+            evaluator.serve_http(listener)?;
+        }
+        None => evaluator.serve_lines(std::io::stdin().lock(), std::io::stdout().lock())?,
+    }
+    Ok(())
+}
+
+fn trace_diff(a: &Path, b: &Path, options: &[&str]) -> Result<(), Box<dyn Error>> {
+    let ticks = option_value(options, "--ticks").map_or(Ok(200), str::parse)?;
+    let steps_per_tick = option_value(options, "--steps-per-tick").map_or(Ok(1), str::parse)?;
+    let seed = option_value(options, "--seed").map_or(Ok(selftest::DEFAULT_SEED), str::parse)?;
+
+    let stream = SensorStream::wandering(seed, ticks);
+    let divergence = Divergence::compare(
+        &read_genome(a)?,
+        &read_genome(b)?,
+        &stream,
+        steps_per_tick,
+        seed,
+    );
+    println!("A: {}", a.display());
+    println!("B: {}", b.display());
+    println!("{}", divergence);
+    Ok(())
+}
+
+#[cfg(feature = "rhai")]
+fn hostcall(script: &Path, genome: &Path, options: &[&str]) -> Result<(), Box<dyn Error>> {
+    use life::compute::VM;
+    use life::extension::OpcodeRegistry;
+    use life::hostcall::{HOSTCALL_OPCODE, HostcallScript};
+
+    let steps = option_value(options, "--steps").map_or(Ok(DEFAULT_HOSTCALL_STEPS), str::parse)?;
+    let mut registry = OpcodeRegistry::new();
+    HostcallScript::load(script)?.register(&mut registry, HOSTCALL_OPCODE)?;
+
+    // Failing script calls are logged as warnings
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::WARN)
+        .with_writer(std::io::stderr)
+        .init();
+    let program = read_genome(genome)?;
+    let mut vm: VM = VM::new();
+    vm.isa = program.isa;
+    vm.load_program(&program.genome);
+    let mut calls = 0;
+    for step in 0..steps {
+        let hostcall = vm.memory.get(vm.pc) == Some(&HOSTCALL_OPCODE);
+        let (pc, acc) = (vm.pc, vm.acc);
+        vm.step_with(&registry, &mut ());
+        if hostcall {
+            calls += 1;
+            let arg = vm.memory.get(pc + 1).copied().unwrap_or(0);
+            println!(
+                "step {:>5}  pc {:>3}  HOSTCALL {:>3}  acc {:>3} -> {:>3}",
+                step, pc, arg, acc, vm.acc
+            );
+        }
+        if vm.halted {
+            break;
+        }
+    }
+    println!(
+        "{} steps, {} host calls, acc {}{}",
+        vm.total_steps_count,
+        calls,
+        vm.acc,
+        if vm.halted { ", halted" } else { "" }
+    );
+    Ok(())
+}
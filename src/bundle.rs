@@ -0,0 +1,226 @@
+// bundle.rs
+
+// Single-file CBOR bundles holding a whole population with its config and stats
+
+#[cfg(feature = "serde")]
+use std::fs::File;
+#[cfg(feature = "serde")]
+use std::io::{BufReader, BufWriter};
+#[cfg(feature = "serde")]
+use std::path::Path;
+
+use rand::Rng;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::compute::{MEM_SIZE, VM};
+use crate::isa::{IsaVersion, Program};
+use crate::relocation;
+use crate::world::{Lifeform, ReproductionStats, Tag, World, WorldConfig};
+
+/// Bumped whenever the bundle layout changes incompatibly
+pub const BUNDLE_FORMAT_VERSION: u32 = 1;
+
+/// One creature as stored in a bundle
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CreatureRecord {
+    /// Program the creature was born with
+    pub genome: Vec<u8>,
+    /// Live memory, including anything the program wrote since birth
+    pub memory: Vec<u8>,
+    pub x: f32,
+    pub y: f32,
+    pub energy: f32,
+    pub age: u32,
+    pub color: [f32; 3],
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub tag: Option<Tag>,
+    /// Held to the world's sandbox; always set again when a world admits the record
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub sandboxed: bool,
+    /// Species in the world that wrote the record; cleared when another world admits it
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub species: Option<u32>,
+    /// Instruction set the genome runs under; bundles from before versioning ran v2
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub isa: IsaVersion,
+}
+
+impl CreatureRecord {
+    pub fn from_lifeform(lifeform: &Lifeform) -> Self {
+        Self {
+            genome: lifeform.vm.initial_state.to_vec(),
+            memory: lifeform.vm.memory.to_vec(),
+            x: lifeform.x,
+            y: lifeform.y,
+            energy: lifeform.energy,
+            age: lifeform.age,
+            color: lifeform.color,
+            tag: lifeform.tag.clone(),
+            sandboxed: lifeform.sandboxed,
+            species: lifeform.species,
+            isa: lifeform.vm.isa,
+        }
+    }
+
+    /// A freshly born creature carrying `program`, for bundles built from bare genome files
+    pub fn from_program(program: &Program, x: f32, y: f32, rng: &mut impl Rng) -> Self {
+        let mut vm = VM::new();
+        vm.isa = program.isa;
+        vm.load_program(&program.genome);
+        Self::from_lifeform(&Lifeform::from_vm_with_rng(vm, x, y, rng))
+    }
+
+    /// The creature as a live lifeform, with a new RND seed and color drawn from `rng`
+    pub fn to_lifeform(&self, rng: &mut impl Rng) -> Lifeform {
+        let mut vm = VM::new();
+        vm.isa = self.isa;
+        vm.load_program(&self.genome);
+        let len = self.memory.len().min(MEM_SIZE);
+        vm.memory[..len].copy_from_slice(&self.memory[..len]);
+
+        let mut lifeform = Lifeform::from_vm_with_rng(vm, self.x, self.y, rng);
+        lifeform.energy = self.energy;
+        lifeform.age = self.age;
+        lifeform.color = self.color;
+        lifeform.tag = self.tag.clone();
+        lifeform.sandboxed = self.sandboxed;
+        lifeform.species = self.species;
+        lifeform
+    }
+}
+
+/// Summary statistics captured when the bundle was written
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct PopulationStats {
+    pub generation: u32,
+    pub population: usize,
+    pub food_count: usize,
+    pub total_energy: f32,
+    pub mean_age: f32,
+    pub reproduction: ReproductionStats,
+    /// Mean share of relative branches in the genomes, see `relocation::mean_relative_fraction`
+    pub relative_branches: f32,
+}
+
+impl PopulationStats {
+    pub fn from_world(world: &World) -> Self {
+        let population = world.lifeforms.len();
+        let total_energy = world.lifeforms.iter().map(|l| l.energy).sum();
+        let mean_age = if population > 0 {
+            world.lifeforms.iter().map(|l| l.age as f32).sum::<f32>() / population as f32
+        } else {
+            0.0
+        };
+        Self {
+            generation: world.generation,
+            population,
+            food_count: world.food_items.len(),
+            total_energy,
+            mean_age,
+            reproduction: world.reproduction_stats,
+            relative_branches: relocation::mean_relative_fraction(
+                world.lifeforms.iter().map(|l| &l.vm.initial_state[..]),
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PopulationBundle {
+    pub format_version: u32,
+    pub config: WorldConfig,
+    pub stats: PopulationStats,
+    pub creatures: Vec<CreatureRecord>,
+}
+
+#[derive(Debug)]
+pub enum BundleError {
+    Io(std::io::Error),
+    Encode(String),
+    Decode(String),
+    UnsupportedVersion(u32),
+}
+
+impl std::fmt::Display for BundleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BundleError::Io(e) => write!(f, "bundle I/O error: {}", e),
+            BundleError::Encode(e) => write!(f, "failed to encode bundle: {}", e),
+            BundleError::Decode(e) => write!(f, "failed to decode bundle: {}", e),
+            BundleError::UnsupportedVersion(v) => {
+                write!(f, "unsupported bundle format version {}", v)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BundleError {}
+
+impl From<std::io::Error> for BundleError {
+    fn from(e: std::io::Error) -> Self {
+        BundleError::Io(e)
+    }
+}
+
+impl PopulationBundle {
+    pub fn from_world(world: &World) -> Self {
+        Self {
+            format_version: BUNDLE_FORMAT_VERSION,
+            config: world.config.clone(),
+            stats: PopulationStats::from_world(world),
+            creatures: world
+                .lifeforms
+                .iter()
+                .map(CreatureRecord::from_lifeform)
+                .collect(),
+        }
+    }
+
+    /// Replace the world's population and config with the bundle's contents.
+    ///
+    /// Bundles may come from anyone, so the world keeps its own sandbox and every
+    /// creature is sandboxed.
+    pub fn apply_to(&self, world: &mut World) {
+        let sandbox = world.config.sandbox.take();
+        world.config = self.config.clone();
+        world.config.sandbox = sandbox;
+        world.generation = self.stats.generation;
+        world.reproduction_stats = self.stats.reproduction;
+        world.lifeforms = self
+            .creatures
+            .iter()
+            .map(|record| {
+                let mut lifeform = record.to_lifeform(&mut world.rng);
+                lifeform.sandboxed = true;
+                lifeform
+            })
+            .collect();
+        world.reset_species();
+        // The population was replaced wholesale, so restart any audit from here
+        if world.audit.is_some() {
+            world.enable_audit();
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), BundleError> {
+        let writer = BufWriter::new(File::create(path)?);
+        ciborium::into_writer(self, writer).map_err(|e| BundleError::Encode(e.to_string()))
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, BundleError> {
+        let reader = BufReader::new(File::open(path)?);
+        let bundle: Self =
+            ciborium::from_reader(reader).map_err(|e| BundleError::Decode(e.to_string()))?;
+        if bundle.format_version != BUNDLE_FORMAT_VERSION {
+            return Err(BundleError::UnsupportedVersion(bundle.format_version));
+        }
+        Ok(bundle)
+    }
+}
@@ -2,58 +2,1035 @@
 
 // Simple 8-bit virtual machine
 
-pub const MEM_SIZE: usize = 256;
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::ops::{Range, RangeInclusive};
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+pub mod mutation;
+
+use crate::extension::{CustomOpcode, OpcodeRegistry, RegistryError};
+use crate::io::{IoDevice, IoPorts};
+use crate::isa::{IsaError, IsaVersion, Program};
+use crate::mmio::{MmioError, MmioHandler, MmioMap};
+use crate::provenance::Fnv1a;
+#[cfg(feature = "heatmap")]
+use crate::trace::Heatmap;
+use crate::trace::{OpcodeProfile, RecentTrace, SampledTrace, TRACE_CAPACITY, TraceEvent};
+
+pub const MEM_SIZE: usize = 256; // Memory of the default VM, one byte of address space
+pub const STACK_TOP: u8 = 240; // CALL pushes return addresses just below this, growing down
+pub const DATA_STACK_BASE: u8 = STACK_TOP - 16; // PUSH stores from here up, towards the call stack
+pub const VM16_MEM_SIZE: usize = 1 << 16; // Memory of `VM16`, all of it addressable
+pub const LOOP_SAMPLE_INTERVAL: u32 = 8; // Default steps between loop detector state samples
+pub const LOOP_MAX_PERIOD: u32 = 512; // Default longest cycle the loop detector looks for, in samples
+
+// Bits of the flags register, set by ADD, SUB and CMP
+pub const FLAG_ZERO: u8 = 0x01; // Result was zero
+pub const FLAG_CARRY: u8 = 0x02; // Unsigned overflow on ADD, borrow on SUB/CMP
+pub const FLAG_NEGATIVE: u8 = 0x04; // Top bit of the result was set
+pub const FLAG_TRAP: u8 = 0x08; // A store hit protected memory; stays set until reset or restart
+
+// Program file constants
+pub const PROGRAM_FILE_MAGIC: [u8; 4] = *b"BVMF";
+pub const PROGRAM_FILE_VERSION: u8 = 1; // Header layout written by this build
+pub const PROGRAM_HEADER_LEN: usize = 36; // Magic, version, ISA, 2 reserved, seed, generation, fitness, checksum
+
+/// A VM with `N` bytes of memory and address operands `A` wide.
+///
+/// With the default one-byte addresses, LDA, STA, absolute jumps and the stack
+/// reach the first 256 bytes; code beyond that runs by falling through or through
+/// relative jumps. Addresses past the end of a smaller memory read as 0 and ignore
+/// stores, and executing past the end halts.
+///
+/// With the `serde` feature a VM serializes its memory, registers, counters and
+/// settings; attached devices, breakpoints, watchpoints and execution history are
+/// left out and come back empty.
 #[derive(Debug, Clone)]
-pub struct VM {
-    pub memory: [u8; MEM_SIZE],
-    pub initial_state: [u8; MEM_SIZE],
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct VM<const N: usize = MEM_SIZE, A: AddressWidth = Addr8> {
+    #[cfg_attr(feature = "serde", serde(with = "byte_array"))]
+    pub memory: [u8; N],
+    #[cfg_attr(feature = "serde", serde(with = "byte_array"))]
+    pub initial_state: [u8; N],
     pub pc: usize, // program counter
     pub acc: u8,   // accumulator
+    pub x: u8,     // general-purpose register, reached through TAX and TXA
+    pub y: u8,     // general-purpose register, reached through TAY and TYA
+    pub sp: usize, // stack pointer, the address of the most recent return address
+    #[cfg_attr(feature = "serde", serde(default = "data_stack_base::<A>"))]
+    pub dsp: usize, // data stack pointer, where the next PUSH stores the accumulator
+    pub flags: u8, // FLAG_* bits from the last ADD, SUB or CMP
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub isa: IsaVersion, // decode table for the program, kept across loads
     pub halted: bool,
-    pub total_steps_count: usize,         // steps before halting
-    pub recent_instructions: Vec<String>, // log of recent instructions
+    pub total_steps_count: usize, // steps before halting
+    pub costs: CostTable,         // energy charged per executed opcode
+    pub consumed_energy: f32,     // sum of `costs` over the steps since reset
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub cycle_table: Option<CycleTable>, // cycles per executed opcode, one per step without
+    #[cfg_attr(feature = "serde", serde(default))]
+    cycle_credit: i64, // cycles granted to `run_for_cycles` and not yet run, negative when overspent
+    #[cfg_attr(feature = "serde", serde(default))]
+    metrics: VmMetrics, // counters behind `metrics`; energy comes from `consumed_energy`
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub recent: RecentTrace, // the last few executed instructions
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub last_write: Option<MemoryWrite>, // store made by the last step, for MMIO devices
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub trace: SampledTrace, // sparse long-horizon history, for post-mortems
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub profile: Option<OpcodeProfile>, // executions per opcode since reset, off by default
+    rng_seed: u64,  // RND sequence restarts from here on reset
+    rng_state: u64, // SplitMix64 state behind RND
+    #[cfg_attr(feature = "serde", serde(skip))]
+    ports: IoPorts, // host devices reached with IN and OUT
+    pub interrupt: Option<TimerInterrupt>, // periodic jump to a handler, off by default
+    #[cfg_attr(feature = "serde", serde(default))]
+    coroutine: Option<usize>, // entry of the second thread, set by `set_coroutine`
+    #[cfg_attr(feature = "serde", serde(default))]
+    threads: Vec<Thread>, // every thread's registers, the running one's stale; empty with one thread
+    #[cfg_attr(feature = "serde", serde(default))]
+    thread: usize, // index of the running thread in `threads`
+    steps_since_interrupt: u32, // steps counted toward the next interrupt
+    #[cfg_attr(feature = "serde", serde(with = "byte_array::bank"))]
+    data: Option<Box<[u8; N]>>, // separate data bank in Harvard mode
+    protected: Vec<(Range<usize>, Protection)>, // set by `protect`, later ranges win
+    pub protection_fault: ProtectionFault, // what a refused store does
+    trapped: bool,              // the last step raised FLAG_TRAP
+    pub loop_detector: Option<LoopDetector>, // halts programs stuck repeating a state
+    #[cfg_attr(feature = "serde", serde(skip))]
+    opcodes: OpcodeRegistry<(), N, A>, // set by `register_opcode`, kept across loads
+    #[cfg_attr(feature = "serde", serde(skip))]
+    mmio: MmioMap, // set by `map_io`, kept across loads
+    #[cfg_attr(feature = "serde", serde(skip))]
+    breakpoints: Vec<Breakpoint>, // set by `add_breakpoint`, kept across loads
+    #[cfg_attr(feature = "serde", serde(skip))]
+    watchpoints: Vec<Watchpoint>, // set by `add_watchpoint`, kept across loads
+    #[cfg_attr(feature = "serde", serde(skip))]
+    hit: Option<DebugHit>, // first breakpoint or watchpoint of this step
+    #[cfg_attr(feature = "serde", serde(skip))]
+    fault: Option<VmError>, // first mistake `step` papered over this step, for `try_step`
+    #[cfg_attr(feature = "serde", serde(skip))]
+    undo: VecDeque<StepUndo>, // the last `undo_depth` steps, newest last, for `step_back`
+    #[cfg_attr(feature = "serde", serde(skip))]
+    undo_depth: usize, // steps `step_back` can rewind; 0 keeps no journal
+    cycle_search: CycleSearch,  // loop detector progress since reset or restart
+    #[cfg(feature = "heatmap")]
+    #[cfg_attr(feature = "serde", serde(skip, default = "fresh_heatmap::<N>"))]
+    pub heatmap: Heatmap, // reads, writes and executions per cell since reset
+    #[cfg_attr(feature = "serde", serde(skip))]
+    address_width: PhantomData<A>,
+}
+
+/// The standard 256-byte VM used by the simulations
+pub type Vm256 = VM<MEM_SIZE, Addr8>;
+
+/// A 64KB VM with two-byte, little-endian address operands and return addresses.
+///
+/// It decodes and executes the same instruction set as the 8-bit VM; only the
+/// instructions taking an address grow by a byte. Immediates, ports and relative
+/// offsets stay one byte.
+pub type VM16 = VM<VM16_MEM_SIZE, Addr16>;
+
+/// Width of address operands, the one thing that differs between the 8- and 16-bit VMs
+pub trait AddressWidth: Copy + Default + std::fmt::Debug + 'static {
+    /// Bytes in an address operand or a return address on the stack
+    const BYTES: usize;
+    /// Initial stack pointer; the stack grows down from here
+    const STACK_TOP: usize;
+    /// Initial data stack pointer; PUSH grows the data stack up from here
+    const DATA_STACK_BASE: usize;
+    /// Addresses are taken modulo this, like the 8-bit stack pointer wrapping
+    const SPACE: usize = 1 << (8 * Self::BYTES);
+}
+
+/// One-byte addresses, reaching 256 bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Addr8;
+
+impl AddressWidth for Addr8 {
+    const BYTES: usize = 1;
+    const STACK_TOP: usize = STACK_TOP as usize;
+    const DATA_STACK_BASE: usize = DATA_STACK_BASE as usize;
+}
+
+/// Two-byte addresses, reaching 64KB
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Addr16;
+
+impl AddressWidth for Addr16 {
+    const BYTES: usize = 2;
+    const STACK_TOP: usize = VM16_MEM_SIZE - 256;
+    const DATA_STACK_BASE: usize = VM16_MEM_SIZE - 512;
+}
+
+/// Every `period` steps, push `pc` like CALL and jump to `vector`; RET resumes the program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TimerInterrupt {
+    /// Steps between interrupts; 0 behaves like 1
+    pub period: u32,
+    /// Address of the handler
+    pub vector: u16,
+}
+
+/// Energy charged for executing each opcode, indexed by the opcode byte.
+///
+/// Every opcode is free until given a cost, so the default table allocates nothing.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CostTable(Vec<f32>);
+
+impl CostTable {
+    /// The same cost for every opcode, including undefined ones
+    pub fn uniform(cost: f32) -> Self {
+        Self(vec![cost; 256])
+    }
+
+    pub fn is_free(&self) -> bool {
+        self.0.iter().all(|&cost| cost == 0.0)
+    }
+}
+
+impl std::ops::Index<u8> for CostTable {
+    type Output = f32;
+
+    fn index(&self, opcode: u8) -> &f32 {
+        self.0.get(opcode as usize).unwrap_or(&0.0)
+    }
+}
+
+impl std::ops::IndexMut<u8> for CostTable {
+    fn index_mut(&mut self, opcode: u8) -> &mut f32 {
+        if self.0.len() < 256 {
+            self.0.resize(256, 0.0);
+        }
+        &mut self.0[opcode as usize]
+    }
+}
+
+/// Cycles each opcode takes when a VM is cycle-timed, indexed by the opcode byte.
+///
+/// Opcodes without an entry take one cycle, so the default table allocates nothing
+/// and times every instruction like a step; 0 behaves like 1.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CycleTable(Vec<u32>);
+
+impl CycleTable {
+    /// The same count for every opcode, including undefined ones
+    pub fn uniform(cycles: u32) -> Self {
+        Self(vec![cycles; 256])
+    }
+
+    /// One cycle per byte an instruction of `isa` occupies on the 8-bit VM, plus one
+    /// for each stack access and extra for the slow ones: MUL takes 4 more, and DIV
+    /// and COPY 8 more
+    pub fn standard(isa: IsaVersion) -> Self {
+        let mut table = Self::default();
+        for opcode in 0..=255u8 {
+            let Some(instruction) = isa.decode(opcode) else {
+                continue;
+            };
+            let extra = match instruction {
+                Instruction::MUL => 4,
+                Instruction::DIV | Instruction::COPY => 8,
+                Instruction::CALL | Instruction::RET | Instruction::PUSH | Instruction::POP => 1,
+                _ => 0,
+            };
+            table[opcode] = instruction.size() as u32 + extra;
+        }
+        table
+    }
+}
+
+impl std::ops::Index<u8> for CycleTable {
+    type Output = u32;
+
+    fn index(&self, opcode: u8) -> &u32 {
+        self.0.get(opcode as usize).unwrap_or(&1)
+    }
+}
+
+impl std::ops::IndexMut<u8> for CycleTable {
+    fn index_mut(&mut self, opcode: u8) -> &mut u32 {
+        if self.0.len() < 256 {
+            self.0.resize(256, 1);
+        }
+        &mut self.0[opcode as usize]
+    }
+}
+
+/// Halting programs whose whole state repeats, i.e. that can never do anything new.
+///
+/// Every `interval` steps the VM hashes its registers, memory, data bank and RND
+/// state, and Brent's algorithm looks for a repeat among those samples. Loops that
+/// keep changing anything, such as a counter or a sensor the host rewrites, are
+/// never flagged, however tight. State changed from outside between samples (IO
+/// devices, sensors) counts as progress.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct LoopDetector {
+    /// Steps between state samples; 0 behaves like 1
+    pub interval: u32,
+    /// Longest cycle looked for, in samples; longer ones go unnoticed
+    pub max_period: u32,
+    /// Zero `total_steps_count` when a loop is found, so it scores as no work done
+    pub reset_steps: bool,
+}
+
+impl Default for LoopDetector {
+    fn default() -> Self {
+        Self {
+            interval: LOOP_SAMPLE_INTERVAL,
+            max_period: LOOP_MAX_PERIOD,
+            reset_steps: true,
+        }
+    }
+}
+
+/// Host settings a VM is built with by `VM::with_config`, gathered in one place
+/// instead of set field by field after `VM::new`; the default gives the same VM
+/// as `VM::new`. Set them with the `with_*` builders, e.g.
+/// `VmConfig::default().with_trace_depth(1024).with_loop_detector(None)`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct VmConfig {
+    /// Halts programs stuck repeating a state; `None` lets them run until the budget is spent
+    pub loop_detector: Option<LoopDetector>,
+    /// Entries kept by the sampled trace, `VM::trace`
+    pub trace_depth: usize,
+    /// What a store into read-only memory does
+    pub protection_fault: ProtectionFault,
+    /// Energy charged per executed opcode
+    pub costs: CostTable,
+    /// Cycles per executed opcode; `None` counts one cycle per step
+    pub cycle_table: Option<CycleTable>,
+    /// Steps `VM::step_back` can rewind; 0, the default, keeps no journal
+    pub undo_depth: usize,
+    /// Instruction set programs are decoded with; the latest by default
+    pub isa: IsaVersion,
+}
+
+impl Default for VmConfig {
+    fn default() -> Self {
+        Self {
+            loop_detector: Some(LoopDetector::default()),
+            trace_depth: TRACE_CAPACITY,
+            protection_fault: ProtectionFault::Ignore,
+            costs: CostTable::default(),
+            cycle_table: None,
+            undo_depth: 0,
+            isa: IsaVersion::LATEST,
+        }
+    }
+}
+
+impl VmConfig {
+    pub fn with_loop_detector(mut self, loop_detector: Option<LoopDetector>) -> Self {
+        self.loop_detector = loop_detector;
+        self
+    }
+
+    pub fn with_trace_depth(mut self, depth: usize) -> Self {
+        self.trace_depth = depth;
+        self
+    }
+
+    pub fn with_protection_fault(mut self, fault: ProtectionFault) -> Self {
+        self.protection_fault = fault;
+        self
+    }
+
+    pub fn with_costs(mut self, costs: CostTable) -> Self {
+        self.costs = costs;
+        self
+    }
+
+    pub fn with_cycle_table(mut self, cycle_table: Option<CycleTable>) -> Self {
+        self.cycle_table = cycle_table;
+        self
+    }
+
+    pub fn with_undo_depth(mut self, depth: usize) -> Self {
+        self.undo_depth = depth;
+        self
+    }
+
+    pub fn with_isa(mut self, isa: IsaVersion) -> Self {
+        self.isa = isa;
+        self
+    }
+}
+
+/// Brent's cycle search over the state hashes sampled so far
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct CycleSearch {
+    steps_until_sample: u32,
+    /// The sample everything after it is compared against
+    saved: Option<u64>,
+    /// Samples compared against `saved` before it is replaced
+    power: u32,
+    /// Samples since `saved` was taken
+    lam: u32,
+}
+
+/// What one step changed, enough for `VM::step_back` to undo it
+#[derive(Debug, Clone)]
+struct StepUndo {
+    pc: usize,
+    acc: u8,
+    x: u8,
+    y: u8,
+    sp: usize,
+    dsp: usize,
+    flags: u8,
+    halted: bool,
+    trapped: bool,
+    total_steps_count: usize,
+    consumed_energy: f32,
+    metrics: VmMetrics,
+    cycle_credit: i64,
+    threads: Vec<Thread>,
+    thread: usize,
+    rng_state: u64,
+    steps_since_interrupt: u32,
+    cycle_search: CycleSearch,
+    /// Stores the step made, as address and previous value, oldest first
+    writes: Vec<(usize, u8)>,
+}
+
+/// Why `VM::run_for` stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepResult {
+    /// The program halted, or already had
+    Halted,
+    /// All `max_steps` ran and the program is still going
+    BudgetExhausted,
+    /// A store hit protected memory under `ProtectionFault::Trap`
+    Trapped,
+    /// A breakpoint or watchpoint was hit
+    Hit(DebugHit),
+}
+
+/// Why `VM::run_until` stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunOutcome {
+    /// The predicate held after `steps` steps; 0 if it held before any
+    Satisfied { steps: usize },
+    /// The program halted after `steps` steps without the predicate holding
+    Halted { steps: usize },
+    /// The step cap ran out with the program still going
+    StepLimit,
+}
+
+/// Access allowed to a range of memory marked with `VM::protect`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Protection {
+    #[default]
+    ReadWrite,
+    /// Stores from STA, SWP and CALL are refused; the host can still write
+    ReadOnly,
+}
+
+/// What a store into read-only memory does besides being dropped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ProtectionFault {
+    /// Nothing; the program carries on as if the store happened
+    #[default]
+    Ignore,
+    /// Set `FLAG_TRAP` so the host can notice the violation
+    Trap,
+}
+
+/// Memory access a watchpoint fires on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    OnRead,
+    OnWrite,
+}
+
+/// An address execution is reported at, and how often it was reached
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub addr: usize,
+    pub hits: u64,
+}
+
+/// A data address whose reads or writes are reported, and how often that happened
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub addr: usize,
+    pub kind: WatchKind,
+    pub hits: u64,
+}
+
+/// A breakpoint or watchpoint reported by `VM::step`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugHit {
+    /// `pc` arrived at a breakpoint; the instruction there has not run yet
+    Breakpoint { addr: usize },
+    /// The step read or wrote a watched address
+    Watchpoint { addr: usize, kind: WatchKind },
+}
+
+/// A mistake in a program that `VM::step` silently works around and `VM::try_step` reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VmError {
+    /// The instruction at `pc` read or wrote `addr`, or read an operand byte there,
+    /// past the end of memory; reads gave 0 and writes were dropped
+    OutOfBounds { pc: usize, addr: usize },
+    /// `opcode` at `pc` is not an instruction; it ran as HLT
+    InvalidOpcode { pc: usize, opcode: u8 },
+}
+
+impl std::fmt::Display for VmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VmError::OutOfBounds { pc, addr } => {
+                write!(
+                    f,
+                    "instruction at {} accessed address {} past the end of memory",
+                    pc, addr
+                )
+            }
+            VmError::InvalidOpcode { pc, opcode } => {
+                write!(f, "invalid opcode 0x{:02X} at {}", opcode, pc)
+            }
+        }
+    }
+}
+
+impl std::error::Error for VmError {}
+
+/// Counters a VM accumulates while stepping, since it was loaded or reset; read
+/// with `VM::metrics`. Restarts keep them, like `total_steps_count`, and
+/// `step_back`, `snapshot` and `restore` carry them along.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct VmMetrics {
+    /// Steps run, including those the loop detector takes back from `total_steps_count`
+    pub steps: u64,
+    /// Cycles those steps took under `VM::cycle_table`, or one per step without it
+    pub cycles: u64,
+    /// Steps that left the PC anywhere but the next instruction: taken jumps, calls,
+    /// returns and skips
+    pub jumps_taken: u64,
+    /// Stores that landed in memory; refused ones are not counted
+    pub memory_writes: u64,
+    /// Times the program halted, by HLT, running off memory or the loop detector
+    pub halts: u64,
+    /// Stores refused under `ProtectionFault::Trap`
+    pub traps: u64,
+    /// Sum of `VM::costs` over the steps run, the same as `VM::consumed_energy`
+    pub energy_spent: f32,
+}
+
+impl std::fmt::Display for VmMetrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} steps, {} cycles, {} jumps, {} writes, {} halts, {} traps, {:.1} energy",
+            self.steps,
+            self.cycles,
+            self.jumps_taken,
+            self.memory_writes,
+            self.halts,
+            self.traps,
+            self.energy_spent
+        )
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
+/// Registers of one execution thread of a VM running co-routines; see `VM::coroutine`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Thread {
+    pub pc: usize,
+    pub acc: u8,
+    pub x: u8,
+    pub y: u8,
+    pub flags: u8,
+    /// This thread ran HLT or off the end of memory; the others carry on
+    pub halted: bool,
+}
+
+impl Thread {
+    /// A thread about to start at `pc` with cleared registers
+    pub fn at(pc: usize) -> Self {
+        Self {
+            pc,
+            ..Self::default()
+        }
+    }
+}
+
+/// A store to memory made by one VM step
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryWrite {
+    pub addr: usize,
+    pub old: u8,
+    pub new: u8,
+}
+
+/// A VM's execution state at one moment, taken by `VM::snapshot`.
+///
+/// Holds everything the rest of a run depends on: memory and the data bank,
+/// registers, counters and the RND state. Debugging history (`recent`, `trace`,
+/// profiles) and host configuration (devices, costs, breakpoints) are not part of it.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct VmSnapshot<const N: usize = MEM_SIZE> {
+    #[cfg_attr(feature = "serde", serde(with = "byte_array"))]
+    pub memory: [u8; N],
+    #[cfg_attr(feature = "serde", serde(with = "byte_array"))]
+    pub initial_state: [u8; N],
+    /// The separate data bank, if the VM was in Harvard mode
+    #[cfg_attr(feature = "serde", serde(with = "byte_array::bank"))]
+    pub data: Option<Box<[u8; N]>>,
+    pub pc: usize,
+    pub acc: u8,
+    pub x: u8,
+    pub y: u8,
+    pub sp: usize,
+    pub dsp: usize,
+    pub flags: u8,
+    pub halted: bool,
+    pub total_steps_count: usize,
+    pub consumed_energy: f32,
+    #[cfg_attr(feature = "serde", serde(default))]
+    metrics: VmMetrics,
+    #[cfg_attr(feature = "serde", serde(default))]
+    cycle_credit: i64,
+    #[cfg_attr(feature = "serde", serde(default))]
+    threads: Vec<Thread>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    thread: usize,
+    rng_state: u64,
+    steps_since_interrupt: u32,
+    trapped: bool,
+    cycle_search: CycleSearch,
+}
+
+/// A register or counter compared by `VM::diff`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    Pc,
+    Acc,
+    X,
+    Y,
+    Sp,
+    Dsp,
+    Flags,
+    /// 1 when halted, 0 when running
+    Halted,
+    /// `total_steps_count`
+    Steps,
+}
+
+impl Register {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Register::Pc => "PC",
+            Register::Acc => "ACC",
+            Register::X => "X",
+            Register::Y => "Y",
+            Register::Sp => "SP",
+            Register::Dsp => "DSP",
+            Register::Flags => "FLAGS",
+            Register::Halted => "HALTED",
+            Register::Steps => "STEPS",
+        }
+    }
+}
+
+/// A register holding different values in two VMs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterDiff {
+    pub register: Register,
+    pub old: usize,
+    pub new: usize,
+}
+
+/// A memory cell holding different values in two VMs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CellDiff {
+    pub addr: usize,
+    pub old: u8,
+    pub new: u8,
+}
+
+/// What differs between two VMs, from `VM::diff`: `old` values come from the VM
+/// `diff` was called on, `new` values from the one it was given
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VmDiff {
+    pub registers: Vec<RegisterDiff>,
+    /// Differing cells of `memory`, by address
+    pub memory: Vec<CellDiff>,
+    /// Differing cells of the data bank, by address; only compared when either VM
+    /// runs in Harvard mode
+    pub data: Vec<CellDiff>,
+}
+
+impl VmDiff {
+    pub fn is_empty(&self) -> bool {
+        self.registers.is_empty() && self.memory.is_empty() && self.data.is_empty()
+    }
+}
+
+impl std::fmt::Display for VmDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return write!(f, "no differences");
+        }
+        let registers: Vec<String> = self
+            .registers
+            .iter()
+            .map(|r| format!("{} {} -> {}", r.register.name(), r.old, r.new))
+            .collect();
+        if registers.is_empty() {
+            write!(f, "registers: same")?;
+        } else {
+            write!(f, "registers: {}", registers.join(", "))?;
+        }
+        for (bank, cells) in [("memory", &self.memory), ("data", &self.data)] {
+            if cells.is_empty() {
+                continue;
+            }
+            write!(f, "\n{}: {} cells differ", bank, cells.len())?;
+            for cell in cells {
+                write!(
+                    f,
+                    "\n  {:04}: 0x{:02X} -> 0x{:02X}",
+                    cell.addr, cell.old, cell.new
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Where a saved program came from; zero where unknown
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ProgramMetadata {
+    /// Seed of the run that bred it
+    pub seed: u64,
+    pub generation: u32,
+    pub fitness: f64,
+}
+
+#[derive(Debug)]
+pub enum ProgramFileError {
+    Io(std::io::Error),
+    /// The file starts with the magic but is shorter than a header
+    Truncated,
+    /// A header layout newer than this build
+    UnsupportedVersion(u8),
+    Isa(IsaError),
+    /// The header or genome changed after the file was written
+    Checksum,
+}
+
+impl std::fmt::Display for ProgramFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProgramFileError::Io(e) => write!(f, "program file I/O error: {}", e),
+            ProgramFileError::Truncated => write!(f, "program file header is truncated"),
+            ProgramFileError::UnsupportedVersion(v) => {
+                write!(f, "program file format v{} is newer than this build", v)
+            }
+            ProgramFileError::Isa(e) => write!(f, "{}", e),
+            ProgramFileError::Checksum => write!(f, "program file checksum does not match"),
+        }
+    }
+}
+
+impl std::error::Error for ProgramFileError {}
+
+impl From<std::io::Error> for ProgramFileError {
+    fn from(e: std::io::Error) -> Self {
+        ProgramFileError::Io(e)
+    }
+}
+
+impl From<IsaError> for ProgramFileError {
+    fn from(e: IsaError) -> Self {
+        ProgramFileError::Isa(e)
+    }
+}
+
+/// A program file: a `PROGRAM_HEADER_LEN`-byte header holding the ISA and
+/// metadata, sealed by a checksum, then the genome.
+///
+/// Files without the header are legacy dumps: raw memory, optionally followed by
+/// an ISA tag, loaded with default metadata.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProgramFile {
+    pub program: Program,
+    pub metadata: ProgramMetadata,
+    /// Read from a file without the header
+    pub legacy: bool,
+}
+
+impl ProgramFile {
+    pub fn new(program: Program, metadata: ProgramMetadata) -> Self {
+        ProgramFile {
+            program,
+            metadata,
+            legacy: false,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut file = Vec::with_capacity(PROGRAM_HEADER_LEN + self.program.genome.len());
+        file.extend_from_slice(&PROGRAM_FILE_MAGIC);
+        file.extend_from_slice(&[PROGRAM_FILE_VERSION, self.program.isa.number(), 0, 0]);
+        file.extend_from_slice(&self.metadata.seed.to_le_bytes());
+        file.extend_from_slice(&self.metadata.generation.to_le_bytes());
+        file.extend_from_slice(&self.metadata.fitness.to_bits().to_le_bytes());
+        let checksum = Self::checksum(&file, &self.program.genome);
+        file.extend_from_slice(&checksum.to_le_bytes());
+        file.extend_from_slice(&self.program.genome);
+        file
+    }
+
+    /// Parse a program file, falling back to `Program::from_file_bytes` for legacy dumps
+    pub fn from_bytes(file: Vec<u8>) -> Result<Self, ProgramFileError> {
+        if !file.starts_with(&PROGRAM_FILE_MAGIC) {
+            return Ok(ProgramFile {
+                program: Program::from_file_bytes(file)?,
+                metadata: ProgramMetadata::default(),
+                legacy: true,
+            });
+        }
+        if file.len() < PROGRAM_HEADER_LEN {
+            return Err(ProgramFileError::Truncated);
+        }
+        if file[4] > PROGRAM_FILE_VERSION {
+            return Err(ProgramFileError::UnsupportedVersion(file[4]));
+        }
+        let isa = IsaVersion::from_number(file[5]).ok_or(IsaError::UnknownVersion(file[5]))?;
+        let field = |range: Range<usize>| -> [u8; 8] {
+            let mut bytes = [0; 8];
+            bytes[..range.len()].copy_from_slice(&file[range]);
+            bytes
+        };
+        let (header, genome) = file.split_at(PROGRAM_HEADER_LEN);
+        let checksum = u64::from_le_bytes(field(28..36));
+        if checksum != Self::checksum(&header[..28], genome) {
+            return Err(ProgramFileError::Checksum);
+        }
+        Ok(ProgramFile {
+            program: Program {
+                genome: genome.to_vec(),
+                isa,
+            },
+            metadata: ProgramMetadata {
+                seed: u64::from_le_bytes(field(8..16)),
+                generation: u64::from_le_bytes(field(16..20)) as u32,
+                fitness: f64::from_bits(u64::from_le_bytes(field(20..28))),
+            },
+            legacy: false,
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_bytes())
+    }
+
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<Self, ProgramFileError> {
+        Self::from_bytes(std::fs::read(path)?)
+    }
+
+    fn checksum(header: &[u8], genome: &[u8]) -> u64 {
+        let mut hash = Fnv1a::new();
+        hash.write(header);
+        hash.write(genome);
+        hash.finish()
+    }
+}
+
+/// How a halted VM is brought back to life
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum RestartMode {
+    /// Jump back to address 0 and keep whatever the program wrote to memory
+    #[default]
+    Warm,
+    /// Restore memory from `initial_state` before jumping back to address 0
+    Cold,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Instruction {
-    NOP = 0x00, // No operation
-    LDA = 0x01, // Load accumulator from memory
-    STA = 0x02, // Store accumulator to memory
-    ADD = 0x03, // Add memory to accumulator
-    SUB = 0x04, // Subtract memory from accumulator
-    JMP = 0x05, // Jump to address
-    JZ = 0x06,  // Jump if accumulator is zero
-    INC = 0x07, // Increment accumulator
-    DEC = 0x08, // Decrement accumulator
-    SWP = 0x09, // Swap accumulator with memory
-    CMP = 0x0A, // Compare accumulator with memory
-    HLT = 0xFF, // Halt
+    NOP = 0x00,  // No operation
+    LDA = 0x01,  // Load accumulator from memory
+    STA = 0x02,  // Store accumulator to memory
+    ADD = 0x03,  // Add memory to accumulator
+    SUB = 0x04,  // Subtract memory from accumulator
+    JMP = 0x05,  // Jump to address
+    JZ = 0x06,   // Jump if accumulator is zero
+    INC = 0x07,  // Increment accumulator
+    DEC = 0x08,  // Decrement accumulator
+    SWP = 0x09,  // Swap accumulator with memory
+    CMP = 0x0A,  // Compare accumulator with memory
+    CALL = 0x0B, // Push return address and jump to subroutine
+    RET = 0x0C,  // Pop return address and jump back
+    JNZ = 0x0D,  // Jump if the zero flag is clear
+    JC = 0x0E,   // Jump if the carry flag is set
+    JNC = 0x0F,  // Jump if the carry flag is clear
+    JN = 0x10,   // Jump if the negative flag is set
+    AND = 0x11,  // Bitwise AND memory into accumulator
+    OR = 0x12,   // Bitwise OR memory into accumulator
+    XOR = 0x13,  // Bitwise XOR memory into accumulator
+    NOT = 0x14,  // Invert every bit of the accumulator
+    SHL = 0x15,  // Shift accumulator left one bit
+    SHR = 0x16,  // Shift accumulator right one bit
+    ROL = 0x17,  // Rotate accumulator left one bit
+    ROR = 0x18,  // Rotate accumulator right one bit
+    MUL = 0x19,  // Multiply accumulator by memory, keeping the low byte
+    DIV = 0x1A,  // Divide accumulator by memory; by zero gives 0xFF with carry set
+    LDI = 0x1B,  // Load accumulator with the operand byte itself
+    ADI = 0x1C,  // Add the operand byte to accumulator
+    SBI = 0x1D,  // Subtract the operand byte from accumulator
+    CPI = 0x1E,  // Compare accumulator with the operand byte
+    SKE = 0x1F,  // Skip the next instruction if accumulator equals memory
+    SKNE = 0x20, // Skip the next instruction if accumulator differs from memory
+    RND = 0x21,  // Load a pseudo-random byte into accumulator
+    IN = 0x22,   // Load accumulator from the device on the operand port
+    OUT = 0x23,  // Write accumulator to the device on the operand port
+    JR = 0x24,   // Jump by a signed offset from this instruction
+    JRZ = 0x25,  // Jump by a signed offset if accumulator is zero
+    TAX = 0x26,  // Copy accumulator to X
+    TXA = 0x27,  // Copy X to accumulator
+    TAY = 0x28,  // Copy accumulator to Y
+    TYA = 0x29,  // Copy Y to accumulator
+    PUSH = 0x2B, // Push accumulator onto the data stack
+    POP = 0x2C,  // Pop the data stack into accumulator
+    COPY = 0x2D, // Copy accumulator-many bytes from address X to address Y
+    HLT = 0xFF,  // Halt
 }
 
-impl From<u8> for Instruction {
-    fn from(value: u8) -> Self {
+impl Instruction {
+    /// Decode an opcode byte, returning `None` for bytes outside the built-in set
+    pub fn decode(value: u8) -> Option<Self> {
         match value {
-            0x00 => Instruction::NOP,
-            0x01 => Instruction::LDA,
-            0x02 => Instruction::STA,
-            0x03 => Instruction::ADD,
-            0x04 => Instruction::SUB,
-            0x05 => Instruction::JMP,
-            0x06 => Instruction::JZ,
-            0x07 => Instruction::INC,
-            0x08 => Instruction::DEC,
-            0x09 => Instruction::SWP,
-            0x0A => Instruction::CMP,
-            0xFF => Instruction::HLT,
-            _ => Instruction::HLT, // Default to halt for unknown instructions
+            0x00 => Some(Instruction::NOP),
+            0x01 => Some(Instruction::LDA),
+            0x02 => Some(Instruction::STA),
+            0x03 => Some(Instruction::ADD),
+            0x04 => Some(Instruction::SUB),
+            0x05 => Some(Instruction::JMP),
+            0x06 => Some(Instruction::JZ),
+            0x07 => Some(Instruction::INC),
+            0x08 => Some(Instruction::DEC),
+            0x09 => Some(Instruction::SWP),
+            0x0A => Some(Instruction::CMP),
+            0x0B => Some(Instruction::CALL),
+            0x0C => Some(Instruction::RET),
+            0x0D => Some(Instruction::JNZ),
+            0x0E => Some(Instruction::JC),
+            0x0F => Some(Instruction::JNC),
+            0x10 => Some(Instruction::JN),
+            0x11 => Some(Instruction::AND),
+            0x12 => Some(Instruction::OR),
+            0x13 => Some(Instruction::XOR),
+            0x14 => Some(Instruction::NOT),
+            0x15 => Some(Instruction::SHL),
+            0x16 => Some(Instruction::SHR),
+            0x17 => Some(Instruction::ROL),
+            0x18 => Some(Instruction::ROR),
+            0x19 => Some(Instruction::MUL),
+            0x1A => Some(Instruction::DIV),
+            0x1B => Some(Instruction::LDI),
+            0x1C => Some(Instruction::ADI),
+            0x1D => Some(Instruction::SBI),
+            0x1E => Some(Instruction::CPI),
+            0x1F => Some(Instruction::SKE),
+            0x20 => Some(Instruction::SKNE),
+            0x21 => Some(Instruction::RND),
+            0x22 => Some(Instruction::IN),
+            0x23 => Some(Instruction::OUT),
+            0x24 => Some(Instruction::JR),
+            0x25 => Some(Instruction::JRZ),
+            0x26 => Some(Instruction::TAX),
+            0x27 => Some(Instruction::TXA),
+            0x28 => Some(Instruction::TAY),
+            0x29 => Some(Instruction::TYA),
+            0x2B => Some(Instruction::PUSH),
+            0x2C => Some(Instruction::POP),
+            0x2D => Some(Instruction::COPY),
+            0xFF => Some(Instruction::HLT),
+            _ => None,
         }
     }
 }
 
-impl std::fmt::Display for Instruction {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let name = match self {
+impl Instruction {
+    /// Encoded length in bytes on a VM whose address operands are `address_bytes` wide
+    pub fn size_for(self, address_bytes: usize) -> usize {
+        match self.size() {
+            2 if self.has_address_operand() => 1 + address_bytes,
+            size => size,
+        }
+    }
+
+    /// Whether the operand is an address, as opposed to a value, port or offset
+    pub fn has_address_operand(self) -> bool {
+        self.size() > 1 && !self.is_immediate() && !self.is_port_io() && !self.is_relative()
+    }
+
+    /// Encoded length in bytes on the 8-bit VM, including the opcode
+    pub fn size(self) -> usize {
+        match self {
+            Instruction::NOP
+            | Instruction::INC
+            | Instruction::DEC
+            | Instruction::RET
+            | Instruction::NOT
+            | Instruction::SHL
+            | Instruction::SHR
+            | Instruction::ROL
+            | Instruction::ROR
+            | Instruction::RND
+            | Instruction::TAX
+            | Instruction::TXA
+            | Instruction::TAY
+            | Instruction::TYA
+            | Instruction::PUSH
+            | Instruction::POP
+            | Instruction::COPY
+            | Instruction::HLT => 1,
+            _ => 2,
+        }
+    }
+}
+
+impl From<u8> for Instruction {
+    fn from(value: u8) -> Self {
+        // Default to halt for unknown instructions
+        Instruction::decode(value).unwrap_or(Instruction::HLT)
+    }
+}
+
+impl Instruction {
+    /// Every built-in instruction in opcode order, derived from `decode` so it cannot drift
+    pub fn all() -> impl Iterator<Item = Self> {
+        (0..=u8::MAX).filter_map(Self::decode)
+    }
+
+    pub fn mnemonic(self) -> &'static str {
+        match self {
             Instruction::NOP => "NOP",
             Instruction::LDA => "LDA",
             Instruction::STA => "STA",
@@ -65,22 +1042,461 @@ impl std::fmt::Display for Instruction {
             Instruction::DEC => "DEC",
             Instruction::SWP => "SWP",
             Instruction::CMP => "CMP",
+            Instruction::CALL => "CALL",
+            Instruction::RET => "RET",
+            Instruction::JNZ => "JNZ",
+            Instruction::JC => "JC",
+            Instruction::JNC => "JNC",
+            Instruction::JN => "JN",
+            Instruction::AND => "AND",
+            Instruction::OR => "OR",
+            Instruction::XOR => "XOR",
+            Instruction::NOT => "NOT",
+            Instruction::SHL => "SHL",
+            Instruction::SHR => "SHR",
+            Instruction::ROL => "ROL",
+            Instruction::ROR => "ROR",
+            Instruction::MUL => "MUL",
+            Instruction::DIV => "DIV",
+            Instruction::LDI => "LDI",
+            Instruction::ADI => "ADI",
+            Instruction::SBI => "SBI",
+            Instruction::CPI => "CPI",
+            Instruction::SKE => "SKE",
+            Instruction::SKNE => "SKNE",
+            Instruction::RND => "RND",
+            Instruction::IN => "IN",
+            Instruction::OUT => "OUT",
+            Instruction::JR => "JR",
+            Instruction::JRZ => "JRZ",
+            Instruction::TAX => "TAX",
+            Instruction::TXA => "TXA",
+            Instruction::TAY => "TAY",
+            Instruction::TYA => "TYA",
+            Instruction::PUSH => "PUSH",
+            Instruction::POP => "POP",
+            Instruction::COPY => "COPY",
             Instruction::HLT => "HLT",
-        };
-        write!(f, "{}", name)
+        }
+    }
+
+    /// What the instruction does, in one line
+    pub fn summary(self) -> &'static str {
+        match self {
+            Instruction::NOP => "No operation",
+            Instruction::LDA => "Load accumulator from memory",
+            Instruction::STA => "Store accumulator to memory",
+            Instruction::ADD => "Add memory to accumulator",
+            Instruction::SUB => "Subtract memory from accumulator",
+            Instruction::JMP => "Jump to address",
+            Instruction::JZ => "Jump if accumulator is zero",
+            Instruction::INC => "Increment accumulator",
+            Instruction::DEC => "Decrement accumulator",
+            Instruction::SWP => "Swap accumulator with memory",
+            Instruction::CMP => "Compare accumulator with memory",
+            Instruction::CALL => "Push return address and jump to subroutine",
+            Instruction::RET => "Pop return address and jump back",
+            Instruction::JNZ => "Jump if the zero flag is clear",
+            Instruction::JC => "Jump if the carry flag is set",
+            Instruction::JNC => "Jump if the carry flag is clear",
+            Instruction::JN => "Jump if the negative flag is set",
+            Instruction::AND => "Bitwise AND memory into accumulator",
+            Instruction::OR => "Bitwise OR memory into accumulator",
+            Instruction::XOR => "Bitwise XOR memory into accumulator",
+            Instruction::NOT => "Invert every bit of the accumulator",
+            Instruction::SHL => "Shift accumulator left one bit",
+            Instruction::SHR => "Shift accumulator right one bit",
+            Instruction::ROL => "Rotate accumulator left one bit",
+            Instruction::ROR => "Rotate accumulator right one bit",
+            Instruction::MUL => "Multiply accumulator by memory (low byte, carry on overflow)",
+            Instruction::DIV => "Divide accumulator by memory (by zero: 0xFF, carry set)",
+            Instruction::LDI => "Load accumulator with immediate value",
+            Instruction::ADI => "Add immediate value to accumulator",
+            Instruction::SBI => "Subtract immediate value from accumulator",
+            Instruction::CPI => "Compare accumulator with immediate value",
+            Instruction::SKE => "Skip next instruction if accumulator equals memory",
+            Instruction::SKNE => "Skip next instruction if accumulator differs from memory",
+            Instruction::RND => "Load pseudo-random byte from the VM's seeded RNG",
+            Instruction::IN => "Load accumulator from the device on a port (none: 0)",
+            Instruction::OUT => "Write accumulator to the device on a port",
+            Instruction::JR => "Jump by signed offset from this instruction",
+            Instruction::JRZ => "Jump by signed offset if accumulator is zero",
+            Instruction::TAX => "Copy accumulator to X",
+            Instruction::TXA => "Copy X to accumulator",
+            Instruction::TAY => "Copy accumulator to Y",
+            Instruction::TYA => "Copy Y to accumulator",
+            Instruction::PUSH => "Push accumulator onto the data stack",
+            Instruction::POP => "Pop the data stack into accumulator",
+            Instruction::COPY => "Copy ACC bytes from address X to Y; X and Y move past them",
+            Instruction::HLT => "Halt",
+        }
+    }
+
+    /// Whether the operand byte is the value itself rather than an address holding it
+    pub fn is_immediate(self) -> bool {
+        matches!(
+            self,
+            Instruction::LDI | Instruction::ADI | Instruction::SBI | Instruction::CPI
+        )
+    }
+
+    /// Whether the operand byte is an I/O port number
+    pub fn is_port_io(self) -> bool {
+        matches!(self, Instruction::IN | Instruction::OUT)
+    }
+
+    /// Whether the operand byte is a signed jump offset from the instruction's own address
+    pub fn is_relative(self) -> bool {
+        matches!(self, Instruction::JR | Instruction::JRZ)
+    }
+
+    /// Flags the instruction writes, as letters (`Z`, `C`, `N`), or `-` for none
+    pub fn flag_effects(self) -> &'static str {
+        match self {
+            Instruction::ADD
+            | Instruction::SUB
+            | Instruction::CMP
+            | Instruction::AND
+            | Instruction::OR
+            | Instruction::XOR
+            | Instruction::NOT
+            | Instruction::SHL
+            | Instruction::SHR
+            | Instruction::ROL
+            | Instruction::ROR
+            | Instruction::MUL
+            | Instruction::DIV
+            | Instruction::ADI
+            | Instruction::SBI
+            | Instruction::CPI => "ZCN",
+            _ => "-",
+        }
+    }
+}
+
+impl std::fmt::Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.mnemonic())
+    }
+}
+
+/// Columns and rows of the smallest near-square grid with room for `len` memory cells
+pub fn grid_shape(len: usize) -> (usize, usize) {
+    let cols = (len as f64).sqrt().ceil().max(1.0) as usize;
+    (cols, len.div_ceil(cols))
+}
+
+/// Address `offset` bytes from `addr`, wrapping around a memory of `len` bytes
+pub fn relative_target(addr: usize, offset: i8, len: usize) -> usize {
+    (addr as isize + offset as isize).rem_euclid(len as isize) as usize
+}
+
+impl<const N: usize, A: AddressWidth> Default for VM<N, A> {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
-impl VM {
-    /// Helper to safely read memory with bounds checking
-    fn read_memory(&self, addr: usize) -> u8 {
+impl<const N: usize, A: AddressWidth> VM<N, A> {
+    /// Length of an instruction with an address operand
+    const ADDRESS_INSTRUCTION_SIZE: usize = 1 + A::BYTES;
+
+    /// Read the little-endian address operand following the opcode
+    fn address_operand(&self) -> usize {
+        (0..A::BYTES).fold(0, |addr, i| {
+            addr | (self.read_code(self.pc + 1 + i) as usize) << (8 * i)
+        })
+    }
+
+    /// Push a return address, low byte at the lowest address
+    fn push_address(&mut self, addr: usize) {
+        // The stack lives in data memory and wraps around, so it stays visible
+        self.sp = (self.sp + A::SPACE - A::BYTES) % A::SPACE;
+        for i in 0..A::BYTES {
+            self.write_data((self.sp + i) % A::SPACE, (addr >> (8 * i)) as u8);
+        }
+    }
+
+    fn pop_address(&mut self) -> usize {
+        let addr = (0..A::BYTES).fold(0, |addr, i| {
+            addr | (self.read_data((self.sp + i) % A::SPACE) as usize) << (8 * i)
+        });
+        self.sp = (self.sp + A::BYTES) % A::SPACE;
+        addr
+    }
+
+    /// Read an opcode or operand byte from the code bank, 0 past the end
+    fn read_code(&self, addr: usize) -> u8 {
         self.memory.get(addr).copied().unwrap_or(0)
     }
 
-    /// Helper to safely write memory with bounds checking
-    fn write_memory(&mut self, addr: usize, value: u8) {
-        if addr < MEM_SIZE {
-            self.memory[addr] = value;
+    /// Read a byte for LDA, arithmetic and the stack, 0 past the end
+    fn read_data(&mut self, addr: usize) -> u8 {
+        self.watch(addr, WatchKind::OnRead);
+        self.check_bounds(addr);
+        #[cfg(feature = "heatmap")]
+        if let Some(count) = self.heatmap.reads.get_mut(addr) {
+            *count += 1;
+        }
+        let stored = self.data().get(addr).copied().unwrap_or(0);
+        if self.mmio.is_empty() {
+            return stored;
+        }
+        self.mmio.read(addr, stored)
+    }
+
+    /// Store a byte for STA, SWP and the stack; stores past the end are dropped
+    fn write_data(&mut self, addr: usize, value: u8) {
+        // Refused stores still count as attempts to write
+        self.watch(addr, WatchKind::OnWrite);
+        self.check_bounds(addr);
+        if self.protection(addr) == Protection::ReadOnly {
+            if self.protection_fault == ProtectionFault::Trap {
+                self.flags |= FLAG_TRAP;
+                self.trapped = true;
+                self.metrics.traps += 1;
+            }
+            tracing::trace!("Store to protected addr={} refused", addr);
+            return;
+        }
+        if addr < N {
+            #[cfg(feature = "heatmap")]
+            {
+                self.heatmap.writes[addr] += 1;
+            }
+            self.metrics.memory_writes += 1;
+            let data = self.data_mut();
+            let old = std::mem::replace(&mut data[addr], value);
+            if let Some(undo) = self.undo.back_mut() {
+                undo.writes.push((addr, old));
+            }
+            self.last_write = Some(MemoryWrite {
+                addr,
+                old,
+                new: value,
+            });
+            if !self.mmio.is_empty() {
+                self.mmio.write(addr, value);
+            }
+        }
+    }
+
+    /// Note an access past the end of memory for `try_step`
+    fn check_bounds(&mut self, addr: usize) {
+        if addr >= N && self.fault.is_none() {
+            self.fault = Some(VmError::OutOfBounds { pc: self.pc, addr });
+        }
+    }
+
+    /// Revert the store made by the last step, e.g. when a device rejects it.
+    ///
+    /// The store leaves the step's undo journal and stops counting toward
+    /// `memory_writes` and the heatmap, so it is as if it never landed. A mapped
+    /// handler has already been told of it by `MmioHandler::write`.
+    pub fn undo_last_write(&mut self) {
+        let Some(write) = self.last_write.take() else {
+            return;
+        };
+        self.data_mut()[write.addr] = write.old;
+        self.metrics.memory_writes = self.metrics.memory_writes.saturating_sub(1);
+        #[cfg(feature = "heatmap")]
+        {
+            self.heatmap.writes[write.addr] = self.heatmap.writes[write.addr].saturating_sub(1);
+        }
+        if let Some(undo) = self.undo.back_mut()
+            && let Some(index) = undo
+                .writes
+                .iter()
+                .rposition(|&(addr, _)| addr == write.addr)
+        {
+            undo.writes.remove(index);
+        }
+    }
+
+    /// Set the access the program has to `range` of the memory it stores into (the
+    /// data bank in Harvard mode), overriding earlier calls for the same cells.
+    ///
+    /// Protection survives program loads and restarts.
+    pub fn protect(&mut self, range: Range<usize>, protection: Protection) {
+        self.protected.push((range, protection));
+    }
+
+    /// Access the program has to `addr`
+    pub fn protection(&self, addr: usize) -> Protection {
+        self.protected
+            .iter()
+            .rev()
+            .find(|(range, _)| range.contains(&addr))
+            .map_or(Protection::ReadWrite, |&(_, protection)| protection)
+    }
+
+    /// Run `handler` for `opcode` instead of the unknown-opcode policy, which halts.
+    ///
+    /// The VM keeps these in an `OpcodeRegistry` of its own, under the same rules as
+    /// the one passed to `step_with`, which is tried first: only bytes outside the
+    /// built-in instruction set can be registered, and the handler sees `pc` at the
+    /// opcode and must advance it. Registrations survive program loads and restarts,
+    /// and clones of the VM share them.
+    pub fn register_opcode<F>(
+        &mut self,
+        opcode: u8,
+        mnemonic: &'static str,
+        handler: F,
+    ) -> Result<(), RegistryError>
+    where
+        F: Fn(&mut VM<N, A>) + Send + Sync + 'static,
+    {
+        self.opcodes
+            .register(opcode, mnemonic, move |vm: &mut VM<N, A>, _: &mut ()| {
+                handler(vm)
+            })
+    }
+
+    /// Map `handler` over `range` of the data memory, e.g. `vm.map_io(250..=255, sensors)`.
+    ///
+    /// See `MmioHandler` for when its hooks run. Ranges may not overlap, and mappings
+    /// survive program loads and restarts.
+    pub fn map_io(
+        &mut self,
+        range: RangeInclusive<usize>,
+        handler: impl MmioHandler,
+    ) -> Result<(), MmioError> {
+        self.mmio.map(range, Box::new(handler), N)
+    }
+
+    /// Remove the handler mapped over `addr`, returning it
+    pub fn unmap_io(&mut self, addr: usize) -> Option<Box<dyn MmioHandler>> {
+        self.mmio.unmap(addr)
+    }
+
+    /// The first mapped handler of type `T`, for the host to update or inspect
+    pub fn io_handler_mut<T: MmioHandler>(&mut self) -> Option<&mut T> {
+        self.mmio.handler_mut()
+    }
+
+    pub fn mmio(&self) -> &MmioMap {
+        &self.mmio
+    }
+
+    /// Remove an opcode added with `register_opcode`, returning whether it existed
+    pub fn unregister_opcode(&mut self, opcode: u8) -> bool {
+        self.opcodes.unregister(opcode)
+    }
+
+    pub fn opcodes(&self) -> &OpcodeRegistry<(), N, A> {
+        &self.opcodes
+    }
+
+    /// Report arriving at `addr`: `step` returns a hit once `pc` gets there.
+    ///
+    /// Breakpoints and watchpoints survive program loads and restarts.
+    pub fn add_breakpoint(&mut self, addr: usize) {
+        if !self.breakpoints.iter().any(|b| b.addr == addr) {
+            self.breakpoints.push(Breakpoint { addr, hits: 0 });
+        }
+    }
+
+    /// Returns whether there was a breakpoint at `addr`
+    pub fn remove_breakpoint(&mut self, addr: usize) -> bool {
+        let before = self.breakpoints.len();
+        self.breakpoints.retain(|b| b.addr != addr);
+        self.breakpoints.len() != before
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    /// Report the program reading or writing data at `addr`, including stack accesses
+    /// and refused stores; operand fetches are not data reads
+    pub fn add_watchpoint(&mut self, addr: usize, kind: WatchKind) {
+        if !self
+            .watchpoints
+            .iter()
+            .any(|w| w.addr == addr && w.kind == kind)
+        {
+            self.watchpoints.push(Watchpoint {
+                addr,
+                kind,
+                hits: 0,
+            });
+        }
+    }
+
+    /// Returns whether there was such a watchpoint
+    pub fn remove_watchpoint(&mut self, addr: usize, kind: WatchKind) -> bool {
+        let before = self.watchpoints.len();
+        self.watchpoints
+            .retain(|w| w.addr != addr || w.kind != kind);
+        self.watchpoints.len() != before
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    /// Zero every breakpoint and watchpoint hit count
+    pub fn clear_hit_counts(&mut self) {
+        self.breakpoints.iter_mut().for_each(|b| b.hits = 0);
+        self.watchpoints.iter_mut().for_each(|w| w.hits = 0);
+    }
+
+    /// Count an access against matching watchpoints, keeping the step's first hit
+    fn watch(&mut self, addr: usize, kind: WatchKind) {
+        for watchpoint in &mut self.watchpoints {
+            if watchpoint.addr == addr && watchpoint.kind == kind {
+                watchpoint.hits += 1;
+                self.hit.get_or_insert(DebugHit::Watchpoint { addr, kind });
+            }
+        }
+    }
+
+    /// Count a breakpoint at the next instruction, once the step is over
+    fn check_breakpoint(&mut self) {
+        if self.halted {
+            return;
+        }
+        let pc = self.pc;
+        if let Some(breakpoint) = self.breakpoints.iter_mut().find(|b| b.addr == pc) {
+            breakpoint.hits += 1;
+            self.hit.get_or_insert(DebugHit::Breakpoint { addr: pc });
+        }
+    }
+
+    /// Switch between one shared memory and separate code and data banks.
+    ///
+    /// In Harvard mode the program in `memory` is read-only: LDA, STA, arithmetic
+    /// and the stack use a data bank that starts as a copy of `initial_state`.
+    /// Switching on when already on keeps the data bank as it is.
+    pub fn set_harvard(&mut self, harvard: bool) {
+        match (harvard, self.data.is_some()) {
+            (true, false) => self.data = Some(Box::new(self.initial_state)),
+            (false, true) => self.data = None,
+            _ => {}
+        }
+    }
+
+    pub fn is_harvard(&self) -> bool {
+        self.data.is_some()
+    }
+
+    /// Memory the program reads and writes as data: the data bank in Harvard mode,
+    /// otherwise `memory` itself
+    pub fn data(&self) -> &[u8; N] {
+        self.data.as_deref().unwrap_or(&self.memory)
+    }
+
+    pub fn data_mut(&mut self) -> &mut [u8; N] {
+        match &mut self.data {
+            Some(data) => data,
+            None => &mut self.memory,
+        }
+    }
+
+    /// Refill the data bank from the genome after the program changed or restarted cold
+    fn reload_data(&mut self) {
+        if let Some(data) = &mut self.data {
+            data.copy_from_slice(&self.initial_state);
         }
     }
 
@@ -88,91 +1504,680 @@ impl VM {
     fn reset(&mut self) {
         self.pc = 0;
         self.acc = 0;
+        self.x = 0;
+        self.y = 0;
+        self.sp = A::STACK_TOP;
+        self.dsp = A::DATA_STACK_BASE;
+        self.flags = 0;
         self.halted = false;
         self.total_steps_count = 0;
-        self.recent_instructions.clear();
+        self.consumed_energy = 0.0;
+        self.cycle_credit = 0;
+        self.metrics = VmMetrics::default();
+        self.recent.clear();
+        self.trace.clear();
+        if let Some(profile) = &mut self.profile {
+            profile.reset();
+        }
+        #[cfg(feature = "heatmap")]
+        self.heatmap.clear();
+        self.rng_state = self.rng_seed;
+        self.steps_since_interrupt = 0;
+        self.cycle_search = CycleSearch::default();
+        self.undo.clear();
+        self.start_threads();
+    }
+
+    /// Seed the generator behind RND; the same seed and program give the same run
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng_seed = seed;
+        self.rng_state = seed;
+    }
+
+    /// Attach a host device to `port` for IN and OUT, returning the device it replaced.
+    ///
+    /// Devices stay attached across program loads and restarts.
+    pub fn attach_device(
+        &mut self,
+        port: u8,
+        device: Box<dyn IoDevice>,
+    ) -> Option<Box<dyn IoDevice>> {
+        self.ports.attach(port, device)
+    }
+
+    pub fn detach_device(&mut self, port: u8) -> Option<Box<dyn IoDevice>> {
+        self.ports.detach(port)
+    }
+
+    /// Only let IN and OUT reach the `allowed` ports; `None` allows every port
+    pub fn restrict_ports(&mut self, allowed: Option<&[u8]>) {
+        self.ports.restrict(allowed);
+    }
+
+    /// Next byte of the RND sequence (SplitMix64, so any seed works, including 0)
+    fn next_random(&mut self) -> u8 {
+        self.rng_state = self.rng_state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.rng_state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        (z ^ (z >> 31)) as u8
+    }
+
+    /// Resume execution from address 0 with a cleared accumulator.
+    ///
+    /// Unlike `reset`, the step counter and recent trace are kept,
+    /// so a restarted program still reports its lifetime activity.
+    pub fn restart(&mut self, mode: RestartMode) {
+        if mode == RestartMode::Cold {
+            self.memory.copy_from_slice(&self.initial_state);
+            self.reload_data();
+        }
+        self.pc = 0;
+        self.acc = 0;
+        self.x = 0;
+        self.y = 0;
+        self.sp = A::STACK_TOP;
+        self.dsp = A::DATA_STACK_BASE;
+        self.flags = 0;
+        self.halted = false;
+        self.steps_since_interrupt = 0;
+        self.cycle_search = CycleSearch::default();
+        self.undo.clear();
+        self.start_threads();
+        tracing::trace!("VM restarted ({:?})", mode);
+    }
+
+    /// Start a second thread at `entry` next to the program's own, or with `None` go
+    /// back to one thread. Both threads share memory, the stacks and the step budget,
+    /// and take turns one instruction each, starting with the program's own; each has
+    /// its own PC, accumulator, X, Y and flags. A thread that halts drops out and the
+    /// VM halts once both have. Loads, resets and restarts start both threads afresh.
+    ///
+    /// Setting the entry already in use changes nothing. Otherwise the running program
+    /// carries on as the first thread, and the second starts at `entry`.
+    pub fn set_coroutine(&mut self, entry: Option<usize>) {
+        if entry == self.coroutine {
+            return;
+        }
+        if self.threads.is_empty() {
+            self.threads.push(Thread::default());
+        } else if self.thread != 0 {
+            self.park_thread();
+            self.resume_thread(0);
+        }
+        self.coroutine = entry;
+        self.threads.truncate(1);
+        match entry {
+            Some(entry) => self.threads.push(Thread::at(entry)),
+            None => self.threads.clear(),
+        }
+        self.thread = 0;
+    }
+
+    /// Entry of the second thread, set by `set_coroutine`
+    pub fn coroutine(&self) -> Option<usize> {
+        self.coroutine
+    }
+
+    /// Every thread's registers, the program's own first; one entry without a coroutine
+    pub fn threads(&self) -> Vec<Thread> {
+        let mut threads = self.threads.clone();
+        let current = self.current_registers();
+        match threads.get_mut(self.thread) {
+            Some(thread) => *thread = current,
+            None => threads.push(current),
+        }
+        threads
+    }
+
+    /// Index in `threads` of the thread that runs next
+    pub fn current_thread(&self) -> usize {
+        self.thread
+    }
+
+    fn current_registers(&self) -> Thread {
+        Thread {
+            pc: self.pc,
+            acc: self.acc,
+            x: self.x,
+            y: self.y,
+            flags: self.flags,
+            halted: self.halted,
+        }
+    }
+
+    fn park_thread(&mut self) {
+        self.threads[self.thread] = self.current_registers();
+    }
+
+    fn resume_thread(&mut self, index: usize) {
+        let thread = self.threads[index];
+        self.thread = index;
+        self.pc = thread.pc;
+        self.acc = thread.acc;
+        self.x = thread.x;
+        self.y = thread.y;
+        self.flags = thread.flags;
+        self.halted = thread.halted;
+    }
+
+    /// Hand over to the next thread that has not halted, round-robin, leaving the VM
+    /// halted if none has. The running thread goes on only when it is the last one left.
+    fn switch_thread(&mut self) {
+        self.park_thread();
+        let count = self.threads.len();
+        let next = (1..=count)
+            .map(|offset| (self.thread + offset) % count)
+            .find(|&index| !self.threads[index].halted);
+        if let Some(index) = next {
+            self.resume_thread(index);
+        }
+    }
+
+    /// Lay out the threads for a fresh start: the program's own at the registers
+    /// just reset, and the coroutine at its entry
+    fn start_threads(&mut self) {
+        self.threads.clear();
+        self.thread = 0;
+        if let Some(entry) = self.coroutine {
+            self.threads.push(self.current_registers());
+            self.threads.push(Thread::at(entry));
+        }
     }
 
     /// Randomize a random percent of the program
     pub fn partial_randomize<R: rand::Rng>(&mut self, rng: &mut R) {
-        // Choose a random percent between 1 and 50
-        let percent: u8 = rng.gen_range(1..=10);
-        let count = MEM_SIZE * percent as usize / 100;
-        for _ in 0..count {
-            let idx = rng.gen_range(0..MEM_SIZE);
-            let val = rng.random();
-            self.memory[idx] = val;
-            self.initial_state[idx] = val;
-        }
+        // Choose a random percent between 1 and 10
+        let percent: u8 = rng.random_range(1..=10);
+        self.mutate(N * percent as usize / 100, rng);
+    }
+
+    /// Overwrite `count` randomly chosen bytes of the program (repeats allowed) and reset
+    pub fn mutate<R: rand::Rng>(&mut self, count: usize, rng: &mut R) {
+        self.mutate_with(|genome| mutation::point(genome, count, rng));
+    }
+
+    /// Run `mutation` on the program, load the result and reset
+    pub fn mutate_with(&mut self, mutation: impl FnOnce(&mut [u8; N])) {
+        mutation(&mut self.initial_state);
+        self.memory.copy_from_slice(&self.initial_state);
+        self.reload_data();
         self.reset();
     }
-    /// Save VM program (memory) to a file
-    pub fn save_to_file(&self, path: &str) -> std::io::Result<()> {
-        use std::fs::File;
-        use std::io::{BufWriter, Write};
-        let mut file = BufWriter::new(File::create(path)?);
-        file.write_all(&self.memory)?;
-        Ok(())
+    /// Save VM program (memory) to a program file under the VM's instruction set
+    pub fn save_to_file(&self, path: &str, metadata: ProgramMetadata) -> std::io::Result<()> {
+        let program = Program {
+            genome: self.memory.to_vec(),
+            isa: self.isa,
+        };
+        ProgramFile::new(program, metadata).save(path)
     }
 
-    /// Load VM program (memory) from a file
-    pub fn load_from_file(&mut self, path: &str) -> std::io::Result<()> {
-        use std::fs::File;
-        use std::io::{BufReader, Read};
-        let mut file = BufReader::new(File::open(path)?);
-        file.read_exact(&mut self.memory)?;
-        Ok(())
+    /// Load VM program (memory) from a program file or legacy dump, switching to the
+    /// instruction set it was saved under, and return the file's metadata
+    pub fn load_from_file(&mut self, path: &str) -> Result<ProgramMetadata, ProgramFileError> {
+        let file = ProgramFile::load(path)?;
+        if file.program.genome.len() < N {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+        self.memory.copy_from_slice(&file.program.genome[..N]);
+        self.isa = file.program.isa;
+        Ok(file.metadata)
     }
     pub fn new() -> Self {
+        Self::with_config(VmConfig::default())
+    }
+
+    /// A blank VM with the host settings in `config`
+    pub fn with_config(config: VmConfig) -> Self {
         VM {
-            memory: [0; MEM_SIZE],
-            initial_state: [0; MEM_SIZE],
+            memory: [0; N],
+            initial_state: [0; N],
             pc: 0,
             acc: 0,
+            x: 0,
+            y: 0,
+            sp: A::STACK_TOP,
+            dsp: A::DATA_STACK_BASE,
+            flags: 0,
             halted: false,
             total_steps_count: 0,
-            recent_instructions: Vec::with_capacity(16),
+            costs: config.costs,
+            consumed_energy: 0.0,
+            cycle_table: config.cycle_table,
+            cycle_credit: 0,
+            metrics: VmMetrics::default(),
+            recent: RecentTrace::default(),
+            last_write: None,
+            trace: SampledTrace::with_depth(config.trace_depth),
+            profile: None,
+            #[cfg(feature = "heatmap")]
+            heatmap: Heatmap::new(N),
+            rng_seed: 0,
+            rng_state: 0,
+            ports: IoPorts::default(),
+            interrupt: None,
+            coroutine: None,
+            threads: Vec::new(),
+            thread: 0,
+            steps_since_interrupt: 0,
+            data: None,
+            protected: Vec::new(),
+            protection_fault: config.protection_fault,
+            trapped: false,
+            loop_detector: config.loop_detector,
+            cycle_search: CycleSearch::default(),
+            opcodes: OpcodeRegistry::new(),
+            mmio: MmioMap::default(),
+            breakpoints: Vec::new(),
+            watchpoints: Vec::new(),
+            hit: None,
+            fault: None,
+            undo: VecDeque::new(),
+            undo_depth: config.undo_depth,
+            isa: config.isa,
+            address_width: PhantomData,
         }
     }
 
     pub fn load_program(&mut self, program: &[u8]) {
-        let len = program.len().min(MEM_SIZE);
+        let len = program.len().min(N);
         self.memory[..len].copy_from_slice(&program[..len]);
         self.initial_state[..len].copy_from_slice(&program[..len]);
+        self.reload_data();
         self.reset();
     }
 
     pub fn randomize<R: rand::Rng>(&mut self, rng: &mut R) {
-        for i in 0..MEM_SIZE {
+        for i in 0..N {
             let val = rng.random();
             self.memory[i] = val;
             self.initial_state[i] = val;
         }
+        self.reload_data();
         self.reset();
     }
 
-    pub fn step(&mut self) {
-        if self.halted || self.pc >= MEM_SIZE {
+    /// Capture the execution state, to resume from later with `restore`
+    pub fn snapshot(&self) -> VmSnapshot<N> {
+        VmSnapshot {
+            memory: self.memory,
+            initial_state: self.initial_state,
+            data: self.data.clone(),
+            pc: self.pc,
+            acc: self.acc,
+            x: self.x,
+            y: self.y,
+            sp: self.sp,
+            dsp: self.dsp,
+            flags: self.flags,
+            halted: self.halted,
+            total_steps_count: self.total_steps_count,
+            consumed_energy: self.consumed_energy,
+            metrics: self.metrics,
+            cycle_credit: self.cycle_credit,
+            threads: self.threads.clone(),
+            thread: self.thread,
+            rng_state: self.rng_state,
+            steps_since_interrupt: self.steps_since_interrupt,
+            trapped: self.trapped,
+            cycle_search: self.cycle_search.clone(),
+        }
+    }
+
+    /// What differs from this VM to `other`: registers, counters and memory cells.
+    ///
+    /// The data banks are only compared when either VM runs in Harvard mode;
+    /// otherwise data lives in `memory`, which is always compared.
+    pub fn diff(&self, other: &Self) -> VmDiff {
+        let registers = [
+            (Register::Pc, self.pc, other.pc),
+            (Register::Acc, self.acc as usize, other.acc as usize),
+            (Register::X, self.x as usize, other.x as usize),
+            (Register::Y, self.y as usize, other.y as usize),
+            (Register::Sp, self.sp, other.sp),
+            (Register::Dsp, self.dsp, other.dsp),
+            (Register::Flags, self.flags as usize, other.flags as usize),
+            (
+                Register::Halted,
+                self.halted as usize,
+                other.halted as usize,
+            ),
+            (
+                Register::Steps,
+                self.total_steps_count,
+                other.total_steps_count,
+            ),
+        ]
+        .into_iter()
+        .filter(|(_, old, new)| old != new)
+        .map(|(register, old, new)| RegisterDiff { register, old, new })
+        .collect();
+
+        let harvard = self.data.is_some() || other.data.is_some();
+        VmDiff {
+            registers,
+            memory: cell_diffs(&self.memory, &other.memory),
+            data: if harvard {
+                cell_diffs(self.data(), other.data())
+            } else {
+                Vec::new()
+            },
+        }
+    }
+
+    /// Return to the state captured by `snapshot`, so the same steps run again.
+    ///
+    /// The last write is forgotten so it cannot be undone across the jump.
+    pub fn restore(&mut self, snapshot: &VmSnapshot<N>) {
+        self.memory = snapshot.memory;
+        self.initial_state = snapshot.initial_state;
+        self.data = snapshot.data.clone();
+        self.pc = snapshot.pc;
+        self.acc = snapshot.acc;
+        self.x = snapshot.x;
+        self.y = snapshot.y;
+        self.sp = snapshot.sp;
+        self.dsp = snapshot.dsp;
+        self.flags = snapshot.flags;
+        self.halted = snapshot.halted;
+        self.total_steps_count = snapshot.total_steps_count;
+        self.consumed_energy = snapshot.consumed_energy;
+        self.metrics = snapshot.metrics;
+        self.cycle_credit = snapshot.cycle_credit;
+        self.threads = snapshot.threads.clone();
+        self.thread = snapshot.thread;
+        self.rng_state = snapshot.rng_state;
+        self.steps_since_interrupt = snapshot.steps_since_interrupt;
+        self.trapped = snapshot.trapped;
+        self.last_write = None;
+        self.hit = None;
+        self.fault = None;
+        self.cycle_search = snapshot.cycle_search.clone();
+        self.undo.clear();
+    }
+
+    /// Execute one instruction, returning the first breakpoint or watchpoint it hit
+    pub fn step(&mut self) -> Option<DebugHit> {
+        let opcode = self.fetch()?;
+
+        let (pc, acc) = (self.pc, self.acc);
+        let operand = self.execute_opcode(pc, opcode);
+        self.finish_step(pc, opcode, operand, acc);
+        self.hit
+    }
+
+    /// Strict `step` for hand-written programs: the step runs exactly as `step` runs
+    /// it, then the first out-of-bounds access or invalid opcode it papered over is
+    /// returned as an error. Evolution keeps using `step`, where such genomes are normal.
+    pub fn try_step(&mut self) -> Result<Option<DebugHit>, VmError> {
+        let hit = self.step();
+        match self.fault.take() {
+            Some(fault) => Err(fault),
+            None => Ok(hit),
+        }
+    }
+
+    /// Step the VM, dispatching opcodes registered in `registry` to their host
+    /// handlers before falling back to the built-in instruction set
+    pub fn step_with<C>(
+        &mut self,
+        registry: &OpcodeRegistry<C, N, A>,
+        ctx: &mut C,
+    ) -> Option<DebugHit> {
+        let opcode = self.fetch()?;
+
+        let (pc, acc) = (self.pc, self.acc);
+        let operand = match registry.get(opcode) {
+            Some(custom) => {
+                self.execute_custom(custom, ctx);
+                None
+            }
+            None => self.execute_opcode(pc, opcode),
+        };
+        self.finish_step(pc, opcode, operand, acc);
+        self.hit
+    }
+
+    /// Fetch the opcode at `pc`, halting the VM if it ran off the end of memory
+    fn fetch(&mut self) -> Option<u8> {
+        if !self.mmio.is_empty() {
+            let data = match &mut self.data {
+                Some(data) => data.as_mut(),
+                None => &mut self.memory,
+            };
+            self.mmio.before_step(data);
+        }
+        self.journal_step();
+        self.last_write = None;
+        self.trapped = false;
+        self.hit = None;
+        self.fault = None;
+        let was_halted = self.halted;
+        // A thread that ran off memory halts alone while another can take over
+        while !self.halted && self.pc >= N && !self.threads.is_empty() {
             self.halted = true;
-            tracing::trace!(
-                "VM halted: pc={}, acc={}, halted={}",
-                self.pc,
-                self.acc,
-                self.halted
-            );
-            return;
+            self.switch_thread();
+        }
+        if self.halted || self.pc >= N {
+            self.halt_fetch(was_halted);
+            return None;
         }
 
         self.total_steps_count += 1;
-        let opcode = self.memory[self.pc];
-        let instruction = Instruction::from(opcode);
+        if let Some(timer) = self.interrupt {
+            self.tick_timer(timer);
+            // A vector past the end of memory halts like running off it
+            if self.pc >= N {
+                self.halt_fetch(was_halted);
+                return None;
+            }
+        }
+        Some(self.memory[self.pc])
+    }
+
+    /// Halt because there is nothing to fetch, counting the halt if the VM was running
+    fn halt_fetch(&mut self, was_halted: bool) {
+        if !was_halted {
+            self.metrics.halts += 1;
+            self.cycle_credit = self.cycle_credit.min(0);
+        }
+        self.halted = true;
+        tracing::trace!(
+            "VM halted: pc={}, acc={}, halted={}",
+            self.pc,
+            self.acc,
+            self.halted
+        );
+    }
+
+    /// Open a journal entry for the step about to run, dropping the oldest past `undo_depth`
+    fn journal_step(&mut self) {
+        if self.undo_depth == 0 || self.halted {
+            return;
+        }
+        if self.undo.len() >= self.undo_depth {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(StepUndo {
+            pc: self.pc,
+            acc: self.acc,
+            x: self.x,
+            y: self.y,
+            sp: self.sp,
+            dsp: self.dsp,
+            flags: self.flags,
+            halted: self.halted,
+            trapped: self.trapped,
+            total_steps_count: self.total_steps_count,
+            consumed_energy: self.consumed_energy,
+            metrics: self.metrics,
+            cycle_credit: self.cycle_credit,
+            threads: self.threads.clone(),
+            thread: self.thread,
+            rng_state: self.rng_state,
+            steps_since_interrupt: self.steps_since_interrupt,
+            cycle_search: self.cycle_search.clone(),
+            writes: Vec::new(),
+        });
+    }
+
+    /// Keep the last `depth` steps for `step_back`, dropping older ones; 0 turns the journal off
+    pub fn set_undo_depth(&mut self, depth: usize) {
+        self.undo_depth = depth;
+        let excess = self.undo.len().saturating_sub(depth);
+        self.undo.drain(..excess);
+    }
+
+    /// Counters accumulated while stepping since the program was loaded or reset
+    pub fn metrics(&self) -> VmMetrics {
+        VmMetrics {
+            energy_spent: self.consumed_energy,
+            ..self.metrics
+        }
+    }
+
+    /// Steps `step_back` can currently undo
+    pub fn undo_len(&self) -> usize {
+        self.undo.len()
+    }
+
+    /// Undo the last step: registers, counters, RND state and the stores it made
+    /// return to what they were before it, and `false` means there was nothing to undo.
+    ///
+    /// Only what the program itself did is rewound. Changes the host made between
+    /// steps, such as sensor inputs or device state, stay, and so do the execution
+    /// history, profile and heatmap. Loads, restarts and `restore` clear the journal.
+    pub fn step_back(&mut self) -> bool {
+        let Some(undo) = self.undo.pop_back() else {
+            return false;
+        };
+        let data = self.data_mut();
+        for &(addr, old) in undo.writes.iter().rev() {
+            data[addr] = old;
+        }
+        self.pc = undo.pc;
+        self.acc = undo.acc;
+        self.x = undo.x;
+        self.y = undo.y;
+        self.sp = undo.sp;
+        self.dsp = undo.dsp;
+        self.flags = undo.flags;
+        self.halted = undo.halted;
+        self.trapped = undo.trapped;
+        self.total_steps_count = undo.total_steps_count;
+        self.consumed_energy = undo.consumed_energy;
+        self.metrics = undo.metrics;
+        self.cycle_credit = undo.cycle_credit;
+        self.threads = undo.threads;
+        self.thread = undo.thread;
+        self.rng_state = undo.rng_state;
+        self.steps_since_interrupt = undo.steps_since_interrupt;
+        self.cycle_search = undo.cycle_search;
+        self.last_write = None;
+        self.hit = None;
+        self.fault = None;
+        true
+    }
+
+    /// Count a step toward the timer and take the interrupt once its period is up.
+    ///
+    /// The interrupt itself is free: the handler's first instruction runs in the same step.
+    fn tick_timer(&mut self, timer: TimerInterrupt) {
+        self.steps_since_interrupt += 1;
+        if self.steps_since_interrupt < timer.period.max(1) {
+            return;
+        }
+        self.steps_since_interrupt = 0;
+        let return_addr = self.pc;
+        tracing::trace!(
+            "Timer interrupt to addr={}, return to {}",
+            timer.vector,
+            return_addr
+        );
+        self.push_address(return_addr);
+        self.pc = timer.vector as usize;
+    }
+
+    /// Operand bytes of the built-in `instruction` at `pc`, read as the VM reads them
+    fn operand_at(&self, pc: usize, instruction: Option<Instruction>) -> Option<u16> {
+        let len = instruction?.size_for(A::BYTES) - 1;
+        (len > 0).then(|| {
+            (0..len).fold(0u16, |operand, i| {
+                operand | (self.read_code(pc + 1 + i) as u16) << (8 * i)
+            })
+        })
+    }
+
+    /// Log a step that ran `opcode` at `pc` with the accumulator at `acc`
+    fn finish_step(&mut self, pc: usize, opcode: u8, operand: Option<u16>, acc: u8) {
+        self.trace.record(pc, opcode, acc, self.pc, A::BYTES);
+        self.consumed_energy += self.costs[opcode];
+        self.metrics.steps += 1;
+        let cycles = self
+            .cycle_table
+            .as_ref()
+            .map_or(1, |table| table[opcode].max(1));
+        self.metrics.cycles += cycles as u64;
+        if self.cycle_credit > 0 {
+            self.cycle_credit -= cycles as i64;
+        }
+        let next = pc + self.isa.decode(opcode).map_or(1, |i| i.size_for(A::BYTES));
+        if !self.halted && self.pc != next {
+            self.metrics.jumps_taken += 1;
+        }
+        if let Some(profile) = &mut self.profile {
+            profile.record(opcode);
+        }
+        #[cfg(feature = "heatmap")]
+        if let Some(count) = self.heatmap.executes.get_mut(pc) {
+            *count += 1;
+        }
+        self.recent.push(TraceEvent {
+            pc,
+            opcode,
+            operand,
+            acc_before: acc,
+            acc_after: self.acc,
+            dsp: self.dsp,
+        });
+        if !self.threads.is_empty() {
+            self.switch_thread();
+        }
+        self.check_for_infinite_loop();
+        if self.halted {
+            self.metrics.halts += 1;
+            // A halted program forfeits what it was granted
+            self.cycle_credit = self.cycle_credit.min(0);
+        }
+        self.check_breakpoint();
+    }
+
+    /// Decode and run `opcode`: a built-in instruction, else an opcode registered with
+    /// `register_opcode`, else the unknown-opcode policy. Returns the operand shown in traces.
+    fn execute_opcode(&mut self, pc: usize, opcode: u8) -> Option<u16> {
+        let decoded = self.isa.decode(opcode);
+        let instruction = decoded.unwrap_or(Instruction::HLT);
+        if decoded.is_none() {
+            if let Some(custom) = self.opcodes.get(opcode).cloned() {
+                self.execute_custom(&custom, &mut ());
+                return None;
+            }
+            self.fault = Some(VmError::InvalidOpcode { pc, opcode });
+        } else if pc + instruction.size_for(A::BYTES) > N {
+            self.fault = Some(VmError::OutOfBounds { pc, addr: N });
+        }
+        let operand = self.operand_at(pc, Some(instruction));
+        self.execute_instruction(instruction);
+        operand
+    }
 
-        let log_entry = self.execute_instruction(instruction);
-        self.log_instruction(log_entry);
-        self.check_for_infinite_loop();
+    fn execute_custom<C>(&mut self, custom: &CustomOpcode<C, N, A>, ctx: &mut C) {
+        tracing::trace!("{} (custom opcode)", custom.mnemonic);
+        (custom.handler)(self, ctx);
     }
 
-    fn execute_instruction(&mut self, instruction: Instruction) -> String {
+    fn execute_instruction(&mut self, instruction: Instruction) {
         match instruction {
             Instruction::NOP => self.execute_nop(),
             Instruction::LDA => self.execute_lda(),
@@ -185,236 +2190,996 @@ impl VM {
             Instruction::DEC => self.execute_dec(),
             Instruction::SWP => self.execute_swp(),
             Instruction::CMP => self.execute_cmp(),
+            Instruction::CALL => self.execute_call(),
+            Instruction::RET => self.execute_ret(),
+            Instruction::JNZ => self.execute_flag_jump(Instruction::JNZ, FLAG_ZERO, false),
+            Instruction::JC => self.execute_flag_jump(Instruction::JC, FLAG_CARRY, true),
+            Instruction::JNC => self.execute_flag_jump(Instruction::JNC, FLAG_CARRY, false),
+            Instruction::JN => self.execute_flag_jump(Instruction::JN, FLAG_NEGATIVE, true),
+            Instruction::AND => self.execute_logic(Instruction::AND, |a, b| a & b),
+            Instruction::OR => self.execute_logic(Instruction::OR, |a, b| a | b),
+            Instruction::XOR => self.execute_logic(Instruction::XOR, |a, b| a ^ b),
+            Instruction::NOT => self.execute_not(),
+            Instruction::SHL => self.execute_shift(Instruction::SHL),
+            Instruction::SHR => self.execute_shift(Instruction::SHR),
+            Instruction::ROL => self.execute_shift(Instruction::ROL),
+            Instruction::ROR => self.execute_shift(Instruction::ROR),
+            Instruction::MUL => self.execute_mul(),
+            Instruction::DIV => self.execute_div(),
+            Instruction::LDI => self.execute_ldi(),
+            Instruction::ADI => self.execute_adi(),
+            Instruction::SBI => self.execute_sbi(),
+            Instruction::CPI => self.execute_cpi(),
+            Instruction::SKE => self.execute_skip(Instruction::SKE, true),
+            Instruction::SKNE => self.execute_skip(Instruction::SKNE, false),
+            Instruction::RND => self.execute_rnd(),
+            Instruction::IN => self.execute_in(),
+            Instruction::OUT => self.execute_out(),
+            Instruction::JR => self.execute_relative_jump(Instruction::JR, true),
+            Instruction::JRZ => {
+                let taken = self.acc == 0;
+                self.execute_relative_jump(Instruction::JRZ, taken)
+            }
+            Instruction::TAX | Instruction::TXA | Instruction::TAY | Instruction::TYA => {
+                self.execute_transfer(instruction)
+            }
+            Instruction::PUSH => self.execute_push(),
+            Instruction::POP => self.execute_pop(),
+            Instruction::COPY => self.execute_copy(),
             Instruction::HLT => self.execute_hlt(),
         }
     }
-    fn execute_nop(&mut self) -> String {
+    fn execute_nop(&mut self) {
         tracing::trace!("NOP");
-        let log = format!(
-            "{:04}: {} (0x{:02X})",
-            self.pc,
-            Instruction::NOP,
-            self.memory[self.pc]
-        );
         self.pc += 1;
-        log
     }
 
-    fn execute_lda(&mut self) -> String {
-        let addr = self.read_memory(self.pc + 1) as usize;
-        let value = self.read_memory(addr);
-        let log = format!(
-            "{:04}: {} (0x{:02X}) addr={} -> acc={}",
-            self.pc,
-            Instruction::LDA,
-            self.memory[self.pc],
-            addr,
-            value
-        );
+    fn execute_lda(&mut self) {
+        let addr = self.address_operand();
+        let value = self.read_data(addr);
         tracing::trace!("LDA from addr={}", addr);
         self.acc = value;
+        self.pc += Self::ADDRESS_INSTRUCTION_SIZE;
+    }
+
+    fn execute_ldi(&mut self) {
+        let value = self.read_code(self.pc + 1);
+        tracing::trace!("LDI value={}", value);
+        self.acc = value;
         self.pc += 2;
-        log
     }
 
-    fn execute_sta(&mut self) -> String {
-        let addr = self.read_memory(self.pc + 1) as usize;
-        let log = format!(
-            "{:04}: {} (0x{:02X}) acc={} -> addr={}",
-            self.pc,
-            Instruction::STA,
-            self.memory[self.pc],
-            self.acc,
-            addr
-        );
-        tracing::trace!("STA to addr={}", addr);
-        self.write_memory(addr, self.acc);
+    fn execute_adi(&mut self) {
+        let val = self.read_code(self.pc + 1);
+        tracing::trace!("ADI value={}", val);
+        let (result, carry) = self.acc.overflowing_add(val);
+        self.set_flags(result, carry);
+        self.acc = result;
         self.pc += 2;
-        log
     }
 
-    fn execute_add(&mut self) -> String {
-        let addr = self.read_memory(self.pc + 1) as usize;
-        let val = self.read_memory(addr);
-        let log = format!(
-            "{:04}: {} (0x{:02X}) acc={} + val={} (addr={})",
-            self.pc,
-            Instruction::ADD,
-            self.memory[self.pc],
-            self.acc,
-            val,
-            addr
-        );
-        tracing::trace!("ADD from addr={}, value={}", addr, val);
-        self.acc = self.acc.wrapping_add(val);
+    fn execute_sbi(&mut self) {
+        let val = self.read_code(self.pc + 1);
+        tracing::trace!("SBI value={}", val);
+        let (result, borrow) = self.acc.overflowing_sub(val);
+        self.set_flags(result, borrow);
+        self.acc = result;
         self.pc += 2;
-        log
     }
 
-    fn execute_sub(&mut self) -> String {
-        let addr = self.read_memory(self.pc + 1) as usize;
-        let val = self.read_memory(addr);
-        let log = format!(
-            "{:04}: {} (0x{:02X}) acc={} - val={} (addr={})",
-            self.pc,
-            Instruction::SUB,
-            self.memory[self.pc],
-            self.acc,
-            val,
-            addr
-        );
+    fn execute_cpi(&mut self) {
+        let val = self.read_code(self.pc + 1);
+        tracing::trace!("CPI acc={} with value={}", self.acc, val);
+        let (result, borrow) = self.acc.overflowing_sub(val);
+        self.set_flags(result, borrow);
+        self.pc += 2;
+    }
+
+    fn execute_sta(&mut self) {
+        let addr = self.address_operand();
+        tracing::trace!("STA to addr={}", addr);
+        self.write_data(addr, self.acc);
+        self.pc += Self::ADDRESS_INSTRUCTION_SIZE;
+    }
+
+    fn execute_add(&mut self) {
+        let addr = self.address_operand();
+        let val = self.read_data(addr);
+        tracing::trace!("ADD from addr={}, value={}", addr, val);
+        let (result, carry) = self.acc.overflowing_add(val);
+        self.set_flags(result, carry);
+        self.acc = result;
+        self.pc += Self::ADDRESS_INSTRUCTION_SIZE;
+    }
+
+    fn execute_sub(&mut self) {
+        let addr = self.address_operand();
+        let val = self.read_data(addr);
         tracing::trace!("SUB from addr={}, value={}", addr, val);
-        self.acc = self.acc.wrapping_sub(val);
+        let (result, borrow) = self.acc.overflowing_sub(val);
+        self.set_flags(result, borrow);
+        self.acc = result;
+        self.pc += Self::ADDRESS_INSTRUCTION_SIZE;
+    }
+
+    fn execute_mul(&mut self) {
+        let addr = self.address_operand();
+        let val = self.read_data(addr);
+        tracing::trace!("MUL from addr={}, value={}", addr, val);
+        let (result, overflow) = self.acc.overflowing_mul(val);
+        self.set_flags(result, overflow);
+        self.acc = result;
+        self.pc += Self::ADDRESS_INSTRUCTION_SIZE;
+    }
+
+    /// Division by zero is not fatal: the quotient saturates to 0xFF and carry is set
+    fn execute_div(&mut self) {
+        let addr = self.address_operand();
+        let val = self.read_data(addr);
+        tracing::trace!("DIV from addr={}, value={}", addr, val);
+        let (result, by_zero) = match self.acc.checked_div(val) {
+            Some(quotient) => (quotient, false),
+            None => (0xFF, true),
+        };
+        self.set_flags(result, by_zero);
+        self.acc = result;
+        self.pc += Self::ADDRESS_INSTRUCTION_SIZE;
+    }
+
+    /// Skip the next instruction if the accumulator equals memory, or if it differs when
+    /// `skip_if_equal` is false.
+    ///
+    /// The skipped instruction's length comes from its opcode as the VM's ISA decodes it,
+    /// so the target is always an instruction boundary. Bytes the ISA does not define
+    /// count as one byte, like HLT, and so do registered opcodes, whose length only their
+    /// handler knows. Flags are left alone.
+    fn execute_skip(&mut self, instruction: Instruction, skip_if_equal: bool) {
+        let addr = self.address_operand();
+        let val = self.read_data(addr);
+        let skipped = (self.acc == val) == skip_if_equal;
+        tracing::trace!("{} with addr={}, skipped={}", instruction, addr, skipped);
+        self.pc += Self::ADDRESS_INSTRUCTION_SIZE;
+        if skipped {
+            let next = self.isa.decode(self.read_code(self.pc));
+            self.pc += next.map_or(1, |i| i.size_for(A::BYTES));
+        }
+    }
+
+    fn execute_rnd(&mut self) {
+        let value = self.next_random();
+        tracing::trace!("RND value={}", value);
+        self.acc = value;
+        self.pc += 1;
+    }
+
+    fn execute_in(&mut self) {
+        let port = self.read_code(self.pc + 1);
+        let value = self.ports.read(port);
+        tracing::trace!("IN from port={}, value={}", port, value);
+        self.acc = value;
         self.pc += 2;
-        log
     }
 
-    fn execute_jmp(&mut self) -> String {
-        let addr = self.read_memory(self.pc + 1) as usize;
-        let log = format!(
-            "{:04}: {} (0x{:02X}) to addr={}",
-            self.pc,
-            Instruction::JMP,
-            self.memory[self.pc],
-            addr
-        );
+    fn execute_out(&mut self) {
+        let port = self.read_code(self.pc + 1);
+        tracing::trace!("OUT to port={}, value={}", port, self.acc);
+        self.ports.write(port, self.acc);
+        self.pc += 2;
+    }
+
+    fn execute_jmp(&mut self) {
+        let addr = self.address_operand();
         tracing::trace!("JMP to addr={}", addr);
         self.pc = addr;
-        log
     }
 
-    fn execute_jz(&mut self) -> String {
-        let addr = self.read_memory(self.pc + 1) as usize;
-        let log = format!(
-            "{:04}: {} (0x{:02X}) to addr={} if acc==0 (acc={})",
-            self.pc,
-            Instruction::JZ,
-            self.memory[self.pc],
-            addr,
-            self.acc
+    /// Jump `offset` bytes from this instruction when `taken`; the target wraps around memory
+    fn execute_relative_jump(&mut self, instruction: Instruction, taken: bool) {
+        let offset = self.read_code(self.pc + 1) as i8;
+        let target = relative_target(self.pc, offset, N);
+        tracing::trace!(
+            "{} by {:+} to addr={}, taken={}",
+            instruction,
+            offset,
+            target,
+            taken
         );
+        if taken {
+            self.pc = target;
+        } else {
+            self.pc += 2;
+        }
+    }
+
+    fn execute_jz(&mut self) {
+        let addr = self.address_operand();
         tracing::trace!("JZ to addr={} if acc==0", addr);
         if self.acc == 0 {
             self.pc = addr;
         } else {
-            self.pc += 2;
+            self.pc += Self::ADDRESS_INSTRUCTION_SIZE;
+        }
+    }
+
+    /// Jump if `flag` is set, or if it is clear when `jump_if_set` is false
+    fn execute_flag_jump(&mut self, instruction: Instruction, flag: u8, jump_if_set: bool) {
+        let addr = self.address_operand();
+        let taken = (self.flags & flag != 0) == jump_if_set;
+        tracing::trace!("{} to addr={}, taken={}", instruction, addr, taken);
+        if taken {
+            self.pc = addr;
+        } else {
+            self.pc += Self::ADDRESS_INSTRUCTION_SIZE;
         }
-        log
     }
 
-    fn execute_inc(&mut self) -> String {
+    /// Combine the accumulator with a memory value bit by bit; carry is always cleared
+    fn execute_logic(&mut self, instruction: Instruction, op: fn(u8, u8) -> u8) {
+        let addr = self.address_operand();
+        let val = self.read_data(addr);
+        let result = op(self.acc, val);
+        tracing::trace!("{} with addr={}, value={}", instruction, addr, val);
+        self.set_flags(result, false);
+        self.acc = result;
+        self.pc += Self::ADDRESS_INSTRUCTION_SIZE;
+    }
+
+    fn execute_not(&mut self) {
+        self.acc = !self.acc;
+        tracing::trace!("NOT");
+        self.set_flags(self.acc, false);
+        self.pc += 1;
+    }
+
+    /// Shift or rotate the accumulator one bit; carry receives the bit moved out of it
+    fn execute_shift(&mut self, instruction: Instruction) {
         let old_acc = self.acc;
+        let (result, carry) = match instruction {
+            Instruction::SHL => (old_acc << 1, old_acc & 0x80 != 0),
+            Instruction::SHR => (old_acc >> 1, old_acc & 0x01 != 0),
+            Instruction::ROL => (old_acc.rotate_left(1), old_acc & 0x80 != 0),
+            Instruction::ROR => (old_acc.rotate_right(1), old_acc & 0x01 != 0),
+            _ => unreachable!("{} is not a shift", instruction),
+        };
+        self.acc = result;
+        tracing::trace!("{}", instruction);
+        self.set_flags(result, carry);
+        self.pc += 1;
+    }
+
+    /// Set the flags from an arithmetic, compare or logic result
+    fn set_flags(&mut self, result: u8, carry: bool) {
+        self.flags &= FLAG_TRAP;
+        if result == 0 {
+            self.flags |= FLAG_ZERO;
+        }
+        if carry {
+            self.flags |= FLAG_CARRY;
+        }
+        if result & 0x80 != 0 {
+            self.flags |= FLAG_NEGATIVE;
+        }
+    }
+
+    /// Copy between the accumulator and X or Y; flags are left alone
+    fn execute_transfer(&mut self, instruction: Instruction) {
+        let value = match instruction {
+            Instruction::TAX => {
+                self.x = self.acc;
+                self.acc
+            }
+            Instruction::TXA => {
+                self.acc = self.x;
+                self.x
+            }
+            Instruction::TAY => {
+                self.y = self.acc;
+                self.acc
+            }
+            Instruction::TYA => {
+                self.acc = self.y;
+                self.y
+            }
+            _ => unreachable!("{} is not a register transfer", instruction),
+        };
+        tracing::trace!("{} value={}", instruction, value);
+        self.pc += 1;
+    }
+
+    fn execute_inc(&mut self) {
         self.acc = self.acc.wrapping_add(1);
-        let log = format!(
-            "{:04}: {} (0x{:02X}) acc={} -> {}",
-            self.pc,
-            Instruction::INC,
-            self.memory[self.pc],
-            old_acc,
-            self.acc
-        );
         tracing::trace!("INC");
         self.pc += 1;
-        log
     }
 
-    fn execute_dec(&mut self) -> String {
-        let old_acc = self.acc;
+    fn execute_dec(&mut self) {
         self.acc = self.acc.wrapping_sub(1);
-        let log = format!(
-            "{:04}: {} (0x{:02X}) acc={} -> {}",
-            self.pc,
-            Instruction::DEC,
-            self.memory[self.pc],
-            old_acc,
-            self.acc
-        );
         tracing::trace!("DEC");
         self.pc += 1;
-        log
     }
 
-    fn execute_swp(&mut self) -> String {
-        let addr = self.read_memory(self.pc + 1) as usize;
-        let old_mem_val = self.read_memory(addr);
-        let log = format!(
-            "{:04}: {} (0x{:02X}) acc={} <-> addr={} val={}",
-            self.pc,
-            Instruction::SWP,
-            self.memory[self.pc],
-            self.acc,
-            addr,
-            old_mem_val
-        );
+    fn execute_swp(&mut self) {
+        let addr = self.address_operand();
+        let old_mem_val = self.read_data(addr);
         tracing::trace!("SWP with addr={}", addr);
-        if addr < MEM_SIZE {
-            let tmp = self.memory[addr];
-            self.memory[addr] = self.acc;
-            self.acc = tmp;
+        if addr < N {
+            let acc = self.acc;
+            self.acc = old_mem_val;
+            self.write_data(addr, acc);
         }
-        self.pc += 2;
-        log
+        self.pc += Self::ADDRESS_INSTRUCTION_SIZE;
     }
 
-    fn execute_cmp(&mut self) -> String {
-        let addr = self.read_memory(self.pc + 1) as usize;
-        let val = self.read_memory(addr);
-        let log = format!(
-            "{:04}: {} (0x{:02X}) acc={} addr={} val={}",
-            self.pc,
-            Instruction::CMP,
-            self.memory[self.pc],
-            self.acc,
-            addr,
-            val
-        );
+    fn execute_cmp(&mut self) {
+        let addr = self.address_operand();
+        let val = self.read_data(addr);
         tracing::trace!("CMP acc={} with addr={}, value={}", self.acc, addr, val);
-        self.pc += 2;
-        log
+        // Like SUB, but only the flags keep the result
+        let (result, borrow) = self.acc.overflowing_sub(val);
+        self.set_flags(result, borrow);
+        self.pc += Self::ADDRESS_INSTRUCTION_SIZE;
     }
 
-    fn execute_hlt(&mut self) -> String {
-        let log = format!(
-            "{:04}: {} (0x{:02X})",
-            self.pc,
-            Instruction::HLT,
-            self.memory[self.pc]
-        );
-        tracing::debug!("HLT - VM halted!");
-        self.halted = true;
-        log
+    fn execute_call(&mut self) {
+        let addr = self.address_operand();
+        let return_addr = self.pc + Self::ADDRESS_INSTRUCTION_SIZE;
+        tracing::trace!("CALL addr={}, return to {}", addr, return_addr);
+        self.push_address(return_addr);
+        self.pc = addr;
+    }
+
+    fn execute_ret(&mut self) {
+        let return_addr = self.pop_address();
+        tracing::trace!("RET to addr={}", return_addr);
+        self.pc = return_addr;
+    }
+
+    fn execute_push(&mut self) {
+        tracing::trace!("PUSH acc={} to addr={}", self.acc, self.dsp);
+        // Like the call stack, the data stack lives in data memory and wraps around
+        self.write_data(self.dsp, self.acc);
+        self.dsp = (self.dsp + 1) % A::SPACE;
+        self.pc += 1;
     }
 
-    fn log_instruction(&mut self, log_entry: String) {
-        self.recent_instructions.push(log_entry);
-        if self.recent_instructions.len() > 16 {
-            self.recent_instructions.remove(0);
+    fn execute_pop(&mut self) {
+        self.dsp = (self.dsp + A::SPACE - 1) % A::SPACE;
+        self.acc = self.read_data(self.dsp);
+        tracing::trace!("POP acc={} from addr={}", self.acc, self.dsp);
+        self.pc += 1;
+    }
+
+    fn execute_copy(&mut self) {
+        let (src, dst, len) = (self.x as usize, self.y as usize, self.acc as usize);
+        tracing::trace!("COPY {} bytes from addr={} to addr={}", len, src, dst);
+        // Read the whole block before writing, so overlapping copies move it intact
+        let block: Vec<u8> = (0..len)
+            .map(|i| self.read_data((src + i) % A::SPACE))
+            .collect();
+        for (i, value) in block.into_iter().enumerate() {
+            self.write_data((dst + i) % A::SPACE, value);
         }
+        self.x = self.x.wrapping_add(self.acc);
+        self.y = self.y.wrapping_add(self.acc);
+        self.pc += 1;
     }
 
+    fn execute_hlt(&mut self) {
+        tracing::debug!("HLT - VM halted!");
+        self.halted = true;
+    }
+
+    /// Sample the state every `interval` steps and halt once a sample repeats
     fn check_for_infinite_loop(&mut self) {
-        // If only 2 unique instructions in recent_instructions, halt and reset steps
-        if self.recent_instructions.len() == 16 {
-            let mut unique_instr = std::collections::HashSet::new();
-            for s in &self.recent_instructions {
-                // Extract instruction name (assumes format: "xxxx: NAME (0xYY)...")
-                if let Some(colon) = s.find(':') {
-                    if let Some(space) = s[colon + 2..].find(' ') {
-                        let name = &s[colon + 2..colon + 2 + space];
-                        unique_instr.insert(name);
-                    }
-                }
-            }
-            if unique_instr.len() <= 2 {
-                self.halted = true;
+        let Some(detector) = self.loop_detector else {
+            return;
+        };
+        if self.halted {
+            return;
+        }
+        let search = &mut self.cycle_search;
+        if search.steps_until_sample > 1 {
+            search.steps_until_sample -= 1;
+            return;
+        }
+        search.steps_until_sample = detector.interval.max(1);
+
+        let hash = self.state_hash();
+        let search = &mut self.cycle_search;
+        let Some(saved) = search.saved else {
+            search.saved = Some(hash);
+            search.power = 1;
+            return;
+        };
+        search.lam += 1;
+        if hash == saved {
+            tracing::debug!(
+                "Loop detected: state repeats every {} samples of {} steps",
+                search.lam,
+                detector.interval.max(1)
+            );
+            self.halted = true;
+            if detector.reset_steps {
                 self.total_steps_count = 0;
             }
+            return;
+        }
+        if search.lam == search.power {
+            search.saved = Some(hash);
+            search.lam = 0;
+            if search.power < detector.max_period {
+                search.power *= 2;
+            }
+        }
+    }
+
+    /// Hash of everything that decides what the program does next
+    fn state_hash(&self) -> u64 {
+        use std::hash::{DefaultHasher, Hash, Hasher};
+        let mut hasher = DefaultHasher::new();
+        (
+            self.pc, self.acc, self.x, self.y, self.sp, self.dsp, self.flags,
+        )
+            .hash(&mut hasher);
+        (self.rng_state, self.steps_since_interrupt).hash(&mut hasher);
+        for (i, thread) in self.threads.iter().enumerate() {
+            if i != self.thread {
+                thread.hash(&mut hasher);
+            }
+        }
+        self.memory.hash(&mut hasher);
+        if let Some(data) = &self.data {
+            data.hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    /// Step until the program halts, traps, hits a breakpoint or watchpoint, or
+    /// `max_steps` steps have run
+    pub fn run_for(&mut self, max_steps: usize) -> StepResult {
+        for _ in 0..max_steps {
+            if self.halted {
+                return StepResult::Halted;
+            }
+            let hit = self.step();
+            if self.trapped {
+                return StepResult::Trapped;
+            }
+            if let Some(hit) = hit {
+                return StepResult::Hit(hit);
+            }
+        }
+        if self.halted {
+            StepResult::Halted
+        } else {
+            StepResult::BudgetExhausted
+        }
+    }
+
+    /// Cycles run so far; see `VmMetrics::cycles`
+    pub fn cycles(&self) -> u64 {
+        self.metrics.cycles
+    }
+
+    /// Grant `cycles` more cycles to spend on steps. The last step may overspend,
+    /// and the overspend is taken from the next grant, so VMs granted the same
+    /// cycles get the same time however their instructions are timed. Halting
+    /// forfeits what is left.
+    pub fn grant_cycles(&mut self, cycles: u64) {
+        self.cycle_credit = self
+            .cycle_credit
+            .saturating_add(cycles.min(i64::MAX as u64) as i64);
+    }
+
+    /// Whether granted cycles are left to spend
+    pub fn has_cycle_credit(&self) -> bool {
+        self.cycle_credit > 0 && !self.halted
+    }
+
+    /// `run_for` by cycles instead of steps: grant `cycles` with `grant_cycles` and
+    /// step until they are spent, or the program halts, traps or hits a breakpoint or watchpoint
+    pub fn run_for_cycles(&mut self, cycles: u64) -> StepResult {
+        self.grant_cycles(cycles);
+        while self.has_cycle_credit() {
+            let hit = self.step();
+            if self.trapped {
+                return StepResult::Trapped;
+            }
+            if let Some(hit) = hit {
+                return StepResult::Hit(hit);
+            }
+        }
+        if self.halted {
+            self.cycle_credit = self.cycle_credit.min(0);
+            StepResult::Halted
+        } else {
+            StepResult::BudgetExhausted
         }
     }
 
+    /// Step until the program halts, carrying on past traps and hits
     pub fn run(&mut self) {
-        while !self.halted {
-            self.step();
+        while self.run_for(usize::MAX) != StepResult::Halted {}
+    }
+
+    /// Step until `predicate` holds, checking it before the first step and after every
+    /// one, e.g. `vm.run_until(1000, |vm| vm.memory[200] == 42)`. Stops early if the
+    /// program halts and gives up after `max_steps` steps; traps, breakpoints and
+    /// watchpoints do not stop it.
+    pub fn run_until<F>(&mut self, max_steps: usize, mut predicate: F) -> RunOutcome
+    where
+        F: FnMut(&Self) -> bool,
+    {
+        for steps in 0..=max_steps {
+            if predicate(self) {
+                return RunOutcome::Satisfied { steps };
+            }
+            if self.halted {
+                return RunOutcome::Halted { steps };
+            }
+            if steps < max_steps {
+                self.step();
+            }
+        }
+        RunOutcome::StepLimit
+    }
+}
+
+/// Cells at which `old` and `new` hold different values
+fn cell_diffs(old: &[u8], new: &[u8]) -> Vec<CellDiff> {
+    old.iter()
+        .zip(new)
+        .enumerate()
+        .filter(|(_, (a, b))| a != b)
+        .map(|(addr, (&old, &new))| CellDiff { addr, old, new })
+        .collect()
+}
+
+/// The data stack pointer of a VM read back without one
+#[cfg(feature = "serde")]
+fn data_stack_base<A: AddressWidth>() -> usize {
+    A::DATA_STACK_BASE
+}
+
+/// A heatmap sized for a VM read back without one
+#[cfg(all(feature = "serde", feature = "heatmap"))]
+fn fresh_heatmap<const N: usize>() -> Heatmap {
+    Heatmap::new(N)
+}
+
+/// Serde for memory-sized byte arrays, which serde only supports up to 32 bytes.
+///
+/// Arrays are written as byte strings, which self-describing formats like JSON
+/// show as arrays of numbers; either form is accepted back.
+#[cfg(feature = "serde")]
+mod byte_array {
+    use std::fmt;
+
+    use serde::de::{self, Deserializer, SeqAccess, Visitor};
+    use serde::{Deserialize, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer, const N: usize>(
+        bytes: &[u8; N],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(bytes)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, const N: usize>(
+        deserializer: D,
+    ) -> Result<[u8; N], D::Error> {
+        deserializer.deserialize_bytes(ByteArrayVisitor::<N>)
+    }
+
+    struct ByteArrayVisitor<const N: usize>;
+
+    impl<'de, const N: usize> Visitor<'de> for ByteArrayVisitor<N> {
+        type Value = [u8; N];
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{} bytes", N)
+        }
+
+        fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<Self::Value, E> {
+            bytes
+                .try_into()
+                .map_err(|_| E::invalid_length(bytes.len(), &self))
+        }
+
+        fn visit_seq<S: SeqAccess<'de>>(self, mut seq: S) -> Result<Self::Value, S::Error> {
+            let mut bytes = [0; N];
+            for (i, byte) in bytes.iter_mut().enumerate() {
+                *byte = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(i, &self))?;
+            }
+            if seq.next_element::<u8>()?.is_some() {
+                return Err(de::Error::invalid_length(N + 1, &self));
+            }
+            Ok(bytes)
+        }
+    }
+
+    /// One byte array, for wrapping in an `Option`
+    struct Bank<const N: usize>([u8; N]);
+
+    impl<const N: usize> Serialize for Bank<N> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serialize(&self.0, serializer)
+        }
+    }
+
+    impl<'de, const N: usize> Deserialize<'de> for Bank<N> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserialize(deserializer).map(Bank)
+        }
+    }
+
+    /// The optional Harvard data bank
+    pub mod bank {
+        use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+        use super::Bank;
+
+        pub fn serialize<S: Serializer, const N: usize>(
+            data: &Option<Box<[u8; N]>>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            data.as_deref()
+                .map(|bytes| Bank(*bytes))
+                .serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>, const N: usize>(
+            deserializer: D,
+        ) -> Result<Option<Box<[u8; N]>>, D::Error> {
+            Ok(Option::<Bank<N>>::deserialize(deserializer)?.map(|bank| Box::new(bank.0)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATA_ADDR: u8 = 0x80;
+
+    /// Run `program` for one step with `acc` preloaded and `value` at `DATA_ADDR`
+    fn step_once(program: &[u8], acc: u8, value: u8) -> Vm256 {
+        let mut vm = Vm256::new();
+        vm.load_program(program);
+        vm.memory[DATA_ADDR as usize] = value;
+        vm.acc = acc;
+        vm.step();
+        vm
+    }
+
+    fn flags(zero: bool, carry: bool, negative: bool) -> u8 {
+        let mut flags = 0;
+        if zero {
+            flags |= FLAG_ZERO;
+        }
+        if carry {
+            flags |= FLAG_CARRY;
+        }
+        if negative {
+            flags |= FLAG_NEGATIVE;
+        }
+        flags
+    }
+
+    #[test]
+    fn logic_sets_flags_and_traces_its_operand() {
+        let cases = [
+            (Instruction::AND, 0b1100_1010, 0b1010_0110, 0b1000_0010),
+            (Instruction::AND, 0b0101_0101, 0b1010_1010, 0),
+            (Instruction::OR, 0b0000_0001, 0b0100_0000, 0b0100_0001),
+            (Instruction::OR, 0, 0, 0),
+            (Instruction::OR, 0b0000_1111, 0b1111_0000, 0xFF),
+            (Instruction::XOR, 0xFF, 0x0F, 0xF0),
+            (Instruction::XOR, 0x5A, 0x5A, 0),
+            (Instruction::XOR, 0x01, 0x03, 0x02),
+        ];
+        for (instruction, acc, value, result) in cases {
+            let opcode = instruction as u8;
+            let mut vm = Vm256::new();
+            vm.load_program(&[opcode, DATA_ADDR]);
+            vm.memory[DATA_ADDR as usize] = value;
+            vm.acc = acc;
+            vm.flags = FLAG_CARRY;
+            vm.step();
+
+            let case = format!("{} {:#04X}, {:#04X}", instruction, acc, value);
+            assert_eq!(vm.acc, result, "{case}");
+            assert_eq!(
+                vm.flags,
+                flags(result == 0, false, result >= 0x80),
+                "{case}"
+            );
+            assert_eq!(vm.pc, 2, "{case}");
+            assert_eq!(
+                vm.recent.last(),
+                Some(&TraceEvent {
+                    pc: 0,
+                    opcode,
+                    operand: Some(DATA_ADDR as u16),
+                    acc_before: acc,
+                    acc_after: result,
+                    dsp: vm.dsp,
+                }),
+                "{case}"
+            );
+        }
+    }
+
+    #[test]
+    fn not_inverts_the_accumulator() {
+        for (acc, result) in [(0x00, 0xFF), (0xFF, 0x00), (0x0F, 0xF0), (0xAA, 0x55)] {
+            let vm = step_once(&[Instruction::NOT as u8], acc, 0);
+            assert_eq!(vm.acc, result);
+            assert_eq!(vm.flags, flags(result == 0, false, result >= 0x80));
+            assert_eq!(vm.pc, 1);
+            assert_eq!(
+                vm.recent.last(),
+                Some(&TraceEvent {
+                    pc: 0,
+                    opcode: Instruction::NOT as u8,
+                    operand: None,
+                    acc_before: acc,
+                    acc_after: result,
+                    dsp: vm.dsp,
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn logic_keeps_the_trap_flag() {
+        let mut vm = Vm256::new();
+        vm.load_program(&[Instruction::AND as u8, DATA_ADDR]);
+        vm.flags = FLAG_TRAP | FLAG_CARRY;
+        vm.step();
+        assert_eq!(vm.flags, FLAG_TRAP | FLAG_ZERO);
+    }
+
+    #[test]
+    fn legacy_dumps_load_as_v1() {
+        let dump = vec![Instruction::LDA as u8, DATA_ADDR, Instruction::HLT as u8];
+        let file = ProgramFile::from_bytes(dump.clone()).unwrap();
+        assert!(file.legacy);
+        assert_eq!(file.program.isa, IsaVersion::V1);
+        assert_eq!(file.program.genome, dump);
+        assert_eq!(file.metadata, ProgramMetadata::default());
+    }
+
+    #[test]
+    fn shifts_move_the_outgoing_bit_into_carry() {
+        let cases = [
+            (Instruction::SHL, 0b1000_0001, 0b0000_0010, true),
+            (Instruction::SHL, 0b0100_0000, 0b1000_0000, false),
+            (Instruction::SHL, 0b1000_0000, 0, true),
+            (Instruction::SHR, 0b1000_0001, 0b0100_0000, true),
+            (Instruction::SHR, 0b0000_0010, 0b0000_0001, false),
+            (Instruction::SHR, 0b0000_0001, 0, true),
+            (Instruction::ROL, 0b1000_0001, 0b0000_0011, true),
+            (Instruction::ROL, 0b0100_0000, 0b1000_0000, false),
+            (Instruction::ROL, 0, 0, false),
+            (Instruction::ROR, 0b1000_0001, 0b1100_0000, true),
+            (Instruction::ROR, 0b0000_0010, 0b0000_0001, false),
+            (Instruction::ROR, 0, 0, false),
+        ];
+        for (instruction, acc, result, carry) in cases {
+            let opcode = instruction as u8;
+            let vm = step_once(&[opcode], acc, 0);
+
+            let case = format!("{} {:#010b}", instruction, acc);
+            assert_eq!(vm.acc, result, "{case}");
+            assert_eq!(
+                vm.flags,
+                flags(result == 0, carry, result >= 0x80),
+                "{case}"
+            );
+            assert_eq!(vm.pc, 1, "{case}");
+            assert_eq!(
+                vm.recent.last(),
+                Some(&TraceEvent {
+                    pc: 0,
+                    opcode,
+                    operand: None,
+                    acc_before: acc,
+                    acc_after: result,
+                    dsp: vm.dsp,
+                }),
+                "{case}"
+            );
+        }
+    }
+
+    #[test]
+    fn diff_of_equal_vms_is_empty() {
+        let mut vm = Vm256::new();
+        vm.set_harvard(true);
+        vm.randomize(&mut crate::rng::SimRng::new(7));
+        let mut other = vm.clone();
+        assert!(vm.diff(&other).is_empty());
+
+        vm.run_for(100);
+        other.run_for(100);
+        assert!(vm.diff(&other).is_empty());
+        assert_eq!(vm.diff(&other), VmDiff::default());
+    }
+
+    #[test]
+    fn diff_reports_every_register() {
+        let vm = Vm256::new();
+        type Change = fn(&mut Vm256);
+        let changes: [(Register, Change); 9] = [
+            (Register::Pc, |vm| vm.pc = 17),
+            (Register::Acc, |vm| vm.acc = 17),
+            (Register::X, |vm| vm.x = 17),
+            (Register::Y, |vm| vm.y = 17),
+            (Register::Sp, |vm| vm.sp = 17),
+            (Register::Dsp, |vm| vm.dsp = 17),
+            (Register::Flags, |vm| vm.flags = FLAG_NEGATIVE),
+            (Register::Halted, |vm| vm.halted = true),
+            (Register::Steps, |vm| vm.total_steps_count = 17),
+        ];
+        for (register, change) in changes {
+            let mut other = vm.clone();
+            change(&mut other);
+            let diff = vm.diff(&other);
+            assert_eq!(diff.registers.len(), 1, "{}", register.name());
+            let RegisterDiff {
+                register: r,
+                old,
+                new,
+            } = diff.registers[0];
+            assert_eq!(r, register);
+            assert_ne!(old, new, "{}", register.name());
+            assert!(diff.memory.is_empty() && diff.data.is_empty());
+            assert_eq!(other.diff(&vm).registers[0].old, new);
+        }
+    }
+
+    #[test]
+    fn diff_reports_memory_and_data_cells() {
+        let mut vm = Vm256::new();
+        vm.set_harvard(true);
+        let mut other = vm.clone();
+        other.memory[3] = 9;
+        other.data_mut()[200] = 4;
+        let diff = vm.diff(&other);
+        assert!(diff.registers.is_empty());
+        assert_eq!(
+            diff.memory,
+            [CellDiff {
+                addr: 3,
+                old: 0,
+                new: 9
+            }]
+        );
+        assert_eq!(
+            diff.data,
+            [CellDiff {
+                addr: 200,
+                old: 0,
+                new: 4
+            }]
+        );
+    }
+
+    /// Touches every register and counter a step can change: both data and call
+    /// stacks, X and Y, flags, RND, stores, a coroutine and a timer interrupt
+    const STEP_BACK_PROGRAM: &str = "
+        start:  LDI #5
+                TAX
+                LDI #9
+                TAY
+                PUSH
+                ADI #250
+                STA 0x90
+                CALL sub
+                POP
+                RND
+                JMP start
+        sub:    SWP 0x90
+                RET
+        co:     INC
+                STA 0x91
+                MUL 0x90
+                JR co
+        tick:   RET
+    ";
+
+    fn step_back_vm() -> Vm256 {
+        let assembly = crate::asm::assemble(STEP_BACK_PROGRAM, IsaVersion::LATEST).unwrap();
+        let mut vm = Vm256::with_config(VmConfig {
+            undo_depth: 256,
+            cycle_table: Some(CycleTable::standard(IsaVersion::LATEST)),
+            ..VmConfig::default()
+        });
+        vm.load_program(&assembly.image);
+        vm.set_coroutine(Some(assembly.labels["co"]));
+        vm.interrupt = Some(TimerInterrupt {
+            period: 7,
+            vector: assembly.labels["tick"] as u16,
+        });
+        vm
+    }
+
+    #[test]
+    fn timer_vector_past_memory_halts() {
+        let mut vm = Vm256::new();
+        vm.load_program(&[Instruction::NOP as u8; 4]);
+        vm.interrupt = Some(TimerInterrupt {
+            period: 2,
+            vector: MEM_SIZE as u16 + 44,
+        });
+        for _ in 0..4 {
+            vm.step();
+        }
+        assert!(vm.halted);
+        assert_eq!(vm.metrics().halts, 1);
+    }
+
+    #[test]
+    fn step_back_undoes_each_step() {
+        let mut vm = step_back_vm();
+        for _ in 0..200 {
+            let before = vm.snapshot();
+            let (threads, metrics) = (vm.threads(), vm.metrics());
+            vm.step();
+            assert_ne!(vm.snapshot(), before);
+
+            assert!(vm.step_back());
+            assert_eq!(vm.snapshot(), before);
+            assert_eq!(vm.threads(), threads);
+            assert_eq!(vm.metrics(), metrics);
+            assert!(vm.diff(&vm.clone()).is_empty());
+            vm.step();
+        }
+    }
+
+    #[test]
+    fn step_back_rewinds_a_whole_run() {
+        let mut vm = step_back_vm();
+        let start = vm.clone();
+        let mut snapshots = Vec::new();
+        let mut changed = Vec::new();
+        for _ in 0..200 {
+            snapshots.push(vm.snapshot());
+            let previous = vm.clone();
+            vm.step();
+            changed.extend(previous.diff(&vm).registers.iter().map(|r| r.register));
+        }
+        assert!(!vm.halted);
+        for register in [
+            Register::Pc,
+            Register::Acc,
+            Register::X,
+            Register::Y,
+            Register::Sp,
+            Register::Dsp,
+            Register::Flags,
+            Register::Steps,
+        ] {
+            assert!(
+                changed.contains(&register),
+                "{} never changed",
+                register.name()
+            );
+        }
+        // The coroutine stores its counter at 0x91
+        assert_ne!(vm.memory[0x91], 0);
+        assert_ne!(vm.metrics().memory_writes, 0);
+
+        while let Some(snapshot) = snapshots.pop() {
+            assert!(vm.step_back());
+            assert_eq!(vm.snapshot(), snapshot);
         }
+        assert!(!vm.step_back());
+        assert!(vm.diff(&start).is_empty());
+        assert_eq!(vm.threads(), start.threads());
+        assert_eq!(vm.metrics(), start.metrics());
     }
 }
@@ -0,0 +1,149 @@
+// mutation.rs
+
+// Genome mutation operators: point rewrites, insertions and deletions that shift the bytes behind them, and block duplication and reversal
+
+use rand::Rng;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+// Mutation constants
+pub const DEFAULT_MAX_BLOCK: usize = 16; // Longest block duplicated or reversed by default
+
+/// Overwrite `count` randomly chosen bytes with random values; a byte may be chosen twice
+pub fn point<const N: usize, R: Rng>(genome: &mut [u8; N], count: usize, rng: &mut R) {
+    for _ in 0..count {
+        let idx = rng.random_range(0..N);
+        genome[idx] = rng.random();
+    }
+}
+
+/// Insert `count` random bytes at random positions, shifting the bytes after each
+/// one up; what is pushed off the end is lost
+pub fn insert<const N: usize, R: Rng>(genome: &mut [u8; N], count: usize, rng: &mut R) {
+    for _ in 0..count {
+        let idx = rng.random_range(0..N);
+        genome[idx..].rotate_right(1);
+        genome[idx] = rng.random();
+    }
+}
+
+/// Delete `count` bytes at random positions, shifting the bytes after each one
+/// down and filling the end with zeros (NOPs)
+pub fn delete<const N: usize, R: Rng>(genome: &mut [u8; N], count: usize, rng: &mut R) {
+    for _ in 0..count {
+        let idx = rng.random_range(0..N);
+        genome[idx..].rotate_left(1);
+        genome[N - 1] = 0;
+    }
+}
+
+/// Copy a random block of 1 to `max_len` bytes in right after itself, shifting
+/// the rest of the genome up like `insert`
+pub fn duplicate_block<const N: usize, R: Rng>(genome: &mut [u8; N], max_len: usize, rng: &mut R) {
+    let Some((start, len)) = block(N, max_len, rng) else {
+        return;
+    };
+    let end = start + len;
+    let copied = len.min(N - end);
+    let block = genome[start..start + copied].to_vec();
+    genome[end..].rotate_right(copied);
+    genome[end..end + copied].copy_from_slice(&block);
+}
+
+/// Reverse the byte order of a random block of 1 to `max_len` bytes
+pub fn reverse_block<const N: usize, R: Rng>(genome: &mut [u8; N], max_len: usize, rng: &mut R) {
+    if let Some((start, len)) = block(N, max_len, rng) {
+        genome[start..start + len].reverse();
+    }
+}
+
+/// Start and length of a random block that fits in `n` bytes
+fn block<R: Rng>(n: usize, max_len: usize, rng: &mut R) -> Option<(usize, usize)> {
+    if n == 0 || max_len == 0 {
+        return None;
+    }
+    let len = rng.random_range(1..=max_len.min(n));
+    let start = rng.random_range(0..=n - len);
+    Some((start, len))
+}
+
+/// How hard `MutationRates::apply` mutates a genome. Point, insertion and deletion
+/// rates are fractions of the genome's bytes; duplication and reversal rates are
+/// the chance of one block operation per genome.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct MutationRates {
+    pub point: f64,
+    pub insertion: f64,
+    pub deletion: f64,
+    pub duplication: f64,
+    pub reversal: f64,
+    /// Longest block duplicated or reversed
+    pub max_block: usize,
+}
+
+impl Default for MutationRates {
+    /// 2% point mutations and nothing else
+    fn default() -> Self {
+        Self {
+            point: 0.02,
+            insertion: 0.0,
+            deletion: 0.0,
+            duplication: 0.0,
+            reversal: 0.0,
+            max_block: DEFAULT_MAX_BLOCK,
+        }
+    }
+}
+
+impl MutationRates {
+    pub fn with_point(mut self, rate: f64) -> Self {
+        self.point = rate;
+        self
+    }
+
+    pub fn with_insertion(mut self, rate: f64) -> Self {
+        self.insertion = rate;
+        self
+    }
+
+    pub fn with_deletion(mut self, rate: f64) -> Self {
+        self.deletion = rate;
+        self
+    }
+
+    pub fn with_duplication(mut self, rate: f64) -> Self {
+        self.duplication = rate;
+        self
+    }
+
+    pub fn with_reversal(mut self, rate: f64) -> Self {
+        self.reversal = rate;
+        self
+    }
+
+    pub fn with_max_block(mut self, max_block: usize) -> Self {
+        self.max_block = max_block;
+        self
+    }
+
+    /// Run every operator on `genome`: duplication and reversal first, then
+    /// deletions, insertions and point mutations
+    pub fn apply<const N: usize, R: Rng>(&self, genome: &mut [u8; N], rng: &mut R) {
+        if rng.random_bool(self.duplication.clamp(0.0, 1.0)) {
+            duplicate_block(genome, self.max_block, rng);
+        }
+        if rng.random_bool(self.reversal.clamp(0.0, 1.0)) {
+            reverse_block(genome, self.max_block, rng);
+        }
+        delete(genome, byte_count(N, self.deletion), rng);
+        insert(genome, byte_count(N, self.insertion), rng);
+        point(genome, byte_count(N, self.point), rng);
+    }
+}
+
+/// How many of `n` bytes `rate` stands for, rounded
+pub fn byte_count(n: usize, rate: f64) -> usize {
+    (n as f64 * rate.clamp(0.0, 1.0)).round() as usize
+}
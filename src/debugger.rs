@@ -0,0 +1,374 @@
+// debugger.rs
+
+// Line-oriented debugger for stepping a VM by hand, the engine behind `bacteria debug`
+
+use std::fmt;
+use std::fmt::Write as _;
+
+use crate::asm::ADDRESS_NAMES;
+use crate::compute::{
+    DebugHit, FLAG_CARRY, FLAG_NEGATIVE, FLAG_TRAP, FLAG_ZERO, MEM_SIZE, StepResult, VM, VmConfig,
+    WatchKind,
+};
+use crate::disasm;
+use crate::isa::Program;
+
+// Debugger constants
+pub const DEFAULT_RUN_STEPS: usize = 10_000; // Steps `run` takes without a count
+pub const DEFAULT_PEEK_LEN: usize = 16; // Bytes `peek` shows without a length
+pub const LIST_INSTRUCTIONS: usize = 8; // Instructions `list` shows from the PC
+pub const DEBUGGER_UNDO_DEPTH: usize = 1024; // Steps `back` can rewind
+
+pub const HELP: &str = "Commands:
+  step [n]              Run n instructions (default 1), stopping early at a breakpoint,
+                        watchpoint, halt or trap
+  run [n]               The same with a default of 10000
+  back [n]              Undo the last n steps (default 1)
+  peek <addr> [len]     Show len bytes of data memory from addr (default 16)
+  poke <addr> <value>   Write a byte of data memory
+  break [addr]          Stop when the PC arrives at addr; without one, list breakpoints and watchpoints
+  delete <addr>         Remove the breakpoint and watchpoints at addr
+  watch <addr> [read|write]  Stop when the program reads or writes addr (default write)
+  trace on|off          Print every executed instruction while stepping
+  regs                  Show the registers
+  list                  Disassemble the instructions from the PC
+  reset                 Reload the program and start again from address 0
+  help                  Show this help
+  quit                  Leave the debugger
+Numbers are decimal or 0x hex; addresses may also be world names such as food_x.
+An empty line repeats the last command.";
+
+/// A debugger command, parsed from one line of input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Step(usize),
+    Back(usize),
+    Peek {
+        addr: usize,
+        len: usize,
+    },
+    Poke {
+        addr: usize,
+        value: u8,
+    },
+    Break(usize),
+    Delete(usize),
+    Watch {
+        addr: usize,
+        kind: WatchKind,
+    },
+    /// List breakpoints and watchpoints
+    Points,
+    Trace(bool),
+    Registers,
+    List,
+    Reset,
+    Help,
+    Quit,
+}
+
+/// Why a line is not a command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DebugError {
+    UnknownCommand(String),
+    MissingArgument(&'static str),
+    UnexpectedArgument(String),
+    BadNumber(String),
+    OutOfRange(usize),
+}
+
+impl fmt::Display for DebugError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DebugError::UnknownCommand(name) => {
+                write!(f, "unknown command `{}`; try `help`", name)
+            }
+            DebugError::MissingArgument(what) => write!(f, "missing {}", what),
+            DebugError::UnexpectedArgument(text) => write!(f, "unexpected `{}`", text),
+            DebugError::BadNumber(text) => write!(f, "`{}` is not a number", text),
+            DebugError::OutOfRange(value) => write!(f, "{} is out of range", value),
+        }
+    }
+}
+
+impl std::error::Error for DebugError {}
+
+impl Command {
+    /// Parse one line; `Ok(None)` for a blank line
+    pub fn parse(line: &str) -> Result<Option<Self>, DebugError> {
+        let mut words = line.split_whitespace();
+        let Some(name) = words.next() else {
+            return Ok(None);
+        };
+        let args: Vec<&str> = words.collect();
+        let arg = |index: usize| args.get(index).copied();
+        let count = |index: usize, default: usize| arg(index).map_or(Ok(default), parse_number);
+        let address = |index: usize, what| {
+            arg(index)
+                .ok_or(DebugError::MissingArgument(what))
+                .and_then(parse_address)
+        };
+
+        let (command, used) = match name.to_ascii_lowercase().as_str() {
+            "step" | "s" => (Command::Step(count(0, 1)?), 1),
+            "run" | "r" => (Command::Step(count(0, DEFAULT_RUN_STEPS)?), 1),
+            "back" => (Command::Back(count(0, 1)?), 1),
+            "peek" | "x" => {
+                let addr = address(0, "address")?;
+                let len = count(1, DEFAULT_PEEK_LEN)?;
+                (Command::Peek { addr, len }, 2)
+            }
+            "poke" => {
+                let addr = address(0, "address")?;
+                let value = arg(1).ok_or(DebugError::MissingArgument("value"))?;
+                let value = parse_number(value)?;
+                let value = u8::try_from(value).map_err(|_| DebugError::OutOfRange(value))?;
+                (Command::Poke { addr, value }, 2)
+            }
+            "break" | "b" if args.is_empty() => (Command::Points, 0),
+            "break" | "b" => (Command::Break(address(0, "address")?), 1),
+            "delete" | "d" => (Command::Delete(address(0, "address")?), 1),
+            "watch" | "w" => {
+                let addr = address(0, "address")?;
+                let kind = match arg(1) {
+                    None | Some("write") => WatchKind::OnWrite,
+                    Some("read") => WatchKind::OnRead,
+                    Some(other) => return Err(DebugError::UnexpectedArgument(other.to_string())),
+                };
+                (Command::Watch { addr, kind }, 2)
+            }
+            "trace" | "t" => match arg(0) {
+                Some("on") => (Command::Trace(true), 1),
+                Some("off") => (Command::Trace(false), 1),
+                Some(other) => return Err(DebugError::UnexpectedArgument(other.to_string())),
+                None => return Err(DebugError::MissingArgument("`on` or `off`")),
+            },
+            "regs" | "registers" => (Command::Registers, 0),
+            "list" | "l" => (Command::List, 0),
+            "reset" => (Command::Reset, 0),
+            "help" | "h" | "?" => (Command::Help, 0),
+            "quit" | "q" | "exit" => (Command::Quit, 0),
+            _ => return Err(DebugError::UnknownCommand(name.to_string())),
+        };
+        match args.get(used) {
+            Some(extra) => Err(DebugError::UnexpectedArgument(extra.to_string())),
+            None => Ok(Some(command)),
+        }
+    }
+}
+
+/// A VM under the debugger, and the debugger's own settings
+pub struct Debugger {
+    pub vm: VM,
+    program: Program,
+    trace: bool,
+    last: Option<Command>,
+}
+
+impl Debugger {
+    /// Load `program` into a fresh VM, stopped before its first instruction
+    pub fn new(program: Program) -> Self {
+        let config = VmConfig::default()
+            .with_isa(program.isa)
+            .with_undo_depth(DEBUGGER_UNDO_DEPTH);
+        let mut vm = VM::with_config(config);
+        vm.load_program(&program.genome);
+        Self {
+            vm,
+            program,
+            trace: false,
+            last: None,
+        }
+    }
+
+    /// Parse and run one line of input, an empty line repeating the last command.
+    ///
+    /// Returns the text to show, or `None` once the line asked to quit.
+    pub fn execute_line(&mut self, line: &str) -> Result<Option<String>, DebugError> {
+        let command = match Command::parse(line)? {
+            Some(command) => command,
+            None => match self.last {
+                Some(command) => command,
+                None => return Ok(Some(String::new())),
+            },
+        };
+        self.last = Some(command);
+        Ok((command != Command::Quit).then(|| self.execute(command)))
+    }
+
+    /// Run `command` and return the text to show
+    pub fn execute(&mut self, command: Command) -> String {
+        let mut out = String::new();
+        match command {
+            Command::Step(count) => {
+                let mut stop = None;
+                for _ in 0..count {
+                    let result = self.vm.run_for(1);
+                    if self.trace
+                        && result != StepResult::Halted
+                        && let Some(event) = self.vm.recent.last()
+                    {
+                        let _ = writeln!(out, "  {}", event);
+                    }
+                    if result != StepResult::BudgetExhausted {
+                        stop = Some(result);
+                        break;
+                    }
+                }
+                match stop {
+                    Some(StepResult::Halted) => out.push_str("halted\n"),
+                    Some(StepResult::Trapped) => {
+                        out.push_str("trapped: store to protected memory\n")
+                    }
+                    Some(StepResult::Hit(DebugHit::Breakpoint { addr })) => {
+                        let _ = writeln!(out, "breakpoint at {}", addr);
+                    }
+                    Some(StepResult::Hit(DebugHit::Watchpoint { addr, kind })) => {
+                        let _ = writeln!(out, "{} {}", watch_name(kind), addr);
+                    }
+                    _ => {}
+                }
+                out.push_str(&self.status());
+            }
+            Command::Back(count) => {
+                let undone = (0..count).take_while(|_| self.vm.step_back()).count();
+                if undone < count {
+                    let _ = writeln!(out, "undid {} of {} steps; no older history", undone, count);
+                }
+                out.push_str(&self.status());
+            }
+            Command::Peek { addr, len } => {
+                let end = (addr + len).min(MEM_SIZE);
+                for start in (addr..end).step_by(8) {
+                    let bytes: Vec<_> = self.vm.data()[start..(start + 8).min(end)]
+                        .iter()
+                        .map(|b| format!("{:02X}", b))
+                        .collect();
+                    let _ = writeln!(out, "{:03}  {}", start, bytes.join(" "));
+                }
+            }
+            Command::Poke { addr, value } => {
+                self.vm.data_mut()[addr] = value;
+                let _ = writeln!(out, "{:03} = {} (0x{:02X})", addr, value, value);
+            }
+            Command::Break(addr) => {
+                self.vm.add_breakpoint(addr);
+                let _ = writeln!(out, "set breakpoint at {}", addr);
+            }
+            Command::Delete(addr) => {
+                let mut removed = self.vm.remove_breakpoint(addr);
+                for kind in [WatchKind::OnRead, WatchKind::OnWrite] {
+                    removed |= self.vm.remove_watchpoint(addr, kind);
+                }
+                if !removed {
+                    let _ = writeln!(out, "nothing set at {}", addr);
+                }
+            }
+            Command::Watch { addr, kind } => {
+                self.vm.add_watchpoint(addr, kind);
+                let _ = writeln!(out, "watching {} at {}", watch_name(kind), addr);
+            }
+            Command::Points => {
+                for breakpoint in self.vm.breakpoints() {
+                    let _ = writeln!(
+                        out,
+                        "break {:03}  {} hits",
+                        breakpoint.addr, breakpoint.hits
+                    );
+                }
+                for watchpoint in self.vm.watchpoints() {
+                    let _ = writeln!(
+                        out,
+                        "watch {:03}  {}, {} hits",
+                        watchpoint.addr,
+                        watch_name(watchpoint.kind),
+                        watchpoint.hits
+                    );
+                }
+            }
+            Command::Trace(on) => self.trace = on,
+            Command::Registers => out.push_str(&self.status()),
+            Command::List => {
+                let mut addr = self.vm.pc;
+                for _ in 0..LIST_INSTRUCTIONS {
+                    if addr >= MEM_SIZE {
+                        break;
+                    }
+                    let entry = disasm::decode_at(&self.vm.memory, addr);
+                    let marker = if addr == self.vm.pc { "=>" } else { "  " };
+                    let breakpoint = self.vm.breakpoints().iter().any(|b| b.addr == addr);
+                    let _ = writeln!(
+                        out,
+                        "{}{} {}",
+                        marker,
+                        if breakpoint { "*" } else { " " },
+                        entry
+                    );
+                    addr += entry.size();
+                }
+            }
+            Command::Reset => {
+                self.vm.load_program(&self.program.genome);
+                self.vm.clear_hit_counts();
+                out.push_str(&self.status());
+            }
+            Command::Help => {
+                out.push_str(HELP);
+                out.push('\n');
+            }
+            Command::Quit => {}
+        }
+        out
+    }
+
+    /// The registers and the next instruction, on one line
+    pub fn status(&self) -> String {
+        let vm = &self.vm;
+        let flags: String = [
+            (FLAG_ZERO, 'Z'),
+            (FLAG_CARRY, 'C'),
+            (FLAG_NEGATIVE, 'N'),
+            (FLAG_TRAP, 'T'),
+        ]
+        .iter()
+        .map(|&(flag, name)| if vm.flags & flag != 0 { name } else { '-' })
+        .collect();
+        let next = if vm.halted {
+            "halted".to_string()
+        } else {
+            disasm::decode_at(&vm.memory, vm.pc).to_string()
+        };
+        format!(
+            "pc={:03} acc={:3} x={:3} y={:3} sp={:03} dsp={:03} flags={} steps={}  {}\n",
+            vm.pc, vm.acc, vm.x, vm.y, vm.sp, vm.dsp, flags, vm.total_steps_count, next
+        )
+    }
+}
+
+fn watch_name(kind: WatchKind) -> &'static str {
+    match kind {
+        WatchKind::OnRead => "read",
+        WatchKind::OnWrite => "write",
+    }
+}
+
+fn parse_number(text: &str) -> Result<usize, DebugError> {
+    let parsed = match text.strip_prefix("0x").or(text.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+    parsed.map_err(|_| DebugError::BadNumber(text.to_string()))
+}
+
+/// A memory address, as a number or one of the world's address names
+fn parse_address(text: &str) -> Result<usize, DebugError> {
+    let addr = match ADDRESS_NAMES.iter().find(|(name, _)| *name == text) {
+        Some(&(_, addr)) => addr,
+        None => parse_number(text)?,
+    };
+    if addr < MEM_SIZE {
+        Ok(addr)
+    } else {
+        Err(DebugError::OutOfRange(addr))
+    }
+}
@@ -0,0 +1,424 @@
+// disasm.rs
+
+// Disassembler turning raw VM memory back into readable instructions
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::analysis::{self, EdgeKind};
+use crate::asm;
+use crate::compute::{Instruction, MEM_SIZE, relative_target};
+use crate::isa::IsaVersion;
+
+// Listing constants
+pub const LISTING_BYTES_PER_LINE: usize = 8; // Most data bytes a listing line holds
+
+/// One decoded instruction (or stray data byte) in a program image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodedInstruction {
+    pub addr: usize,
+    pub opcode: u8,
+    /// `None` for bytes that are not a built-in opcode
+    pub instruction: Option<Instruction>,
+    pub operand: Option<u8>,
+}
+
+impl DecodedInstruction {
+    pub fn size(&self) -> usize {
+        self.instruction.map_or(1, Instruction::size)
+    }
+}
+
+impl std::fmt::Display for DecodedInstruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.instruction, self.operand) {
+            (Some(instruction), Some(operand)) if instruction.is_immediate() => {
+                write!(f, "{} #{}", instruction, operand)
+            }
+            (Some(instruction), Some(operand)) if instruction.is_relative() => {
+                let offset = operand as i8;
+                let target = relative_target(self.addr, offset, MEM_SIZE);
+                write!(f, "{} {:+} ({})", instruction, offset, target)
+            }
+            (Some(instruction), Some(operand)) => write!(f, "{} {}", instruction, operand),
+            (Some(instruction), None) => write!(f, "{}", instruction),
+            (None, _) => write!(f, ".byte 0x{:02X}", self.opcode),
+        }
+    }
+}
+
+/// Decode a program image linearly from address 0.
+///
+/// Operands that would run past the end of memory read as 0, matching the VM.
+pub fn decode(program: &[u8]) -> Vec<DecodedInstruction> {
+    let len = program.len().min(MEM_SIZE);
+    let mut decoded = Vec::new();
+    let mut addr = 0;
+    while addr < len {
+        let entry = decode_at(program, addr);
+        addr += entry.size();
+        decoded.push(entry);
+    }
+    decoded
+}
+
+/// Decode the single instruction starting at `addr`, as the VM would execute it there
+pub fn decode_at(program: &[u8], addr: usize) -> DecodedInstruction {
+    let opcode = program.get(addr).copied().unwrap_or(0);
+    let instruction = Instruction::decode(opcode);
+    let operand = match instruction {
+        Some(i) if i.size() > 1 => Some(program.get(addr + 1).copied().unwrap_or(0)),
+        _ => None,
+    };
+    DecodedInstruction {
+        addr,
+        opcode,
+        instruction,
+        operand,
+    }
+}
+
+/// Render a program image as assembler source, one instruction per line with
+/// its address in a comment.
+///
+/// `asm::assemble` with `IsaVersion::LATEST` turns the text back into exactly
+/// `program`: operands stay numeric, relative jumps keep their signed offsets,
+/// bytes that are not opcodes become `.byte`, and an instruction whose operand
+/// would lie past the end of `program` is written as its opcode byte alone.
+pub fn disassemble(program: &[u8]) -> String {
+    let len = program.len().min(MEM_SIZE);
+    decode(&program[..len])
+        .iter()
+        .map(|d| {
+            let statement = match (d.instruction, d.operand) {
+                (Some(_), _) if d.addr + d.size() > len => format!(".byte 0x{:02X}", d.opcode),
+                (Some(instruction), Some(operand)) if instruction.is_immediate() => {
+                    format!("{} #{}", instruction, operand)
+                }
+                (Some(instruction), Some(operand)) if instruction.is_relative() => {
+                    format!("{} {:+}", instruction, operand as i8)
+                }
+                _ => d.to_string(),
+            };
+            format!("        {:<16}; {:03}\n", statement, d.addr)
+        })
+        .collect()
+}
+
+/// One line of a `Listing`: an instruction, or a run of bytes no reachable code runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListingLine {
+    pub addr: usize,
+    /// `None` for a line of likely data
+    pub instruction: Option<DecodedInstruction>,
+    pub bytes: Vec<u8>,
+    /// `l_<addr>` for jump and call targets, `d_<addr>` for data the code reads or writes
+    pub label: Option<String>,
+    /// Addresses of the reachable instructions that jump, call, load or store here
+    pub referrers: Vec<usize>,
+    /// The instruction or `.byte` directive, with labels and address names for operands
+    pub text: String,
+    pub comment: Option<String>,
+}
+
+impl ListingLine {
+    pub fn is_data(&self) -> bool {
+        self.instruction.is_none()
+    }
+
+    /// Whether `addr` falls within the line's bytes
+    pub fn contains(&self, addr: usize) -> bool {
+        (self.addr..self.addr + self.bytes.len()).contains(&addr)
+    }
+}
+
+/// A whole memory image decoded for reading: code reachable from address 0 as
+/// instructions, everything else as data bytes, and labels on the addresses
+/// the code jumps to or accesses
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Listing {
+    pub lines: Vec<ListingLine>,
+}
+
+impl Listing {
+    pub fn new(program: &[u8], isa: IsaVersion) -> Self {
+        let len = program.len().min(MEM_SIZE);
+        let code: BTreeMap<usize, DecodedInstruction> = analysis::reachable(program, isa, &[0])
+            .into_iter()
+            .map(|entry| (entry.addr, entry))
+            .collect();
+
+        // Who refers to each address, and whether any of them jumps or calls there
+        let mut referrers: BTreeMap<usize, (Vec<usize>, bool)> = BTreeMap::new();
+        for entry in code.values() {
+            let jumps: Vec<usize> = analysis::exits(program, entry, isa)
+                .into_iter()
+                .filter(|&(_, kind)| matches!(kind, EdgeKind::Jump | EdgeKind::Call))
+                .map(|(target, _)| target)
+                .collect();
+            for &target in &jumps {
+                let refs = referrers.entry(target).or_default();
+                refs.0.push(entry.addr);
+                refs.1 = true;
+            }
+            if let (Some(instruction), Some(operand)) = (entry.instruction, entry.operand)
+                && instruction.has_address_operand()
+                && jumps.is_empty()
+                && asm::address_name(operand as usize).is_none()
+            {
+                referrers
+                    .entry(operand as usize)
+                    .or_default()
+                    .0
+                    .push(entry.addr);
+            }
+        }
+
+        // Lines break at every instruction and every referenced address
+        let mut lines = Vec::new();
+        let mut addr = 0;
+        while addr < len {
+            let (refs, jumped) = referrers.remove(&addr).unwrap_or_default();
+            let label = match (refs.is_empty(), jumped) {
+                (true, _) => None,
+                (false, true) => Some(format!("l_{:03}", addr)),
+                (false, false) => Some(format!("d_{:03}", addr)),
+            };
+            let (instruction, size) = match code.get(&addr) {
+                Some(entry) => (Some(*entry), entry.size()),
+                None => {
+                    let end = (addr + 1..len)
+                        .find(|a| {
+                            code.contains_key(a)
+                                || referrers.contains_key(a)
+                                || a - addr == LISTING_BYTES_PER_LINE
+                        })
+                        .unwrap_or(len);
+                    (None, end - addr)
+                }
+            };
+            let end = (addr + size).min(len);
+            lines.push(ListingLine {
+                addr,
+                instruction,
+                bytes: program[addr..end].to_vec(),
+                label,
+                referrers: refs,
+                text: String::new(),
+                comment: None,
+            });
+            addr = end;
+        }
+
+        let mut listing = Listing { lines };
+        listing.annotate(&code);
+        listing
+    }
+
+    /// The line holding `addr`
+    pub fn line_at(&self, addr: usize) -> Option<&ListingLine> {
+        self.lines.iter().find(|line| line.contains(addr))
+    }
+
+    /// Fill in each line's text and comment once every label is known
+    fn annotate(&mut self, code: &BTreeMap<usize, DecodedInstruction>) {
+        let labels: BTreeMap<usize, String> = self
+            .lines
+            .iter()
+            .filter_map(|line| line.label.clone().map(|label| (line.addr, label)))
+            .collect();
+        let name = |addr: usize| {
+            labels
+                .get(&addr)
+                .cloned()
+                .or_else(|| asm::address_name(addr).map(str::to_string))
+        };
+
+        for index in 0..self.lines.len() {
+            let line = &self.lines[index];
+            let mut notes = Vec::new();
+            let text = match line.instruction {
+                None => {
+                    if index == 0 || !self.lines[index - 1].is_data() {
+                        let run = self.lines[index..]
+                            .iter()
+                            .take_while(|l| l.is_data())
+                            .map(|l| l.bytes.len())
+                            .sum::<usize>();
+                        notes.push(format!("data, {} bytes", run));
+                    }
+                    let bytes: Vec<_> = line.bytes.iter().map(|b| format!("0x{:02X}", b)).collect();
+                    format!(".byte {}", bytes.join(", "))
+                }
+                Some(entry) => match (entry.instruction, entry.operand) {
+                    (Some(instruction), Some(operand)) if instruction.is_relative() => {
+                        let target = relative_target(entry.addr, operand as i8, MEM_SIZE);
+                        notes.push(format!("{:+}", operand as i8));
+                        match name(target) {
+                            Some(label) => format!("{} {}", instruction, label),
+                            None => format!("{} {:+}", instruction, operand as i8),
+                        }
+                    }
+                    (Some(instruction), Some(operand)) if instruction.has_address_operand() => {
+                        match name(operand as usize) {
+                            Some(label) => {
+                                notes.push(operand.to_string());
+                                format!("{} {}", instruction, label)
+                            }
+                            None => entry.to_string(),
+                        }
+                    }
+                    (None, _) => {
+                        notes.push("not an instruction; halts".to_string());
+                        entry.to_string()
+                    }
+                    _ => entry.to_string(),
+                },
+            };
+            if let Some(entry) = line.instruction {
+                let end = entry.addr + entry.size();
+                for (&inside, _) in code.range(entry.addr + 1..end) {
+                    notes.push(format!("overlaps code at {}", inside));
+                }
+            }
+            if !line.referrers.is_empty() {
+                let from: Vec<_> = line.referrers.iter().map(usize::to_string).collect();
+                notes.push(format!("from {}", from.join(", ")));
+            }
+            let line = &mut self.lines[index];
+            line.text = text;
+            line.comment = (!notes.is_empty()).then(|| notes.join("; "));
+        }
+    }
+}
+
+impl fmt::Display for Listing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in &self.lines {
+            if let Some(label) = &line.label {
+                writeln!(f, "{}:", label)?;
+            }
+            let bytes: Vec<_> = match line.instruction {
+                Some(_) => line.bytes.iter().map(|b| format!("{:02X}", b)).collect(),
+                None => Vec::new(),
+            };
+            let code = format!("{:03}  {:<6} {}", line.addr, bytes.join(" "), line.text);
+            match &line.comment {
+                Some(comment) => writeln!(f, "{:<32} ; {}", code, comment)?,
+                None => writeln!(f, "{}", code.trim_end())?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Render a whole memory image as an annotated listing for the latest ISA: code
+/// reachable from address 0, likely data as `.byte` lines, labelled jump targets
+/// and named world addresses
+pub fn listing(program: &[u8]) -> String {
+    Listing::new(program, IsaVersion::LATEST).to_string()
+}
+
+/// An instruction that differs between two program images
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstructionChange {
+    pub addr: usize,
+    pub before: Option<DecodedInstruction>,
+    pub after: Option<DecodedInstruction>,
+}
+
+/// Compare the disassemblies of two images instruction by instruction.
+///
+/// Instructions are matched by start address, so a change that shifts the
+/// decode alignment shows up as removed and added instructions.
+pub fn diff(before: &[u8], after: &[u8]) -> Vec<InstructionChange> {
+    let old = decode(before);
+    let new = decode(after);
+    let mut changes = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        let a = old.get(i).copied();
+        let b = new.get(j).copied();
+        let addr_a = a.map_or(usize::MAX, |d| d.addr);
+        let addr_b = b.map_or(usize::MAX, |d| d.addr);
+        if addr_a == addr_b {
+            if a.map(|d| (d.opcode, d.operand)) != b.map(|d| (d.opcode, d.operand)) {
+                changes.push(InstructionChange {
+                    addr: addr_a,
+                    before: a,
+                    after: b,
+                });
+            }
+            i += 1;
+            j += 1;
+        } else if addr_a < addr_b {
+            changes.push(InstructionChange {
+                addr: addr_a,
+                before: a,
+                after: None,
+            });
+            i += 1;
+        } else {
+            changes.push(InstructionChange {
+                addr: addr_b,
+                before: None,
+                after: b,
+            });
+            j += 1;
+        }
+    }
+    changes
+}
+
+/// Number of bytes that differ between two program images
+pub fn byte_distance(a: &[u8], b: &[u8]) -> usize {
+    let len = a.len().max(b.len());
+    (0..len).filter(|&i| a.get(i) != b.get(i)).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::RngCore;
+
+    use super::*;
+    use crate::rng::SimRng;
+
+    fn reassemble(program: &[u8]) -> Vec<u8> {
+        let source = disassemble(program);
+        asm::assemble(&source, IsaVersion::LATEST)
+            .unwrap_or_else(|e| panic!("{e}\n{source}"))
+            .code()
+            .to_vec()
+    }
+
+    #[test]
+    fn every_opcode_with_every_operand_round_trips() {
+        for opcode in 0..=u8::MAX {
+            for operand in 0..=u8::MAX {
+                let program = [opcode, operand];
+                assert_eq!(reassemble(&program), program);
+            }
+        }
+    }
+
+    #[test]
+    fn operands_past_the_end_round_trip() {
+        for opcode in 0..=u8::MAX {
+            assert_eq!(reassemble(&[opcode]), [opcode]);
+
+            let mut image = [0u8; MEM_SIZE];
+            image[MEM_SIZE - 1] = opcode;
+            assert_eq!(reassemble(&image), image);
+        }
+    }
+
+    #[test]
+    fn random_images_round_trip() {
+        let mut rng = SimRng::new(0x5EED);
+        for _ in 0..256 {
+            let mut image = [0u8; MEM_SIZE];
+            rng.fill_bytes(&mut image);
+            assert_eq!(reassemble(&image), image);
+        }
+    }
+}
@@ -0,0 +1,54 @@
+// display.rs
+
+// Memory-mapped 8x8 monochrome display that programs draw on by storing bytes
+
+use std::ops::Range;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+// Display constants
+pub const DISPLAY_SIZE: usize = 8; // Pixels per side, one byte per row
+pub const DEFAULT_DISPLAY_ADDR: usize = 212; // 212-219: just below the skin bytes
+
+/// Where in a VM's data the display rows live.
+///
+/// Row `y` is the byte at `base + y`; bit 7 is the leftmost pixel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Display {
+    pub base: usize,
+}
+
+impl Default for Display {
+    fn default() -> Self {
+        Self {
+            base: DEFAULT_DISPLAY_ADDR,
+        }
+    }
+}
+
+impl Display {
+    pub fn new(base: usize) -> Self {
+        Self { base }
+    }
+
+    /// Addresses holding the display rows
+    pub fn range(&self) -> Range<usize> {
+        self.base..self.base + DISPLAY_SIZE
+    }
+
+    /// The display rows as stored in `data`; rows past the end of memory read as 0
+    pub fn rows(&self, data: &[u8]) -> [u8; DISPLAY_SIZE] {
+        std::array::from_fn(|y| data.get(self.base + y).copied().unwrap_or(0))
+    }
+
+    pub fn pixel(&self, data: &[u8], x: usize, y: usize) -> bool {
+        x < DISPLAY_SIZE && y < DISPLAY_SIZE && self.rows(data)[y] & (0x80 >> x) != 0
+    }
+
+    /// True when no pixel is lit, so there is nothing worth drawing
+    pub fn is_blank(&self, data: &[u8]) -> bool {
+        self.rows(data).iter().all(|&row| row == 0)
+    }
+}
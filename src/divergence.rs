@@ -0,0 +1,247 @@
+// divergence.rs
+
+// Two genomes run side by side on the same sensor stream, to find where their execution parts ways
+
+use std::fmt;
+
+use rand::Rng;
+
+use crate::compute::{RestartMode, VM, VmDiff};
+use crate::disasm::{self, DecodedInstruction};
+use crate::isa::Program;
+use crate::rng::SimRng;
+use crate::world::Senses;
+
+// Divergence constants
+pub const CONTEXT_STEPS: usize = 4; // Matching steps shown before the first divergence
+pub const SHOWN_STEPS: usize = 12; // Steps shown from the first divergence on
+pub const MAX_LISTED_STRETCHES: usize = 10; // Divergent stretches listed in the summary
+
+/// Food sensor readings (X, Y) both programs see, one pair per tick
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SensorStream {
+    pub frames: Vec<(u8, u8)>,
+}
+
+impl SensorStream {
+    /// Food drifting around the creature and now and then out of range, drawn from `seed`
+    pub fn wandering(seed: u64, ticks: usize) -> Self {
+        let mut rng = SimRng::new(seed);
+        let (mut x, mut y) = (rng.random_range(-60.0..60.0), rng.random_range(-60.0..60.0));
+        let frames = (0..ticks)
+            .map(|_| {
+                x = f32::clamp(x + rng.random_range(-4.0..4.0), -127.0, 127.0);
+                y = f32::clamp(y + rng.random_range(-4.0..4.0), -127.0, 127.0);
+                // Out of range reads neutral, like the world's sensors
+                if rng.random_bool(0.1) {
+                    (128, 128)
+                } else {
+                    ((x + 128.0) as u8, (y + 128.0) as u8)
+                }
+            })
+            .collect();
+        Self { frames }
+    }
+}
+
+/// One instruction a program executed, with the accumulator it left behind
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExecutedStep {
+    pub tick: usize,
+    pub instruction: DecodedInstruction,
+    pub acc: u8,
+}
+
+/// Step-by-step executions of two programs on the same input; a `None` step means
+/// the program had halted and sat out the rest of its tick
+#[derive(Debug, Clone)]
+pub struct Divergence {
+    pub a: Vec<Option<ExecutedStep>>,
+    pub b: Vec<Option<ExecutedStep>>,
+    /// How B's VM ended up different from A's after the last tick
+    pub final_state: VmDiff,
+}
+
+impl Divergence {
+    /// Run `program_a` and `program_b` through `stream` like the world would, each under
+    /// its own instruction set, with `steps_per_tick` steps per frame and the same RND
+    /// seed for both
+    pub fn compare(
+        program_a: &Program,
+        program_b: &Program,
+        stream: &SensorStream,
+        steps_per_tick: usize,
+        rng_seed: u64,
+    ) -> Self {
+        let (a, vm_a) = execute(program_a, stream, steps_per_tick, rng_seed);
+        let (b, vm_b) = execute(program_b, stream, steps_per_tick, rng_seed);
+        Self {
+            a,
+            b,
+            final_state: vm_a.diff(&vm_b),
+        }
+    }
+
+    /// Whether step `index` differs between the two runs
+    pub fn differs_at(&self, index: usize) -> bool {
+        let instruction =
+            |run: &[Option<ExecutedStep>]| run.get(index).copied().flatten().map(|s| s.instruction);
+        instruction(&self.a) != instruction(&self.b)
+    }
+
+    pub fn first_divergence(&self) -> Option<usize> {
+        (0..self.a.len()).find(|&i| self.differs_at(i))
+    }
+
+    pub fn matching_steps(&self) -> usize {
+        (0..self.a.len()).filter(|&i| !self.differs_at(i)).count()
+    }
+
+    /// Runs of consecutive differing steps, as (first step, length)
+    pub fn stretches(&self) -> Vec<(usize, usize)> {
+        let mut stretches: Vec<(usize, usize)> = Vec::new();
+        for i in (0..self.a.len()).filter(|&i| self.differs_at(i)) {
+            match stretches.last_mut() {
+                Some((start, len)) if *start + *len == i => *len += 1,
+                _ => stretches.push((i, 1)),
+            }
+        }
+        stretches
+    }
+
+    /// Registers in full, memory as a count of differing cells
+    fn write_final_state(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let diff = &self.final_state;
+        if diff.is_empty() {
+            return write!(f, "final state: identical");
+        }
+        write!(f, "final state: ")?;
+        for (i, r) in diff.registers.iter().enumerate() {
+            let separator = if i == 0 { "" } else { ", " };
+            write!(
+                f,
+                "{}{} {} -> {}",
+                separator,
+                r.register.name(),
+                r.old,
+                r.new
+            )?;
+        }
+        if !diff.registers.is_empty() {
+            write!(f, "; ")?;
+        }
+        write!(f, "{} memory cells differ", diff.memory.len())
+    }
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.a.len();
+        writeln!(
+            f,
+            "{} of {} steps execute the same instruction",
+            self.matching_steps(),
+            total
+        )?;
+        let Some(first) = self.first_divergence() else {
+            writeln!(f, "the executions never diverge")?;
+            return self.write_final_state(f);
+        };
+
+        let show = |step: Option<ExecutedStep>| {
+            step.map_or_else(
+                || "(halted)".to_string(),
+                |s| {
+                    format!(
+                        "{:04}: {:<14} acc={:3}",
+                        s.instruction.addr,
+                        s.instruction.to_string(),
+                        s.acc
+                    )
+                },
+            )
+        };
+        let tick = |i: usize| {
+            self.a[i]
+                .or(self.b[i])
+                .map_or_else(|| "-".to_string(), |s| s.tick.to_string())
+        };
+        writeln!(
+            f,
+            "first divergence at step {} (tick {}):",
+            first,
+            tick(first)
+        )?;
+        let from = first.saturating_sub(CONTEXT_STEPS);
+        for i in from..(first + SHOWN_STEPS).min(total) {
+            let marker = if self.differs_at(i) { '*' } else { ' ' };
+            writeln!(
+                f,
+                "{} {:>6} | {:<30} | {}",
+                marker,
+                i,
+                show(self.a[i]),
+                show(self.b[i])
+            )?;
+        }
+
+        let stretches = self.stretches();
+        write!(f, "{} divergent stretches:", stretches.len())?;
+        for (start, len) in stretches.iter().take(MAX_LISTED_STRETCHES) {
+            write!(
+                f,
+                "\n  steps {}..{} (tick {})",
+                start,
+                start + len,
+                tick(*start)
+            )?;
+        }
+        if stretches.len() > MAX_LISTED_STRETCHES {
+            write!(f, "\n  ...")?;
+        }
+        writeln!(f)?;
+        self.write_final_state(f)
+    }
+}
+
+/// Execute `program` on `stream`, writing sensors and restarting after halts as the world does
+fn execute(
+    program: &Program,
+    stream: &SensorStream,
+    steps_per_tick: usize,
+    rng_seed: u64,
+) -> (Vec<Option<ExecutedStep>>, VM) {
+    let mut vm: VM = VM::new();
+    vm.isa = program.isa;
+    vm.load_program(&program.genome);
+    vm.seed_rng(rng_seed);
+    let mut steps = Vec::with_capacity(stream.frames.len() * steps_per_tick);
+    let mut last_tick_instructions = 0;
+    for (tick, &(food_x, food_y)) in stream.frames.iter().enumerate() {
+        if vm.halted {
+            vm.restart(RestartMode::default());
+        }
+        let senses = Senses::attach(&mut vm).expect("a fresh VM has no other mappings");
+        senses.sense(last_tick_instructions, food_x, food_y);
+        senses.start_tick(steps_per_tick);
+
+        last_tick_instructions = 0;
+        for _ in 0..steps_per_tick {
+            if vm.halted || vm.pc >= vm.memory.len() {
+                // Running off the end halts without executing anything
+                vm.step();
+                steps.push(None);
+                continue;
+            }
+            let instruction = disasm::decode_at(&vm.memory, vm.pc);
+            vm.step();
+            last_tick_instructions = last_tick_instructions.saturating_add(1);
+            steps.push(Some(ExecutedStep {
+                tick,
+                instruction,
+                acc: vm.acc,
+            }));
+        }
+    }
+    (steps, vm)
+}
@@ -0,0 +1,308 @@
+// evaluator.rs
+
+// Headless genome scoring for external optimizers, served over stdin or HTTP by `bacteria serve-eval`
+
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+use rand::Rng;
+
+use crate::compute::{MEM_SIZE, VM, VmMetrics};
+use crate::fitness::{Evaluation, FitnessPipeline};
+use crate::notify::json_escape;
+use crate::trace::OpcodeProfile;
+use crate::world::{Lifeform, MAP_BOUNDARY, Tag, World, WorldConfig};
+
+// Evaluation service constants
+pub const DEFAULT_EVAL_STEPS: usize = 10_000; // VM steps a genome may run before it counts as still going
+pub const DEFAULT_EVAL_TICKS: u64 = 2_000; // World ticks a genome's copies are followed for
+pub const DEFAULT_EVAL_COPIES: usize = 5; // Copies of the genome dropped into the world
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30); // Per read and write of an HTTP request
+const MAX_REQUEST_BYTES: usize = 16 << 20; // Larger HTTP bodies are refused
+const EVAL_TAG: &str = "serve-eval"; // Marks the copies under evaluation; offspring start untagged
+
+/// What genomes are scored against
+#[derive(Debug, Clone)]
+pub enum EvalTarget {
+    /// A bare VM, scored by the steps it runs before halting, like the VM grid
+    Vm { steps: usize },
+    /// `copies` lifeforms dropped into a fresh world built from `config`, scored by
+    /// their mean survival in simulated seconds over at most `ticks` ticks
+    World {
+        config: Box<WorldConfig>,
+        ticks: u64,
+        copies: usize,
+    },
+}
+
+impl EvalTarget {
+    /// A world target that builds the same world for every genome from `seed`
+    pub fn world(config: WorldConfig, seed: u64, ticks: u64, copies: usize) -> Self {
+        let config = WorldConfig {
+            seed: Some(seed),
+            // Both depend on wall-clock time or the camera, which would make scores noisy
+            tick_budget_ms: None,
+            coarsening: None,
+            ..config
+        };
+        EvalTarget::World {
+            config: Box::new(config),
+            ticks,
+            copies,
+        }
+    }
+}
+
+/// The result for one genome
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EvalScore {
+    /// `raw` after the fitness pipeline
+    pub score: f64,
+    /// Steps run, or mean seconds survived in a world
+    pub raw: f64,
+    /// Whether the program halted, or every copy died, within the budget
+    pub finished: bool,
+    /// What the VM did, when scored by steps
+    pub metrics: Option<VmMetrics>,
+}
+
+impl EvalScore {
+    /// The score as a one-line JSON object, as sent back to clients
+    pub fn to_json(&self) -> String {
+        let metrics = match &self.metrics {
+            Some(m) => format!(
+                ",\"metrics\":{{\"steps\":{},\"cycles\":{},\"jumps_taken\":{},\"memory_writes\":{},\"halts\":{},\"traps\":{},\"energy_spent\":{}}}",
+                m.steps, m.cycles, m.jumps_taken, m.memory_writes, m.halts, m.traps, m.energy_spent
+            ),
+            None => String::new(),
+        };
+        format!(
+            "{{\"score\":{},\"raw\":{},\"finished\":{}{}}}",
+            self.score, self.raw, self.finished, metrics
+        )
+    }
+}
+
+/// A genome line that could not be read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GenomeError {
+    /// A character other than a hex digit or whitespace
+    InvalidDigit(char),
+    /// An odd number of hex digits
+    OddLength,
+    /// More bytes than a VM has memory
+    TooLong(usize),
+}
+
+impl fmt::Display for GenomeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GenomeError::InvalidDigit(c) => write!(f, "invalid hex digit {:?}", c),
+            GenomeError::OddLength => write!(f, "odd number of hex digits"),
+            GenomeError::TooLong(len) => {
+                write!(f, "genome is {} bytes, more than {}", len, MEM_SIZE)
+            }
+        }
+    }
+}
+
+impl std::error::Error for GenomeError {}
+
+/// A genome written as hex digits, optionally separated by whitespace; short genomes
+/// are padded with zeros like `VM::load_program`
+pub fn parse_genome(line: &str) -> Result<Vec<u8>, GenomeError> {
+    let digits = line
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| c.to_digit(16).ok_or(GenomeError::InvalidDigit(c)))
+        .collect::<Result<Vec<u32>, _>>()?;
+    if digits.len() % 2 != 0 {
+        return Err(GenomeError::OddLength);
+    }
+    if digits.len() / 2 > MEM_SIZE {
+        return Err(GenomeError::TooLong(digits.len() / 2));
+    }
+    Ok(digits
+        .chunks(2)
+        .map(|pair| (pair[0] << 4 | pair[1]) as u8)
+        .collect())
+}
+
+/// Scores genomes one at a time against a target, shaping them with a fitness pipeline.
+///
+/// The pipeline keeps its novelty archive across calls, so novelty scores depend on
+/// what was evaluated before.
+pub struct Evaluator {
+    pub target: EvalTarget,
+    pub fitness: FitnessPipeline,
+}
+
+impl Evaluator {
+    pub fn new(target: EvalTarget, fitness: FitnessPipeline) -> Self {
+        Self { target, fitness }
+    }
+
+    pub fn evaluate(&mut self, genome: &[u8]) -> EvalScore {
+        let mut vm: VM = VM::new();
+        vm.load_program(genome);
+        let (raw, finished, profile, metrics) = match &self.target {
+            EvalTarget::Vm { steps } => {
+                if self.fitness.config.needs_profile() {
+                    vm.profile = Some(OpcodeProfile::default());
+                }
+                vm.run_for(*steps);
+                let raw = vm.total_steps_count as f64;
+                (raw, vm.halted, vm.profile.take(), Some(vm.metrics()))
+            }
+            EvalTarget::World {
+                config,
+                ticks,
+                copies,
+            } => {
+                let (raw, finished) = survival(&vm, config, *ticks, *copies);
+                (raw, finished, None, None)
+            }
+        };
+        let score = self.fitness.shape(&Evaluation {
+            genome: &vm.initial_state,
+            raw,
+            generation: 0,
+            profile: profile.as_ref(),
+        });
+        EvalScore {
+            score,
+            raw,
+            finished,
+            metrics,
+        }
+    }
+
+    /// Score one hex genome line, as a JSON object with either the score or an `error`
+    pub fn evaluate_line(&mut self, line: &str) -> String {
+        match parse_genome(line) {
+            Ok(genome) => self.evaluate(&genome).to_json(),
+            Err(e) => format!("{{\"error\":\"{}\"}}", json_escape(&e.to_string())),
+        }
+    }
+
+    /// Score every non-empty line of `input`, writing one JSON line per genome
+    /// as soon as it is done
+    pub fn serve_lines(&mut self, input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+        for line in input.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            writeln!(output, "{}", self.evaluate_line(&line))?;
+            output.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Answer `POST /eval` requests, whose body holds one hex genome per line, with
+    /// one JSON line per genome. Requests are handled one at a time, forever.
+    pub fn serve_http(&mut self, listener: TcpListener) -> io::Result<()> {
+        for stream in listener.incoming() {
+            if let Err(e) = stream.and_then(|stream| self.handle_request(stream)) {
+                tracing::warn!("serve-eval request failed: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_request(&mut self, mut stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+        stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        let mut content_length = 0;
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':')
+                && name.trim().eq_ignore_ascii_case("content-length")
+            {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+
+        let mut parts = request_line.split_whitespace();
+        let (status, body) = match (parts.next(), parts.next()) {
+            (Some("POST"), Some("/eval")) if content_length > MAX_REQUEST_BYTES => (
+                "413 Payload Too Large",
+                error_body(&format!("body is over {} bytes", MAX_REQUEST_BYTES)),
+            ),
+            (Some("POST"), Some("/eval")) => {
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body)?;
+                match String::from_utf8(body) {
+                    Ok(text) => ("200 OK", self.evaluate_text(&text)),
+                    Err(_) => ("400 Bad Request", error_body("body is not UTF-8")),
+                }
+            }
+            _ => ("404 Not Found", error_body("expected POST /eval")),
+        };
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: application/x-ndjson\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        )
+    }
+
+    /// `serve_lines` over a string
+    fn evaluate_text(&mut self, text: &str) -> String {
+        let mut output = Vec::new();
+        // Writing to a Vec cannot fail
+        let _ = self.serve_lines(text.as_bytes(), &mut output);
+        String::from_utf8_lossy(&output).into_owned()
+    }
+}
+
+fn error_body(message: &str) -> String {
+    format!("{{\"error\":\"{}\"}}\n", json_escape(message))
+}
+
+/// Mean seconds survived by `copies` lifeforms running `vm`'s program in a fresh world,
+/// and whether all of them died within `ticks`
+fn survival(vm: &VM, config: &WorldConfig, ticks: u64, copies: usize) -> (f64, bool) {
+    let mut world = World::new(config.clone());
+    let tag = Tag {
+        name: EVAL_TAG.to_string(),
+        ..Tag::default()
+    };
+    for _ in 0..copies {
+        let x = world.rng.random_range(-MAP_BOUNDARY..MAP_BOUNDARY);
+        let y = world.rng.random_range(-MAP_BOUNDARY..MAP_BOUNDARY);
+        let mut lifeform = Lifeform::from_vm_with_rng(vm.clone(), x, y, &mut world.rng);
+        lifeform.tag = Some(tag.clone());
+        world.lifeforms.push(lifeform);
+    }
+
+    let mut lived_ticks = 0;
+    for _ in 0..ticks {
+        world.tick();
+        world.update_ecology();
+        let alive = world
+            .lifeforms
+            .iter()
+            .filter(|l| l.tag.as_ref() == Some(&tag))
+            .count();
+        if alive == 0 {
+            return (mean_seconds(lived_ticks, copies, config), true);
+        }
+        lived_ticks += alive as u64;
+    }
+    (mean_seconds(lived_ticks, copies, config), false)
+}
+
+fn mean_seconds(lived_ticks: u64, copies: usize, config: &WorldConfig) -> f64 {
+    lived_ticks as f64 / copies.max(1) as f64 * config.tick_seconds
+}
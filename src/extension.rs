@@ -0,0 +1,205 @@
+// extension.rs
+
+// Host-defined opcodes layered on top of the built-in instruction set
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::compute::{Addr8, AddressWidth, Instruction, MEM_SIZE, VM};
+
+/// Handler invoked when the VM executes a custom opcode.
+///
+/// The handler sees the VM with `pc` still pointing at the opcode and is
+/// responsible for advancing it, exactly like the built-in instructions.
+/// Handlers are shared, so clones of a registry, and of a VM holding one, keep them.
+pub type OpcodeHandler<C, const N: usize = MEM_SIZE, A = Addr8> =
+    Arc<dyn Fn(&mut VM<N, A>, &mut C) + Send + Sync>;
+
+/// A single host-defined instruction
+pub struct CustomOpcode<C, const N: usize = MEM_SIZE, A: AddressWidth = Addr8> {
+    pub mnemonic: &'static str,
+    pub handler: OpcodeHandler<C, N, A>,
+}
+
+impl<C, const N: usize, A: AddressWidth> Clone for CustomOpcode<C, N, A> {
+    fn clone(&self) -> Self {
+        CustomOpcode {
+            mnemonic: self.mnemonic,
+            handler: Arc::clone(&self.handler),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegistryError {
+    /// The opcode is already used by a built-in instruction
+    Reserved(u8),
+    /// Another custom opcode was registered for the same byte
+    AlreadyRegistered(u8),
+}
+
+impl std::fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegistryError::Reserved(op) => {
+                write!(f, "opcode 0x{:02X} is a built-in instruction", op)
+            }
+            RegistryError::AlreadyRegistered(op) => {
+                write!(f, "opcode 0x{:02X} is already registered", op)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RegistryError {}
+
+/// Table of custom opcodes with access to a host context of type `C`, for VMs of `N` bytes.
+///
+/// Hosts pass one to `VM::step_with`; each VM also carries an `OpcodeRegistry<()>`
+/// of its own, filled by `VM::register_opcode`, that `step` falls back to.
+pub struct OpcodeRegistry<C, const N: usize = MEM_SIZE, A: AddressWidth = Addr8> {
+    opcodes: HashMap<u8, CustomOpcode<C, N, A>>,
+}
+
+impl<C, const N: usize, A: AddressWidth> Default for OpcodeRegistry<C, N, A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C, const N: usize, A: AddressWidth> Clone for OpcodeRegistry<C, N, A> {
+    fn clone(&self) -> Self {
+        OpcodeRegistry {
+            opcodes: self.opcodes.clone(),
+        }
+    }
+}
+
+impl<C, const N: usize, A: AddressWidth> std::fmt::Debug for OpcodeRegistry<C, N, A> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut opcodes: Vec<(u8, &str)> = self
+            .opcodes
+            .iter()
+            .map(|(&op, custom)| (op, custom.mnemonic))
+            .collect();
+        opcodes.sort_unstable();
+        f.debug_struct("OpcodeRegistry")
+            .field("opcodes", &opcodes)
+            .finish()
+    }
+}
+
+impl<C, const N: usize, A: AddressWidth> OpcodeRegistry<C, N, A> {
+    pub fn new() -> Self {
+        OpcodeRegistry {
+            opcodes: HashMap::new(),
+        }
+    }
+
+    /// Register a handler for an unused opcode byte
+    pub fn register<F>(
+        &mut self,
+        opcode: u8,
+        mnemonic: &'static str,
+        handler: F,
+    ) -> Result<(), RegistryError>
+    where
+        F: Fn(&mut VM<N, A>, &mut C) + Send + Sync + 'static,
+    {
+        if Instruction::decode(opcode).is_some() {
+            return Err(RegistryError::Reserved(opcode));
+        }
+        if self.opcodes.contains_key(&opcode) {
+            return Err(RegistryError::AlreadyRegistered(opcode));
+        }
+        self.opcodes.insert(
+            opcode,
+            CustomOpcode {
+                mnemonic,
+                handler: Arc::new(handler),
+            },
+        );
+        Ok(())
+    }
+
+    /// Remove a previously registered opcode, returning whether it existed
+    pub fn unregister(&mut self, opcode: u8) -> bool {
+        self.opcodes.remove(&opcode).is_some()
+    }
+
+    pub fn get(&self, opcode: u8) -> Option<&CustomOpcode<C, N, A>> {
+        self.opcodes.get(&opcode)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.opcodes.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.opcodes.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CUSTOM: u8 = 0x30;
+
+    fn set_acc(value: u8) -> impl Fn(&mut VM, &mut ()) + Send + Sync + 'static {
+        move |vm: &mut VM, _: &mut ()| {
+            vm.acc = value;
+            vm.pc += 1;
+        }
+    }
+
+    #[test]
+    fn built_in_opcodes_are_reserved() {
+        let mut vm: VM = VM::new();
+        let err = vm.register_opcode(Instruction::SKNE as u8, "X", |_| {});
+        assert_eq!(err, Err(RegistryError::Reserved(Instruction::SKNE as u8)));
+
+        vm.register_opcode(CUSTOM, "X", |_| {}).unwrap();
+        let err = vm.register_opcode(CUSTOM, "Y", |_| {});
+        assert_eq!(err, Err(RegistryError::AlreadyRegistered(CUSTOM)));
+    }
+
+    #[test]
+    fn step_runs_the_vm_registry_and_step_with_tries_its_own_first() {
+        let mut vm: VM = VM::new();
+        vm.isa = crate::isa::IsaVersion::LATEST;
+        vm.register_opcode(CUSTOM, "ONE", |vm| {
+            vm.acc = 1;
+            vm.pc += 1;
+        })
+        .unwrap();
+        vm.load_program(&[CUSTOM, CUSTOM, CUSTOM]);
+
+        vm.step();
+        assert_eq!((vm.acc, vm.pc, vm.halted), (1, 1, false));
+
+        let mut registry = OpcodeRegistry::new();
+        registry.register(CUSTOM, "TWO", set_acc(2)).unwrap();
+        vm.step_with(&registry, &mut ());
+        assert_eq!((vm.acc, vm.pc), (2, 2));
+
+        vm.step_with(&OpcodeRegistry::new(), &mut ());
+        assert_eq!((vm.acc, vm.pc), (1, 3));
+    }
+
+    #[test]
+    fn clones_share_registrations() {
+        let mut vm: VM = VM::new();
+        vm.register_opcode(CUSTOM, "ONE", |vm| {
+            vm.acc = 1;
+            vm.pc += 1;
+        })
+        .unwrap();
+        let mut child = vm.clone();
+        child.load_program(&[CUSTOM]);
+        child.step();
+        assert_eq!((child.acc, child.halted), (1, false));
+        assert!(child.unregister_opcode(CUSTOM));
+        assert_eq!(vm.opcodes().len(), 1);
+    }
+}
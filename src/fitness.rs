@@ -0,0 +1,221 @@
+// fitness.rs
+
+// Fitness shaping: modifiers applied to raw evaluation scores, configured in TOML
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::path::Path;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::compute::Instruction;
+use crate::disasm;
+use crate::trace::OpcodeProfile;
+
+/// A program that has just been evaluated
+#[derive(Debug, Clone, Copy)]
+pub struct Evaluation<'a> {
+    pub genome: &'a [u8],
+    /// Score from the evaluation itself, e.g. steps run before halting
+    pub raw: f64,
+    /// Rounds of breeding behind the program; 0 for a random one
+    pub generation: u32,
+    /// Opcodes the program executed, if it ran with `VM::profile` on
+    pub profile: Option<&'a OpcodeProfile>,
+}
+
+/// One modifier in the pipeline, selected by `kind` in the config
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)
+)]
+pub enum Shaper {
+    /// Adds `per_generation` for every generation behind the program, up to `max`
+    AgeBonus { per_generation: f64, max: f64 },
+    /// Subtracts `weight` for every instruction in the genome other than NOP
+    Parsimony { weight: f64 },
+    /// Adds `weight` times the mean byte distance to the `neighbours` most similar
+    /// of the last `archive_size` evaluated genomes
+    Novelty {
+        weight: f64,
+        neighbours: usize,
+        archive_size: usize,
+    },
+    /// Scales the raw score by the share of `min_opcodes` distinct opcodes the program
+    /// executed, so one that lucks into a score with a couple of instructions cannot
+    /// dominate; has no effect on evaluations without a profile
+    Coverage { min_opcodes: usize },
+}
+
+impl Shaper {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Shaper::AgeBonus { .. } => "age_bonus",
+            Shaper::Parsimony { .. } => "parsimony",
+            Shaper::Novelty { .. } => "novelty",
+            Shaper::Coverage { .. } => "coverage",
+        }
+    }
+
+    /// What this modifier adds to the score of `evaluation`
+    fn adjustment(&self, evaluation: &Evaluation<'_>, archive: &VecDeque<Vec<u8>>) -> f64 {
+        match *self {
+            Shaper::AgeBonus {
+                per_generation,
+                max,
+            } => (per_generation * evaluation.generation as f64).min(max),
+            Shaper::Parsimony { weight } => -weight * complexity(evaluation.genome) as f64,
+            Shaper::Novelty {
+                weight,
+                neighbours,
+                archive_size,
+            } => {
+                let mut distances: Vec<usize> = archive
+                    .iter()
+                    .rev()
+                    .take(archive_size)
+                    .map(|other| disasm::byte_distance(evaluation.genome, other))
+                    .collect();
+                if distances.is_empty() || neighbours == 0 {
+                    return 0.0;
+                }
+                distances.sort_unstable();
+                let nearest = &distances[..neighbours.min(distances.len())];
+                weight * nearest.iter().sum::<usize>() as f64 / nearest.len() as f64
+            }
+            Shaper::Coverage { min_opcodes } => match evaluation.profile {
+                Some(profile) if min_opcodes > 0 => {
+                    let covered = (profile.distinct() as f64 / min_opcodes as f64).min(1.0);
+                    -evaluation.raw * (1.0 - covered)
+                }
+                _ => 0.0,
+            },
+        }
+    }
+
+    fn archive_size(&self) -> usize {
+        match *self {
+            Shaper::Novelty { archive_size, .. } => archive_size,
+            _ => 0,
+        }
+    }
+}
+
+/// Instructions in a genome other than NOP, as decoded linearly from address 0
+pub fn complexity(genome: &[u8]) -> usize {
+    disasm::decode(genome)
+        .iter()
+        .filter(|i| i.instruction != Some(Instruction::NOP))
+        .count()
+}
+
+/// Shapers as listed in a config file, applied in order:
+///
+/// ```toml
+/// [[shaper]]
+/// kind = "parsimony"
+/// weight = 0.5
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FitnessConfig {
+    #[cfg_attr(feature = "serde", serde(default, rename = "shaper"))]
+    pub shapers: Vec<Shaper>,
+}
+
+#[derive(Debug)]
+pub enum FitnessConfigError {
+    Io(std::io::Error),
+    Parse(String),
+}
+
+impl fmt::Display for FitnessConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FitnessConfigError::Io(e) => write!(f, "fitness config I/O error: {}", e),
+            FitnessConfigError::Parse(e) => write!(f, "invalid fitness config: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for FitnessConfigError {}
+
+impl From<std::io::Error> for FitnessConfigError {
+    fn from(e: std::io::Error) -> Self {
+        FitnessConfigError::Io(e)
+    }
+}
+
+impl FitnessConfig {
+    #[cfg(feature = "serde")]
+    pub fn parse(text: &str) -> Result<Self, FitnessConfigError> {
+        toml::from_str(text).map_err(|e| FitnessConfigError::Parse(e.to_string()))
+    }
+
+    #[cfg(not(feature = "serde"))]
+    pub fn parse(_text: &str) -> Result<Self, FitnessConfigError> {
+        Err(FitnessConfigError::Parse(
+            "fitness configs need the serde feature".to_string(),
+        ))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, FitnessConfigError> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Whether a shaper looks at `Evaluation::profile`, so programs must run profiled
+    pub fn needs_profile(&self) -> bool {
+        self.shapers
+            .iter()
+            .any(|s| matches!(s, Shaper::Coverage { .. }))
+    }
+}
+
+/// Applies a config's shapers to every evaluation, keeping the genomes novelty compares against.
+///
+/// With no shapers the shaped score is the raw score.
+#[derive(Debug, Clone, Default)]
+pub struct FitnessPipeline {
+    pub config: FitnessConfig,
+    archive: VecDeque<Vec<u8>>,
+}
+
+impl FitnessPipeline {
+    pub fn new(config: FitnessConfig) -> Self {
+        Self {
+            config,
+            archive: VecDeque::new(),
+        }
+    }
+
+    /// The raw score plus every shaper's adjustment, then remember the genome
+    pub fn shape(&mut self, evaluation: &Evaluation<'_>) -> f64 {
+        let shaped = evaluation.raw + self.adjustments(evaluation).iter().sum::<f64>();
+        let keep = self
+            .config
+            .shapers
+            .iter()
+            .map(Shaper::archive_size)
+            .max()
+            .unwrap_or(0);
+        if keep > 0 {
+            while self.archive.len() >= keep {
+                self.archive.pop_front();
+            }
+            self.archive.push_back(evaluation.genome.to_vec());
+        }
+        shaped
+    }
+
+    /// Each shaper's adjustment to `evaluation`, in config order, without recording it
+    pub fn adjustments(&self, evaluation: &Evaluation<'_>) -> Vec<f64> {
+        self.config
+            .shapers
+            .iter()
+            .map(|s| s.adjustment(evaluation, &self.archive))
+            .collect()
+    }
+}
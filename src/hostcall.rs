@@ -0,0 +1,120 @@
+// hostcall.rs
+
+// HOSTCALL: an opcode whose behavior is defined by a Rhai script, for prototyping instructions
+
+use std::path::Path;
+
+use rhai::{AST, Dynamic, Engine, EvalAltResult, Scope};
+
+use crate::compute::{AddressWidth, VM};
+use crate::extension::{OpcodeRegistry, RegistryError};
+
+// Hostcall constants
+pub const HOSTCALL_OPCODE: u8 = 0x2A; // First byte after the built-in instruction set
+pub const HOSTCALL_ENTRY: &str = "hostcall"; // Script function called for every HOSTCALL
+pub const HOSTCALL_MAX_OPERATIONS: u64 = 10_000; // Script operations allowed per call, so a stuck script halts
+
+#[derive(Debug)]
+pub enum HostcallError {
+    Io(std::io::Error),
+    /// The script failed to parse
+    Parse(String),
+    /// The script has no `fn hostcall(acc, arg)`
+    MissingEntry,
+    /// The handler raised an error, ran out of operations or returned something other than an integer or `()`
+    Script(String),
+}
+
+impl std::fmt::Display for HostcallError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HostcallError::Io(e) => write!(f, "cannot read script: {}", e),
+            HostcallError::Parse(e) => write!(f, "cannot parse script: {}", e),
+            HostcallError::MissingEntry => {
+                write!(f, "script defines no `fn {}(acc, arg)`", HOSTCALL_ENTRY)
+            }
+            HostcallError::Script(e) => write!(f, "{} failed: {}", HOSTCALL_ENTRY, e),
+        }
+    }
+}
+
+impl std::error::Error for HostcallError {}
+
+impl From<std::io::Error> for HostcallError {
+    fn from(e: std::io::Error) -> Self {
+        HostcallError::Io(e)
+    }
+}
+
+/// A compiled Rhai script defining `fn hostcall(acc, arg)`.
+///
+/// `HOSTCALL arg` is two bytes, like `LDI`. The handler gets the accumulator and the
+/// argument byte as integers; an integer result becomes the new accumulator (wrapped
+/// to a byte) and `()` leaves it unchanged. A script error halts the VM.
+pub struct HostcallScript {
+    engine: Engine,
+    ast: AST,
+}
+
+impl HostcallScript {
+    pub fn load(path: &Path) -> Result<Self, HostcallError> {
+        Self::compile(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn compile(source: &str) -> Result<Self, HostcallError> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(HOSTCALL_MAX_OPERATIONS);
+        let ast = engine
+            .compile(source)
+            .map_err(|e| HostcallError::Parse(e.to_string()))?;
+        if !ast
+            .iter_functions()
+            .any(|f| f.name == HOSTCALL_ENTRY && f.params.len() == 2)
+        {
+            return Err(HostcallError::MissingEntry);
+        }
+        Ok(HostcallScript { engine, ast })
+    }
+
+    /// Run the handler, returning the new accumulator
+    pub fn call(&self, acc: u8, arg: u8) -> Result<u8, HostcallError> {
+        let result: Dynamic = self
+            .engine
+            .call_fn(
+                &mut Scope::new(),
+                &self.ast,
+                HOSTCALL_ENTRY,
+                (acc as i64, arg as i64),
+            )
+            .map_err(|e: Box<EvalAltResult>| HostcallError::Script(e.to_string()))?;
+        if result.is_unit() {
+            return Ok(acc);
+        }
+        result
+            .as_int()
+            .map(|value| value as u8)
+            .map_err(|ty| HostcallError::Script(format!("returned {}, not an integer", ty)))
+    }
+
+    /// Bind the script to `opcode` in `registry`; usually `HOSTCALL_OPCODE`
+    pub fn register<C, const N: usize, A: AddressWidth>(
+        self,
+        registry: &mut OpcodeRegistry<C, N, A>,
+        opcode: u8,
+    ) -> Result<(), RegistryError> {
+        registry.register(opcode, "HOSTCALL", move |vm: &mut VM<N, A>, _: &mut C| {
+            let arg = vm.memory.get(vm.pc + 1).copied().unwrap_or(0);
+            tracing::trace!("HOSTCALL arg={}", arg);
+            match self.call(vm.acc, arg) {
+                Ok(acc) => {
+                    vm.acc = acc;
+                    vm.pc += 2;
+                }
+                Err(e) => {
+                    tracing::warn!("HOSTCALL at pc={}: {}", vm.pc, e);
+                    vm.halted = true;
+                }
+            }
+        })
+    }
+}
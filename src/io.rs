@@ -0,0 +1,116 @@
+// io.rs
+
+// Host devices the VM reaches through IN/OUT ports instead of fixed memory addresses
+
+use std::fmt;
+
+/// A sensor or actuator attached to one VM port.
+///
+/// Unimplemented directions are inert: reads give 0 and writes are dropped, so a
+/// sensor only needs `read` and an actuator only `write`. Devices must be `Clone`
+/// because cloning a VM clones its attached devices.
+pub trait IoDevice: IoDeviceClone + Send {
+    /// Value an IN from this device's port loads into the accumulator
+    fn read(&mut self) -> u8 {
+        0
+    }
+
+    /// Accept the accumulator written by an OUT to this device's port
+    fn write(&mut self, value: u8) {
+        let _ = value;
+    }
+}
+
+/// Object-safe cloning for boxed devices, implemented for every `Clone` device
+pub trait IoDeviceClone {
+    fn clone_box(&self) -> Box<dyn IoDevice>;
+}
+
+impl<T: IoDevice + Clone + 'static> IoDeviceClone for T {
+    fn clone_box(&self) -> Box<dyn IoDevice> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn IoDevice> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// Devices attached to a VM, by port number
+#[derive(Clone, Default)]
+pub struct IoPorts {
+    devices: Vec<(u8, Box<dyn IoDevice>)>,
+    /// Ports programs may reach; `None` allows every port
+    allowed: Option<Vec<u8>>,
+}
+
+impl IoPorts {
+    /// Attach `device` to `port`, returning the device it replaced
+    pub fn attach(&mut self, port: u8, device: Box<dyn IoDevice>) -> Option<Box<dyn IoDevice>> {
+        match self.devices.iter_mut().find(|(p, _)| *p == port) {
+            Some((_, slot)) => Some(std::mem::replace(slot, device)),
+            None => {
+                self.devices.push((port, device));
+                None
+            }
+        }
+    }
+
+    pub fn detach(&mut self, port: u8) -> Option<Box<dyn IoDevice>> {
+        let index = self.devices.iter().position(|(p, _)| *p == port)?;
+        Some(self.devices.swap_remove(index).1)
+    }
+
+    pub fn is_attached(&self, port: u8) -> bool {
+        self.devices.iter().any(|(p, _)| *p == port)
+    }
+
+    /// Only let IN and OUT reach the `allowed` ports; `None` lifts the restriction.
+    ///
+    /// Devices stay attached on restricted ports, programs just cannot reach them.
+    pub fn restrict(&mut self, allowed: Option<&[u8]>) {
+        if self.allowed.as_deref() != allowed {
+            self.allowed = allowed.map(<[u8]>::to_vec);
+        }
+    }
+
+    pub fn is_allowed(&self, port: u8) -> bool {
+        self.allowed
+            .as_ref()
+            .is_none_or(|allowed| allowed.contains(&port))
+    }
+
+    /// Read from `port`; a port with nothing attached or not allowed reads 0
+    pub fn read(&mut self, port: u8) -> u8 {
+        self.device_mut(port).map_or(0, |d| d.read())
+    }
+
+    /// Write to `port`; writes to a port with nothing attached or not allowed are dropped
+    pub fn write(&mut self, port: u8, value: u8) {
+        if let Some(device) = self.device_mut(port) {
+            device.write(value);
+        }
+    }
+
+    fn device_mut(&mut self, port: u8) -> Option<&mut Box<dyn IoDevice>> {
+        if !self.is_allowed(port) {
+            return None;
+        }
+        self.devices
+            .iter_mut()
+            .find(|(p, _)| *p == port)
+            .map(|(_, d)| d)
+    }
+}
+
+impl fmt::Debug for IoPorts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ports: Vec<u8> = self.devices.iter().map(|(p, _)| *p).collect();
+        f.debug_struct("IoPorts")
+            .field("attached", &ports)
+            .field("allowed", &self.allowed)
+            .finish()
+    }
+}
@@ -0,0 +1,273 @@
+// isa.rs
+
+// Opcode reference generated from the instruction definitions, and the versioned instruction sets genomes are saved under
+
+use std::fmt;
+use std::str::FromStr;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::compute::{Instruction, MEM_SIZE};
+
+/// VM steps one instruction consumes from a lifeform's per-tick budget
+pub const STEPS_PER_INSTRUCTION: usize = 1;
+
+/// Reference entry for one opcode
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpcodeInfo {
+    pub instruction: Instruction,
+    pub opcode: u8,
+    pub mnemonic: &'static str,
+    /// Bytes including the opcode
+    pub size: usize,
+    /// Encoded form, e.g. `01 aa` where `aa` is an address operand, `nn` an immediate value,
+    /// `pp` an I/O port and `rr` a signed jump offset
+    pub encoding: String,
+    pub steps: usize,
+    /// Flags the instruction updates
+    pub flags: &'static str,
+    pub summary: &'static str,
+}
+
+impl OpcodeInfo {
+    pub fn new(instruction: Instruction) -> Self {
+        let opcode = instruction as u8;
+        let encoding = match instruction.size() {
+            1 => format!("{:02X}", opcode),
+            _ if instruction.is_immediate() => format!("{:02X} nn", opcode),
+            _ if instruction.is_port_io() => format!("{:02X} pp", opcode),
+            _ if instruction.is_relative() => format!("{:02X} rr", opcode),
+            _ => format!("{:02X} aa", opcode),
+        };
+        Self {
+            instruction,
+            opcode,
+            mnemonic: instruction.mnemonic(),
+            size: instruction.size(),
+            encoding,
+            steps: STEPS_PER_INSTRUCTION,
+            flags: instruction.flag_effects(),
+            summary: instruction.summary(),
+        }
+    }
+}
+
+impl fmt::Display for OpcodeInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:<5} {:<6} {:>4} {:>5}  {:<5}  {}",
+            self.mnemonic, self.encoding, self.size, self.steps, self.flags, self.summary
+        )
+    }
+}
+
+/// Column headings matching `OpcodeInfo`'s `Display` layout
+pub const REFERENCE_HEADER: &str = "NAME  CODE   SIZE STEPS  FLAGS  DESCRIPTION";
+
+/// Reference entries for every built-in instruction, in opcode order
+pub fn reference() -> Vec<OpcodeInfo> {
+    Instruction::all().map(OpcodeInfo::new).collect()
+}
+
+/// Marks the ISA tag that ends a saved program file
+pub const ISA_TAG_MAGIC: [u8; 4] = *b"BVMI";
+pub const ISA_TAG_LEN: usize = 8; // Magic, version number and three zero bytes
+
+/// Instruction set a VM decodes its program with.
+///
+/// New opcodes give old genomes new meaning: a byte that used to halt may now jump.
+/// A VM keeps the version its genome evolved under, and program files record it, so
+/// old genomes run exactly as they did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum IsaVersion {
+    /// The original twelve instructions: NOP through CMP, and HLT; every other byte halts
+    V1,
+    /// V1 plus calls, flag jumps, logic, shifts, multiply and divide, immediates,
+    /// skips, RND, port I/O, relative jumps and the X and Y registers. The default,
+    /// because population records written before versions were saved ran under it.
+    #[default]
+    V2,
+    /// V2 plus PUSH and POP on a data stack with its own pointer
+    V3,
+    /// V3 plus COPY, the block copy self-replicating programs are built around
+    V4,
+}
+
+impl IsaVersion {
+    pub const LATEST: IsaVersion = IsaVersion::V4;
+    /// The version assumed for program files without a tag, which predate versioning
+    pub const UNTAGGED: IsaVersion = IsaVersion::V1;
+    pub const ALL: [IsaVersion; 4] = [
+        IsaVersion::V1,
+        IsaVersion::V2,
+        IsaVersion::V3,
+        IsaVersion::V4,
+    ];
+
+    /// The instruction `opcode` means in this version, or `None` if it has none
+    pub fn decode(self, opcode: u8) -> Option<Instruction> {
+        match self {
+            IsaVersion::V1 => match opcode {
+                0x00..=0x0A | 0xFF => Instruction::decode(opcode),
+                _ => None,
+            },
+            IsaVersion::V2 => match opcode {
+                0x00..=0x29 | 0xFF => Instruction::decode(opcode),
+                _ => None,
+            },
+            IsaVersion::V3 => match opcode {
+                0x00..=0x2C | 0xFF => Instruction::decode(opcode),
+                _ => None,
+            },
+            IsaVersion::V4 => Instruction::decode(opcode),
+        }
+    }
+
+    /// Built-in instructions in this version, in opcode order
+    pub fn instructions(self) -> impl Iterator<Item = Instruction> {
+        Instruction::all().filter(move |&i| self.decode(i as u8).is_some())
+    }
+
+    pub fn number(self) -> u8 {
+        match self {
+            IsaVersion::V1 => 1,
+            IsaVersion::V2 => 2,
+            IsaVersion::V3 => 3,
+            IsaVersion::V4 => 4,
+        }
+    }
+
+    pub fn from_number(number: u8) -> Option<Self> {
+        Self::ALL.into_iter().find(|v| v.number() == number)
+    }
+
+    /// Trailer appended to program files saved under this version
+    pub fn tag(self) -> [u8; ISA_TAG_LEN] {
+        let mut tag = [0; ISA_TAG_LEN];
+        tag[..4].copy_from_slice(&ISA_TAG_MAGIC);
+        tag[4] = self.number();
+        tag
+    }
+}
+
+impl fmt::Display for IsaVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "v{}", self.number())
+    }
+}
+
+impl FromStr for IsaVersion {
+    type Err = IsaError;
+
+    /// `v1`, `v2` and so on, or the bare number
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits = s.strip_prefix(['v', 'V']).unwrap_or(s);
+        digits
+            .parse()
+            .ok()
+            .and_then(Self::from_number)
+            .ok_or_else(|| IsaError::UnknownName(s.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IsaError {
+    /// A program file's tag names a version this build does not know
+    UnknownVersion(u8),
+    UnknownName(String),
+}
+
+impl fmt::Display for IsaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IsaError::UnknownVersion(n) => {
+                write!(f, "program was saved for unknown instruction set v{}", n)
+            }
+            IsaError::UnknownName(name) => write!(
+                f,
+                "unknown instruction set `{}`; expected v1 to v{}",
+                name,
+                IsaVersion::LATEST.number()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for IsaError {}
+
+/// A genome with the instruction set it runs under
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Program {
+    pub genome: Vec<u8>,
+    pub isa: IsaVersion,
+}
+
+impl Program {
+    /// Read a program file: the genome, then any provenance watermark, then the ISA tag.
+    /// Files without a tag predate versioning and run as `IsaVersion::UNTAGGED`, i.e. V1.
+    ///
+    /// The genome is the file without its tag; trailers before it are left for the
+    /// caller, e.g. `Provenance::read`.
+    pub fn from_file_bytes(mut file: Vec<u8>) -> Result<Self, IsaError> {
+        let isa = match file.len().checked_sub(ISA_TAG_LEN).map(|at| &file[at..]) {
+            Some(tag) if tag[..4] == ISA_TAG_MAGIC && tag[5..] == [0; 3] => {
+                let isa =
+                    IsaVersion::from_number(tag[4]).ok_or(IsaError::UnknownVersion(tag[4]))?;
+                file.truncate(file.len() - ISA_TAG_LEN);
+                isa
+            }
+            _ => IsaVersion::UNTAGGED,
+        };
+        Ok(Program { genome: file, isa })
+    }
+
+    /// The program file for `genome` padded to memory size, tagged with the version
+    pub fn to_file_bytes(&self) -> Vec<u8> {
+        let mut file = self.genome.clone();
+        file.resize(file.len().max(MEM_SIZE), 0);
+        file.extend_from_slice(&self.isa.tag());
+        file
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn untagged_files_load_as_v1() {
+        let genome = vec![0x01, 0x80, 0x19, 0x02, 0xFF];
+        let program = Program::from_file_bytes(genome.clone()).unwrap();
+        assert_eq!(program.isa, IsaVersion::UNTAGGED);
+        assert_eq!(program.isa, IsaVersion::V1);
+        assert_eq!(program.genome, genome);
+    }
+
+    #[test]
+    fn tagged_files_round_trip() {
+        for isa in IsaVersion::ALL {
+            let program = Program {
+                genome: vec![0x05; MEM_SIZE],
+                isa,
+            };
+            assert_eq!(
+                Program::from_file_bytes(program.to_file_bytes()),
+                Ok(program)
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_tags_are_rejected() {
+        let mut file = vec![0; MEM_SIZE];
+        file.extend_from_slice(&ISA_TAG_MAGIC);
+        file.extend_from_slice(&[9, 0, 0, 0]);
+        assert_eq!(
+            Program::from_file_bytes(file),
+            Err(IsaError::UnknownVersion(9))
+        );
+    }
+}
@@ -0,0 +1,78 @@
+// keyboard.rs
+
+// Memory-mapped keyboard input, so users can drive a selected VM by hand
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::compute::{AddressWidth, VM};
+
+// Keyboard constants
+pub const DEFAULT_KEYBOARD_ADDR: usize = 220; // Unused byte between the code and the skin
+pub const JOY_LEFT: u8 = 0x01; // Joystick bit held while steering left
+pub const JOY_RIGHT: u8 = 0x02; // Joystick bit held while steering right
+pub const JOY_UP: u8 = 0x04; // Joystick bit held while steering up
+pub const JOY_DOWN: u8 = 0x08; // Joystick bit held while steering down
+pub const JOY_FIRE: u8 = 0x10; // Joystick bit held while the fire button is down
+
+/// What the keyboard byte holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum KeyboardMode {
+    /// The code of the last key pressed, stored once per press; programs clear
+    /// the byte to acknowledge it
+    LastKey,
+    /// `JOY_*` bits for the directions and fire button currently held, refreshed
+    /// before every run of the program
+    #[default]
+    Joystick,
+}
+
+/// Keyboard input waiting to be stored at `addr` in a VM's data
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Keyboard {
+    pub addr: usize,
+    pub mode: KeyboardMode,
+    /// Key pressed since the program last saw one
+    pending: Option<u8>,
+    joystick: u8,
+}
+
+impl Keyboard {
+    pub fn new(addr: usize, mode: KeyboardMode) -> Self {
+        Self {
+            addr,
+            mode,
+            pending: None,
+            joystick: 0,
+        }
+    }
+
+    /// Record a key press; only the latest press before the next `apply` is kept
+    pub fn press(&mut self, key: u8) {
+        self.pending = Some(key);
+    }
+
+    /// Set the held directions and fire button as `JOY_*` bits
+    pub fn set_joystick(&mut self, bits: u8) {
+        self.joystick = bits;
+    }
+
+    pub fn joystick(&self) -> u8 {
+        self.joystick
+    }
+
+    /// Store the input in `vm`'s data; an address outside its memory is ignored
+    pub fn apply<const N: usize, A: AddressWidth>(&mut self, vm: &mut VM<N, A>) {
+        let value = match self.mode {
+            KeyboardMode::LastKey => match self.pending.take() {
+                Some(key) => key,
+                None => return,
+            },
+            KeyboardMode::Joystick => self.joystick,
+        };
+        if let Some(byte) = vm.data_mut().get_mut(self.addr) {
+            *byte = value;
+        }
+    }
+}
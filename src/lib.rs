@@ -1 +1,39 @@
+pub mod analysis;
+pub mod asm;
+#[cfg(feature = "serde")]
+pub mod autosave;
+pub mod bundle;
 pub mod compute;
+pub mod debugger;
+pub mod disasm;
+pub mod display;
+pub mod divergence;
+pub mod evaluator;
+pub mod extension;
+pub mod fitness;
+#[cfg(feature = "rhai")]
+pub mod hostcall;
+pub mod io;
+pub mod isa;
+pub mod keyboard;
+pub mod lint;
+pub mod memory_map;
+#[cfg(feature = "serde")]
+pub mod migration;
+pub mod mmio;
+pub mod notify;
+pub mod peephole;
+pub mod provenance;
+pub mod reach;
+pub mod relocation;
+#[cfg(feature = "serde")]
+pub mod replay;
+pub mod rng;
+pub mod sandbox;
+#[cfg(feature = "serde")]
+pub mod scenario;
+#[cfg(feature = "serde")]
+pub mod selftest;
+pub mod stats;
+pub mod trace;
+pub mod world;
@@ -0,0 +1,158 @@
+// lint.rs
+
+// Static checks for program mistakes that run without failing: misaligned jumps, writes the world overwrites, code that can never halt where it seems to and lifeforms that can never move
+
+use std::fmt;
+
+use crate::analysis::{self, EdgeKind};
+use crate::compute::{Instruction, MEM_SIZE};
+use crate::disasm;
+use crate::isa::IsaVersion;
+use crate::memory_map::RegionKind;
+use crate::reach::{self, Reach};
+use crate::world::lifeform_memory_map;
+
+/// How much a diagnostic matters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// Probably harmless, but worth a look
+    Note,
+    /// Almost certainly not what the program meant
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Note => write!(f, "note"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A mistake `lint` found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LintKind {
+    /// A jump or call lands on `target`, inside the operand of the instruction at `inside`
+    JumpIntoOperand { target: usize, inside: usize },
+    /// A store to `target`, a sensor the world rewrites every tick
+    SensorWrite { target: usize },
+    /// A HLT no code reachable from address 0 runs
+    UnreachableHalt,
+    /// The operand would sit past the end of memory, so it reads as 0
+    OperandPastEnd,
+    /// No run can store to the movement actuators, so the lifeform never moves;
+    /// reported at address 0
+    NeverMoves,
+}
+
+/// A lint finding at the instruction starting at `addr`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Diagnostic {
+    pub addr: usize,
+    pub kind: LintKind,
+}
+
+impl Diagnostic {
+    pub fn severity(&self) -> Severity {
+        match self.kind {
+            LintKind::UnreachableHalt | LintKind::NeverMoves => Severity::Note,
+            _ => Severity::Warning,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            LintKind::JumpIntoOperand { target, inside } => write!(
+                f,
+                "jump to {} lands inside the operand of the instruction at {}",
+                target, inside
+            ),
+            LintKind::SensorWrite { target } => {
+                write!(f, "store to sensor {}, which the world overwrites", target)
+            }
+            LintKind::UnreachableHalt => write!(f, "HLT is never reached"),
+            LintKind::OperandPastEnd => write!(f, "operand lies past the end of memory"),
+            LintKind::NeverMoves => write!(f, "no store can reach the movement actuators"),
+        }
+    }
+}
+
+/// Check a program image for mistakes that run without failing, in address order.
+///
+/// Only code reachable from address 0 is checked, except for unreachable HLTs,
+/// which are found by decoding linearly from 0 like the disassembler. Whether the
+/// program can move is proved with `reach::Reach`, which also follows
+/// self-modifying code.
+pub fn lint(program: &[u8], isa: IsaVersion) -> Vec<Diagnostic> {
+    let mut image = program.to_vec();
+    image.resize(MEM_SIZE, 0);
+    let code = analysis::reachable(&image, isa, &[0]);
+    let map = lifeform_memory_map();
+    let mut covered = vec![false; MEM_SIZE];
+    for entry in &code {
+        covered[entry.addr..(entry.addr + entry.size()).min(MEM_SIZE)].fill(true);
+    }
+
+    let mut diagnostics = Vec::new();
+    for entry in &code {
+        let diagnostic = |kind| Diagnostic {
+            addr: entry.addr,
+            kind,
+        };
+        if entry.addr + entry.size() > MEM_SIZE {
+            diagnostics.push(diagnostic(LintKind::OperandPastEnd));
+        }
+        for (target, kind) in analysis::exits(&image, entry, isa) {
+            if !matches!(kind, EdgeKind::Jump | EdgeKind::Call) {
+                continue;
+            }
+            let inside = code
+                .iter()
+                .find(|other| other.addr < target && target < other.addr + other.size());
+            if let Some(inside) = inside {
+                diagnostics.push(diagnostic(LintKind::JumpIntoOperand {
+                    target,
+                    inside: inside.addr,
+                }));
+            }
+        }
+        if let (Some(Instruction::STA | Instruction::SWP), Some(operand)) =
+            (entry.instruction, entry.operand)
+        {
+            let target = operand as usize;
+            if map
+                .region_at(target)
+                .is_some_and(|region| region.kind == RegionKind::Sensors)
+            {
+                diagnostics.push(diagnostic(LintKind::SensorWrite { target }));
+            }
+        }
+    }
+
+    for entry in disasm::decode(&image) {
+        if entry.instruction == Some(Instruction::HLT)
+            && isa.decode(entry.opcode).is_some()
+            && !covered[entry.addr]
+        {
+            diagnostics.push(Diagnostic {
+                addr: entry.addr,
+                kind: LintKind::UnreachableHalt,
+            });
+        }
+    }
+    let actuators = reach::lifeform_addresses(RegionKind::Actuators);
+    if !Reach::for_lifeform(&image, isa)
+        .written
+        .intersects(&actuators)
+    {
+        diagnostics.push(Diagnostic {
+            addr: 0,
+            kind: LintKind::NeverMoves,
+        });
+    }
+    diagnostics.sort_by_key(|d| d.addr);
+    diagnostics
+}
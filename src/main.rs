@@ -1,49 +1,103 @@
-use std::fs::File;
-use std::io::Write;
-
-use ::rand::thread_rng;
+use life::analysis::{self, DuplicateFilter};
+use life::compute::{
+    self, DebugHit, ProgramFile, ProgramMetadata, StepResult, VmConfig, WatchKind,
+};
+use life::disasm::Listing;
+use life::display::{DISPLAY_SIZE, Display};
+use life::fitness::{Evaluation, FitnessConfig, FitnessPipeline};
+use life::isa::Program;
+use life::lint::{self, Severity};
+use life::rng::SimRng;
+#[cfg(feature = "heatmap")]
+use life::trace::Heatmap;
+use life::trace::OpcodeProfile;
 use macroquad::prelude::*;
 use tracing::info;
-use tracing_subscriber;
 
-mod compute;
+const TURBO_STEPS: usize = 1000; // Steps each VM runs per frame in turbo mode
+const UNDO_STEPS: usize = 256; // Steps each VM can be rewound with 'b' while paused
+const DUPLICATE_MEMORY: usize = 4096; // Evaluated programs remembered, by canonical hash
+const MAX_DUPLICATE_REDRAWS: usize = 16; // Mutations tried before a duplicate child runs anyway
 
-/// Draw a single VM's memory as a grid at the given offset
-fn draw_vm(vm: &compute::VM, offset_x: f32, offset_y: f32, grid_size: f32, padding: f32) {
-    // Draw the VM grid centered in its pane
-    let cols = 16;
-    let rows = 16;
+/// Color of a memory cell holding `value`
+fn cell_color(value: u8) -> Color {
+    let t = value as f32 / 255.0;
+    if t < 0.15 {
+        Color::new(1.0, t * 6.0, 0.0, 1.0)
+    } else if t < 0.30 {
+        Color::new(1.0, 0.5 + (t - 0.15) * 3.33, 0.0, 1.0)
+    } else if t < 0.45 {
+        Color::new(1.0, 1.0, (t - 0.30) * 6.66, 1.0)
+    } else if t < 0.60 {
+        Color::new(1.0 - (t - 0.45) * 6.66, 1.0, 0.0, 1.0)
+    } else if t < 0.75 {
+        Color::new(0.0, 1.0, (t - 0.60) * 6.66, 1.0)
+    } else if t < 0.90 {
+        Color::new(0.0, 1.0 - (t - 0.75) * 6.66, 1.0, 1.0)
+    } else if t < 0.98 {
+        Color::new((t - 0.90) * 12.5, 0.0, 1.0, 1.0)
+    } else {
+        Color::new(1.0, 1.0, 1.0, 1.0)
+    }
+}
+
+/// Draw memory cells as a grid, outlining the cells `outline` returns a color for
+fn draw_memory(
+    memory: &[u8],
+    offset_x: f32,
+    offset_y: f32,
+    grid_size: f32,
+    padding: f32,
+    outline: impl Fn(usize) -> Option<Color>,
+) {
+    let (cols, rows) = compute::grid_shape(memory.len());
     let square_width = (grid_size - (cols as f32 - 1.0) * padding) / cols as f32;
     let square_height = (grid_size - (rows as f32 - 1.0) * padding) / rows as f32;
-    for row in 0..rows {
-        for col in 0..cols {
-            let x = offset_x + col as f32 * (square_width + padding);
-            let y = offset_y + row as f32 * (square_height + padding);
-            let idx = row * cols + col;
-            let value = vm.memory[idx];
-            let t = value as f32 / 255.0;
-            let color = if t < 0.15 {
-                Color::new(1.0, t * 6.0, 0.0, 1.0)
-            } else if t < 0.30 {
-                Color::new(1.0, 0.5 + (t - 0.15) * 3.33, 0.0, 1.0)
-            } else if t < 0.45 {
-                Color::new(1.0, 1.0, (t - 0.30) * 6.66, 1.0)
-            } else if t < 0.60 {
-                Color::new(1.0 - (t - 0.45) * 6.66, 1.0, 0.0, 1.0)
-            } else if t < 0.75 {
-                Color::new(0.0, 1.0, (t - 0.60) * 6.66, 1.0)
-            } else if t < 0.90 {
-                Color::new(0.0, 1.0 - (t - 0.75) * 6.66, 1.0, 1.0)
-            } else if t < 0.98 {
-                Color::new((t - 0.90) * 12.5, 0.0, 1.0, 1.0)
-            } else {
-                Color::new(1.0, 1.0, 1.0, 1.0)
-            };
-            draw_rectangle(x, y, square_width, square_height, color);
-            if idx == vm.pc {
-                draw_rectangle_lines(x, y, square_width, square_height, 5.0, WHITE);
-            }
+    for (idx, &value) in memory.iter().enumerate() {
+        let x = offset_x + (idx % cols) as f32 * (square_width + padding);
+        let y = offset_y + (idx / cols) as f32 * (square_height + padding);
+        draw_rectangle(x, y, square_width, square_height, cell_color(value));
+        if let Some(color) = outline(idx) {
+            draw_rectangle_lines(x, y, square_width, square_height, 5.0, color);
+        }
+    }
+}
+
+/// Draw a Harvard VM's data bank over the lower half of each code cell
+fn draw_data_bank(data: &[u8], offset_x: f32, offset_y: f32, grid_size: f32, padding: f32) {
+    let (cols, rows) = compute::grid_shape(data.len());
+    let square_width = (grid_size - (cols as f32 - 1.0) * padding) / cols as f32;
+    let square_height = (grid_size - (rows as f32 - 1.0) * padding) / rows as f32;
+    for (idx, &value) in data.iter().enumerate() {
+        let x = offset_x + (idx % cols) as f32 * (square_width + padding);
+        let y = offset_y + (idx / cols) as f32 * (square_height + padding) + square_height / 2.0;
+        draw_rectangle(x, y, square_width, square_height / 2.0, cell_color(value));
+    }
+}
+
+/// Draw a single VM's memory as a grid at the given offset
+fn draw_vm<const N: usize>(
+    vm: &compute::VM<N>,
+    offset_x: f32,
+    offset_y: f32,
+    grid_size: f32,
+    padding: f32,
+    listing: bool,
+) {
+    // Draw the VM grid centered in its pane
+    draw_memory(&vm.memory, offset_x, offset_y, grid_size, padding, |idx| {
+        if idx == vm.pc {
+            Some(WHITE)
+        } else if vm.breakpoints().iter().any(|b| b.addr == idx) {
+            Some(RED)
+        } else if vm.watchpoints().iter().any(|w| w.addr == idx) {
+            Some(SKYBLUE)
+        } else {
+            None
         }
+    });
+    if vm.is_harvard() {
+        draw_data_bank(vm.data(), offset_x, offset_y, grid_size, padding);
     }
     // Draw the current number of steps centered and large
     let steps_text = format!("{}", vm.total_steps_count);
@@ -53,24 +107,178 @@ fn draw_vm(vm: &compute::VM, offset_x: f32, offset_y: f32, grid_size: f32, paddi
     let text_y = offset_y + (grid_size + text_dimensions.height) / 2.0;
     draw_text(&steps_text, text_x, text_y, text_size, WHITE);
     // Draw the log view to the right of the VM grid (no background, white text)
-    let log_width = grid_size * 1.2;
+    let _log_width = grid_size * 1.2;
     let log_height = grid_size;
     let log_x = offset_x + grid_size + padding * 2.0;
     let log_y = offset_y;
     let log_font_size = (grid_size / 18.0).max(12.0);
     let mut y = log_y + log_font_size + 4.0;
+    // One line is kept for the metrics below the log
     let max_lines = (log_height / (log_font_size + 2.0)).floor() as usize;
-    let start_idx = if vm.recent_instructions.len() > max_lines {
-        vm.recent_instructions.len() - max_lines
+    if listing {
+        // The program's listing around the PC, the PC's line highlighted and lines
+        // with lint warnings in orange
+        let listing = Listing::new(&vm.memory, vm.isa);
+        let diagnostics = lint::lint(&vm.memory, vm.isa);
+        let pc_line = listing
+            .lines
+            .iter()
+            .position(|line| line.contains(vm.pc))
+            .unwrap_or(0);
+        let shown = max_lines.saturating_sub(1);
+        let start_idx = pc_line
+            .saturating_sub(shown / 2)
+            .min(listing.lines.len().saturating_sub(shown));
+        for (idx, line) in listing.lines.iter().enumerate().skip(start_idx).take(shown) {
+            let label = line
+                .label
+                .as_deref()
+                .map_or(String::new(), |l| l.to_string() + ": ");
+            let text = format!("{:03} {}{}", line.addr, label, line.text);
+            let linted = diagnostics
+                .iter()
+                .any(|d| d.severity() == Severity::Warning && line.contains(d.addr));
+            let color = if idx == pc_line {
+                YELLOW
+            } else if linted {
+                ORANGE
+            } else {
+                WHITE
+            };
+            draw_text(&text, log_x + 8.0, y, log_font_size, color);
+            y += log_font_size + 2.0;
+        }
     } else {
-        0
+        let start_idx = vm.recent.len().saturating_sub(max_lines.saturating_sub(1));
+        for line in vm.recent.lines().skip(start_idx) {
+            draw_text(&line, log_x + 8.0, y, log_font_size, WHITE);
+            y += log_font_size + 2.0;
+        }
+    }
+    let metrics = vm.metrics().to_string();
+    draw_text(&metrics, log_x + 8.0, y, log_font_size, GRAY);
+}
+
+/// Color of a cell in the coverage view: green for execution, red for writes and
+/// blue for reads, each brighter the more often it happened; untouched cells are dim
+#[cfg(feature = "heatmap")]
+fn coverage_color(heatmap: &Heatmap, addr: usize, max: u32) -> Color {
+    let scale = |counts: &[u32]| {
+        let count = counts.get(addr).copied().unwrap_or(0);
+        if count == 0 {
+            0.0
+        } else {
+            // Log scale, so a tight loop does not wash out everything run once
+            0.3 + 0.7 * (count as f32).ln_1p() / (max as f32).ln_1p()
+        }
     };
-    for line in vm.recent_instructions.iter().skip(start_idx) {
-        draw_text(line, log_x + 8.0, y, log_font_size, WHITE);
-        y += log_font_size + 2.0;
+    let (r, g, b) = (
+        scale(&heatmap.writes),
+        scale(&heatmap.executes),
+        scale(&heatmap.reads),
+    );
+    if r + g + b == 0.0 {
+        Color::new(0.15, 0.15, 0.15, 1.0)
+    } else {
+        Color::new(r, g, b, 1.0)
+    }
+}
+
+/// Draw which cells of a VM were executed, written and read since its program started
+#[cfg(feature = "heatmap")]
+fn draw_coverage<const N: usize>(
+    vm: &compute::VM<N>,
+    offset_x: f32,
+    offset_y: f32,
+    grid_size: f32,
+    padding: f32,
+) {
+    let max = vm.heatmap.max();
+    let (cols, rows) = compute::grid_shape(N);
+    let square_width = (grid_size - (cols as f32 - 1.0) * padding) / cols as f32;
+    let square_height = (grid_size - (rows as f32 - 1.0) * padding) / rows as f32;
+    for idx in 0..N {
+        let x = offset_x + (idx % cols) as f32 * (square_width + padding);
+        let y = offset_y + (idx / cols) as f32 * (square_height + padding);
+        let color = coverage_color(&vm.heatmap, idx, max);
+        draw_rectangle(x, y, square_width, square_height, color);
+        if idx == vm.pc {
+            draw_rectangle_lines(x, y, square_width, square_height, 5.0, WHITE);
+        }
     }
 }
 
+/// Draw a VM's display as a square pane, lit pixels in white
+fn draw_display(display: &Display, data: &[u8], offset_x: f32, offset_y: f32, size: f32) {
+    draw_rectangle(offset_x, offset_y, size, size, BLACK);
+    let pixel = size / DISPLAY_SIZE as f32;
+    for y in 0..DISPLAY_SIZE {
+        for x in 0..DISPLAY_SIZE {
+            if display.pixel(data, x, y) {
+                let px = offset_x + x as f32 * pixel;
+                let py = offset_y + y as f32 * pixel;
+                draw_rectangle(px, py, pixel, pixel, WHITE);
+            }
+        }
+    }
+}
+
+/// A bred program next to the program it was mutated from, with the steps each ran
+struct MutationComparison {
+    parent: [u8; compute::MEM_SIZE],
+    parent_steps: usize,
+    child: [u8; compute::MEM_SIZE],
+    child_steps: usize,
+}
+
+/// Draw parent and child side by side over the screen, outlining the mutated cells
+fn draw_comparison(comparison: &MutationComparison, padding: f32) {
+    draw_rectangle(
+        0.0,
+        0.0,
+        screen_width(),
+        screen_height(),
+        Color::new(0.0, 0.0, 0.0, 0.85),
+    );
+    let grid_size = (screen_width() / 2.0 - 60.0).min(screen_height() - 120.0);
+    let top = (screen_height() - grid_size) / 2.0;
+    let gap = 40.0;
+    let left = (screen_width() - 2.0 * grid_size - gap) / 2.0;
+    let changed = |idx: usize| (comparison.parent[idx] != comparison.child[idx]).then_some(MAGENTA);
+    let mutated = (0..compute::MEM_SIZE)
+        .filter(|&i| changed(i).is_some())
+        .count();
+    let delta = comparison.child_steps as i64 - comparison.parent_steps as i64;
+
+    draw_text(
+        &format!(
+            "Last evaluated mutation: {} bytes changed (C to close)",
+            mutated
+        ),
+        left,
+        top - 40.0,
+        24.0,
+        WHITE,
+    );
+    draw_text(
+        &format!("Parent: {} steps", comparison.parent_steps),
+        left,
+        top - 10.0,
+        20.0,
+        WHITE,
+    );
+    draw_memory(&comparison.parent, left, top, grid_size, padding, changed);
+    let child_x = left + grid_size + gap;
+    draw_text(
+        &format!("Child: {} steps ({:+})", comparison.child_steps, delta),
+        child_x,
+        top - 10.0,
+        20.0,
+        if delta > 0 { GREEN } else { RED },
+    );
+    draw_memory(&comparison.child, child_x, top, grid_size, padding, changed);
+}
+
 // Configure tracing subscriber for logging
 fn configure_tracing() {
     use tracing_subscriber::filter::LevelFilter;
@@ -85,23 +293,103 @@ fn configure_tracing() {
 async fn main() {
     configure_tracing();
 
-    let mut longest_steps: usize = 0;
+    let mut best_steps: usize = 0;
+    let mut best_fitness: Option<f64> = None;
+    let mut best_generation: u32 = 0;
     let mut best_initial_state: Option<[u8; compute::MEM_SIZE]> = None;
 
-    let mut rng = thread_rng();
+    // `--seed <n>` breeds the same programs as an earlier run; without it a fresh seed
+    // is drawn and logged
+    let seed = std::env::args()
+        .skip_while(|a| a != "--seed")
+        .nth(1)
+        .and_then(|v| v.parse().ok())
+        .unwrap_or_else(SimRng::entropy_seed);
+    info!("Seed {} (rerun with --seed {})", seed, seed);
+    let mut rng = SimRng::new(seed);
+    // `--harvard` evolves programs that cannot modify themselves
+    let harvard = std::env::args().any(|a| a == "--harvard");
+    // `--fitness <file.toml>` shapes the steps-run score before picking the best program
+    let mut fitness = match std::env::args().skip_while(|a| a != "--fitness").nth(1) {
+        Some(path) => match FitnessConfig::load(&path) {
+            Ok(config) => {
+                info!(
+                    "Loaded {} fitness shapers from {}",
+                    config.shapers.len(),
+                    path
+                );
+                FitnessPipeline::new(config)
+            }
+            Err(e) => {
+                tracing::error!("{}: {}", path, e);
+                return;
+            }
+        },
+        None => FitnessPipeline::default(),
+    };
+    // `--break <addr>` and `--watch <addr>[:read|:write]` (repeatable) pause every VM that hits them
+    let args: Vec<String> = std::env::args().collect();
+    let flag_values = |flag: &'static str| {
+        args.windows(2)
+            .filter(move |w| w[0] == flag)
+            .map(|w| w[1].clone())
+    };
+    let breakpoints: Vec<usize> = flag_values("--break")
+        .filter_map(|v| v.parse().ok())
+        .collect();
+    let watchpoints: Vec<(usize, WatchKind)> = flag_values("--watch")
+        .filter_map(|v| {
+            let (addr, kind) = v.split_once(':').unwrap_or((&v, "write"));
+            let kind = match kind {
+                "read" => WatchKind::OnRead,
+                "write" => WatchKind::OnWrite,
+                _ => return None,
+            };
+            addr.parse().ok().map(|addr| (addr, kind))
+        })
+        .collect();
+    // `--display [addr]` shows an 8x8 display read from `addr` (default 212) beside each VM
+    let display = args.iter().any(|a| a == "--display").then(|| {
+        flag_values("--display")
+            .next()
+            .and_then(|v| v.parse().ok())
+            .map_or_else(Display::default, Display::new)
+    });
+    // `--profile` counts each VM's opcodes and reports which ones every new best program ran;
+    // coverage shapers need the counts even without it
+    let report_profile = args.iter().any(|a| a == "--profile");
+    let profile = report_profile || fitness.config.needs_profile();
+    let mut successful = OpcodeProfile::default();
     // Set grid dimensions (e.g., 2x6)
     let vm_rows = 4;
     let vm_cols = 4;
     let vm_count = vm_rows * vm_cols;
     let mut vms: Vec<compute::VM> = (0..vm_count)
         .map(|_| {
-            let mut vm = compute::VM::new();
+            let mut vm = compute::VM::with_config(VmConfig::default().with_undo_depth(UNDO_STEPS));
+            vm.set_harvard(harvard);
+            vm.profile = profile.then(OpcodeProfile::default);
+            breakpoints.iter().for_each(|&addr| vm.add_breakpoint(addr));
+            watchpoints
+                .iter()
+                .for_each(|&(addr, kind)| vm.add_watchpoint(addr, kind));
             vm.randomize(&mut rng);
             vm
         })
         .collect();
+    // The program each VM was bred from and the steps it had run, once breeding starts
+    let mut parents: Vec<Option<([u8; compute::MEM_SIZE], usize)>> = vec![None; vm_count];
+    // Rounds of breeding behind each VM's current program
+    let mut generations: Vec<u32> = vec![0; vm_count];
+    let mut comparison: Option<MutationComparison> = None;
+    let mut evaluated = DuplicateFilter::new(DUPLICATE_MEMORY);
+    let mut show_comparison = false;
+    let mut listing = false;
 
     let mut paused = false;
+    let mut turbo = false;
+    #[cfg(feature = "heatmap")]
+    let mut coverage = false;
 
     let mut step_delay_ms: f64 = 10.0; // milliseconds between VM steps
     let mut last_step_time = get_time();
@@ -128,7 +416,7 @@ async fn main() {
         let start_y = (screen_height() - total_grid_height) / 2.0 + padding + extra_padding;
 
         // Arrange VMs in a vm_rows x vm_cols grid
-        for i in 0..vm_count {
+        for (i, vm) in vms.iter().enumerate() {
             let row = i / vm_cols;
             let col = i % vm_cols;
             let offset_x = start_x + col as f32 * (cell_width + padding + extra_padding);
@@ -145,7 +433,18 @@ async fn main() {
             let vm_size = cell_width.min(cell_height);
             let center_x = offset_x + (cell_width - vm_size) / 2.0;
             let center_y = offset_y + (cell_height - vm_size) / 2.0;
-            draw_vm(&vms[i], center_x, center_y, vm_size, padding);
+            draw_vm(vm, center_x, center_y, vm_size, padding, listing);
+            #[cfg(feature = "heatmap")]
+            if coverage {
+                draw_coverage(vm, center_x, center_y, vm_size, padding);
+            }
+            // The display pane fills the margin left of the memory grid
+            let pane_size = ((cell_width - vm_size) / 2.0 - 2.0 * padding).min(vm_size / 2.0);
+            if let Some(display) = &display
+                && pane_size >= DISPLAY_SIZE as f32
+            {
+                draw_display(display, vm.data(), offset_x + padding, center_y, pane_size);
+            }
         }
 
         // Toggle pause/unpause with space
@@ -168,52 +467,150 @@ async fn main() {
             info!("step_delay_ms reset to 100 ms");
         }
 
+        // Toggle turbo with 't': every frame, each VM runs until it halts or uses up its budget
+        if is_key_pressed(KeyCode::T) {
+            turbo = !turbo;
+            info!("Turbo {}", if turbo { "on" } else { "off" });
+        }
+
         // Run simulation at user-defined interval if not paused
         let now = get_time();
-        if !paused && (now - last_step_time) * 1000.0 >= step_delay_ms {
-            for vm in &mut vms {
-                vm.step();
+        let mut hits: Vec<(usize, DebugHit)> = Vec::new();
+        if !paused && turbo {
+            for (i, vm) in vms.iter_mut().enumerate() {
+                if let StepResult::Hit(hit) = vm.run_for(TURBO_STEPS) {
+                    hits.push((i, hit));
+                }
+            }
+        } else if !paused && (now - last_step_time) * 1000.0 >= step_delay_ms {
+            for (i, vm) in vms.iter_mut().enumerate() {
+                hits.extend(vm.step().map(|hit| (i, hit)));
             }
             last_step_time = now;
         }
-        // Single step forward with 's' key when paused
+        // Single step forward with 's' key when paused, reporting the program mistakes
+        // that free-running evolution ignores
         if paused && is_key_pressed(KeyCode::S) {
             info!("Single step");
-            for vm in &mut vms {
-                vm.step();
+            for (i, vm) in vms.iter_mut().enumerate() {
+                match vm.try_step() {
+                    Ok(hit) => hits.extend(hit.map(|hit| (i, hit))),
+                    Err(e) => info!("VM {}: {}", i, e),
+                }
             }
         }
+        // Single step back with 'b' key when paused, as far as each VM's journal reaches
+        if paused && is_key_pressed(KeyCode::B) {
+            let rewound = vms
+                .iter_mut()
+                .map(|vm| vm.step_back())
+                .filter(|&b| b)
+                .count();
+            info!("Step back ({} of {} VMs rewound)", rewound, vm_count);
+        }
+        // Auto-pause on breakpoints and watchpoints
+        for (i, hit) in &hits {
+            info!("VM {} hit {:?}", i, hit);
+        }
+        if !hits.is_empty() && !paused {
+            paused = true;
+            info!("Simulation paused");
+        }
+        // Toggle the coverage view with 'v'
+        #[cfg(feature = "heatmap")]
+        if is_key_pressed(KeyCode::V) {
+            coverage = !coverage;
+            info!("Coverage view {}", if coverage { "on" } else { "off" });
+        }
+        // Toggle the parent/child comparison with 'c'
+        if is_key_pressed(KeyCode::C) {
+            show_comparison = !show_comparison;
+        }
+        // Toggle the log view between the execution log and the program listing with 'd'
+        if is_key_pressed(KeyCode::D) {
+            listing = !listing;
+            info!(
+                "Log view shows the {}",
+                if listing { "listing" } else { "execution log" }
+            );
+        }
         // Toggle fullscreen with 'f' key
         if is_key_pressed(KeyCode::F) {
             set_fullscreen(true);
         }
 
         // If any VM is halted, check if it has the longest run
-        for vm in &mut vms {
+        for ((vm, parent), generation) in vms.iter_mut().zip(&mut parents).zip(&mut generations) {
             if vm.halted {
                 tracing::debug!("VM halted, generating new program and restarting");
-                if vm.total_steps_count > longest_steps {
-                    longest_steps = vm.total_steps_count;
+                if let Some((parent, parent_steps)) = *parent {
+                    comparison = Some(MutationComparison {
+                        parent,
+                        parent_steps,
+                        child: vm.initial_state,
+                        child_steps: vm.total_steps_count,
+                    });
+                }
+                evaluated.insert(analysis::canonical_hash(&vm.initial_state, vm.isa));
+                let score = fitness.shape(&Evaluation {
+                    genome: &vm.initial_state,
+                    raw: vm.total_steps_count as f64,
+                    generation: *generation,
+                    profile: vm.profile.as_ref(),
+                });
+                if best_fitness.is_none_or(|best| score > best) {
+                    best_fitness = Some(score);
+                    best_steps = vm.total_steps_count;
+                    best_generation = *generation;
                     best_initial_state = Some(vm.initial_state);
-                    // Save to file
-                    if let Ok(mut file) = File::create("best_vm_program.bin") {
-                        let _ = file.write_all(&vm.initial_state);
+                    // Save to file, with the run it came from
+                    let program = Program {
+                        genome: vm.initial_state.to_vec(),
+                        isa: vm.isa,
+                    };
+                    let metadata = ProgramMetadata {
+                        seed,
+                        generation: *generation,
+                        fitness: score,
+                    };
+                    if ProgramFile::new(program, metadata)
+                        .save("best_vm_program.bin")
+                        .is_ok()
+                    {
                         info!(
-                            "Saved best initial_state to best_vm_program.bin (steps: {})",
-                            longest_steps
+                            "Saved best initial_state to best_vm_program.bin (steps: {}, fitness: {:.1})",
+                            best_steps, score
                         );
                     }
+                    if report_profile && let Some(profile) = &vm.profile {
+                        successful.merge(profile);
+                        info!("Opcodes run by the new best program: {}", profile);
+                        info!("Opcodes run by all best programs so far: {}", successful);
+                    }
                 }
-                // Genetic evolution: use best VM, then partial_randomize
+                // Genetic evolution: use best VM, then partial_randomize, drawing again
+                // while the child only repeats a program already run
                 if let Some(best) = best_initial_state {
-                    vm.memory.copy_from_slice(&best);
-                    vm.initial_state.copy_from_slice(&best);
-                    vm.partial_randomize(&mut rng);
+                    for _ in 0..MAX_DUPLICATE_REDRAWS {
+                        vm.memory.copy_from_slice(&best);
+                        vm.initial_state.copy_from_slice(&best);
+                        vm.partial_randomize(&mut rng);
+                        if !evaluated.contains(analysis::canonical_hash(&vm.initial_state, vm.isa))
+                        {
+                            break;
+                        }
+                        tracing::debug!("Skipping a child identical to an evaluated program");
+                    }
+                    *parent = Some((best, best_steps));
+                    *generation = best_generation + 1;
                 } else {
                     vm.randomize(&mut rng);
                 }
             }
         }
+        if show_comparison && let Some(comparison) = &comparison {
+            draw_comparison(comparison, padding);
+        }
         next_frame().await;
     }
 }
@@ -0,0 +1,78 @@
+// memory_map.rs
+
+// Named regions of VM memory, used to document and visualize a program's layout
+
+use std::ops::Range;
+
+use crate::compute::MEM_SIZE;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RegionKind {
+    Code,
+    Scratch,
+    Stack,
+    Sensors,
+    Actuators,
+    Display,
+}
+
+impl std::fmt::Display for RegionKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            RegionKind::Code => "CODE",
+            RegionKind::Scratch => "SCRATCH",
+            RegionKind::Stack => "STACK",
+            RegionKind::Sensors => "SENSORS",
+            RegionKind::Actuators => "ACTUATORS",
+            RegionKind::Display => "DISPLAY",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A contiguous, named range of addresses
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryRegion {
+    pub name: String,
+    pub kind: RegionKind,
+    pub range: Range<usize>,
+}
+
+/// Layout of a VM's address space as declared by the host or program
+#[derive(Debug, Clone, Default)]
+pub struct MemoryMap {
+    regions: Vec<MemoryRegion>,
+}
+
+impl MemoryMap {
+    pub fn new() -> Self {
+        MemoryMap {
+            regions: Vec::new(),
+        }
+    }
+
+    /// Declare a region; the range is clamped to the VM's address space
+    pub fn with_region(mut self, name: &str, kind: RegionKind, range: Range<usize>) -> Self {
+        self.declare(name, kind, range);
+        self
+    }
+
+    /// Declare a region in place; later declarations win where ranges overlap
+    pub fn declare(&mut self, name: &str, kind: RegionKind, range: Range<usize>) {
+        let range = range.start.min(MEM_SIZE)..range.end.min(MEM_SIZE);
+        self.regions.push(MemoryRegion {
+            name: name.to_string(),
+            kind,
+            range,
+        });
+    }
+
+    pub fn regions(&self) -> &[MemoryRegion] {
+        &self.regions
+    }
+
+    /// Find the region covering `addr`, if any
+    pub fn region_at(&self, addr: usize) -> Option<&MemoryRegion> {
+        self.regions.iter().rev().find(|r| r.range.contains(&addr))
+    }
+}
@@ -0,0 +1,400 @@
+// migration.rs
+
+// Live exchange of creatures between two running worlds over TCP
+
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TryRecvError, TrySendError};
+use std::thread;
+use std::time::Duration;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::bundle::{BUNDLE_FORMAT_VERSION, CreatureRecord};
+
+/// Frames larger than this are treated as a protocol error rather than allocated
+const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+// Link constants
+pub const MIGRATION_QUEUE_LEN: usize = 16; // Batches buffered each way; more emigrants are refused
+const ERROR_QUEUE_LEN: usize = 16; // Connection errors kept for `take_error`; later ones are only logged
+const DISCONNECT_POLL: Duration = Duration::from_millis(100); // How often idle threads check their peer and link
+
+/// One batch of emigrants sent to the peer world
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct MigrationMessage {
+    format_version: u32,
+    creatures: Vec<CreatureRecord>,
+}
+
+/// A connection to one peer world.
+///
+/// Networking runs on background threads; the simulation only exchanges
+/// batches of creatures through bounded channels and never blocks on the socket.
+/// Connection errors, including the peer hanging up, come back through `take_error`.
+pub struct MigrationLink {
+    outgoing: SyncSender<Vec<CreatureRecord>>,
+    incoming: Receiver<Vec<CreatureRecord>>,
+    errors: Receiver<std::io::Error>,
+    connected: Arc<AtomicBool>,
+    /// Address a listening link accepts peers on
+    local_addr: Option<SocketAddr>,
+}
+
+/// The background threads' ends of a `MigrationLink`
+struct PeerChannels {
+    outgoing: Receiver<Vec<CreatureRecord>>,
+    incoming: SyncSender<Vec<CreatureRecord>>,
+    errors: SyncSender<std::io::Error>,
+    connected: Arc<AtomicBool>,
+}
+
+impl PeerChannels {
+    fn report(&self, error: std::io::Error) {
+        tracing::debug!("Migration link: {}", error);
+        let _ = self.errors.try_send(error);
+    }
+}
+
+impl MigrationLink {
+    /// Accept peers on `addr` in the background, one at a time: when a peer
+    /// disconnects, the next one to connect takes its place. Batches still queued
+    /// for a peer that left are dropped rather than sent to the next one.
+    ///
+    /// A failing `accept` stops the listener; its error comes back through `take_error`.
+    /// Dropping the link closes the listener within `DISCONNECT_POLL`.
+    pub fn listen(addr: impl ToSocketAddrs) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        // Polled, so the thread notices the link being dropped while no peer is attached
+        listener.set_nonblocking(true)?;
+        let (mut link, peer) = Self::channels();
+        link.local_addr = Some(listener.local_addr()?);
+        thread::spawn(move || accept_peers(listener, &peer));
+        Ok(link)
+    }
+
+    /// Connect to a peer that is listening on `addr`
+    pub fn connect(addr: impl ToSocketAddrs) -> std::io::Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+        tracing::info!("Connected to migration peer {}", stream.peer_addr()?);
+        let (link, peer) = Self::channels();
+        thread::spawn(move || run_peer(stream, &peer));
+        Ok(link)
+    }
+
+    fn channels() -> (Self, PeerChannels) {
+        let (out_tx, out_rx) = mpsc::sync_channel(MIGRATION_QUEUE_LEN);
+        let (in_tx, in_rx) = mpsc::sync_channel(MIGRATION_QUEUE_LEN);
+        let (err_tx, err_rx) = mpsc::sync_channel(ERROR_QUEUE_LEN);
+        let connected = Arc::new(AtomicBool::new(false));
+        let link = MigrationLink {
+            outgoing: out_tx,
+            incoming: in_rx,
+            errors: err_rx,
+            connected: connected.clone(),
+            local_addr: None,
+        };
+        let peer = PeerChannels {
+            outgoing: out_rx,
+            incoming: in_tx,
+            errors: err_tx,
+            connected,
+        };
+        (link, peer)
+    }
+
+    /// Where a link made by `listen` accepts peers, e.g. the port picked for `:0`
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+
+    /// Whether a peer is currently attached; emigrants sent while disconnected are lost
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    /// Queue a batch for the peer.
+    ///
+    /// Fails with `NotConnected` when no peer is attached and with `WouldBlock` when
+    /// `MIGRATION_QUEUE_LEN` batches are already waiting; the batch is lost either way.
+    pub fn send_emigrants(&self, creatures: Vec<CreatureRecord>) -> std::io::Result<()> {
+        if creatures.is_empty() {
+            return Ok(());
+        }
+        if !self.is_connected() {
+            return Err(std::io::ErrorKind::NotConnected.into());
+        }
+        self.outgoing.try_send(creatures).map_err(|e| match e {
+            TrySendError::Full(_) => std::io::ErrorKind::WouldBlock.into(),
+            TrySendError::Disconnected(_) => std::io::ErrorKind::NotConnected.into(),
+        })
+    }
+
+    /// Drain every creature that has arrived since the last call
+    pub fn receive_immigrants(&self) -> Vec<CreatureRecord> {
+        self.incoming.try_iter().flatten().collect()
+    }
+
+    /// The oldest connection error not yet taken, such as the peer disconnecting
+    pub fn take_error(&self) -> Option<std::io::Error> {
+        self.errors.try_recv().ok()
+    }
+}
+
+/// Serve each peer `listener` accepts in turn until the `MigrationLink` is dropped
+fn accept_peers(listener: TcpListener, peer: &PeerChannels) {
+    loop {
+        // Emigrants queued for a peer that has left are lost, like any sent while disconnected
+        loop {
+            match peer.outgoing.try_recv() {
+                Ok(_) => {}
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return,
+            }
+        }
+        match listener.accept() {
+            Ok((stream, addr)) => {
+                tracing::info!("Migration peer connected from {}", addr);
+                if let Err(e) = stream.set_nonblocking(false) {
+                    peer.report(e);
+                    continue;
+                }
+                if !run_peer(stream, peer) {
+                    return;
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => thread::sleep(DISCONNECT_POLL),
+            Err(e) => {
+                peer.report(e);
+                return;
+            }
+        }
+    }
+}
+
+/// Exchange batches with one peer until either side hangs up, returning `false`
+/// once the `MigrationLink` has been dropped
+fn run_peer(stream: TcpStream, peer: &PeerChannels) -> bool {
+    let reader = match stream.try_clone() {
+        Ok(s) => s,
+        Err(e) => {
+            peer.report(e);
+            return true;
+        }
+    };
+    peer.connected.store(true, Ordering::Relaxed);
+
+    let reader_connected = peer.connected.clone();
+    let incoming = peer.incoming.clone();
+    let reader = thread::spawn(move || {
+        let mut reader = BufReader::new(reader);
+        let error = loop {
+            match read_message(&mut reader) {
+                Ok(message) => {
+                    if incoming.send(message.creatures).is_err() {
+                        break None;
+                    }
+                }
+                Err(e) => break Some(e),
+            }
+        };
+        reader_connected.store(false, Ordering::Relaxed);
+        error
+    });
+
+    let mut writer = BufWriter::new(stream);
+    let mut link_alive = true;
+    let mut write_failed = false;
+    while peer.connected.load(Ordering::Relaxed) {
+        let creatures = match peer.outgoing.recv_timeout(DISCONNECT_POLL) {
+            Ok(creatures) => creatures,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => {
+                link_alive = false;
+                break;
+            }
+        };
+        let message = MigrationMessage {
+            format_version: BUNDLE_FORMAT_VERSION,
+            creatures,
+        };
+        if let Err(e) = write_message(&mut writer, &message) {
+            peer.report(e);
+            write_failed = true;
+            break;
+        }
+    }
+
+    // Wake the reader if it is still waiting on the socket
+    peer.connected.store(false, Ordering::Relaxed);
+    let _ = writer.get_ref().shutdown(Shutdown::Both);
+    let read_error = reader.join().ok().flatten();
+    // Only the reader saw why the connection ended unless we closed it ourselves
+    if let Some(e) = read_error
+        && link_alive
+        && !write_failed
+    {
+        peer.report(match e.kind() {
+            std::io::ErrorKind::UnexpectedEof => std::io::Error::new(
+                std::io::ErrorKind::ConnectionAborted,
+                "migration peer disconnected",
+            ),
+            _ => e,
+        });
+    }
+    link_alive
+}
+
+/// Frames are a big-endian u32 length followed by a CBOR-encoded message
+fn write_message(writer: &mut impl Write, message: &MigrationMessage) -> std::io::Result<()> {
+    let mut payload = Vec::new();
+    ciborium::into_writer(message, &mut payload)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))?;
+    writer.write_all(&(payload.len() as u32).to_be_bytes())?;
+    writer.write_all(&payload)?;
+    writer.flush()
+}
+
+fn read_message(reader: &mut impl Read) -> std::io::Result<MigrationMessage> {
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len)?;
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_FRAME_SIZE {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("migration frame of {} bytes is too large", len),
+        ));
+    }
+
+    let mut payload = vec![0u8; len];
+    reader.read_exact(&mut payload)?;
+    let message: MigrationMessage = ciborium::from_reader(payload.as_slice())
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))?;
+    if message.format_version != BUNDLE_FORMAT_VERSION {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("unsupported format version {}", message.format_version),
+        ));
+    }
+    Ok(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::isa::IsaVersion;
+
+    fn record(energy: f32) -> CreatureRecord {
+        CreatureRecord {
+            genome: vec![0xFF],
+            memory: vec![0xFF],
+            x: 0.0,
+            y: 0.0,
+            energy,
+            age: 0,
+            color: [1.0; 3],
+            tag: None,
+            sandboxed: false,
+            species: None,
+            isa: IsaVersion::LATEST,
+        }
+    }
+
+    fn wait_until(mut done: impl FnMut() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !done() {
+            assert!(Instant::now() < deadline, "timed out");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn receive(link: &MigrationLink) -> Vec<CreatureRecord> {
+        let mut received = Vec::new();
+        wait_until(|| {
+            received.extend(link.receive_immigrants());
+            !received.is_empty()
+        });
+        received
+    }
+
+    #[test]
+    fn listener_reports_disconnects_and_accepts_the_next_peer() {
+        let host = MigrationLink::listen("127.0.0.1:0").unwrap();
+        let addr = host.local_addr().unwrap();
+        assert_eq!(
+            host.send_emigrants(vec![record(1.0)]).unwrap_err().kind(),
+            std::io::ErrorKind::NotConnected
+        );
+
+        let first = MigrationLink::connect(addr).unwrap();
+        wait_until(|| host.is_connected() && first.is_connected());
+        first.send_emigrants(vec![record(1.0)]).unwrap();
+        assert_eq!(receive(&host)[0].energy, 1.0);
+
+        drop(first);
+        let mut error = None;
+        wait_until(|| {
+            error = host.take_error();
+            error.is_some()
+        });
+        assert_eq!(
+            error.map(|e| e.kind()),
+            Some(std::io::ErrorKind::ConnectionAborted)
+        );
+
+        let second = MigrationLink::connect(addr).unwrap();
+        wait_until(|| host.is_connected());
+        host.send_emigrants(vec![record(2.0)]).unwrap();
+        assert_eq!(receive(&second)[0].energy, 2.0);
+        assert!(host.take_error().is_none());
+    }
+
+    #[test]
+    fn outgoing_queue_is_bounded() {
+        let host = MigrationLink::listen("127.0.0.1:0").unwrap();
+        let stream = TcpStream::connect(host.local_addr().unwrap()).unwrap();
+        wait_until(|| host.is_connected());
+
+        // Nobody reads from `stream`, so the socket buffer and then the queue fill up
+        let big = vec![record(0.0); 1024];
+        let full = (0..10_000).find_map(|_| host.send_emigrants(big.clone()).err());
+        assert_eq!(full.map(|e| e.kind()), Some(std::io::ErrorKind::WouldBlock));
+        drop(stream);
+    }
+
+    #[test]
+    fn batches_for_a_departed_peer_are_dropped() {
+        let host = MigrationLink::listen("127.0.0.1:0").unwrap();
+        let addr = host.local_addr().unwrap();
+        let stream = TcpStream::connect(addr).unwrap();
+        wait_until(|| host.is_connected());
+        let big = vec![record(0.0); 1024];
+        let full = (0..10_000).find_map(|_| host.send_emigrants(big.clone()).err());
+        assert_eq!(full.map(|e| e.kind()), Some(std::io::ErrorKind::WouldBlock));
+
+        drop(stream);
+        wait_until(|| !host.is_connected());
+        let next = MigrationLink::connect(addr).unwrap();
+        wait_until(|| host.is_connected());
+        host.send_emigrants(vec![record(2.0)]).unwrap();
+        let received = receive(&next);
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].energy, 2.0);
+    }
+
+    #[test]
+    fn dropping_a_listener_releases_its_port() {
+        let host = MigrationLink::listen("127.0.0.1:0").unwrap();
+        let addr = host.local_addr().unwrap();
+        drop(host);
+        wait_until(|| TcpListener::bind(addr).is_ok());
+        let again = MigrationLink::listen(addr).unwrap();
+        assert_eq!(again.local_addr(), Some(addr));
+    }
+}
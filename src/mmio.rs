@@ -0,0 +1,306 @@
+// mmio.rs
+
+// Memory-mapped I/O: host handlers for ranges of VM memory, and rate limits on actuator writes
+
+use std::any::Any;
+use std::fmt;
+use std::ops::RangeInclusive;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::compute::{AddressWidth, VM};
+
+/// What happens to a write that arrives before its address has cooled down
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum OverLimit {
+    /// The store is reverted as if it never happened
+    Ignore,
+    /// The store goes through but costs the writer this much energy
+    Charge(f32),
+}
+
+/// Accept at most one write to `addr` every `period` ticks
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RateLimit {
+    pub addr: usize,
+    pub period: u32,
+    pub over_limit: OverLimit,
+}
+
+/// Outcome of checking a step's store against the rate limits
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WriteVerdict {
+    /// No store, or one to an address without a limit
+    Unlimited,
+    Accepted,
+    Ignored,
+    Charged(f32),
+}
+
+/// Tick of the last accepted write to each limited address, kept per VM
+#[derive(Debug, Clone, Default)]
+pub struct RateLimitState {
+    last_accepted: Vec<(usize, u32)>,
+}
+
+impl RateLimitState {
+    /// Apply `limits` to the store made by the VM's last step, reverting it if ignored
+    pub fn check<const N: usize, A: AddressWidth>(
+        &mut self,
+        limits: &[RateLimit],
+        vm: &mut VM<N, A>,
+        now: u32,
+    ) -> WriteVerdict {
+        let Some(write) = vm.last_write else {
+            return WriteVerdict::Unlimited;
+        };
+        let Some(limit) = limits.iter().find(|l| l.addr == write.addr) else {
+            return WriteVerdict::Unlimited;
+        };
+
+        let last = self
+            .last_accepted
+            .iter_mut()
+            .find(|(a, _)| *a == write.addr);
+        let cooled_down = last
+            .as_ref()
+            .is_none_or(|(_, tick)| now.wrapping_sub(*tick) >= limit.period);
+        if cooled_down {
+            match last {
+                Some((_, tick)) => *tick = now,
+                None => self.last_accepted.push((write.addr, now)),
+            }
+            return WriteVerdict::Accepted;
+        }
+
+        match limit.over_limit {
+            OverLimit::Ignore => {
+                vm.undo_last_write();
+                WriteVerdict::Ignored
+            }
+            OverLimit::Charge(cost) => WriteVerdict::Charged(cost),
+        }
+    }
+}
+
+/// Host callbacks for a range of a VM's data memory, attached with `VM::map_io`.
+///
+/// Relative to `VM::step` the hooks run in a fixed order: `before_step` first, even
+/// in a step that finds the program halted, so what it stores is what the instruction
+/// sees; then `read` for each load of a mapped byte; then `write` as soon as a store
+/// to a mapped byte lands, before the step returns. Host writes through
+/// `VM::data_mut` bypass every hook, and `VM::step_back` does not rewind handlers.
+/// Handlers must be `Clone` because cloning a VM clones its handlers.
+pub trait MmioHandler: MmioHandlerClone + Any + Send {
+    /// Refresh the mapped cells, `cells[0]` being the first mapped address
+    fn before_step(&mut self, cells: &mut [u8]) {
+        let _ = cells;
+    }
+
+    /// Value a program load from `addr` sees; `stored` is the byte in memory
+    fn read(&mut self, addr: usize, stored: u8) -> u8 {
+        let _ = addr;
+        stored
+    }
+
+    /// A program store of `value` to `addr`, already in memory
+    fn write(&mut self, addr: usize, value: u8) {
+        let _ = (addr, value);
+    }
+}
+
+/// Object-safe cloning for boxed handlers, implemented for every `Clone` handler
+pub trait MmioHandlerClone {
+    fn clone_box(&self) -> Box<dyn MmioHandler>;
+}
+
+impl<T: MmioHandler + Clone> MmioHandlerClone for T {
+    fn clone_box(&self) -> Box<dyn MmioHandler> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn MmioHandler> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MmioError {
+    /// The range reaches past the end of the VM's memory
+    OutOfRange(RangeInclusive<usize>),
+    /// Part of the range is already mapped to another handler
+    Overlaps(RangeInclusive<usize>),
+}
+
+impl fmt::Display for MmioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MmioError::OutOfRange(range) => write!(
+                f,
+                "range {}..={} is outside the VM's memory",
+                range.start(),
+                range.end()
+            ),
+            MmioError::Overlaps(range) => write!(
+                f,
+                "range {}..={} overlaps a mapped range",
+                range.start(),
+                range.end()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MmioError {}
+
+/// Handlers mapped into one VM, kept across loads and restarts
+#[derive(Clone, Default)]
+pub struct MmioMap {
+    mappings: Vec<(RangeInclusive<usize>, Box<dyn MmioHandler>)>,
+}
+
+impl fmt::Debug for MmioMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ranges: Vec<_> = self.mappings.iter().map(|(r, _)| r).collect();
+        f.debug_struct("MmioMap").field("mapped", &ranges).finish()
+    }
+}
+
+impl MmioMap {
+    /// Map `handler` over `range` of a memory of `size` bytes
+    pub fn map(
+        &mut self,
+        range: RangeInclusive<usize>,
+        handler: Box<dyn MmioHandler>,
+        size: usize,
+    ) -> Result<(), MmioError> {
+        if range.is_empty() || *range.end() >= size {
+            return Err(MmioError::OutOfRange(range));
+        }
+        if self
+            .mappings
+            .iter()
+            .any(|(r, _)| r.start() <= range.end() && range.start() <= r.end())
+        {
+            return Err(MmioError::Overlaps(range));
+        }
+        self.mappings.push((range, handler));
+        Ok(())
+    }
+
+    /// Remove the handler mapped over `addr`, returning it
+    pub fn unmap(&mut self, addr: usize) -> Option<Box<dyn MmioHandler>> {
+        let index = self.mappings.iter().position(|(r, _)| r.contains(&addr))?;
+        Some(self.mappings.swap_remove(index).1)
+    }
+
+    /// The first mapped handler of type `T`
+    pub fn handler_mut<T: MmioHandler>(&mut self) -> Option<&mut T> {
+        self.mappings.iter_mut().find_map(|(_, handler)| {
+            let handler: &mut dyn Any = handler.as_mut();
+            handler.downcast_mut::<T>()
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mappings.is_empty()
+    }
+
+    pub fn ranges(&self) -> impl Iterator<Item = &RangeInclusive<usize>> {
+        self.mappings.iter().map(|(r, _)| r)
+    }
+
+    /// Run every handler's `before_step` over its cells of `memory`
+    pub fn before_step(&mut self, memory: &mut [u8]) {
+        for (range, handler) in &mut self.mappings {
+            if let Some(cells) = memory.get_mut(range.clone()) {
+                handler.before_step(cells);
+            }
+        }
+    }
+
+    /// What a load from `addr` gives, `stored` unless a handler maps it
+    pub fn read(&mut self, addr: usize, stored: u8) -> u8 {
+        match self.mappings.iter_mut().find(|(r, _)| r.contains(&addr)) {
+            Some((_, handler)) => handler.read(addr, stored),
+            None => stored,
+        }
+    }
+
+    /// Tell the handler mapping `addr`, if any, about a store
+    pub fn write(&mut self, addr: usize, value: u8) {
+        if let Some((_, handler)) = self.mappings.iter_mut().find(|(r, _)| r.contains(&addr)) {
+            handler.write(addr, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compute::{Instruction, VmConfig};
+
+    const LIMITED: u8 = 0xF0;
+
+    #[test]
+    fn ignored_writes_leave_no_trace_for_step_back() {
+        let limits = [RateLimit {
+            addr: LIMITED as usize,
+            period: 10,
+            over_limit: OverLimit::Ignore,
+        }];
+        let mut vm: VM = VM::with_config(VmConfig {
+            undo_depth: 4,
+            ..VmConfig::default()
+        });
+        let sta = Instruction::STA as u8;
+        vm.load_program(&[sta, LIMITED, Instruction::INC as u8, sta, LIMITED]);
+        vm.acc = 7;
+        let mut state = RateLimitState::default();
+
+        vm.step();
+        assert_eq!(state.check(&limits, &mut vm, 0), WriteVerdict::Accepted);
+        vm.step();
+        let before = vm.snapshot();
+
+        vm.step();
+        assert_eq!(state.check(&limits, &mut vm, 1), WriteVerdict::Ignored);
+        assert_eq!(vm.memory[LIMITED as usize], 7);
+        assert_eq!(vm.metrics().memory_writes, 1);
+        assert_eq!(vm.last_write, None);
+
+        assert!(vm.step_back());
+        assert_eq!(vm.snapshot(), before);
+        assert_eq!(vm.metrics().memory_writes, 1);
+        assert!(vm.step_back());
+        assert!(vm.step_back());
+        assert_eq!(vm.memory[LIMITED as usize], 0);
+        assert_eq!(vm.metrics().memory_writes, 0);
+    }
+
+    #[test]
+    fn charged_writes_still_land() {
+        let limits = [RateLimit {
+            addr: LIMITED as usize,
+            period: 10,
+            over_limit: OverLimit::Charge(2.5),
+        }];
+        let mut vm: VM = VM::new();
+        let sta = Instruction::STA as u8;
+        vm.load_program(&[sta, LIMITED, Instruction::INC as u8, sta, LIMITED]);
+        let mut state = RateLimitState::default();
+
+        vm.step();
+        assert_eq!(state.check(&limits, &mut vm, 0), WriteVerdict::Accepted);
+        vm.step();
+        vm.step();
+        assert_eq!(state.check(&limits, &mut vm, 1), WriteVerdict::Charged(2.5));
+        assert_eq!(vm.memory[LIMITED as usize], 1);
+        assert_eq!(vm.metrics().memory_writes, 2);
+    }
+}
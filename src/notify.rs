@@ -0,0 +1,326 @@
+// notify.rs
+
+// Milestone notifications for long runs: desktop popups and webhook calls
+
+use std::fmt;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::mpsc::{self, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::world::World;
+
+// Notification constants
+pub const NEW_BEST_MARGIN: f64 = 0.1; // Fitness must beat the last reported best by this fraction
+pub const NEW_BEST_COOLDOWN: u64 = 1000; // Ticks between new best reports
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10); // Per connect, read and write
+const NOTIFICATION_TITLE: &str = "Bacteria VM"; // Heading of desktop notifications
+
+/// Something worth telling the user about
+#[derive(Debug, Clone, PartialEq)]
+pub enum Milestone {
+    /// Mean lifeform age, in simulated seconds, beat the best reported so far
+    NewBest { fitness: f64, tick: u64 },
+    /// The last lifeform of `generation` died; the world restarts from random programs
+    Extinction { generation: u32, tick: u64 },
+    /// The population was saved to `path`
+    Checkpoint { path: PathBuf, tick: u64 },
+}
+
+impl Milestone {
+    /// Short machine-readable name, sent as `event` to webhooks
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Milestone::NewBest { .. } => "new_best",
+            Milestone::Extinction { .. } => "extinction",
+            Milestone::Checkpoint { .. } => "checkpoint",
+        }
+    }
+
+    pub fn tick(&self) -> u64 {
+        match *self {
+            Milestone::NewBest { tick, .. }
+            | Milestone::Extinction { tick, .. }
+            | Milestone::Checkpoint { tick, .. } => tick,
+        }
+    }
+}
+
+impl fmt::Display for Milestone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Milestone::NewBest { fitness, tick } => {
+                write!(f, "New best fitness {:.1} s at tick {}", fitness, tick)
+            }
+            Milestone::Extinction { generation, tick } => {
+                write!(f, "Generation {} went extinct at tick {}", generation, tick)
+            }
+            Milestone::Checkpoint { path, tick } => {
+                write!(f, "Checkpoint {} written at tick {}", path.display(), tick)
+            }
+        }
+    }
+}
+
+/// Spots new bests and extinctions by looking at the world after each tick.
+///
+/// Mean age climbs steadily while a generation lives, so a new best is only
+/// reported once it beats the last report by `NEW_BEST_MARGIN` and at most once
+/// every `NEW_BEST_COOLDOWN` ticks.
+#[derive(Debug, Clone, Default)]
+pub struct MilestoneTracker {
+    best: f64,
+    reported_best: f64,
+    reported_at: Option<u64>,
+    /// Generation seen last time; the world starts a new one when the old one dies out
+    generation: Option<u32>,
+}
+
+impl MilestoneTracker {
+    pub fn observe(&mut self, world: &World) -> Option<Milestone> {
+        let tick = world.tick_count();
+        let previous = self.generation.replace(world.generation);
+        if let Some(generation) = previous
+            && generation != world.generation
+        {
+            return Some(Milestone::Extinction { generation, tick });
+        }
+
+        self.best = self.best.max(world.fitness());
+        let cooled_down = self
+            .reported_at
+            .is_none_or(|at| tick - at >= NEW_BEST_COOLDOWN);
+        if cooled_down
+            && self.best > 0.0
+            && self.best > self.reported_best * (1.0 + NEW_BEST_MARGIN)
+        {
+            self.reported_best = self.best;
+            self.reported_at = Some(tick);
+            return Some(Milestone::NewBest {
+                fitness: self.best,
+                tick,
+            });
+        }
+        None
+    }
+}
+
+/// Where notifications go
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotifyTarget {
+    /// A desktop notification through `notify-send`, or `osascript` on macOS
+    Desktop,
+    /// A JSON POST to a plain `http://` URL
+    Webhook(String),
+}
+
+impl NotifyTarget {
+    /// `desktop`, or a webhook URL
+    pub fn parse(spec: &str) -> Result<Self, NotifyError> {
+        match spec {
+            "desktop" => Ok(NotifyTarget::Desktop),
+            url => WebhookUrl::parse(url).map(|_| NotifyTarget::Webhook(url.to_string())),
+        }
+    }
+
+    fn deliver(&self, milestone: &Milestone) -> Result<(), NotifyError> {
+        match self {
+            NotifyTarget::Desktop => desktop_notification(&milestone.to_string()),
+            NotifyTarget::Webhook(url) => post_webhook(&WebhookUrl::parse(url)?, milestone),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum NotifyError {
+    Io(std::io::Error),
+    /// Not a plain `http://host[:port][/path]` URL
+    InvalidUrl(String),
+    /// The notifier or webhook answered with an error
+    Rejected(String),
+}
+
+impl fmt::Display for NotifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NotifyError::Io(e) => write!(f, "notification I/O error: {}", e),
+            NotifyError::InvalidUrl(url) => write!(
+                f,
+                "unsupported webhook URL {:?}; expected http://host[:port][/path]",
+                url
+            ),
+            NotifyError::Rejected(e) => write!(f, "notification rejected: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for NotifyError {}
+
+impl From<std::io::Error> for NotifyError {
+    fn from(e: std::io::Error) -> Self {
+        NotifyError::Io(e)
+    }
+}
+
+/// Sends milestones to every target on a background thread.
+///
+/// A slow or unreachable target only delays later notifications, never the
+/// simulation; failures are logged and otherwise ignored.
+pub struct Notifier {
+    outgoing: Option<Sender<Milestone>>,
+    sender: Option<JoinHandle<()>>,
+}
+
+impl Notifier {
+    pub fn start(targets: Vec<NotifyTarget>) -> Self {
+        let (tx, rx) = mpsc::channel::<Milestone>();
+        let sender = thread::spawn(move || {
+            for milestone in rx {
+                for target in &targets {
+                    if let Err(e) = target.deliver(&milestone) {
+                        tracing::warn!("Failed to notify {:?}: {}", target, e);
+                    }
+                }
+            }
+        });
+        Self {
+            outgoing: Some(tx),
+            sender: Some(sender),
+        }
+    }
+
+    pub fn notify(&self, milestone: Milestone) {
+        tracing::info!("{}", milestone);
+        if let Some(outgoing) = &self.outgoing {
+            let _ = outgoing.send(milestone);
+        }
+    }
+
+    /// Wait until every queued notification has been delivered or has failed
+    pub fn finish(mut self) {
+        self.shut_down();
+    }
+
+    fn shut_down(&mut self) {
+        self.outgoing = None;
+        if let Some(sender) = self.sender.take()
+            && sender.join().is_err()
+        {
+            tracing::error!("Notification sender panicked");
+        }
+    }
+}
+
+impl Drop for Notifier {
+    fn drop(&mut self) {
+        self.shut_down();
+    }
+}
+
+fn desktop_notification(text: &str) -> Result<(), NotifyError> {
+    let status = if cfg!(target_os = "macos") {
+        let script = format!(
+            "display notification {:?} with title {:?}",
+            text, NOTIFICATION_TITLE
+        );
+        Command::new("osascript").args(["-e", &script]).status()?
+    } else {
+        Command::new("notify-send")
+            .args([NOTIFICATION_TITLE, text])
+            .status()?
+    };
+    if !status.success() {
+        return Err(NotifyError::Rejected(format!(
+            "notifier exited with {}",
+            status
+        )));
+    }
+    Ok(())
+}
+
+/// The parts of an `http://` URL needed to send a request
+struct WebhookUrl<'a> {
+    host: &'a str,
+    port: u16,
+    path: &'a str,
+}
+
+impl<'a> WebhookUrl<'a> {
+    fn parse(url: &'a str) -> Result<Self, NotifyError> {
+        let invalid = || NotifyError::InvalidUrl(url.to_string());
+        let rest = url.strip_prefix("http://").ok_or_else(invalid)?;
+        let (authority, path) = match rest.find('/') {
+            Some(i) => rest.split_at(i),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.split_once(':') {
+            Some((host, port)) => (host, port.parse().map_err(|_| invalid())?),
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self { host, port, path })
+    }
+}
+
+fn post_webhook(url: &WebhookUrl<'_>, milestone: &Milestone) -> Result<(), NotifyError> {
+    let body = format!(
+        "{{\"event\":\"{}\",\"tick\":{},\"text\":\"{}\"}}",
+        milestone.kind(),
+        milestone.tick(),
+        json_escape(&milestone.to_string())
+    );
+    let mut stream = connect(url)?;
+    stream.set_read_timeout(Some(WEBHOOK_TIMEOUT))?;
+    stream.set_write_timeout(Some(WEBHOOK_TIMEOUT))?;
+    write!(
+        stream,
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        url.path,
+        url.host,
+        body.len(),
+        body
+    )?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    let status_line = response.lines().next().unwrap_or_default();
+    match status_line.split_whitespace().nth(1) {
+        Some(code) if code.starts_with('2') => Ok(()),
+        _ => Err(NotifyError::Rejected(status_line.to_string())),
+    }
+}
+
+/// Connect to the first address `url` resolves to that answers within `WEBHOOK_TIMEOUT`
+fn connect(url: &WebhookUrl<'_>) -> std::io::Result<TcpStream> {
+    let mut last_error = None;
+    for addr in (url.host, url.port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, WEBHOOK_TIMEOUT) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("{} did not resolve", url.host),
+        )
+    }))
+}
+
+pub(crate) fn json_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,443 @@
+// peephole.rs
+
+// Peephole optimizer dropping junk instructions from evolved programs, with a replay check that the result still behaves the same
+
+use std::fmt;
+
+use crate::analysis::{self, ControlFlowGraph};
+use crate::compute::{Instruction, MEM_SIZE, VM, VmConfig, relative_target};
+use crate::disasm::DecodedInstruction;
+use crate::isa::IsaVersion;
+
+/// A junk pattern the optimizer removes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rewrite {
+    /// NOP
+    Nop,
+    /// INC then DEC, or DEC then INC, which leave the accumulator and flags as they were
+    IncDec,
+    /// A jump, taken or not, to the instruction right after it
+    JumpToNext,
+    /// LDA or LDI whose value the next load overwrites
+    OverwrittenLoad,
+    /// LDA of the address the previous STA just stored the accumulator to
+    ReloadAfterStore,
+}
+
+impl Rewrite {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Rewrite::Nop => "NOP",
+            Rewrite::IncDec => "INC/DEC pair",
+            Rewrite::JumpToNext => "jump to next instruction",
+            Rewrite::OverwrittenLoad => "overwritten load",
+            Rewrite::ReloadAfterStore => "reload after store",
+        }
+    }
+}
+
+/// Instructions dropped by one rewrite
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Removal {
+    pub rewrite: Rewrite,
+    /// Where the instructions started in the program as it stood when the rewrite applied
+    pub addr: usize,
+    pub bytes: usize,
+}
+
+/// Why a program was left as it was
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptimizeError {
+    /// CALL, RET, PUSH or POP: stack contents move with the code or are read back unseen
+    UsesStack,
+    /// A reachable instruction reads or writes `addr`, which holds reachable code
+    TouchesCode { addr: usize },
+    /// Reachable instructions at `addr` and before it share bytes
+    OverlappingCode { addr: usize },
+    /// COPY reads and writes wherever X and Y point, possibly code that moved
+    CopiesMemory,
+}
+
+impl fmt::Display for OptimizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OptimizeError::UsesStack => write!(f, "program uses the stack"),
+            OptimizeError::TouchesCode { addr } => {
+                write!(f, "program reads or writes its own code at {}", addr)
+            }
+            OptimizeError::OverlappingCode { addr } => {
+                write!(f, "instruction at {} overlaps the one before it", addr)
+            }
+            OptimizeError::CopiesMemory => write!(f, "program copies memory blocks"),
+        }
+    }
+}
+
+impl std::error::Error for OptimizeError {}
+
+/// A program with its junk instructions removed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Optimized {
+    /// The memory image, padded to memory size
+    pub program: Vec<u8>,
+    /// In the order they applied
+    pub removals: Vec<Removal>,
+}
+
+impl Optimized {
+    pub fn removed_bytes(&self) -> usize {
+        self.removals.iter().map(|r| r.bytes).sum()
+    }
+}
+
+/// Remove junk from the code reachable from address 0 until none is left.
+///
+/// Code after a removed instruction moves down within its run of contiguous
+/// code, and jumps into it are retargeted; data stays where it was. Programs that
+/// use the stacks or COPY, touch their own code or decode overlapping instructions
+/// are refused, since moving their code would change what they do. Equivalence holds
+/// on a plain VM: timer interrupt handlers are not followed, and MMIO devices that
+/// answer loads differently from what was stored are not modelled. Code that runs
+/// off the top of memory into address 0 stays as it is.
+pub fn optimize(program: &[u8], isa: IsaVersion) -> Result<Optimized, OptimizeError> {
+    let mut image = program.to_vec();
+    image.resize(MEM_SIZE, 0);
+    let mut removals = Vec::new();
+    loop {
+        let code = check(&image, isa)?;
+        let found = find_junk(&image, isa, &code);
+        if found.is_empty() {
+            return Ok(Optimized {
+                program: image,
+                removals,
+            });
+        }
+        image = relocate(&image, &code, &found);
+        removals.extend(found);
+    }
+}
+
+/// The reachable instructions of `image`, or why its code cannot be moved
+fn check(image: &[u8], isa: IsaVersion) -> Result<Vec<DecodedInstruction>, OptimizeError> {
+    let code = analysis::reachable(image, isa, &[0]);
+    let mut covered = vec![false; MEM_SIZE];
+    let mut end = 0;
+    for entry in &code {
+        if entry.addr < end {
+            return Err(OptimizeError::OverlappingCode { addr: entry.addr });
+        }
+        end = entry.addr + entry.size();
+        covered[entry.addr..end.min(MEM_SIZE)].fill(true);
+    }
+    for entry in &code {
+        match entry.instruction {
+            Some(Instruction::CALL | Instruction::RET | Instruction::PUSH | Instruction::POP) => {
+                return Err(OptimizeError::UsesStack);
+            }
+            Some(Instruction::COPY) => return Err(OptimizeError::CopiesMemory),
+            Some(i) if i.has_address_operand() && !is_jump(i) => {
+                let addr = entry.operand.unwrap_or(0) as usize;
+                if covered[addr] {
+                    return Err(OptimizeError::TouchesCode { addr });
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(code)
+}
+
+/// Junk in one pass over the basic blocks; no instruction is part of two removals
+fn find_junk(image: &[u8], isa: IsaVersion, code: &[DecodedInstruction]) -> Vec<Removal> {
+    // A skip passes over exactly one instruction, which must stay to keep it that way
+    let skipped: Vec<usize> = code
+        .iter()
+        .filter(|e| matches!(e.instruction, Some(Instruction::SKE | Instruction::SKNE)))
+        .map(|e| e.addr + e.size())
+        .collect();
+    let graph = ControlFlowGraph::build(image, isa);
+    let mut found = Vec::new();
+    for block in &graph.blocks {
+        let instructions = &block.instructions;
+        let mut i = 0;
+        while i < instructions.len() {
+            let entry = &instructions[i];
+            let next = instructions.get(i + 1);
+            let (rewrite, count) = match junk_at(entry, next) {
+                Some(junk) if !skipped.contains(&entry.addr) => junk,
+                _ => {
+                    i += 1;
+                    continue;
+                }
+            };
+            // A reload is the second instruction of its pair, and only it goes
+            let removed = if rewrite == Rewrite::ReloadAfterStore {
+                &instructions[i + 1..i + 2]
+            } else {
+                &instructions[i..i + count]
+            };
+            found.push(Removal {
+                rewrite,
+                addr: removed[0].addr,
+                bytes: removed.iter().map(DecodedInstruction::size).sum(),
+            });
+            i += 2;
+        }
+    }
+    // Compacting the run that falls off the end of memory would leave a gap it runs into
+    let wrapping = wrapping_run_start(code);
+    found.retain(|r| r.addr < wrapping);
+    found.sort_by_key(|r| r.addr);
+    found
+}
+
+/// Where the run of contiguous code that ends at the top of memory and carries on
+/// at address 0 starts, or `MEM_SIZE` if there is none
+fn wrapping_run_start(code: &[DecodedInstruction]) -> usize {
+    let mut start = MEM_SIZE;
+    for entry in code.iter().rev() {
+        if entry.addr + entry.size() < start {
+            break;
+        }
+        start = entry.addr;
+    }
+    start
+}
+
+/// The rewrite starting at `entry`, given the instruction after it in the same block,
+/// and how many instructions from `entry` on it spans
+fn junk_at(
+    entry: &DecodedInstruction,
+    next: Option<&DecodedInstruction>,
+) -> Option<(Rewrite, usize)> {
+    let instruction = entry.instruction?;
+    let after = entry.addr + entry.size();
+    let operand = entry.operand.unwrap_or(0);
+    if instruction == Instruction::NOP {
+        return Some((Rewrite::Nop, 1));
+    }
+    let target = match instruction {
+        Instruction::JR | Instruction::JRZ => relative_target(entry.addr, operand as i8, MEM_SIZE),
+        i if is_jump(i) => operand as usize,
+        _ => usize::MAX,
+    };
+    if target == after {
+        return Some((Rewrite::JumpToNext, 1));
+    }
+
+    let next_instruction = next?.instruction?;
+    match (instruction, next_instruction) {
+        (Instruction::INC, Instruction::DEC) | (Instruction::DEC, Instruction::INC) => {
+            Some((Rewrite::IncDec, 2))
+        }
+        (Instruction::LDA | Instruction::LDI, Instruction::LDA | Instruction::LDI) => {
+            Some((Rewrite::OverwrittenLoad, 1))
+        }
+        (Instruction::STA, Instruction::LDA) if next?.operand == entry.operand => {
+            Some((Rewrite::ReloadAfterStore, 2))
+        }
+        _ => None,
+    }
+}
+
+/// Absolute and relative jumps, conditional or not
+fn is_jump(instruction: Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::JMP
+            | Instruction::JZ
+            | Instruction::JNZ
+            | Instruction::JC
+            | Instruction::JNC
+            | Instruction::JN
+            | Instruction::JR
+            | Instruction::JRZ
+    )
+}
+
+/// `image` with the `removed` instructions cut out of their runs of code, the rest
+/// of each run moved down over them and jump targets moved along
+fn relocate(image: &[u8], code: &[DecodedInstruction], removed: &[Removal]) -> Vec<u8> {
+    let is_removed = |addr: usize| {
+        removed
+            .iter()
+            .any(|r| (r.addr..r.addr + r.bytes).contains(&addr))
+    };
+
+    // Where every instruction start moves to; a removed one maps to whatever follows it
+    let mut moved_to = vec![None; MEM_SIZE];
+    let mut run_end = 0;
+    let mut new_addr = 0;
+    for entry in code {
+        if entry.addr != run_end {
+            new_addr = entry.addr;
+        }
+        moved_to[entry.addr] = Some(new_addr);
+        if !is_removed(entry.addr) {
+            new_addr += entry.size();
+        }
+        run_end = entry.addr + entry.size();
+    }
+
+    let mut relocated = image.to_vec();
+    for entry in code {
+        relocated[entry.addr..(entry.addr + entry.size()).min(MEM_SIZE)].fill(0);
+    }
+    for entry in code.iter().filter(|e| !is_removed(e.addr)) {
+        let at = moved_to[entry.addr].unwrap_or(entry.addr);
+        let mut bytes = vec![entry.opcode];
+        if let Some(operand) = entry.operand {
+            let retarget = |old: usize| moved_to.get(old).copied().flatten().unwrap_or(old);
+            bytes.push(match entry.instruction {
+                Some(Instruction::JR | Instruction::JRZ) => {
+                    let old = relative_target(entry.addr, operand as i8, MEM_SIZE);
+                    (retarget(old) + MEM_SIZE - at) as u8
+                }
+                Some(i) if is_jump(i) => retarget(operand as usize) as u8,
+                _ => operand,
+            });
+        }
+        for (i, byte) in bytes.into_iter().enumerate() {
+            if let Some(cell) = relocated.get_mut(at + i) {
+                *cell = byte;
+            }
+        }
+    }
+    relocated
+}
+
+/// Where two programs' replays part ways
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mismatch {
+    /// The `index`th store differs, as address and value
+    Store {
+        index: usize,
+        original: Option<(usize, u8)>,
+        optimized: Option<(usize, u8)>,
+    },
+    /// Both halted after the same stores, but with different registers
+    Registers {
+        original: [u8; 4],
+        optimized: [u8; 4],
+    },
+    /// Only one of them halted within the step budget, after every store of the other
+    Halting { original: bool, optimized: bool },
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let store = |s: &Option<(usize, u8)>| match s {
+            Some((addr, value)) => format!("{} to {}", value, addr),
+            None => "nothing".to_string(),
+        };
+        match self {
+            Mismatch::Store {
+                index,
+                original,
+                optimized,
+            } => write!(
+                f,
+                "store #{} differs: original stored {}, optimized {}",
+                index,
+                store(original),
+                store(optimized)
+            ),
+            Mismatch::Registers {
+                original,
+                optimized,
+            } => write!(
+                f,
+                "registers at halt differ: ACC/X/Y/FLAGS {:?} vs {:?}",
+                original, optimized
+            ),
+            Mismatch::Halting {
+                original,
+                optimized,
+            } => write!(
+                f,
+                "original {} but optimized {}",
+                if *original { "halted" } else { "kept running" },
+                if *optimized { "halted" } else { "kept running" }
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Mismatch {}
+
+/// What a replay of a program shows from outside: its stores in order, and whether
+/// and with what registers it halted
+struct Replay {
+    stores: Vec<(usize, u8)>,
+    halted: bool,
+    registers: [u8; 4],
+}
+
+impl Replay {
+    fn record(program: &[u8], isa: IsaVersion, steps: usize) -> Self {
+        let config = VmConfig::default().with_loop_detector(None).with_isa(isa);
+        let mut vm: VM = VM::with_config(config);
+        vm.load_program(program);
+        let mut stores = Vec::new();
+        for _ in 0..steps {
+            vm.step();
+            if let Some(write) = vm.last_write {
+                stores.push((write.addr, write.new));
+            }
+            if vm.halted {
+                break;
+            }
+        }
+        Replay {
+            stores,
+            halted: vm.halted,
+            registers: [vm.acc, vm.x, vm.y, vm.flags],
+        }
+    }
+}
+
+/// Replay both programs from a fresh VM for up to `steps` steps each and compare
+/// what they do. Removed instructions take steps, so the original may stop short:
+/// a run that did not halt only has to agree with the other as far as it got.
+pub fn verify(
+    original: &[u8],
+    optimized: &[u8],
+    isa: IsaVersion,
+    steps: usize,
+) -> Result<(), Mismatch> {
+    let a = Replay::record(original, isa, steps);
+    let b = Replay::record(optimized, isa, steps);
+    let compared = match (a.halted, b.halted) {
+        (true, true) => a.stores.len().max(b.stores.len()),
+        (true, false) => a.stores.len().min(b.stores.len()),
+        (false, true) => b.stores.len().min(a.stores.len()),
+        (false, false) => a.stores.len().min(b.stores.len()),
+    };
+    for index in 0..compared {
+        let (original, optimized) = (a.stores.get(index), b.stores.get(index));
+        if original != optimized {
+            return Err(Mismatch::Store {
+                index,
+                original: original.copied(),
+                optimized: optimized.copied(),
+            });
+        }
+    }
+    // A halted run must have made every store of a run still going
+    if a.halted != b.halted {
+        let (done, going) = if a.halted { (&a, &b) } else { (&b, &a) };
+        if going.stores.len() > done.stores.len() {
+            return Err(Mismatch::Halting {
+                original: a.halted,
+                optimized: b.halted,
+            });
+        }
+    }
+    if a.halted && b.halted && a.registers != b.registers {
+        return Err(Mismatch::Registers {
+            original: a.registers,
+            optimized: b.registers,
+        });
+    }
+    Ok(())
+}
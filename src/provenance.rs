@@ -0,0 +1,143 @@
+// provenance.rs
+
+// Origin watermarks appended to exported genome files, after the executable 256 bytes
+
+use std::fmt;
+
+use crate::compute::MEM_SIZE;
+#[cfg(feature = "serde")]
+use crate::world::WorldConfig;
+
+pub const PROVENANCE_MAGIC: [u8; 4] = *b"BVMP";
+pub const PROVENANCE_LEN: usize = 24; // Magic, generation, manifest hash and seal
+
+/// Streaming FNV-1a, stable across platforms and releases
+#[derive(Debug, Clone, Copy)]
+pub struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Fnv1a {
+    pub fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[derive(Debug)]
+pub enum ProvenanceError {
+    /// A trailer is present but is not a complete watermark
+    Malformed,
+    /// The watermark does not match the genome it is attached to
+    Tampered,
+}
+
+impl fmt::Display for ProvenanceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProvenanceError::Malformed => write!(f, "malformed provenance trailer"),
+            ProvenanceError::Tampered => {
+                write!(
+                    f,
+                    "provenance does not match the genome (edited after export?)"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProvenanceError {}
+
+/// Where a genome came from: a hash of the run that produced it and its generation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Provenance {
+    pub manifest_hash: u64,
+    pub generation: u32,
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "run {:016x}, generation {}",
+            self.manifest_hash, self.generation
+        )
+    }
+}
+
+impl Provenance {
+    pub fn new(manifest: &[u8], generation: u32) -> Self {
+        let mut hash = Fnv1a::new();
+        hash.write(manifest);
+        Self {
+            manifest_hash: hash.finish(),
+            generation,
+        }
+    }
+
+    /// Provenance for a genome exported from a world running `config`
+    #[cfg(feature = "serde")]
+    pub fn for_world(config: &WorldConfig, generation: u32) -> Self {
+        let mut manifest = Vec::new();
+        ciborium::into_writer(config, &mut manifest).expect("world config always serializes");
+        Self::new(&manifest, generation)
+    }
+
+    /// Hash tying this provenance to one exact genome
+    fn seal(&self, genome: &[u8]) -> u64 {
+        let mut hash = Fnv1a::new();
+        hash.write(genome);
+        hash.write(&self.generation.to_le_bytes());
+        hash.write(&self.manifest_hash.to_le_bytes());
+        hash.finish()
+    }
+
+    /// The genome padded to full memory size with this watermark appended
+    pub fn watermark(&self, genome: &[u8]) -> Vec<u8> {
+        let mut file = genome[..genome.len().min(MEM_SIZE)].to_vec();
+        file.resize(MEM_SIZE, 0);
+        let seal = self.seal(&file);
+        file.extend_from_slice(&PROVENANCE_MAGIC);
+        file.extend_from_slice(&self.generation.to_le_bytes());
+        file.extend_from_slice(&self.manifest_hash.to_le_bytes());
+        file.extend_from_slice(&seal.to_le_bytes());
+        file
+    }
+
+    /// Read and verify the watermark of a genome file; `None` if it has none
+    pub fn read(file: &[u8]) -> Result<Option<Self>, ProvenanceError> {
+        let Some(trailer) = file.get(MEM_SIZE..) else {
+            return Ok(None);
+        };
+        if trailer.is_empty() {
+            return Ok(None);
+        }
+        if trailer.len() != PROVENANCE_LEN || trailer[..4] != PROVENANCE_MAGIC {
+            return Err(ProvenanceError::Malformed);
+        }
+
+        let u64_at = |at: usize| u64::from_le_bytes(trailer[at..at + 8].try_into().unwrap());
+        let provenance = Self {
+            generation: u32::from_le_bytes(trailer[4..8].try_into().unwrap()),
+            manifest_hash: u64_at(8),
+        };
+        if provenance.seal(&file[..MEM_SIZE]) != u64_at(16) {
+            return Err(ProvenanceError::Tampered);
+        }
+        Ok(Some(provenance))
+    }
+}
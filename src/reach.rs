@@ -0,0 +1,596 @@
+// reach.rs
+
+// Abstract interpretation of genomes: every address a program could ever run, jump to or store to, whatever its inputs
+
+use std::fmt;
+use std::ops::Range;
+
+use crate::compute::{DATA_STACK_BASE, Instruction, MEM_SIZE, STACK_TOP, relative_target};
+use crate::isa::IsaVersion;
+use crate::memory_map::RegionKind;
+use crate::world::{DAUGHTER_LEN, DAUGHTER_START_ADDR, lifeform_memory_map};
+
+// Reach constants
+pub const MAX_PAIRWISE_RESULTS: usize = 4096; // Largest operand pairing computed value by value; bigger ones give any byte
+pub const WIDENING_THRESHOLD: u8 = 8; // Times a register or cell may grow before it is taken to hold any byte
+
+/// A set of byte values, or of addresses in 256-byte memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ByteSet([u64; 4]);
+
+impl ByteSet {
+    pub const EMPTY: Self = ByteSet([0; 4]);
+    pub const FULL: Self = ByteSet([u64::MAX; 4]);
+
+    pub fn single(value: u8) -> Self {
+        let mut set = Self::EMPTY;
+        set.insert(value);
+        set
+    }
+
+    /// The addresses in `range` that fit in a byte
+    pub fn range(range: Range<usize>) -> Self {
+        (range.start.min(MEM_SIZE)..range.end.min(MEM_SIZE))
+            .map(|addr| addr as u8)
+            .collect()
+    }
+
+    /// Returns whether `value` was new
+    pub fn insert(&mut self, value: u8) -> bool {
+        let (word, bit) = (value as usize / 64, 1 << (value % 64));
+        let new = self.0[word] & bit == 0;
+        self.0[word] |= bit;
+        new
+    }
+
+    pub fn remove(&mut self, value: u8) {
+        self.0[value as usize / 64] &= !(1 << (value % 64));
+    }
+
+    pub fn contains(&self, value: u8) -> bool {
+        self.0[value as usize / 64] & (1 << (value % 64)) != 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::EMPTY
+    }
+
+    pub fn is_full(&self) -> bool {
+        *self == Self::FULL
+    }
+
+    /// Add every value of `other`, returning whether the set grew
+    pub fn extend(&mut self, other: ByteSet) -> bool {
+        let before = *self;
+        for (word, other) in self.0.iter_mut().zip(other.0) {
+            *word |= other;
+        }
+        *self != before
+    }
+
+    pub fn union(mut self, other: ByteSet) -> Self {
+        self.extend(other);
+        self
+    }
+
+    pub fn intersects(&self, other: &ByteSet) -> bool {
+        self.0.iter().zip(other.0).any(|(a, b)| a & b != 0)
+    }
+
+    /// The values in ascending order
+    pub fn iter(&self) -> impl Iterator<Item = u8> + '_ {
+        self.0.iter().enumerate().flat_map(|(index, &word)| {
+            let mut bits = word;
+            std::iter::from_fn(move || {
+                let bit = bits.trailing_zeros();
+                bits &= bits.wrapping_sub(1);
+                (bit < 64).then(|| (index * 64) as u8 + bit as u8)
+            })
+        })
+    }
+
+    /// Every value `f` gives for a value in the set
+    pub fn map(&self, f: impl Fn(u8) -> u8) -> Self {
+        self.iter().map(f).collect()
+    }
+
+    /// Every value `f` gives for a value of each set, or any byte once that would
+    /// take more than `MAX_PAIRWISE_RESULTS` pairs
+    pub fn pairwise(&self, other: &ByteSet, f: impl Fn(u8, u8) -> u8) -> Self {
+        if self.len() * other.len() > MAX_PAIRWISE_RESULTS {
+            return Self::FULL;
+        }
+        self.iter()
+            .flat_map(|a| other.iter().map(move |b| (a, b)))
+            .map(|(a, b)| f(a, b))
+            .collect()
+    }
+}
+
+impl FromIterator<u8> for ByteSet {
+    fn from_iter<I: IntoIterator<Item = u8>>(iter: I) -> Self {
+        let mut set = Self::EMPTY;
+        for value in iter {
+            set.insert(value);
+        }
+        set
+    }
+}
+
+impl fmt::Display for ByteSet {
+    /// Runs of consecutive values as `a-b`, e.g. `0-8, 250, 252-255`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "none");
+        }
+        let mut runs: Vec<(u8, u8)> = Vec::new();
+        for value in self.iter() {
+            match runs.last_mut() {
+                Some((_, end)) if *end as usize + 1 == value as usize => *end = value,
+                _ => runs.push((value, value)),
+            }
+        }
+        let runs: Vec<_> = runs
+            .iter()
+            .map(|&(start, end)| match start == end {
+                true => start.to_string(),
+                false => format!("{}-{}", start, end),
+            })
+            .collect();
+        write!(f, "{}", runs.join(", "))
+    }
+}
+
+/// What the registers can hold when an instruction starts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct State {
+    acc: ByteSet,
+    x: ByteSet,
+    y: ByteSet,
+    sp: ByteSet,
+    dsp: ByteSet,
+}
+
+impl State {
+    /// Registers as a load, reset or restart leaves them
+    fn initial() -> Self {
+        State {
+            acc: ByteSet::single(0),
+            x: ByteSet::single(0),
+            y: ByteSet::single(0),
+            sp: ByteSet::single(STACK_TOP),
+            dsp: ByteSet::single(DATA_STACK_BASE),
+        }
+    }
+
+    /// Returns whether `self` grew. With `widen`, a register that grows takes any byte,
+    /// so loops counting through values settle at once.
+    fn join(&mut self, other: &State, widen: bool) -> bool {
+        let mut grew = false;
+        for (register, other) in [
+            (&mut self.acc, other.acc),
+            (&mut self.x, other.x),
+            (&mut self.y, other.y),
+            (&mut self.sp, other.sp),
+            (&mut self.dsp, other.dsp),
+        ] {
+            if register.extend(other) {
+                grew = true;
+                if widen {
+                    *register = ByteSet::FULL;
+                }
+            }
+        }
+        grew
+    }
+}
+
+/// Everything a program can ever do to control flow and memory, over-approximated
+/// by abstract interpretation: each register and memory cell is tracked as the set
+/// of bytes it could hold, across every run and every input.
+///
+/// Anything a real run does is included, so an address missing from `written`
+/// is proof the program never stores there. The converse does not hold: the
+/// analysis ignores flags, so both ways of a conditional branch count, and memory
+/// is one set per cell for the whole run. Self-modifying code is followed, since
+/// the opcode at an address is taken to be any byte that can be stored there.
+/// Timer interrupts, coroutines other than the `entries` and registered opcodes
+/// are not modelled; registered opcodes are treated as the HLT the VM falls
+/// back to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reach {
+    /// Addresses an instruction can start at
+    pub executed: ByteSet,
+    /// Addresses a jump, call, return or skip can land on
+    pub jump_targets: ByteSet,
+    /// Addresses a STA, SWP, CALL, PUSH or COPY can store to
+    pub written: ByteSet,
+}
+
+impl Reach {
+    /// Analyze `program` as loaded into a VM running `isa`, starting from each of
+    /// `entries` with the registers cleared. Cells in `volatile` can hold any byte
+    /// at any time, like sensors the host rewrites.
+    pub fn analyze(program: &[u8], isa: IsaVersion, entries: &[usize], volatile: ByteSet) -> Self {
+        Interpreter::new(initial_memory(program, volatile), isa).run(entries)
+    }
+
+    /// Analyze a lifeform's genome from address 0 under the world's rules: the
+    /// sensors hold anything, and the daughter region may have been cleared by a
+    /// self-copy
+    pub fn for_lifeform(program: &[u8], isa: IsaVersion) -> Self {
+        let mut memory = initial_memory(program, lifeform_addresses(RegionKind::Sensors));
+        for cell in &mut memory[DAUGHTER_START_ADDR..DAUGHTER_START_ADDR + DAUGHTER_LEN] {
+            cell.insert(0);
+        }
+        Interpreter::new(memory, isa).run(&[0])
+    }
+
+    /// Whether any store can land in `range`
+    pub fn can_write(&self, range: Range<usize>) -> bool {
+        self.written.intersects(&ByteSet::range(range))
+    }
+
+    /// Whether an instruction can start at `addr`
+    pub fn can_execute(&self, addr: usize) -> bool {
+        addr < MEM_SIZE && self.executed.contains(addr as u8)
+    }
+}
+
+impl fmt::Display for Reach {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "executed:     {}", self.executed)?;
+        writeln!(f, "jump targets: {}", self.jump_targets)?;
+        writeln!(f, "written:      {}", self.written)
+    }
+}
+
+/// Every address in the lifeform memory map's regions of `kind`
+pub fn lifeform_addresses(kind: RegionKind) -> ByteSet {
+    lifeform_memory_map()
+        .regions()
+        .iter()
+        .filter(|region| region.kind == kind)
+        .fold(ByteSet::EMPTY, |set, region| {
+            set.union(ByteSet::range(region.range.clone()))
+        })
+}
+
+/// Whether `instruction` reads the memory its operand addresses into the accumulator
+fn loads_operand(instruction: Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::LDA
+            | Instruction::SWP
+            | Instruction::ADD
+            | Instruction::SUB
+            | Instruction::MUL
+            | Instruction::DIV
+            | Instruction::AND
+            | Instruction::OR
+            | Instruction::XOR
+    )
+}
+
+/// Each cell as the program image leaves it, and any byte for `volatile` cells
+fn initial_memory(program: &[u8], volatile: ByteSet) -> [ByteSet; MEM_SIZE] {
+    std::array::from_fn(|addr| match volatile.contains(addr as u8) {
+        true => ByteSet::FULL,
+        false => ByteSet::single(program.get(addr).copied().unwrap_or(0)),
+    })
+}
+
+/// The fixpoint iteration behind `Reach`
+struct Interpreter {
+    isa: IsaVersion,
+    /// What each cell can hold at any point of any run
+    memory: [ByteSet; MEM_SIZE],
+    /// What the registers can hold when an instruction starts at each address
+    states: Vec<Option<State>>,
+    /// Addresses of the instructions whose outcome depends on each cell
+    readers: [ByteSet; MEM_SIZE],
+    /// Times each instruction's state has grown, for widening
+    state_growth: [u8; MEM_SIZE],
+    /// Times each cell has grown, for widening
+    cell_growth: [u8; MEM_SIZE],
+    pending: Vec<usize>,
+    queued: [bool; MEM_SIZE],
+    /// Jumps out of the instruction being interpreted, by the state they carry
+    jumps: Vec<(State, ByteSet)>,
+    /// The instruction being interpreted, recorded as a reader of what it loads
+    current: usize,
+    reach: Reach,
+}
+
+impl Interpreter {
+    fn new(memory: [ByteSet; MEM_SIZE], isa: IsaVersion) -> Self {
+        Interpreter {
+            isa,
+            memory,
+            states: vec![None; MEM_SIZE],
+            readers: [ByteSet::EMPTY; MEM_SIZE],
+            state_growth: [0; MEM_SIZE],
+            cell_growth: [0; MEM_SIZE],
+            pending: Vec::new(),
+            queued: [false; MEM_SIZE],
+            jumps: Vec::new(),
+            current: 0,
+            reach: Reach {
+                executed: ByteSet::EMPTY,
+                jump_targets: ByteSet::EMPTY,
+                written: ByteSet::EMPTY,
+            },
+        }
+    }
+
+    fn run(mut self, entries: &[usize]) -> Reach {
+        for &entry in entries {
+            self.flow(entry, State::initial());
+        }
+        while let Some(pc) = self.pending.pop() {
+            // Nothing left to learn once every address is run, jumped to and written
+            if self.reach.executed.is_full()
+                && self.reach.jump_targets.is_full()
+                && self.reach.written.is_full()
+            {
+                break;
+            }
+            self.queued[pc] = false;
+            if let Some(state) = self.states[pc] {
+                self.current = pc;
+                self.step(pc, state);
+            }
+        }
+        self.reach
+    }
+
+    fn enqueue(&mut self, pc: usize) {
+        if !std::mem::replace(&mut self.queued[pc], true) {
+            self.pending.push(pc);
+        }
+    }
+
+    /// Continue at `pc` with `state`; past the end of memory the VM halts
+    fn flow(&mut self, pc: usize, state: State) {
+        if pc >= MEM_SIZE {
+            return;
+        }
+        self.reach.executed.insert(pc as u8);
+        let widen = self.state_growth[pc] >= WIDENING_THRESHOLD;
+        let grew = match &mut self.states[pc] {
+            Some(known) => known.join(&state, widen),
+            slot @ None => {
+                *slot = Some(state);
+                true
+            }
+        };
+        if grew {
+            self.state_growth[pc] = self.state_growth[pc].saturating_add(1);
+            self.enqueue(pc);
+        }
+    }
+
+    /// Continue at each of `targets` with `state` once the instruction is done;
+    /// the many opcodes a rewritable cell can hold often share their targets
+    fn jump(&mut self, targets: impl IntoIterator<Item = usize>, state: State) {
+        let targets: ByteSet = targets
+            .into_iter()
+            .filter(|&target| target < MEM_SIZE)
+            .map(|target| target as u8)
+            .collect();
+        self.reach.jump_targets.extend(targets);
+        match self.jumps.iter_mut().find(|(known, _)| *known == state) {
+            Some((_, known)) => {
+                known.extend(targets);
+            }
+            None => self.jumps.push((state, targets)),
+        }
+    }
+
+    /// What the cell at `addr` can hold; past the end of memory reads give 0
+    fn cell(&mut self, addr: usize) -> ByteSet {
+        match self.memory.get(addr) {
+            Some(&values) => {
+                self.readers[addr].insert(self.current as u8);
+                values
+            }
+            None => ByteSet::single(0),
+        }
+    }
+
+    fn load(&mut self, addrs: ByteSet) -> ByteSet {
+        addrs.iter().fold(ByteSet::EMPTY, |values, addr| {
+            values.union(self.cell(addr as usize))
+        })
+    }
+
+    /// Store any of `values` to any of `addrs`, revisiting the instructions that
+    /// read a cell it changes
+    fn store(&mut self, addrs: ByteSet, values: ByteSet) {
+        if values.is_empty() {
+            return;
+        }
+        for addr in addrs.iter() {
+            self.reach.written.insert(addr);
+            let addr = addr as usize;
+            if self.memory[addr].extend(values) {
+                self.cell_growth[addr] = self.cell_growth[addr].saturating_add(1);
+                if self.cell_growth[addr] > WIDENING_THRESHOLD {
+                    self.memory[addr] = ByteSet::FULL;
+                }
+                let readers = self.readers[addr];
+                for reader in readers.iter() {
+                    self.enqueue(reader as usize);
+                }
+            }
+        }
+    }
+
+    /// Run every instruction that can start at `pc` on `state`
+    fn step(&mut self, pc: usize, state: State) {
+        let operands = self.cell(pc + 1);
+        // Bytes outside the ISA run as HLT
+        let instructions: Vec<_> = self
+            .cell(pc)
+            .iter()
+            .filter_map(|opcode| self.isa.decode(opcode))
+            .collect();
+        let loaded = match instructions.iter().any(|&i| loads_operand(i)) {
+            true => self.load(operands),
+            false => ByteSet::EMPTY,
+        };
+        for instruction in instructions {
+            self.execute(pc, instruction, operands, loaded, state);
+        }
+        for (state, targets) in std::mem::take(&mut self.jumps) {
+            for target in targets.iter() {
+                self.flow(target as usize, state);
+            }
+        }
+    }
+
+    /// Run `instruction` at `pc`, its operand any of `operands` and the memory it
+    /// addresses holding any of `loaded`
+    fn execute(
+        &mut self,
+        pc: usize,
+        instruction: Instruction,
+        operands: ByteSet,
+        loaded: ByteSet,
+        state: State,
+    ) {
+        let next = pc + instruction.size();
+        let targets = || operands.iter().map(usize::from);
+        let relative = || {
+            operands
+                .iter()
+                .map(move |offset| relative_target(pc, offset as i8, MEM_SIZE))
+        };
+        let with_acc = |acc| State { acc, ..state };
+        let arithmetic = |f: fn(u8, u8) -> u8| with_acc(state.acc.pairwise(&loaded, f));
+
+        match instruction {
+            Instruction::HLT => {}
+            Instruction::NOP | Instruction::CMP | Instruction::CPI | Instruction::OUT => {
+                self.flow(next, state)
+            }
+            Instruction::LDA => self.flow(next, with_acc(loaded)),
+            Instruction::STA => {
+                self.store(operands, state.acc);
+                self.flow(next, state);
+            }
+            Instruction::SWP => {
+                self.store(operands, state.acc);
+                self.flow(next, with_acc(loaded));
+            }
+            Instruction::ADD => self.flow(next, arithmetic(u8::wrapping_add)),
+            Instruction::SUB => self.flow(next, arithmetic(u8::wrapping_sub)),
+            Instruction::MUL => self.flow(next, arithmetic(u8::wrapping_mul)),
+            Instruction::DIV => {
+                self.flow(next, arithmetic(|a, b| a.checked_div(b).unwrap_or(0xFF)))
+            }
+            Instruction::AND => self.flow(next, arithmetic(|a, b| a & b)),
+            Instruction::OR => self.flow(next, arithmetic(|a, b| a | b)),
+            Instruction::XOR => self.flow(next, arithmetic(|a, b| a ^ b)),
+            Instruction::LDI => self.flow(next, with_acc(operands)),
+            Instruction::ADI => self.flow(
+                next,
+                with_acc(state.acc.pairwise(&operands, u8::wrapping_add)),
+            ),
+            Instruction::SBI => self.flow(
+                next,
+                with_acc(state.acc.pairwise(&operands, u8::wrapping_sub)),
+            ),
+            Instruction::INC => self.flow(next, with_acc(state.acc.map(|a| a.wrapping_add(1)))),
+            Instruction::DEC => self.flow(next, with_acc(state.acc.map(|a| a.wrapping_sub(1)))),
+            Instruction::NOT => self.flow(next, with_acc(state.acc.map(|a| !a))),
+            Instruction::SHL => self.flow(next, with_acc(state.acc.map(|a| a << 1))),
+            Instruction::SHR => self.flow(next, with_acc(state.acc.map(|a| a >> 1))),
+            Instruction::ROL => self.flow(next, with_acc(state.acc.map(|a| a.rotate_left(1)))),
+            Instruction::ROR => self.flow(next, with_acc(state.acc.map(|a| a.rotate_right(1)))),
+            Instruction::RND | Instruction::IN => self.flow(next, with_acc(ByteSet::FULL)),
+            Instruction::TAX => self.flow(
+                next,
+                State {
+                    x: state.acc,
+                    ..state
+                },
+            ),
+            Instruction::TAY => self.flow(
+                next,
+                State {
+                    y: state.acc,
+                    ..state
+                },
+            ),
+            Instruction::TXA => self.flow(next, with_acc(state.x)),
+            Instruction::TYA => self.flow(next, with_acc(state.y)),
+            Instruction::JMP => self.jump(targets(), state),
+            Instruction::JR => self.jump(relative(), state),
+            Instruction::JZ | Instruction::JRZ => {
+                // Taken only with a zero accumulator, which it then still holds
+                let mut nonzero = state.acc;
+                nonzero.remove(0);
+                if state.acc.contains(0) {
+                    let taken = with_acc(ByteSet::single(0));
+                    match instruction {
+                        Instruction::JZ => self.jump(targets(), taken),
+                        _ => self.jump(relative(), taken),
+                    }
+                }
+                if !nonzero.is_empty() {
+                    self.flow(next, with_acc(nonzero));
+                }
+            }
+            Instruction::JNZ | Instruction::JC | Instruction::JNC | Instruction::JN => {
+                self.jump(targets(), state);
+                self.flow(next, state);
+            }
+            Instruction::SKE | Instruction::SKNE => {
+                self.flow(next, state);
+                let isa = self.isa;
+                let skipped: Vec<_> = self
+                    .cell(next)
+                    .iter()
+                    .map(|opcode| next + isa.decode(opcode).map_or(1, Instruction::size))
+                    .collect();
+                self.jump(skipped, state);
+            }
+            Instruction::CALL => {
+                let sp = state.sp.map(|sp| sp.wrapping_sub(1));
+                self.store(sp, ByteSet::single(next as u8));
+                self.jump(targets(), State { sp, ..state });
+            }
+            Instruction::RET => {
+                let returns: Vec<_> = self.load(state.sp).iter().map(usize::from).collect();
+                let sp = state.sp.map(|sp| sp.wrapping_add(1));
+                self.jump(returns, State { sp, ..state });
+            }
+            Instruction::PUSH => {
+                self.store(state.dsp, state.acc);
+                let dsp = state.dsp.map(|dsp| dsp.wrapping_add(1));
+                self.flow(next, State { dsp, ..state });
+            }
+            Instruction::POP => {
+                let dsp = state.dsp.map(|dsp| dsp.wrapping_sub(1));
+                let acc = self.load(dsp);
+                self.flow(next, State { acc, dsp, ..state });
+            }
+            Instruction::COPY => {
+                // Up to the largest count, from and to every address X and Y can hold
+                let len = state.acc.iter().last().unwrap_or(0) as usize;
+                let offsets = ByteSet::range(0..len);
+                let window = |starts: ByteSet| starts.pairwise(&offsets, u8::wrapping_add);
+                let values = self.load(window(state.x));
+                self.store(window(state.y), values);
+                let x = state.x.pairwise(&state.acc, u8::wrapping_add);
+                let y = state.y.pairwise(&state.acc, u8::wrapping_add);
+                self.flow(next, State { x, y, ..state });
+            }
+        }
+    }
+}
@@ -0,0 +1,122 @@
+// relocation.rs
+
+// How position-independent a genome's control flow is, to see whether relative jumps catch on
+
+use std::fmt;
+
+use crate::compute::Instruction;
+use crate::disasm::{self, DecodedInstruction};
+
+/// Control flow found in a genome, decoded linearly like the disassembler
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RelocationReport {
+    /// JR, JRZ, SKE and SKNE, which still land correctly after the code moves
+    pub relative_branches: usize,
+    /// Jumps and calls to a fixed address, which break once the code moves
+    pub absolute_branches: usize,
+    /// Straight-line runs ending at JMP, JR, RET or HLT
+    pub blocks: usize,
+    /// Blocks without any absolute branch, so they can be moved intact
+    pub relocatable_blocks: usize,
+    /// Bytes covered by relocatable blocks
+    pub relocatable_bytes: usize,
+}
+
+impl RelocationReport {
+    pub fn analyze(program: &[u8]) -> Self {
+        let mut report = Self::default();
+        let mut block_bytes = 0;
+        let mut block_relocatable = true;
+        let decoded = disasm::decode(program);
+        for (i, entry) in decoded.iter().enumerate() {
+            block_bytes += entry.size();
+            match entry.instruction {
+                Some(i) if is_relative_branch(i) => report.relative_branches += 1,
+                Some(i) if is_absolute_branch(i) => {
+                    report.absolute_branches += 1;
+                    block_relocatable = false;
+                }
+                _ => {}
+            }
+
+            if ends_block(entry) || i + 1 == decoded.len() {
+                report.blocks += 1;
+                if block_relocatable {
+                    report.relocatable_blocks += 1;
+                    report.relocatable_bytes += block_bytes;
+                }
+                block_bytes = 0;
+                block_relocatable = true;
+            }
+        }
+        report
+    }
+
+    /// Share of branches that are relative, or `None` for a genome without branches
+    pub fn relative_fraction(&self) -> Option<f32> {
+        let total = self.relative_branches + self.absolute_branches;
+        (total > 0).then(|| self.relative_branches as f32 / total as f32)
+    }
+
+    pub fn relocatable_fraction(&self) -> f32 {
+        if self.blocks == 0 {
+            return 0.0;
+        }
+        self.relocatable_blocks as f32 / self.blocks as f32
+    }
+}
+
+impl fmt::Display for RelocationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let relative = self
+            .relative_fraction()
+            .map_or_else(|| "-".to_string(), |r| format!("{:.0}%", r * 100.0));
+        write!(
+            f,
+            "{} relative / {} absolute branches ({}), {}/{} blocks relocatable ({} bytes)",
+            self.relative_branches,
+            self.absolute_branches,
+            relative,
+            self.relocatable_blocks,
+            self.blocks,
+            self.relocatable_bytes
+        )
+    }
+}
+
+/// Mean `relative_fraction` over the genomes that branch at all; 0 if none do
+pub fn mean_relative_fraction<'a>(genomes: impl IntoIterator<Item = &'a [u8]>) -> f32 {
+    let fractions: Vec<f32> = genomes
+        .into_iter()
+        .filter_map(|g| RelocationReport::analyze(g).relative_fraction())
+        .collect();
+    if fractions.is_empty() {
+        return 0.0;
+    }
+    fractions.iter().sum::<f32>() / fractions.len() as f32
+}
+
+fn is_relative_branch(instruction: Instruction) -> bool {
+    instruction.is_relative() || matches!(instruction, Instruction::SKE | Instruction::SKNE)
+}
+
+fn is_absolute_branch(instruction: Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::JMP
+            | Instruction::JZ
+            | Instruction::JNZ
+            | Instruction::JC
+            | Instruction::JNC
+            | Instruction::JN
+            | Instruction::CALL
+    )
+}
+
+/// Whether execution never falls through past this instruction
+fn ends_block(entry: &DecodedInstruction) -> bool {
+    matches!(
+        entry.instruction,
+        Some(Instruction::JMP | Instruction::JR | Instruction::RET | Instruction::HLT)
+    )
+}
@@ -0,0 +1,153 @@
+// replay.rs
+
+// Replay files: a seeded world config plus the user's interventions, stamped with the tick
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::world::{Intervention, World, WorldConfig};
+
+/// Bumped whenever the replay layout changes incompatibly
+pub const REPLAY_FORMAT_VERSION: u32 = 1;
+
+/// An intervention and the point in the run where it was applied
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RecordedIntervention {
+    /// `World::tick_count` when it was applied, i.e. after that many ticks
+    pub tick: u64,
+    /// Whether that tick's `World::update_ecology` had already run
+    pub after_ecology: bool,
+    pub intervention: Intervention,
+}
+
+impl RecordedIntervention {
+    /// Whether the world has reached the point where this was applied
+    fn is_due(&self, world: &World) -> bool {
+        (self.tick, self.after_ecology) <= (world.tick_count(), world.ecology_done())
+    }
+}
+
+/// Everything needed to re-run an interactive session: the starting config and what the user did.
+///
+/// Playback only matches the recording when the config has a seed and neither a tick
+/// budget nor coarsening, the same conditions under which an untouched run is deterministic.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Replay {
+    pub format_version: u32,
+    pub config: WorldConfig,
+    /// In the order they were applied
+    pub interventions: Vec<RecordedIntervention>,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(std::io::Error),
+    Encode(String),
+    Decode(String),
+    UnsupportedVersion(u32),
+}
+
+impl std::fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplayError::Io(e) => write!(f, "replay I/O error: {}", e),
+            ReplayError::Encode(e) => write!(f, "failed to encode replay: {}", e),
+            ReplayError::Decode(e) => write!(f, "failed to decode replay: {}", e),
+            ReplayError::UnsupportedVersion(v) => {
+                write!(f, "unsupported replay format version {}", v)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<std::io::Error> for ReplayError {
+    fn from(e: std::io::Error) -> Self {
+        ReplayError::Io(e)
+    }
+}
+
+impl Replay {
+    /// An empty recording of a world created from `config`
+    pub fn new(config: WorldConfig) -> Self {
+        Self {
+            format_version: REPLAY_FORMAT_VERSION,
+            config,
+            interventions: Vec::new(),
+        }
+    }
+
+    /// Apply an intervention to the world and log it at the current point in the run
+    pub fn record(&mut self, world: &mut World, intervention: Intervention) {
+        let tick = world.tick_count();
+        let after_ecology = world.ecology_done();
+        if world.intervene(&intervention) {
+            self.interventions.push(RecordedIntervention {
+                tick,
+                after_ecology,
+                intervention,
+            });
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ReplayError> {
+        let writer = BufWriter::new(File::create(path)?);
+        ciborium::into_writer(self, writer).map_err(|e| ReplayError::Encode(e.to_string()))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ReplayError> {
+        let reader = BufReader::new(File::open(path)?);
+        let replay: Self =
+            ciborium::from_reader(reader).map_err(|e| ReplayError::Decode(e.to_string()))?;
+        if replay.format_version != REPLAY_FORMAT_VERSION {
+            return Err(ReplayError::UnsupportedVersion(replay.format_version));
+        }
+        Ok(replay)
+    }
+}
+
+/// Feeds a replay's interventions back into a world as it reaches their ticks
+#[derive(Debug, Clone)]
+pub struct Playback {
+    pub replay: Replay,
+    next: usize,
+}
+
+impl Playback {
+    pub fn new(replay: Replay) -> Self {
+        Self { replay, next: 0 }
+    }
+
+    /// A fresh world built from the replay's config
+    pub fn world(&self) -> World {
+        World::new(self.replay.config.clone())
+    }
+
+    /// Apply every intervention recorded up to the world's current point in the run.
+    ///
+    /// Call it both before and after each `World::update_ecology` so every intervention
+    /// lands on the same side of the ecology update as when it was recorded.
+    /// Returns how many were applied.
+    pub fn apply_due(&mut self, world: &mut World) -> usize {
+        let start = self.next;
+        while let Some(recorded) = self.replay.interventions.get(self.next)
+            && recorded.is_due(world)
+        {
+            world.intervene(&recorded.intervention);
+            self.next += 1;
+        }
+        self.next - start
+    }
+
+    /// Whether every recorded intervention has been applied
+    pub fn finished(&self) -> bool {
+        self.next == self.replay.interventions.len()
+    }
+}
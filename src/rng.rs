@@ -0,0 +1,55 @@
+// rng.rs
+
+// The one random number generator every randomization path draws from, so a run replays from a seed
+
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+
+/// A random number generator that remembers the `u64` it was seeded from.
+///
+/// Worlds, VM randomization, mutation and the frontends all draw from a `SimRng`
+/// (or from generators seeded by one), so reporting `seed()` is enough to run the
+/// same simulation again. Sequences are only stable for a given `rand` version.
+#[derive(Debug, Clone)]
+pub struct SimRng {
+    seed: u64,
+    inner: StdRng,
+}
+
+impl SimRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            inner: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Seeded from the operating system; `seed()` still tells how to get it back
+    pub fn from_entropy() -> Self {
+        Self::new(Self::entropy_seed())
+    }
+
+    /// A fresh seed from the operating system, for runs started without one
+    pub fn entropy_seed() -> u64 {
+        StdRng::from_os_rng().next_u64()
+    }
+
+    /// The seed this generator started from
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl RngCore for SimRng {
+    fn next_u32(&mut self) -> u32 {
+        self.inner.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.inner.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dst: &mut [u8]) {
+        self.inner.fill_bytes(dst)
+    }
+}
@@ -0,0 +1,59 @@
+// sandbox.rs
+
+// Resource limits for programs from genomes shared by other people
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::compute::{AddressWidth, VM};
+
+// Sandbox constants
+pub const DEFAULT_SANDBOX_STEPS: usize = 64; // VM steps per tick allowed to an untrusted program
+pub const DEFAULT_SANDBOX_TRACE: usize = 32; // Sampled trace entries kept for an untrusted program
+
+/// Limits for lifeforms whose genome came from outside the session: imported
+/// bundles, immigrants and invaders, and everything descended from them.
+///
+/// A genome tuned to run as long as possible, or to fill its history, then costs
+/// the host no more than `max_steps_per_tick` steps and `max_trace_entries` trace
+/// entries, and it can only reach the host devices on `allowed_ports`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Sandbox {
+    /// VM steps per tick, whatever `WorldConfig::steps_per_tick` allows others
+    pub max_steps_per_tick: usize,
+    /// Entries kept in the sampled trace; never more than the VM's trace depth
+    pub max_trace_entries: usize,
+    /// Ports reachable with IN and OUT; the rest read 0 and drop writes
+    pub allowed_ports: Vec<u8>,
+}
+
+impl Default for Sandbox {
+    fn default() -> Self {
+        Self {
+            max_steps_per_tick: DEFAULT_SANDBOX_STEPS,
+            max_trace_entries: DEFAULT_SANDBOX_TRACE,
+            allowed_ports: Vec::new(),
+        }
+    }
+}
+
+impl Sandbox {
+    /// Steps a sandboxed program may run out of `steps` offered this tick
+    pub fn steps(&self, steps: usize) -> usize {
+        steps.min(self.max_steps_per_tick)
+    }
+
+    /// Hold `vm` to the trace and port limits; cheap when they are already in place
+    pub fn apply<const N: usize, A: AddressWidth>(&self, vm: &mut VM<N, A>) {
+        vm.trace.set_limit(Some(self.max_trace_entries));
+        vm.restrict_ports(Some(self.allowed_ports.as_slice()));
+    }
+
+    /// Lift the trace and port limits again, once sandboxing is switched off
+    pub fn release<const N: usize, A: AddressWidth>(vm: &mut VM<N, A>) {
+        vm.trace.set_limit(None);
+        vm.restrict_ports(None);
+    }
+}
@@ -0,0 +1,329 @@
+// scenario.rs
+
+// Scenario directories: a world config, fitness shapers and seed genomes, scaffolded from templates by `bacteria new-scenario`
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use rand::Rng;
+
+use crate::compute::{MEM_SIZE, ProgramFile, ProgramFileError, VM};
+use crate::fitness::{FitnessConfig, Shaper};
+use crate::isa::{IsaVersion, Program};
+use crate::world::{
+    DAUGHTER_START_ADDR, FOOD_DISTANCE_X_ADDR, FOOD_DISTANCE_Y_ADDR, FoodModel, Lifeform,
+    MOVE_DOWN_ADDR, MOVE_LEFT_ADDR, MOVE_RIGHT_ADDR, MOVE_UP_ADDR, ReproductionMode, Speciation,
+    World, WorldConfig,
+};
+
+// Scenario constants
+pub const WORLD_FILE: &str = "world.toml"; // `WorldConfig` of the scenario
+pub const FITNESS_FILE: &str = "fitness.toml"; // `FitnessConfig` applied to scores
+pub const SEEDS_DIR: &str = "seeds"; // `*.bin` genomes the population starts from
+pub const SEED_COPIES: usize = 10; // Lifeforms placed for every seed genome
+const SEED_SPREAD: f32 = 200.0; // Seeds land within this distance of the map center, like the initial population
+
+/// Built-in starting points for `bacteria new-scenario`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Template {
+    /// No reproduction: the seeds chase Gaussian food until they starve
+    Foraging,
+    /// Fission in a closed nutrient cycle, with species tracking, so seeds compete to copy themselves
+    ReplicationArena,
+    /// Seeds copy themselves into their daughter region with COPY and only those copies are born
+    Soup,
+}
+
+impl Template {
+    pub const ALL: [Template; 3] = [
+        Template::Foraging,
+        Template::ReplicationArena,
+        Template::Soup,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Template::Foraging => "foraging",
+            Template::ReplicationArena => "replication-arena",
+            Template::Soup => "soup",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|t| t.name() == name)
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Template::Foraging => {
+                "Lifeforms never reproduce; seeds chase food spawned around the map center \
+                 until they starve, so scores measure foraging alone."
+            }
+            Template::ReplicationArena => {
+                "Lifeforms split by fission and food only grows from the nutrients they excrete, \
+                 so energy is conserved and seeds compete for it; species are tracked."
+            }
+            Template::Soup => {
+                "A digital soup: a well-fed lifeform's child is whatever its program copied \
+                 into its daughter region, so only code that replicates itself survives."
+            }
+        }
+    }
+
+    pub fn config(&self) -> WorldConfig {
+        match self {
+            Template::Foraging => WorldConfig {
+                reproduction: ReproductionMode::Off,
+                food_model: FoodModel::GaussianSpawner,
+                ..WorldConfig::default()
+            },
+            Template::ReplicationArena => WorldConfig {
+                reproduction: ReproductionMode::Fission,
+                food_model: FoodModel::NutrientCycling,
+                mutation_rate: Some(0.02),
+                speciation: Some(Speciation::default()),
+                ..WorldConfig::default()
+            },
+            Template::Soup => WorldConfig {
+                reproduction: ReproductionMode::SelfCopy,
+                mutation_rate: Some(0.01),
+                ..WorldConfig::default()
+            },
+        }
+    }
+
+    pub fn fitness(&self) -> FitnessConfig {
+        let shapers = match self {
+            Template::Foraging => vec![Shaper::Parsimony { weight: 0.1 }],
+            Template::ReplicationArena | Template::Soup => vec![Shaper::Novelty {
+                weight: 0.05,
+                neighbours: 5,
+                archive_size: 100,
+            }],
+        };
+        FitnessConfig { shapers }
+    }
+
+    pub fn scenario(&self) -> Scenario {
+        let seeds = match self {
+            Template::Foraging | Template::ReplicationArena => {
+                vec![("forager", forager()), ("wanderer", wanderer())]
+            }
+            Template::Soup => vec![("replicator", replicator())],
+        };
+        Scenario {
+            config: self.config(),
+            fitness: self.fitness(),
+            seeds: seeds
+                .into_iter()
+                .map(|(name, genome)| {
+                    let isa = IsaVersion::LATEST;
+                    (name.to_string(), Program { genome, isa })
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Steers towards the nearest food: each actuator pair gets the sensor and its inverse
+fn forager() -> Vec<u8> {
+    let mut program = Vec::new();
+    for (sensor, towards, away) in [
+        (FOOD_DISTANCE_X_ADDR, MOVE_RIGHT_ADDR, MOVE_LEFT_ADDR),
+        (FOOD_DISTANCE_Y_ADDR, MOVE_DOWN_ADDR, MOVE_UP_ADDR),
+    ] {
+        // LDA sensor; STA towards; NOT; STA away
+        program.extend([
+            0x01,
+            sensor as u8,
+            0x02,
+            towards as u8,
+            0x14,
+            0x02,
+            away as u8,
+        ]);
+    }
+    program.extend([0x05, 0x00]); // JMP 0
+    program
+}
+
+/// A forager that first copies its own code into the daughter region, once per loop
+fn replicator() -> Vec<u8> {
+    let forager = forager();
+    let len = 9 + forager.len();
+    // LDI 0; TAX; LDI daughter; TAY; LDI len; COPY
+    let mut program = vec![
+        0x1B,
+        0x00,
+        0x26,
+        0x1B,
+        DAUGHTER_START_ADDR as u8,
+        0x28,
+        0x1B,
+        len as u8,
+        0x2D,
+    ];
+    program.extend(forager);
+    program
+}
+
+/// Ignores its sensors and drives every actuator with RND
+fn wanderer() -> Vec<u8> {
+    let mut program = Vec::new();
+    for actuator in [
+        MOVE_LEFT_ADDR,
+        MOVE_RIGHT_ADDR,
+        MOVE_UP_ADDR,
+        MOVE_DOWN_ADDR,
+    ] {
+        program.extend([0x21, 0x02, actuator as u8]); // RND; STA actuator
+    }
+    program.extend([0x05, 0x00]); // JMP 0
+    program
+}
+
+#[derive(Debug)]
+pub enum ScenarioError {
+    Io(std::io::Error),
+    /// A TOML file failed to parse
+    Parse(PathBuf, String),
+    Encode(String),
+    /// A seed is not a readable program file, e.g. its ISA is unknown
+    Program(PathBuf, ProgramFileError),
+    /// Scaffolding would overwrite an existing scenario
+    Exists(PathBuf),
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScenarioError::Io(e) => write!(f, "scenario I/O error: {}", e),
+            ScenarioError::Parse(path, e) => write!(f, "invalid {}: {}", path.display(), e),
+            ScenarioError::Encode(e) => write!(f, "cannot encode scenario: {}", e),
+            ScenarioError::Program(path, e) => write!(f, "{}: {}", path.display(), e),
+            ScenarioError::Exists(path) => write!(f, "{} already exists", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+impl From<std::io::Error> for ScenarioError {
+    fn from(e: std::io::Error) -> Self {
+        ScenarioError::Io(e)
+    }
+}
+
+/// Everything an experiment starts from, as laid out in a scenario directory:
+///
+/// ```text
+/// world.toml     WorldConfig
+/// fitness.toml   FitnessConfig
+/// seeds/*.bin    genomes placed SEED_COPIES times each; untagged ones run as ISA v1
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Scenario {
+    pub config: WorldConfig,
+    pub fitness: FitnessConfig,
+    /// Genome file stems and their programs, ordered by name
+    pub seeds: Vec<(String, Program)>,
+}
+
+impl Scenario {
+    /// Read a scenario directory; a missing `fitness.toml` or `seeds` means none
+    pub fn load(dir: impl AsRef<Path>) -> Result<Self, ScenarioError> {
+        let dir = dir.as_ref();
+        let world_path = dir.join(WORLD_FILE);
+        let config = toml::from_str(&std::fs::read_to_string(&world_path)?)
+            .map_err(|e| ScenarioError::Parse(world_path, e.to_string()))?;
+
+        let fitness_path = dir.join(FITNESS_FILE);
+        let fitness = if fitness_path.is_file() {
+            toml::from_str(&std::fs::read_to_string(&fitness_path)?)
+                .map_err(|e| ScenarioError::Parse(fitness_path, e.to_string()))?
+        } else {
+            FitnessConfig::default()
+        };
+
+        let seeds_dir = dir.join(SEEDS_DIR);
+        let mut seeds = Vec::new();
+        if seeds_dir.is_dir() {
+            let mut paths: Vec<_> = std::fs::read_dir(&seeds_dir)?
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "bin"))
+                .collect();
+            paths.sort();
+            for path in paths {
+                let mut program = ProgramFile::load(&path)
+                    .map_err(|e| ScenarioError::Program(path.clone(), e))?
+                    .program;
+                program.genome.truncate(MEM_SIZE);
+                let name = path
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                seeds.push((name, program));
+            }
+        }
+
+        Ok(Scenario {
+            config,
+            fitness,
+            seeds,
+        })
+    }
+
+    /// Write the scenario into `dir`, creating it; refuses to replace an existing `world.toml`
+    pub fn save(&self, dir: impl AsRef<Path>) -> Result<(), ScenarioError> {
+        let dir = dir.as_ref();
+        let world_path = dir.join(WORLD_FILE);
+        if world_path.exists() {
+            return Err(ScenarioError::Exists(world_path));
+        }
+        std::fs::create_dir_all(dir.join(SEEDS_DIR))?;
+        let encode = |e: toml::ser::Error| ScenarioError::Encode(e.to_string());
+        std::fs::write(
+            dir.join(FITNESS_FILE),
+            toml::to_string_pretty(&self.fitness).map_err(encode)?,
+        )?;
+        for (name, program) in &self.seeds {
+            std::fs::write(
+                dir.join(SEEDS_DIR).join(format!("{}.bin", name)),
+                program.to_file_bytes(),
+            )?;
+        }
+        // Written last, so a failed scaffold can be retried
+        std::fs::write(
+            world_path,
+            toml::to_string_pretty(&self.config).map_err(encode)?,
+        )?;
+        Ok(())
+    }
+
+    /// Replace the world's config and population with the scenario's, placing
+    /// `SEED_COPIES` of every seed around the map center. Without seeds the world's
+    /// random population is kept.
+    pub fn apply_to(&self, world: &mut World) {
+        let seed = world.config.seed;
+        world.config = WorldConfig {
+            seed,
+            ..self.config.clone()
+        };
+        if self.seeds.is_empty() {
+            return;
+        }
+        world.lifeforms.clear();
+        for (_, program) in &self.seeds {
+            for _ in 0..SEED_COPIES {
+                let mut vm = VM::new();
+                vm.isa = program.isa;
+                vm.load_program(&program.genome);
+                let x = world.rng.random_range(-SEED_SPREAD..SEED_SPREAD);
+                let y = world.rng.random_range(-SEED_SPREAD..SEED_SPREAD);
+                let lifeform = Lifeform::from_vm_with_rng(vm, x, y, &mut world.rng);
+                world.lifeforms.push(lifeform);
+            }
+        }
+        world.reset_species();
+    }
+}
@@ -0,0 +1,240 @@
+// selftest.rs
+
+// Short seeded headless runs that check simulation invariants, for CI and custom configs
+
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::bundle::PopulationBundle;
+use crate::provenance::Fnv1a;
+use crate::world::{EnergyLeak, FoodModel, ReproductionMode, World, WorldConfig};
+
+pub const DEFAULT_SEED: u64 = 0x5eed;
+pub const DEFAULT_TICKS: u64 = 1000;
+pub const MAX_POPULATION: usize = 2000; // More lifeforms than this means runaway reproduction
+pub const ENERGY_TOLERANCE: f64 = 0.01; // Allowed relative drift of a closed nutrient budget
+
+/// A way an invariant check failed
+#[derive(Debug)]
+pub enum SelftestError {
+    Panicked {
+        phase: &'static str,
+        message: String,
+    },
+    PopulationOutOfBounds {
+        phase: &'static str,
+        tick: u64,
+        population: usize,
+    },
+    EnergyDrift {
+        phase: &'static str,
+        initial: f64,
+        last: f64,
+    },
+    EnergyLeak {
+        phase: &'static str,
+        leak: EnergyLeak,
+    },
+    Nondeterministic {
+        phase: &'static str,
+        first: u64,
+        second: u64,
+    },
+}
+
+impl fmt::Display for SelftestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SelftestError::Panicked { phase, message } => {
+                write!(f, "{}: simulation panicked: {}", phase, message)
+            }
+            SelftestError::PopulationOutOfBounds {
+                phase,
+                tick,
+                population,
+            } => write!(
+                f,
+                "{}: population {} at tick {} is outside 1..={}",
+                phase, population, tick, MAX_POPULATION
+            ),
+            SelftestError::EnergyDrift {
+                phase,
+                initial,
+                last,
+            } => write!(
+                f,
+                "{}: total energy drifted from {:.1} to {:.1}",
+                phase, initial, last
+            ),
+            SelftestError::EnergyLeak { phase, leak } => write!(
+                f,
+                "{}: energy audit found {:+.3} unaccounted energy at tick {}",
+                phase,
+                leak.amount(),
+                leak.tick
+            ),
+            SelftestError::Nondeterministic {
+                phase,
+                first,
+                second,
+            } => write!(
+                f,
+                "{}: same seed gave different traces ({:016x} vs {:016x})",
+                phase, first, second
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SelftestError {}
+
+/// Outcome of one passing phase
+#[derive(Debug, Clone)]
+pub struct PhaseReport {
+    pub name: &'static str,
+    pub ticks: u64,
+    pub population: usize,
+    pub food_count: usize,
+    pub births: u64,
+    /// Hash of the final world state; equal seeds and configs must give equal hashes
+    pub trace_hash: u64,
+}
+
+/// The phases run by `run`: the plain world, then an evolving world with a closed energy budget
+pub fn phases(base: &WorldConfig, seed: u64) -> Vec<(&'static str, WorldConfig)> {
+    let world = WorldConfig {
+        seed: Some(seed),
+        // Both depend on wall-clock time or the camera, which would break determinism
+        tick_budget_ms: None,
+        coarsening: None,
+        ..base.clone()
+    };
+    let evolution = WorldConfig {
+        reproduction: match world.reproduction {
+            ReproductionMode::Off => ReproductionMode::Fission,
+            mode => mode,
+        },
+        food_model: FoodModel::NutrientCycling,
+        ..world.clone()
+    };
+    vec![("world", world), ("evolution", evolution)]
+}
+
+/// Run every phase for `ticks` ticks, optionally starting from a bundled population
+pub fn run(
+    base: &WorldConfig,
+    population: Option<&PopulationBundle>,
+    seed: u64,
+    ticks: u64,
+) -> Result<Vec<PhaseReport>, SelftestError> {
+    phases(base, seed)
+        .into_iter()
+        .map(|(name, config)| run_phase(name, &config, population, ticks))
+        .collect()
+}
+
+/// Run one phase twice and check its invariants, including that both runs match
+pub fn run_phase(
+    name: &'static str,
+    config: &WorldConfig,
+    population: Option<&PopulationBundle>,
+    ticks: u64,
+) -> Result<PhaseReport, SelftestError> {
+    let first = simulate(name, config, population, ticks)?;
+    let second = simulate(name, config, population, ticks)?;
+    if first.trace_hash != second.trace_hash {
+        return Err(SelftestError::Nondeterministic {
+            phase: name,
+            first: first.trace_hash,
+            second: second.trace_hash,
+        });
+    }
+    Ok(first)
+}
+
+fn simulate(
+    name: &'static str,
+    config: &WorldConfig,
+    population: Option<&PopulationBundle>,
+    ticks: u64,
+) -> Result<PhaseReport, SelftestError> {
+    let run = || -> Result<PhaseReport, SelftestError> {
+        let mut world = World::new(config.clone());
+        if let Some(bundle) = population {
+            let lifeforms = PopulationBundle {
+                config: config.clone(),
+                ..bundle.clone()
+            };
+            lifeforms.apply_to(&mut world);
+        }
+        world.enable_audit();
+
+        let closed_budget = config.food_model == FoodModel::NutrientCycling;
+        let initial_energy = world.energy_stock();
+        for tick in 1..=ticks {
+            world.tick();
+            world.update_ecology();
+
+            let population = world.lifeforms.len();
+            if population == 0 || population > MAX_POPULATION {
+                return Err(SelftestError::PopulationOutOfBounds {
+                    phase: name,
+                    tick,
+                    population,
+                });
+            }
+            if let Some(&leak) = world.audit.as_ref().and_then(|a| a.leaks.first()) {
+                return Err(SelftestError::EnergyLeak { phase: name, leak });
+            }
+            let energy = world.energy_stock();
+            if closed_budget && (energy - initial_energy).abs() > initial_energy * ENERGY_TOLERANCE
+            {
+                return Err(SelftestError::EnergyDrift {
+                    phase: name,
+                    initial: initial_energy,
+                    last: energy,
+                });
+            }
+        }
+
+        Ok(PhaseReport {
+            name,
+            ticks,
+            population: world.lifeforms.len(),
+            food_count: world.food_items.len(),
+            births: world.reproduction_stats.births(),
+            trace_hash: trace_hash(&world),
+        })
+    };
+
+    panic::catch_unwind(AssertUnwindSafe(run)).unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        Err(SelftestError::Panicked {
+            phase: name,
+            message,
+        })
+    })
+}
+
+/// FNV-1a over genomes, memory, positions and food, stable across platforms and releases
+pub fn trace_hash(world: &World) -> u64 {
+    let mut hash = Fnv1a::new();
+    for lifeform in &world.lifeforms {
+        hash.write(&lifeform.vm.initial_state);
+        hash.write(&lifeform.vm.memory);
+        hash.write(&lifeform.x.to_le_bytes());
+        hash.write(&lifeform.y.to_le_bytes());
+        hash.write(&lifeform.energy.to_le_bytes());
+        hash.write(&lifeform.age.to_le_bytes());
+    }
+    for food in &world.food_items {
+        hash.write(&food.x.to_le_bytes());
+        hash.write(&food.y.to_le_bytes());
+        hash.write(&food.energy_value.to_le_bytes());
+    }
+    hash.finish()
+}
@@ -0,0 +1,144 @@
+// stats.rs
+
+// Constant-memory aggregates for long runs: running moments, extremes and reservoir samples
+
+use rand::Rng;
+
+use crate::rng::SimRng;
+
+// Stats constants
+pub const RESERVOIR_SIZE: usize = 64; // Samples a `Metric` keeps, however long the run
+
+/// Count, mean, variance, min and max of a stream of values, in constant memory.
+///
+/// The mean and variance are updated with Welford's method, so they stay accurate
+/// over millions of values.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct RunningStats {
+    count: u64,
+    mean: f64,
+    /// Sum of squared differences from the mean
+    m2: f64,
+    min: f64,
+    max: f64,
+}
+
+impl RunningStats {
+    pub fn push(&mut self, value: f64) {
+        if self.count == 0 {
+            self.min = value;
+            self.max = value;
+        }
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// 0 before any value
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    /// Population variance; 0 before two values
+    pub fn variance(&self) -> f64 {
+        if self.count < 2 {
+            0.0
+        } else {
+            self.m2 / self.count as f64
+        }
+    }
+
+    pub fn std_dev(&self) -> f64 {
+        self.variance().sqrt()
+    }
+
+    pub fn min(&self) -> Option<f64> {
+        (self.count > 0).then_some(self.min)
+    }
+
+    pub fn max(&self) -> Option<f64> {
+        (self.count > 0).then_some(self.max)
+    }
+}
+
+/// A uniform random sample of at most `capacity` items from a stream of any length
+#[derive(Debug, Clone)]
+pub struct Reservoir<T> {
+    samples: Vec<T>,
+    capacity: usize,
+    seen: u64,
+    /// Kept apart from the world's generator so sampling cannot change a run
+    rng: SimRng,
+}
+
+impl<T> Reservoir<T> {
+    pub fn new(capacity: usize, seed: u64) -> Self {
+        Self {
+            samples: Vec::with_capacity(capacity),
+            capacity,
+            seen: 0,
+            rng: SimRng::new(seed),
+        }
+    }
+
+    pub fn push(&mut self, item: T) {
+        self.seen += 1;
+        if self.samples.len() < self.capacity {
+            self.samples.push(item);
+            return;
+        }
+        let slot = self.rng.random_range(0..self.seen);
+        if let Some(sample) = self.samples.get_mut(slot as usize) {
+            *sample = item;
+        }
+    }
+
+    /// In no particular order
+    pub fn samples(&self) -> &[T] {
+        &self.samples
+    }
+
+    /// Items pushed so far, kept or not
+    pub fn seen(&self) -> u64 {
+        self.seen
+    }
+}
+
+/// One tracked quantity: aggregates over the whole run, aggregates since the last
+/// flush, and a sample of `(tick, value)` pairs
+#[derive(Debug, Clone)]
+pub struct Metric {
+    pub name: &'static str,
+    pub total: RunningStats,
+    pub window: RunningStats,
+    pub samples: Reservoir<(u64, f64)>,
+}
+
+impl Metric {
+    pub fn new(name: &'static str, seed: u64) -> Self {
+        Self {
+            name,
+            total: RunningStats::default(),
+            window: RunningStats::default(),
+            samples: Reservoir::new(RESERVOIR_SIZE, seed),
+        }
+    }
+
+    pub fn record(&mut self, tick: u64, value: f64) {
+        self.total.push(value);
+        self.window.push(value);
+        self.samples.push((tick, value));
+    }
+
+    /// The aggregates since the last flush, starting a new window
+    pub fn flush(&mut self) -> RunningStats {
+        std::mem::take(&mut self.window)
+    }
+}
@@ -0,0 +1,336 @@
+// trace.rs
+
+// Execution history: the last few instructions in full, and a sampled long-horizon trace
+
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::compute::Instruction;
+
+pub const TRACE_SAMPLE_PERIOD: u64 = 64; // Every this many steps one instruction is sampled
+pub const TRACE_CAPACITY: usize = 128; // Default depth of `SampledTrace`; oldest entries are dropped past it
+pub const RECENT_EVENTS: usize = 16; // Instructions kept by `RecentTrace`
+
+/// One executed instruction, as recorded on every step
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TraceEvent {
+    pub pc: usize,
+    pub opcode: u8,
+    /// Operand bytes of a built-in instruction, little-endian; `None` for one-byte
+    /// and custom opcodes
+    pub operand: Option<u16>,
+    pub acc_before: u8,
+    pub acc_after: u8,
+    /// Data stack pointer after the step
+    pub dsp: usize,
+}
+
+impl fmt::Display for TraceEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match Instruction::decode(self.opcode) {
+            Some(instruction) => write!(f, "{:04}: {}", self.pc, instruction)?,
+            None => write!(f, "{:04}: 0x{:02X}", self.pc, self.opcode)?,
+        }
+        match (Instruction::decode(self.opcode), self.operand) {
+            (Some(i), Some(operand)) if i.is_immediate() => write!(f, " #{}", operand)?,
+            (Some(i), Some(operand)) if i.is_relative() => write!(f, " {:+}", operand as i8)?,
+            (_, Some(operand)) => write!(f, " {}", operand)?,
+            (_, None) => {}
+        }
+        if self.acc_before == self.acc_after {
+            write!(f, " acc={}", self.acc_after)?;
+        } else {
+            write!(f, " acc={} -> {}", self.acc_before, self.acc_after)?;
+        }
+        write!(f, " dsp={}", self.dsp)
+    }
+}
+
+/// The last `RECENT_EVENTS` executed instructions, in a fixed ring that never allocates
+#[derive(Debug, Clone, Default)]
+pub struct RecentTrace {
+    events: [TraceEvent; RECENT_EVENTS],
+    /// Slot the next event goes into
+    next: usize,
+    len: usize,
+}
+
+impl RecentTrace {
+    pub fn push(&mut self, event: TraceEvent) {
+        self.events[self.next] = event;
+        self.next = (self.next + 1) % RECENT_EVENTS;
+        self.len = (self.len + 1).min(RECENT_EVENTS);
+    }
+
+    /// Oldest first
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &TraceEvent> + ExactSizeIterator {
+        let start = (self.next + RECENT_EVENTS - self.len) % RECENT_EVENTS;
+        (0..self.len).map(move |i| &self.events[(start + i) % RECENT_EVENTS])
+    }
+
+    pub fn last(&self) -> Option<&TraceEvent> {
+        self.iter().next_back()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.next = 0;
+        self.len = 0;
+    }
+
+    /// Each event formatted for display, oldest first
+    pub fn lines(&self) -> impl Iterator<Item = String> + '_ {
+        self.iter().map(TraceEvent::to_string)
+    }
+}
+
+/// One sampled instruction or taken jump
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceEntry {
+    /// Steps the VM had executed before this one, over its whole life
+    pub step: u64,
+    pub pc: usize,
+    pub opcode: u8,
+    pub acc: u8,
+    /// Where control went, if the instruction did not fall through to the next one
+    pub jumped_to: Option<usize>,
+    /// Further consecutive times this same jump was taken, folded into this entry
+    pub repeats: u32,
+}
+
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = Instruction::decode(self.opcode).map_or("???", Instruction::mnemonic);
+        write!(
+            f,
+            "step {:>7}: {:04} {:<4} acc={:<3}",
+            self.step, self.pc, name, self.acc
+        )?;
+        if let Some(target) = self.jumped_to {
+            write!(f, " -> {:04}", target)?;
+        }
+        if self.repeats > 0 {
+            write!(f, " (x{})", self.repeats + 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// Every `TRACE_SAMPLE_PERIOD`-th instruction plus every taken jump, in a ring buffer
+#[derive(Debug, Clone)]
+pub struct SampledTrace {
+    /// Oldest first
+    pub entries: VecDeque<TraceEntry>,
+    steps: u64,
+    /// Entries kept before the oldest are dropped, `TRACE_CAPACITY` by default
+    depth: usize,
+    /// Entries kept below `depth`, for sandboxed programs
+    limit: Option<usize>,
+}
+
+impl Default for SampledTrace {
+    fn default() -> Self {
+        Self::with_depth(TRACE_CAPACITY)
+    }
+}
+
+impl SampledTrace {
+    /// An empty trace keeping up to `depth` entries; 0 behaves like 1
+    pub fn with_depth(depth: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            steps: 0,
+            depth: depth.max(1),
+            limit: None,
+        }
+    }
+
+    /// Note one executed instruction; `next_pc` is where execution continues, and
+    /// `address_bytes` the VM's address operand width
+    pub fn record(&mut self, pc: usize, opcode: u8, acc: u8, next_pc: usize, address_bytes: usize) {
+        let step = self.steps;
+        self.steps += 1;
+
+        // Custom opcodes have no known size, so only built-ins are checked for jumps;
+        // HLT stays put without jumping anywhere
+        let fall_through = Instruction::decode(opcode)
+            .filter(|&i| i != Instruction::HLT)
+            .map(|i| pc + i.size_for(address_bytes));
+        let jumped_to = fall_through
+            .filter(|&next| next != next_pc)
+            .map(|_| next_pc);
+
+        if jumped_to.is_some()
+            && let Some(last) = self.entries.back_mut()
+            && last.pc == pc
+            && last.jumped_to == jumped_to
+        {
+            // A loop retaking the same branch costs no extra memory
+            last.repeats += 1;
+            return;
+        }
+        if jumped_to.is_none() && !step.is_multiple_of(TRACE_SAMPLE_PERIOD) {
+            return;
+        }
+
+        if self.entries.len() >= self.capacity() {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(TraceEntry {
+            step,
+            pc,
+            opcode,
+            acc,
+            jumped_to,
+            repeats: 0,
+        });
+    }
+
+    /// Entries kept before the oldest are dropped
+    pub fn capacity(&self) -> usize {
+        self.limit
+            .map_or(self.depth, |limit| limit.clamp(1, self.depth))
+    }
+
+    /// Keep at most `limit` entries, never more than the depth, dropping the oldest
+    /// ones already over it; `None` restores the depth
+    pub fn set_limit(&mut self, limit: Option<usize>) {
+        self.limit = limit;
+        let excess = self.entries.len().saturating_sub(self.capacity());
+        self.entries.drain(..excess);
+    }
+
+    /// Instructions executed since the trace was started or cleared
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.steps = 0;
+    }
+}
+
+/// How many times each opcode executed, indexed by the opcode byte.
+///
+/// Nothing is allocated until the first opcode is counted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OpcodeProfile(Vec<u64>);
+
+impl OpcodeProfile {
+    pub fn record(&mut self, opcode: u8) {
+        if self.0.len() < 256 {
+            self.0.resize(256, 0);
+        }
+        self.0[opcode as usize] += 1;
+    }
+
+    pub fn count(&self, opcode: u8) -> u64 {
+        self.0.get(opcode as usize).copied().unwrap_or(0)
+    }
+
+    /// Instructions counted since the profile was started or reset
+    pub fn total(&self) -> u64 {
+        self.0.iter().sum()
+    }
+
+    /// Number of different opcodes executed at least once
+    pub fn distinct(&self) -> usize {
+        self.0.iter().filter(|&&count| count > 0).count()
+    }
+
+    /// Opcodes executed at least once with their counts, most frequent first
+    pub fn used(&self) -> Vec<(u8, u64)> {
+        let mut used: Vec<(u8, u64)> = (0..=u8::MAX)
+            .map(|opcode| (opcode, self.count(opcode)))
+            .filter(|&(_, count)| count > 0)
+            .collect();
+        used.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        used
+    }
+
+    /// Add another profile's counts to this one, e.g. to sum over several programs
+    pub fn merge(&mut self, other: &OpcodeProfile) {
+        if self.0.len() < other.0.len() {
+            self.0.resize(other.0.len(), 0);
+        }
+        for (total, count) in self.0.iter_mut().zip(&other.0) {
+            *total += count;
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.0.clear();
+    }
+}
+
+/// The used opcodes, most frequent first, e.g. `LDA 40 (50.0%), 0xF3 2 (2.5%)`
+impl fmt::Display for OpcodeProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.total().max(1) as f64;
+        for (i, (opcode, count)) in self.used().into_iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            match Instruction::decode(opcode) {
+                Some(instruction) => write!(f, "{}", instruction)?,
+                None => write!(f, "0x{:02X}", opcode)?,
+            }
+            write!(f, " {} ({:.1}%)", count, count as f64 * 100.0 / total)?;
+        }
+        Ok(())
+    }
+}
+
+/// How often each memory cell was read, written and executed as the PC, indexed by
+/// address. Only built with the `heatmap` feature, as it costs time on every step.
+#[cfg(feature = "heatmap")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Heatmap {
+    /// Data loads: LDA, arithmetic operands, SKE/SKNE, pops and SWP
+    pub reads: Vec<u32>,
+    /// Stores that went through, including pushes and SWP
+    pub writes: Vec<u32>,
+    /// Instructions that started at the cell
+    pub executes: Vec<u32>,
+}
+
+#[cfg(feature = "heatmap")]
+impl Heatmap {
+    pub fn new(len: usize) -> Self {
+        Self {
+            reads: vec![0; len],
+            writes: vec![0; len],
+            executes: vec![0; len],
+        }
+    }
+
+    /// Whether the cell was ever executed, i.e. is live code rather than junk
+    pub fn is_executed(&self, addr: usize) -> bool {
+        self.executes.get(addr).is_some_and(|&n| n > 0)
+    }
+
+    /// Largest count of any kind in any cell, for scaling a display
+    pub fn max(&self) -> u32 {
+        self.reads
+            .iter()
+            .chain(&self.writes)
+            .chain(&self.executes)
+            .copied()
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn clear(&mut self) {
+        for counts in [&mut self.reads, &mut self.writes, &mut self.executes] {
+            counts.fill(0);
+        }
+    }
+}
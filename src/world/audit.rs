@@ -0,0 +1,133 @@
+// audit.rs
+
+// Energy bookkeeping that flags energy appearing or vanishing without a recorded cause
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::{Lifeform, World};
+
+// Audit constants
+pub const AUDIT_TOLERANCE: f64 = 0.01; // Per-check difference put down to float rounding
+pub const MAX_RECORDED_LEAKS: usize = 64; // Older leaks are dropped once this many are kept
+
+/// Cumulative energy that crossed the world's boundary, by cause
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct EnergyFlow {
+    /// Energy in food created by the spawner
+    pub food_spawned: f64,
+    /// Energy given to random lifeforms topping up the population
+    pub lifeforms_spawned: f64,
+    pub immigrated: f64,
+    /// Energy spent on metabolism and movement and not recycled as nutrients
+    pub metabolism: f64,
+    /// Food energy wasted because the eater was already full or the food was toxic
+    pub overflow: f64,
+    /// Energy still held by lifeforms when they died
+    pub deaths: f64,
+    pub emigrated: f64,
+    /// Energy frozen on disk with streamed-out chunks
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub stored: f64,
+    /// Energy brought back when streamed chunks were reloaded
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub restored: f64,
+}
+
+impl EnergyFlow {
+    pub fn inflow(&self) -> f64 {
+        self.food_spawned + self.lifeforms_spawned + self.immigrated + self.restored
+    }
+
+    pub fn outflow(&self) -> f64 {
+        self.metabolism + self.overflow + self.deaths + self.emigrated + self.stored
+    }
+}
+
+/// A check where the stock did not match the recorded flows
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EnergyLeak {
+    pub tick: u64,
+    /// Stock implied by the previous check and the flows since
+    pub expected: f64,
+    pub actual: f64,
+}
+
+impl EnergyLeak {
+    /// Positive when energy appeared from nowhere, negative when it vanished
+    pub fn amount(&self) -> f64 {
+        self.actual - self.expected
+    }
+}
+
+/// Debug mode comparing the energy stock against recorded flows after every update
+#[derive(Debug, Clone, Default)]
+pub struct EnergyAudit {
+    last_stock: f64,
+    last_flow: EnergyFlow,
+    pub checks: u64,
+    pub leak_count: u64,
+    /// Most recent leaks, oldest first
+    pub leaks: Vec<EnergyLeak>,
+}
+
+/// Energy a lifeform takes with it when it leaves the world
+pub(super) fn held_energy(lifeform: &Lifeform) -> f64 {
+    lifeform.energy.max(0.0) as f64 + lifeform.waste as f64
+}
+
+impl World {
+    /// Energy held by lifeforms, food and the nutrient field
+    pub fn energy_stock(&self) -> f64 {
+        let lifeforms: f64 = self.lifeforms.iter().map(held_energy).sum();
+        let food: f64 = self.food_items.iter().map(|f| f.energy_value as f64).sum();
+        lifeforms + food + self.nutrients.total() as f64
+    }
+
+    /// Start auditing from the current state, discarding any previous audit
+    pub fn enable_audit(&mut self) {
+        self.audit = Some(EnergyAudit {
+            last_stock: self.energy_stock(),
+            last_flow: self.energy_flow,
+            ..EnergyAudit::default()
+        });
+    }
+
+    pub(super) fn audit_energy(&mut self) {
+        if self.audit.is_none() {
+            return;
+        }
+        let stock = self.energy_stock();
+        let flow = self.energy_flow;
+        let tick = self.tick_count;
+        let Some(audit) = &mut self.audit else {
+            return;
+        };
+
+        let expected = audit.last_stock + (flow.inflow() - audit.last_flow.inflow())
+            - (flow.outflow() - audit.last_flow.outflow());
+        audit.checks += 1;
+        if (stock - expected).abs() > AUDIT_TOLERANCE {
+            let leak = EnergyLeak {
+                tick,
+                expected,
+                actual: stock,
+            };
+            tracing::warn!(
+                "Energy leak at tick {}: expected {:.3}, found {:.3} ({:+.3})",
+                tick,
+                expected,
+                stock,
+                leak.amount()
+            );
+            audit.leak_count += 1;
+            if audit.leaks.len() == MAX_RECORDED_LEAKS {
+                audit.leaks.remove(0);
+            }
+            audit.leaks.push(leak);
+        }
+        audit.last_stock = stock;
+        audit.last_flow = flow;
+    }
+}
@@ -0,0 +1,106 @@
+// balance.rs
+
+// Feedback control of the food supply, holding the population near a target
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::World;
+
+// Balance constants
+pub const MAX_RECORDED_ADJUSTMENTS: usize = 256; // Older adjustments are dropped once this many are kept
+
+/// Adjusts how fast food appears so the population settles near `target_population`.
+///
+/// Every `interval` simulated seconds the food rate is scaled by
+/// `exp(gain * error)`, where error is the population's shortfall as a fraction of
+/// the target. A population below target gets more food, one above gets less.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct FoodController {
+    pub target_population: usize,
+    pub gain: f64,
+    /// Simulated seconds between adjustments, long enough for births and deaths to respond
+    pub interval: f64,
+    /// Bounds on the food rate, relative to the normal spawn rate
+    pub min_rate: f64,
+    pub max_rate: f64,
+}
+
+impl Default for FoodController {
+    fn default() -> Self {
+        Self {
+            target_population: 40,
+            gain: 0.5,
+            interval: 10.0,
+            min_rate: 0.1,
+            max_rate: 10.0,
+        }
+    }
+}
+
+/// One change the controller made to the food rate
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FoodRateAdjustment {
+    pub sim_time: f64,
+    pub population: usize,
+    pub old_rate: f64,
+    pub new_rate: f64,
+}
+
+impl World {
+    /// Food spawn rate relative to normal; stays 1.0 without a `food_controller` or `set_food_rate`
+    pub fn food_rate(&self) -> f64 {
+        self.food_rate
+    }
+
+    /// Set the food rate by hand; a food controller goes on adjusting it from there
+    pub fn set_food_rate(&mut self, rate: f64) {
+        self.food_rate = rate.max(f64::MIN_POSITIVE);
+    }
+
+    /// Curriculum scarcity combined with the controller's food rate
+    pub(super) fn food_scarcity(&self) -> f64 {
+        self.difficulty().food_scarcity / self.food_rate
+    }
+
+    /// Nudge the food rate toward the target population once per controller interval
+    pub(super) fn balance_food(&mut self) {
+        let Some(controller) = self.config.food_controller else {
+            return;
+        };
+        if self.sim_time - self.last_balance_time < controller.interval {
+            return;
+        }
+        self.last_balance_time = self.sim_time;
+
+        let target = controller.target_population.max(1) as f64;
+        let population = self.lifeforms.len();
+        let error = (target - population as f64) / target;
+        let old_rate = self.food_rate;
+        let new_rate = (old_rate * (controller.gain * error).exp())
+            .clamp(controller.min_rate, controller.max_rate);
+        if new_rate == old_rate {
+            return;
+        }
+        self.food_rate = new_rate;
+        tracing::info!(
+            "Population {} (target {}): food rate x{:.2} -> x{:.2}",
+            population,
+            controller.target_population,
+            old_rate,
+            new_rate
+        );
+        if self.food_rate_log.len() == MAX_RECORDED_ADJUSTMENTS {
+            self.food_rate_log.remove(0);
+        }
+        self.food_rate_log.push(FoodRateAdjustment {
+            sim_time: self.sim_time,
+            population,
+            old_rate,
+            new_rate,
+        });
+    }
+}
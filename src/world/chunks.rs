@@ -0,0 +1,280 @@
+// chunks.rs
+
+// Square chunks of the map, ticked in parallel and streamed to disk while far from the viewport
+
+use std::collections::BTreeSet;
+#[cfg(feature = "serde")]
+use std::fs::File;
+#[cfg(feature = "serde")]
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::{Food, Lifeform, TickContext, Viewport, World, audit};
+use crate::bundle::CreatureRecord;
+
+// Chunking constants
+pub const DEFAULT_CHUNK_SIZE: f32 = 200.0; // World units along each side of a chunk
+
+/// Splitting the map into chunks; off by default because streaming changes results
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Chunking {
+    pub chunk_size: f32,
+    /// Worker threads sharing the chunks each tick; 1 ticks them in order on the caller
+    pub threads: usize,
+    /// Chunks farther than this from the viewport are frozen and written to
+    /// `stream_dir`; `None` keeps every chunk in memory and running
+    pub stream_distance: Option<f32>,
+    pub stream_dir: PathBuf,
+}
+
+impl Default for Chunking {
+    fn default() -> Self {
+        Self {
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            stream_distance: None,
+            stream_dir: PathBuf::from("chunks"),
+        }
+    }
+}
+
+/// Position of a chunk in the chunk grid; chunk (0, 0) starts at the map origin
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ChunkCoord {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl ChunkCoord {
+    pub fn containing(x: f32, y: f32, chunk_size: f32) -> Self {
+        Self {
+            x: (x / chunk_size).floor() as i32,
+            y: (y / chunk_size).floor() as i32,
+        }
+    }
+
+    pub fn bounds(&self, chunk_size: f32) -> Viewport {
+        Viewport {
+            min_x: self.x as f32 * chunk_size,
+            min_y: self.y as f32 * chunk_size,
+            max_x: (self.x + 1) as f32 * chunk_size,
+            max_y: (self.y + 1) as f32 * chunk_size,
+        }
+    }
+
+    /// Whether any part of the chunk lies within `distance` of `viewport`
+    fn is_near(&self, viewport: &Viewport, chunk_size: f32, distance: f32) -> bool {
+        let bounds = self.bounds(chunk_size);
+        let area = viewport.expanded(distance);
+        bounds.max_x >= area.min_x
+            && bounds.min_x <= area.max_x
+            && bounds.max_y >= area.min_y
+            && bounds.min_y <= area.max_y
+    }
+
+    fn file_name(&self) -> String {
+        format!("chunk_{}_{}.cbor", self.x, self.y)
+    }
+}
+
+/// Everything a frozen chunk held, as written to disk
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct StoredChunk {
+    creatures: Vec<CreatureRecord>,
+    food: Vec<Food>,
+}
+
+#[cfg(feature = "serde")]
+impl StoredChunk {
+    fn save(&self, path: &Path) -> std::io::Result<()> {
+        let writer = BufWriter::new(File::create(path)?);
+        ciborium::into_writer(self, writer).map_err(std::io::Error::other)
+    }
+
+    fn load(path: &Path) -> std::io::Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        ciborium::from_reader(reader).map_err(std::io::Error::other)
+    }
+}
+
+// Without serde chunks cannot be written, so streaming leaves them all in memory
+#[cfg(not(feature = "serde"))]
+impl StoredChunk {
+    fn save(&self, _path: &Path) -> std::io::Result<()> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "chunk streaming needs the serde feature",
+        ))
+    }
+
+    fn load(path: &Path) -> std::io::Result<Self> {
+        Self::default().save(path).map(|_| Self::default())
+    }
+}
+
+/// Tick every lifeform, a chunk at a time, spread over `chunking.threads` workers.
+///
+/// Lifeforms are reordered so each chunk's are contiguous. A lifeform's update only
+/// reads the shared context, so the outcome does not depend on the thread count.
+pub(super) fn tick_chunks(
+    lifeforms: &mut [Lifeform],
+    chunking: &Chunking,
+    context: TickContext<'_>,
+) {
+    let size = chunking.chunk_size;
+    lifeforms.sort_by_key(|l| ChunkCoord::containing(l.x, l.y, size));
+
+    // One slice per chunk, then dealt to the workers in turn
+    let mut chunks = Vec::new();
+    let mut rest = lifeforms;
+    while let Some(first) = rest.first() {
+        let coord = ChunkCoord::containing(first.x, first.y, size);
+        let len = rest
+            .iter()
+            .position(|l| ChunkCoord::containing(l.x, l.y, size) != coord)
+            .unwrap_or(rest.len());
+        let (chunk, tail) = rest.split_at_mut(len);
+        chunks.push(chunk);
+        rest = tail;
+    }
+
+    let threads = chunking.threads.clamp(1, chunks.len().max(1));
+    if threads == 1 {
+        chunks.into_iter().flatten().for_each(|l| l.tick(context));
+        return;
+    }
+    let mut shares: Vec<Vec<&mut [Lifeform]>> = (0..threads).map(|_| Vec::new()).collect();
+    for (i, chunk) in chunks.into_iter().enumerate() {
+        shares[i % threads].push(chunk);
+    }
+    std::thread::scope(|scope| {
+        for share in shares {
+            scope.spawn(move || share.into_iter().flatten().for_each(|l| l.tick(context)));
+        }
+    });
+}
+
+impl World {
+    /// Chunks currently frozen on disk
+    pub fn stored_chunks(&self) -> &BTreeSet<ChunkCoord> {
+        &self.stored_chunks
+    }
+
+    /// Freeze chunks that drifted far from the viewport and thaw those it came near
+    pub(super) fn stream_chunks(&mut self) {
+        let Some(chunking) = &self.config.chunking else {
+            return;
+        };
+        let (Some(distance), Some(viewport)) = (chunking.stream_distance, self.viewport) else {
+            return;
+        };
+        let size = chunking.chunk_size;
+        let dir = chunking.stream_dir.clone();
+
+        let near: Vec<ChunkCoord> = self
+            .stored_chunks
+            .iter()
+            .copied()
+            .filter(|c| c.is_near(&viewport, size, distance))
+            .collect();
+        for coord in near {
+            self.thaw_chunk(coord, &dir);
+        }
+
+        let far: BTreeSet<ChunkCoord> = self
+            .lifeforms
+            .iter()
+            .map(|l| (l.x, l.y))
+            .chain(self.food_items.iter().map(|f| (f.x, f.y)))
+            .map(|(x, y)| ChunkCoord::containing(x, y, size))
+            .filter(|c| !c.is_near(&viewport, size, distance))
+            .collect();
+        for coord in far {
+            self.freeze_chunk(coord, size, &dir);
+        }
+    }
+
+    /// Move a chunk's lifeforms and food to disk; they leave the energy stock meanwhile
+    fn freeze_chunk(&mut self, coord: ChunkCoord, size: f32, dir: &Path) {
+        let in_chunk = |x: f32, y: f32| ChunkCoord::containing(x, y, size) == coord;
+        let path = dir.join(coord.file_name());
+        // A chunk can be frozen again before it was ever thawed, e.g. after a lifeform wandered in
+        let mut stored = if self.stored_chunks.contains(&coord) {
+            match StoredChunk::load(&path) {
+                Ok(stored) => stored,
+                Err(e) => {
+                    tracing::error!("Failed to read chunk {}: {}", path.display(), e);
+                    return;
+                }
+            }
+        } else {
+            StoredChunk::default()
+        };
+        let (leaving, staying): (Vec<_>, Vec<_>) = std::mem::take(&mut self.lifeforms)
+            .into_iter()
+            .partition(|l| in_chunk(l.x, l.y));
+        let (food_leaving, food_staying): (Vec<_>, Vec<_>) = std::mem::take(&mut self.food_items)
+            .into_iter()
+            .partition(|f| in_chunk(f.x, f.y));
+        stored
+            .creatures
+            .extend(leaving.iter().map(CreatureRecord::from_lifeform));
+        stored.food.extend(food_leaving.iter().cloned());
+
+        let saved = std::fs::create_dir_all(dir).and_then(|_| stored.save(&path));
+        if let Err(e) = saved {
+            tracing::error!("Failed to write chunk {}: {}", path.display(), e);
+            self.lifeforms = staying.into_iter().chain(leaving).collect();
+            self.food_items = food_staying.into_iter().chain(food_leaving).collect();
+            return;
+        }
+        self.energy_flow.stored += leaving.iter().map(audit::held_energy).sum::<f64>()
+            + food_leaving
+                .iter()
+                .map(|f| f.energy_value as f64)
+                .sum::<f64>();
+        self.lifeforms = staying;
+        self.food_items = food_staying;
+        self.stored_chunks.insert(coord);
+        tracing::debug!(
+            "Froze chunk ({}, {}): {} lifeforms, {} food",
+            coord.x,
+            coord.y,
+            stored.creatures.len(),
+            stored.food.len()
+        );
+    }
+
+    /// Bring a frozen chunk back exactly as it was stored
+    fn thaw_chunk(&mut self, coord: ChunkCoord, dir: &Path) {
+        let path = dir.join(coord.file_name());
+        let stored = match StoredChunk::load(&path) {
+            Ok(stored) => stored,
+            Err(e) => {
+                tracing::error!("Failed to read chunk {}: {}", path.display(), e);
+                return;
+            }
+        };
+        for record in &stored.creatures {
+            let lifeform = record.to_lifeform(&mut self.rng);
+            self.energy_flow.restored += audit::held_energy(&lifeform);
+            self.lifeforms.push(lifeform);
+        }
+        for food in stored.food {
+            self.energy_flow.restored += food.energy_value as f64;
+            self.food_items.push(food);
+        }
+        if let Err(e) = std::fs::remove_file(&path) {
+            tracing::warn!("Failed to remove chunk {}: {}", path.display(), e);
+        }
+        self.stored_chunks.remove(&coord);
+        tracing::debug!("Thawed chunk ({}, {})", coord.x, coord.y);
+    }
+}
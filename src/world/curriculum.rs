@@ -0,0 +1,127 @@
+// curriculum.rs
+
+// Difficulty schedule that makes the world harsher as the population gets fitter
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::World;
+
+/// How harsh the environment currently is; the default is the world's normal rules
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Difficulty {
+    /// Food appears this many times more slowly, and fewer items are kept around
+    pub food_scarcity: f64,
+    /// Fraction of `MAX_FOOD_DETECTION_RANGE` within which lifeforms sense food
+    pub detection_range: f32,
+    /// Chance that a new food item is toxic
+    pub toxin_chance: f64,
+}
+
+impl Default for Difficulty {
+    fn default() -> Self {
+        Self {
+            food_scarcity: 1.0,
+            detection_range: 1.0,
+            toxin_chance: 0.0,
+        }
+    }
+}
+
+/// A difficulty and the fitness the population needs before it is applied
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CurriculumStage {
+    /// Mean lifeform age, in simulated seconds, that unlocks this stage
+    pub fitness: f64,
+    pub difficulty: Difficulty,
+}
+
+/// Stages entered one after another as fitness improves; the world never goes back
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Curriculum {
+    /// In order of increasing difficulty
+    pub stages: Vec<CurriculumStage>,
+    /// Simulated seconds fitness must stay at a stage's threshold before it is entered
+    pub hold_seconds: f64,
+}
+
+impl Default for Curriculum {
+    fn default() -> Self {
+        let stage = |fitness, food_scarcity, detection_range, toxin_chance| CurriculumStage {
+            fitness,
+            difficulty: Difficulty {
+                food_scarcity,
+                detection_range,
+                toxin_chance,
+            },
+        };
+        Self {
+            stages: vec![
+                // Random programs average a few seconds; these need some foraging skill
+                stage(6.0, 1.5, 1.0, 0.0),
+                stage(9.0, 2.0, 0.75, 0.1),
+                stage(12.0, 3.0, 0.5, 0.25),
+            ],
+            hold_seconds: 20.0,
+        }
+    }
+}
+
+impl World {
+    /// Difficulty of the current curriculum stage, or the normal rules before the first
+    pub fn difficulty(&self) -> Difficulty {
+        match (&self.config.curriculum, self.curriculum_stage) {
+            (Some(curriculum), stage) if stage > 0 => curriculum
+                .stages
+                .get(stage - 1)
+                .map_or_else(Difficulty::default, |s| s.difficulty),
+            _ => Difficulty::default(),
+        }
+    }
+
+    /// Curriculum stages entered so far; 0 while the normal rules apply
+    pub fn curriculum_stage(&self) -> usize {
+        self.curriculum_stage
+    }
+
+    /// Mean age of the living population in simulated seconds
+    pub fn fitness(&self) -> f64 {
+        if self.lifeforms.is_empty() {
+            return 0.0;
+        }
+        let total_age: f64 = self.lifeforms.iter().map(|l| l.age as f64).sum();
+        total_age / self.lifeforms.len() as f64 * self.config.tick_seconds
+    }
+
+    /// Enter the next stage once fitness has held at its threshold long enough
+    pub(super) fn update_curriculum(&mut self) {
+        let Some(curriculum) = &self.config.curriculum else {
+            return;
+        };
+        let Some(next) = curriculum.stages.get(self.curriculum_stage) else {
+            return;
+        };
+        let fitness = self.fitness();
+        if fitness < next.fitness {
+            self.curriculum_held_since = None;
+            return;
+        }
+
+        let since = *self.curriculum_held_since.get_or_insert(self.sim_time);
+        if self.sim_time - since >= curriculum.hold_seconds {
+            self.curriculum_stage += 1;
+            self.curriculum_held_since = None;
+            tracing::info!(
+                "Fitness {:.1} s: entering curriculum stage {} ({:?})",
+                fitness,
+                self.curriculum_stage,
+                next.difficulty
+            );
+        }
+    }
+}
@@ -0,0 +1,70 @@
+// interventions.rs
+
+// God-mode actions applied to a running world by the user rather than by the simulation
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::{Food, Lifeform, World, audit, clamp_to_map_bounds};
+
+// Intervention constants
+pub const DROPPED_FOOD_ENERGY: f32 = 35.0; // Energy of food placed by hand, mid-range of spawned food
+
+/// Something the user did to the world; applying the same interventions at the same
+/// ticks of a seeded world reproduces the run
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Intervention {
+    /// Place food at a point
+    DropFood { x: f32, y: f32, energy: f32 },
+    /// Remove the lifeform at `index` in `World::lifeforms`
+    Kill { index: usize },
+    /// Add a lifeform with a random program at a point
+    Spawn { x: f32, y: f32 },
+}
+
+impl std::fmt::Display for Intervention {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Intervention::DropFood { x, y, energy } => {
+                write!(f, "drop {:.0} food at ({:.1}, {:.1})", energy, x, y)
+            }
+            Intervention::Kill { index } => write!(f, "kill lifeform {}", index),
+            Intervention::Spawn { x, y } => write!(f, "spawn lifeform at ({:.1}, {:.1})", x, y),
+        }
+    }
+}
+
+impl World {
+    /// Apply a user intervention, booking its energy like the natural event it imitates.
+    ///
+    /// Returns false if it no longer applies, e.g. the lifeform to kill is gone.
+    pub fn intervene(&mut self, intervention: &Intervention) -> bool {
+        match *intervention {
+            Intervention::DropFood { x, y, energy } => {
+                let food = Food::new(clamp_to_map_bounds(x), clamp_to_map_bounds(y), energy);
+                self.energy_flow.food_spawned += food.energy_value as f64;
+                self.food_items.push(food);
+            }
+            Intervention::Kill { index } => {
+                if index >= self.lifeforms.len() {
+                    return false;
+                }
+                // `remove` rather than `swap_remove` keeps later indices in order
+                let lifeform = self.lifeforms.remove(index);
+                self.energy_flow.deaths += audit::held_energy(&lifeform);
+            }
+            Intervention::Spawn { x, y } => {
+                let lifeform = Lifeform::new_with_rng(
+                    clamp_to_map_bounds(x),
+                    clamp_to_map_bounds(y),
+                    &mut self.rng,
+                );
+                self.energy_flow.lifeforms_spawned += lifeform.energy as f64;
+                self.lifeforms.push(lifeform);
+            }
+        }
+        tracing::info!("Tick {}: {}", self.tick_count, intervention);
+        true
+    }
+}
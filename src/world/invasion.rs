@@ -0,0 +1,153 @@
+// invasion.rs
+
+// Rare arrivals of outside genomes into a running world, for studying established populations
+
+use std::path::Path;
+
+use rand::Rng;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::{Lifeform, MAP_BOUNDARY, World, audit, clamp_to_map_bounds};
+#[cfg(feature = "serde")]
+use crate::bundle::PopulationBundle;
+use crate::compute::{MEM_SIZE, ProgramFile, VM};
+use crate::isa::Program;
+
+// Invasion constants
+pub const INVASION_SPREAD: f32 = 30.0; // Invaders land within this distance of the invasion site
+pub const MAX_RECORDED_INVASIONS: usize = 256; // Older invasions are dropped once this many are kept
+
+/// How often invasive species arrive and how many at a time
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Invasions {
+    /// Expected invasions per simulated second
+    pub rate: f64,
+    /// Lifeforms arriving together in one invasion
+    pub batch_size: usize,
+}
+
+impl Default for Invasions {
+    fn default() -> Self {
+        Self {
+            rate: 1.0 / 300.0,
+            batch_size: 5,
+        }
+    }
+}
+
+/// One invasion, kept in `World::invasion_log` until `MAX_RECORDED_INVASIONS` newer ones arrive
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct InvasionEvent {
+    pub tick: u64,
+    /// Where the invaders landed
+    pub x: f32,
+    pub y: f32,
+    pub count: usize,
+}
+
+/// Genomes for `World::invasion_pool` from a population bundle (`*.cbor`), a directory of
+/// `*.bin` genome files, or a single genome file such as `best_vm_program.bin`
+pub fn load_invaders(path: &Path) -> std::io::Result<Vec<Program>> {
+    if path.is_dir() {
+        let mut paths: Vec<_> = std::fs::read_dir(path)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| p.is_file() && p.extension().is_some_and(|ext| ext == "bin"))
+            .collect();
+        paths.sort();
+        return paths.iter().map(|p| read_genome(p)).collect();
+    }
+    if path.extension().is_some_and(|ext| ext == "cbor") {
+        return read_bundle_genomes(path);
+    }
+    Ok(vec![read_genome(path)?])
+}
+
+#[cfg(feature = "serde")]
+fn read_bundle_genomes(path: &Path) -> std::io::Result<Vec<Program>> {
+    let bundle = PopulationBundle::load(path).map_err(std::io::Error::other)?;
+    Ok(bundle
+        .creatures
+        .into_iter()
+        .map(|c| Program {
+            genome: c.genome,
+            isa: c.isa,
+        })
+        .collect())
+}
+
+#[cfg(not(feature = "serde"))]
+fn read_bundle_genomes(_path: &Path) -> std::io::Result<Vec<Program>> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "reading population bundles needs the serde feature",
+    ))
+}
+
+/// The executable part of a genome file, without any provenance trailer, and its ISA
+fn read_genome(path: &Path) -> std::io::Result<Program> {
+    let mut program = ProgramFile::load(path)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?
+        .program;
+    program.genome.truncate(MEM_SIZE);
+    Ok(program)
+}
+
+impl World {
+    /// Maybe land a batch of genomes from the invasion pool, `elapsed` seconds' worth of chance
+    pub(super) fn invade(&mut self, elapsed: f64) {
+        let Some(invasions) = self.config.invasions else {
+            return;
+        };
+        // No draws without a pool, so configured but idle invasions leave runs unchanged
+        if self.invasion_pool.is_empty()
+            || !self
+                .rng
+                .random_bool((invasions.rate * elapsed).clamp(0.0, 1.0))
+        {
+            return;
+        }
+
+        let site_x = self.rng.random_range(-MAP_BOUNDARY..MAP_BOUNDARY);
+        let site_y = self.rng.random_range(-MAP_BOUNDARY..MAP_BOUNDARY);
+        for _ in 0..invasions.batch_size {
+            let program = &self.invasion_pool[self.rng.random_range(0..self.invasion_pool.len())];
+            let mut vm = VM::new();
+            vm.isa = program.isa;
+            vm.load_program(&program.genome);
+            let x = site_x + self.rng.random_range(-INVASION_SPREAD..INVASION_SPREAD);
+            let y = site_y + self.rng.random_range(-INVASION_SPREAD..INVASION_SPREAD);
+            let mut lifeform = Lifeform::from_vm_with_rng(
+                vm,
+                clamp_to_map_bounds(x),
+                clamp_to_map_bounds(y),
+                &mut self.rng,
+            );
+            lifeform.sandboxed = true;
+            // Invaders bring their energy from outside, like immigrants
+            self.energy_flow.immigrated += audit::held_energy(&lifeform);
+            self.lifeforms.push(lifeform);
+        }
+
+        let event = InvasionEvent {
+            tick: self.tick_count,
+            x: site_x,
+            y: site_y,
+            count: invasions.batch_size,
+        };
+        tracing::info!(
+            "Tick {}: {} invaders landed at ({:.0}, {:.0})",
+            event.tick,
+            event.count,
+            event.x,
+            event.y
+        );
+        if self.invasion_log.len() == MAX_RECORDED_INVASIONS {
+            self.invasion_log.remove(0);
+        }
+        self.invasion_log.push(event);
+    }
+}
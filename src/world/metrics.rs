@@ -0,0 +1,132 @@
+// metrics.rs
+
+// The world's headline numbers, aggregated over the whole run in constant memory
+
+use std::fmt::Write;
+
+use super::World;
+use crate::stats::{Metric, RunningStats};
+
+/// Population, fitness, food, energy and births, recorded at every ecology update.
+///
+/// Each keeps lifetime aggregates, aggregates since the last `World::flush_metrics`
+/// and a reservoir sample, so a run of millions of ticks holds no growing series.
+#[derive(Debug, Clone)]
+pub struct WorldMetrics {
+    pub population: Metric,
+    /// Mean lifeform age in simulated seconds, as `World::fitness`
+    pub fitness: Metric,
+    pub food: Metric,
+    /// Energy held by lifeforms, food and nutrients, as `World::energy_stock`
+    pub energy: Metric,
+    /// Births since the previous ecology update
+    pub births: Metric,
+    last_births: u64,
+    last_flush_tick: u64,
+}
+
+impl WorldMetrics {
+    /// Reservoirs draw from generators derived from `seed`, never from the world's
+    pub fn new(seed: u64) -> Self {
+        let metric = |name, offset: u64| Metric::new(name, seed.rotate_left(16) ^ offset);
+        Self {
+            population: metric("population", 1),
+            fitness: metric("fitness", 2),
+            food: metric("food", 3),
+            energy: metric("energy", 4),
+            births: metric("births", 5),
+            last_births: 0,
+            last_flush_tick: 0,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Metric> {
+        [
+            &self.population,
+            &self.fitness,
+            &self.food,
+            &self.energy,
+            &self.births,
+        ]
+        .into_iter()
+    }
+
+    fn iter_mut(&mut self) -> impl Iterator<Item = &mut Metric> {
+        [
+            &mut self.population,
+            &mut self.fitness,
+            &mut self.food,
+            &mut self.energy,
+            &mut self.births,
+        ]
+        .into_iter()
+    }
+}
+
+/// Every metric's aggregates over the ticks since the previous flush
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricsFlush {
+    pub from_tick: u64,
+    pub to_tick: u64,
+    pub metrics: Vec<(&'static str, RunningStats)>,
+}
+
+impl MetricsFlush {
+    pub const CSV_HEADER: &str = "from_tick,to_tick,metric,count,mean,std_dev,min,max";
+
+    /// One CSV line per metric, each ending in a newline, matching `CSV_HEADER`
+    pub fn csv_rows(&self) -> String {
+        let mut rows = String::new();
+        for (name, stats) in &self.metrics {
+            let _ = writeln!(
+                rows,
+                "{},{},{},{},{},{},{},{}",
+                self.from_tick,
+                self.to_tick,
+                name,
+                stats.count(),
+                stats.mean(),
+                stats.std_dev(),
+                stats.min().map_or(String::new(), |v| v.to_string()),
+                stats.max().map_or(String::new(), |v| v.to_string())
+            );
+        }
+        rows
+    }
+}
+
+impl World {
+    /// Aggregates since the last flush for every metric, starting new windows;
+    /// exporters call this periodically to write them out
+    pub fn flush_metrics(&mut self) -> MetricsFlush {
+        let from_tick = self.metrics.last_flush_tick;
+        self.metrics.last_flush_tick = self.tick_count;
+        MetricsFlush {
+            from_tick,
+            to_tick: self.tick_count,
+            metrics: self
+                .metrics
+                .iter_mut()
+                .map(|metric| (metric.name, metric.flush()))
+                .collect(),
+        }
+    }
+
+    pub(super) fn record_metrics(&mut self) {
+        let tick = self.tick_count;
+        let births = self.reproduction_stats.births();
+        let new_births = births.saturating_sub(self.metrics.last_births);
+        self.metrics.last_births = births;
+
+        let population = self.lifeforms.len() as f64;
+        let fitness = self.fitness();
+        let food = self.food_items.len() as f64;
+        let energy = self.energy_stock();
+        let metrics = &mut self.metrics;
+        metrics.population.record(tick, population);
+        metrics.fitness.record(tick, fitness);
+        metrics.food.record(tick, food);
+        metrics.energy.record(tick, energy);
+        metrics.births.record(tick, new_births as f64);
+    }
+}
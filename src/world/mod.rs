@@ -0,0 +1,1195 @@
+// world.rs
+
+// Headless bacteria world: lifeforms controlled by VMs foraging for food
+
+use std::collections::{BTreeSet, VecDeque};
+
+use rand::Rng;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+mod audit;
+mod balance;
+mod chunks;
+mod curriculum;
+mod interventions;
+mod invasion;
+mod metrics;
+mod noise;
+mod nutrients;
+mod observation;
+mod reproduction;
+mod senses;
+mod skin;
+mod species;
+
+pub use audit::{AUDIT_TOLERANCE, EnergyAudit, EnergyFlow, EnergyLeak, MAX_RECORDED_LEAKS};
+pub use balance::{FoodController, FoodRateAdjustment, MAX_RECORDED_ADJUSTMENTS};
+pub use chunks::{ChunkCoord, Chunking, DEFAULT_CHUNK_SIZE};
+pub use curriculum::{Curriculum, CurriculumStage, Difficulty};
+pub use interventions::{DROPPED_FOOD_ENERGY, Intervention};
+pub use invasion::{
+    INVASION_SPREAD, InvasionEvent, Invasions, MAX_RECORDED_INVASIONS, load_invaders,
+};
+pub use metrics::{MetricsFlush, WorldMetrics};
+pub use noise::{NoiseKind, SensorNoise};
+pub use nutrients::{
+    FOOD_GROWTH_MAX_ENERGY, FOOD_GROWTH_RATE, FOOD_GROWTH_THRESHOLD, FoodModel, NUTRIENT_CELL_SIZE,
+    NUTRIENT_INITIAL_PER_CELL, NutrientField,
+};
+pub use observation::{
+    FieldSummary, FoodObservation, LifeformObservation, MAX_BUFFERED_OBSERVATIONS, WorldSnapshot,
+    species_id,
+};
+pub use reproduction::{
+    CONJUGATION_RADIUS, CONJUGATION_RATE, CONJUGATION_SEGMENT_MAX, DAUGHTER_LEN,
+    DAUGHTER_START_ADDR, MATING_RADIUS, OFFSPRING_SPAWN_DISTANCE, REPRODUCTION_ENERGY_THRESHOLD,
+    ReproductionMode, ReproductionStats,
+};
+pub use senses::Senses;
+pub use skin::{SKIN_ACCENT_ADDR, SKIN_PATTERN_ADDR, Skin};
+pub use species::{
+    DEFAULT_SPECIES_DISTANCE, DEFAULT_SPECIES_MIN_POPULATION, MAX_RECORDED_EXTINCTIONS,
+    SPECIES_HISTORY, Speciation, Species, SpeciesExtinction, genome_distance,
+};
+
+use crate::bundle::CreatureRecord;
+use crate::compute::{
+    CostTable, CycleTable, DATA_STACK_BASE, LoopDetector, MEM_SIZE, RestartMode, STACK_TOP,
+    TimerInterrupt, VM,
+};
+use crate::display::Display;
+use crate::isa::Program;
+use crate::keyboard::Keyboard;
+use crate::memory_map::{MemoryMap, RegionKind};
+use crate::mmio::{RateLimit, RateLimitState, WriteVerdict};
+use crate::rng::SimRng;
+use crate::sandbox::Sandbox;
+
+// Memory-mapped I/O addresses (using the last bytes of address space)
+pub const MOVE_LEFT_ADDR: usize = MEM_SIZE - 4; // 252: Left movement strength
+pub const MOVE_RIGHT_ADDR: usize = MEM_SIZE - 3; // 253: Right movement strength
+pub const MOVE_UP_ADDR: usize = MEM_SIZE - 2; // 254: Up movement strength
+pub const MOVE_DOWN_ADDR: usize = MEM_SIZE - 1; // 255: Down movement strength
+
+// Sensory input addresses (using addresses before movement commands)
+pub const FOOD_DISTANCE_X_ADDR: usize = MEM_SIZE - 6; // 250: X distance to nearest food (signed)
+pub const FOOD_DISTANCE_Y_ADDR: usize = MEM_SIZE - 5; // 251: Y distance to nearest food (signed)
+
+// Self-sensing addresses describing the lifeform's own computation
+pub const SELF_INSTRUCTIONS_ADDR: usize = MEM_SIZE - 8; // 248: Instructions executed last tick
+pub const STEP_BUDGET_ADDR: usize = MEM_SIZE - 7; // 249: Steps left in the current tick, this one included
+
+// Scratch memory conventionally used by programs for temporaries
+pub const SCRATCH_START_ADDR: usize = MEM_SIZE - 16; // 240: Start of the last grid row
+
+// CALL/RET return addresses grow down from STACK_TOP into the row above scratch, and
+// PUSH/POP values grow up from DATA_STACK_BASE at the start of that row
+pub const STACK_START_ADDR: usize = DATA_STACK_BASE as usize; // 224: Shown as the stack region
+
+// Simulation constants
+pub const INITIAL_POPULATION: usize = 20;
+pub const MAX_ENERGY: f32 = 200.0;
+pub const DEFAULT_TICK_SECONDS: f64 = 1.0 / 60.0; // Simulated time covered by one tick
+pub const ENERGY_DRAIN_PER_SECOND: f32 = 6.0;
+pub const MOVEMENT_ENERGY_COST_PER_SECOND: f32 = 12.0; // Per axis being moved along
+pub const MOVEMENT_SPEED: f32 = 60.0; // World units per second along each axis
+pub const EATING_RADIUS: f32 = 12.0;
+pub const TOXIN_DAMAGE: f32 = 40.0; // Energy lost by eating toxic food, which gives none
+pub const FOOD_SPAWN_INTERVAL: f64 = 2.0; // Simulated seconds
+pub const REPLENISH_INTERVAL: f64 = 5.0; // Simulated seconds between top-ups of a small population
+pub const MIN_FOOD_COUNT: usize = 10;
+pub const INITIAL_FOOD_COUNT: usize = 15;
+pub const FOOD_DISTRIBUTION_STD: f32 = 150.0;
+pub const MAP_BOUNDARY: f32 = 400.0;
+pub const TELEMETRY_HISTORY: usize = 512; // Ticks of per-lifeform history kept for sparklines
+
+// Sensory system constants
+pub const MAX_FOOD_DETECTION_RANGE: f32 = 100.0; // Maximum range for food detection
+pub const SENSORY_SCALE_FACTOR: f32 = 2.0; // Scale factor to convert world distance to memory value
+
+/// Memory layout shared by every lifeform's VM
+pub fn lifeform_memory_map() -> MemoryMap {
+    MemoryMap::new()
+        .with_region("code", RegionKind::Code, 0..SCRATCH_START_ADDR)
+        .with_region(
+            "stack",
+            RegionKind::Stack,
+            STACK_START_ADDR..STACK_TOP as usize,
+        )
+        .with_region(
+            "scratch",
+            RegionKind::Scratch,
+            SCRATCH_START_ADDR..SELF_INSTRUCTIONS_ADDR,
+        )
+        .with_region(
+            "self",
+            RegionKind::Sensors,
+            SELF_INSTRUCTIONS_ADDR..STEP_BUDGET_ADDR + 1,
+        )
+        .with_region(
+            "food",
+            RegionKind::Sensors,
+            FOOD_DISTANCE_X_ADDR..FOOD_DISTANCE_Y_ADDR + 1,
+        )
+        .with_region(
+            "movement",
+            RegionKind::Actuators,
+            MOVE_LEFT_ADDR..MOVE_DOWN_ADDR + 1,
+        )
+}
+
+// Tick budget throttling
+pub const DEFERRED_UPDATE_PERIOD: u64 = 4; // Deferred lifeforms update once every N ticks
+const BUDGET_RECOVERY_RATIO: f64 = 0.5; // Relax throttling once a tick takes under half the budget
+
+/// Tunable world rules that are not fixed simulation constants
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct WorldConfig {
+    /// How a lifeform's VM is restarted after its program halts
+    pub restart_mode: RestartMode,
+    /// VM instructions each lifeform executes per tick, or cycles under `cycle_timing`
+    pub steps_per_tick: usize,
+    /// Simulated seconds per tick; costs and spawn rates are per second, so changing
+    /// this trades temporal resolution without changing the ecological balance
+    pub tick_seconds: f64,
+    /// Wall-clock budget per tick in milliseconds; `None` disables throttling
+    pub tick_budget_ms: Option<f64>,
+    /// Approximate far-off-screen lifeforms at a reduced rate; `None` keeps runs exact
+    pub coarsening: Option<Coarsening>,
+    /// How lifeforms produce offspring
+    pub reproduction: ReproductionMode,
+    /// Where food comes from
+    pub food_model: FoodModel,
+    /// Seed for the world's random number generator; `None` seeds from the OS
+    pub seed: Option<u64>,
+    /// Subsystems that can be switched off for knockout experiments
+    pub subsystems: Subsystems,
+    /// Actuator addresses that only accept a write every few ticks
+    pub actuator_limits: Vec<RateLimit>,
+    /// Schedule making the world harder as fitness improves; `None` keeps the normal rules
+    pub curriculum: Option<Curriculum>,
+    /// Simulated seconds between snapshots buffered for `World::drain_observations`;
+    /// `None` records none
+    pub observation_interval: Option<f64>,
+    /// Rare arrivals of genomes from `World::invasion_pool`; `None` disables them
+    pub invasions: Option<Invasions>,
+    /// Timer interrupt given to every lifeform's VM; `None` leaves programs uninterrupted
+    pub timer_interrupt: Option<TimerInterrupt>,
+    /// Entry address of a second thread sharing every lifeform's VM, so programs can
+    /// run two loops at once; `None` runs one thread
+    pub coroutine: Option<u8>,
+    /// Run programs from a read-only code bank with a separate data bank, so genomes
+    /// cannot modify themselves
+    pub harvard: bool,
+    /// Updates between a program writing the movement actuators and the lifeform
+    /// moving; 0 moves in the same tick
+    pub actuator_latency: u32,
+    /// Noise and dropouts on the food sensors; `None` senses perfectly
+    pub sensor_noise: Option<SensorNoise>,
+    /// Energy each executed opcode costs a lifeform, on top of the steady drain;
+    /// `None` makes computation free
+    pub instruction_costs: Option<CostTable>,
+    /// Cycles each opcode takes; lifeforms then run `steps_per_tick` cycles per tick,
+    /// so slow instructions such as MUL and DIV cost time. `None` runs steps
+    pub cycle_timing: Option<CycleTable>,
+    /// Adjusts the food rate to hold the population near a target; `None` keeps it fixed
+    pub food_controller: Option<FoodController>,
+    /// Chunk-parallel ticking and streaming of far chunks to disk; `None` ticks
+    /// every lifeform in order
+    pub chunking: Option<Chunking>,
+    /// Fraction of genome bytes rewritten in each offspring; `None` rewrites a random 1-10%
+    pub mutation_rate: Option<f64>,
+    /// How lifeform VMs recognise programs stuck repeating a state; `None` lets them spin
+    pub loop_detector: Option<LoopDetector>,
+    /// 8x8 display rows that lifeform programs can draw on; `None` maps no display
+    pub display: Option<Display>,
+    /// Limits for lifeforms from imported, immigrant and invading genomes; `None`
+    /// trusts every genome
+    pub sandbox: Option<Sandbox>,
+    /// Grouping of lifeforms into species by genome similarity; `None` tracks none
+    pub speciation: Option<Speciation>,
+}
+
+/// Runtime switches for individual subsystems; everything else keeps running while one is off
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Subsystems {
+    /// New food from the spawner or nutrient growth
+    pub food_spawning: bool,
+    /// Offspring from the configured reproduction mode
+    pub reproduction: bool,
+    /// Random changes to offspring genomes
+    pub mutation: bool,
+}
+
+impl Default for Subsystems {
+    fn default() -> Self {
+        Self {
+            food_spawning: true,
+            reproduction: true,
+            mutation: true,
+        }
+    }
+}
+
+/// Reduced-frequency updates for lifeforms far outside the viewport.
+///
+/// Skipped ticks accumulate and are caught up in one batch: the VM runs a single
+/// tick's worth of steps, while movement, aging and energy drain are applied once
+/// per accumulated tick. This trades determinism for speed, so it is off by default.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Coarsening {
+    /// Distance beyond the viewport edge after which a lifeform counts as far away
+    pub margin: f32,
+    /// Far lifeforms are updated once every `period` ticks
+    pub period: u32,
+}
+
+impl Default for Coarsening {
+    fn default() -> Self {
+        Self {
+            margin: 200.0,
+            period: 8,
+        }
+    }
+}
+
+impl Default for WorldConfig {
+    fn default() -> Self {
+        Self {
+            restart_mode: RestartMode::default(),
+            steps_per_tick: 1,
+            tick_seconds: DEFAULT_TICK_SECONDS,
+            tick_budget_ms: None,
+            coarsening: None,
+            reproduction: ReproductionMode::default(),
+            food_model: FoodModel::default(),
+            seed: None,
+            subsystems: Subsystems::default(),
+            actuator_limits: Vec::new(),
+            curriculum: None,
+            observation_interval: None,
+            invasions: None,
+            timer_interrupt: None,
+            coroutine: None,
+            harvard: false,
+            actuator_latency: 0,
+            sensor_noise: None,
+            instruction_costs: None,
+            cycle_timing: None,
+            food_controller: None,
+            chunking: None,
+            mutation_rate: None,
+            loop_detector: Some(LoopDetector::default()),
+            display: None,
+            sandbox: Some(Sandbox::default()),
+            speciation: Some(Speciation::default()),
+        }
+    }
+}
+
+/// Axis-aligned rectangle in world coordinates, e.g. the area visible on screen
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    pub min_x: f32,
+    pub min_y: f32,
+    pub max_x: f32,
+    pub max_y: f32,
+}
+
+impl Viewport {
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.min_x && x <= self.max_x && y >= self.min_y && y <= self.max_y
+    }
+
+    /// The viewport grown by `margin` on every side
+    pub fn expanded(&self, margin: f32) -> Self {
+        Self {
+            min_x: self.min_x - margin,
+            min_y: self.min_y - margin,
+            max_x: self.max_x + margin,
+            max_y: self.max_y + margin,
+        }
+    }
+}
+
+/// How far the world currently scales back work to stay within its tick budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TickThrottle {
+    /// VM steps per lifeform actually run this tick (at most `steps_per_tick`)
+    pub steps: usize,
+    /// Whether lifeforms outside the viewport only update every `DEFERRED_UPDATE_PERIOD` ticks
+    pub defer_offscreen: bool,
+}
+
+/// What every lifeform's update reads during one tick, shared between chunks
+#[derive(Clone, Copy)]
+struct TickContext<'a> {
+    food: &'a [Food],
+    config: &'a WorldConfig,
+    difficulty: Difficulty,
+    steps: usize,
+    /// Lifeforms outside this area sit the tick out while throttled
+    skip_outside: Option<Viewport>,
+    /// Coarsening period and the area outside which it applies
+    coarse_area: Option<(u32, Viewport)>,
+}
+
+/// Food that provides energy to lifeforms
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Food {
+    pub x: f32,
+    pub y: f32,
+    pub energy_value: f32,
+    /// Toxic food costs `TOXIN_DAMAGE` instead of giving energy; it senses like any other
+    pub toxic: bool,
+}
+
+impl Food {
+    pub fn new(x: f32, y: f32, energy_value: f32) -> Self {
+        Self {
+            x,
+            y,
+            energy_value,
+            toxic: false,
+        }
+    }
+
+    /// Create food with random energy value in a reasonable range
+    pub fn new_random(x: f32, y: f32, rng: &mut impl Rng) -> Self {
+        let energy_value = rng.random_range(20.0..=50.0);
+        Self::new(x, y, energy_value)
+    }
+}
+
+/// Generate a normally distributed random number using Box-Muller transform
+/// This is more efficient than the previous version and avoids potential edge cases
+pub fn normal_random(mean: f32, std_dev: f32, rng: &mut impl Rng) -> f32 {
+    // Box-Muller transform - generate two independent uniform random numbers
+    let u1: f32 = rng.random_range(f32::EPSILON..1.0); // Avoid exactly 0.0
+    let u2: f32 = rng.random_range(0.0..1.0);
+
+    // Box-Muller transform
+    let z0 = (-2.0 * u1.ln()).sqrt() * (2.0 * std::f32::consts::PI * u2).cos();
+
+    z0 * std_dev + mean
+}
+
+/// Clamp coordinates to map boundaries
+pub fn clamp_to_map_bounds(coord: f32) -> f32 {
+    coord.clamp(-MAP_BOUNDARY, MAP_BOUNDARY)
+}
+
+/// Fixed-size history of a lifeform's recent state, oldest sample first
+#[derive(Debug, Clone)]
+pub struct Telemetry {
+    pub energy: VecDeque<f32>,
+    pub position: VecDeque<(f32, f32)>,
+    pub instructions: VecDeque<u16>,
+}
+
+impl Default for Telemetry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Telemetry {
+    pub fn new() -> Self {
+        Self {
+            energy: VecDeque::with_capacity(TELEMETRY_HISTORY),
+            position: VecDeque::with_capacity(TELEMETRY_HISTORY),
+            instructions: VecDeque::with_capacity(TELEMETRY_HISTORY),
+        }
+    }
+
+    /// Append one tick of samples, dropping the oldest once the history is full
+    pub fn record(&mut self, energy: f32, position: (f32, f32), instructions: u16) {
+        if self.energy.len() == TELEMETRY_HISTORY {
+            self.energy.pop_front();
+            self.position.pop_front();
+            self.instructions.pop_front();
+        }
+        self.energy.push_back(energy);
+        self.position.push_back(position);
+        self.instructions.push_back(instructions);
+    }
+}
+
+/// User annotation that follows an individual lifeform through saves and migration
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Tag {
+    pub name: String,
+    /// Flag color as RGB components in 0.0..=1.0
+    pub color: [f32; 3],
+    pub note: String,
+}
+
+/// A simulated bacteria/lifeform controlled by a VM
+#[derive(Debug, Clone)]
+pub struct Lifeform {
+    pub vm: VM,
+    pub x: f32,
+    pub y: f32,
+    /// Display color as RGB components in 0.0..=1.0
+    pub color: [f32; 3],
+    pub energy: f32,
+    pub age: u32,
+    pub telemetry: Telemetry,
+    /// Ticks skipped by coarsening that still have to be caught up
+    pub pending_ticks: u32,
+    /// Energy spent since the world last collected it as waste
+    pub waste: f32,
+    /// Set by the user to follow this individual; offspring start untagged
+    pub tag: Option<Tag>,
+    /// Cooldowns for `WorldConfig::actuator_limits`
+    pub rate_limits: RateLimitState,
+    /// Source of `WorldConfig::sensor_noise`, separate from the VM's RND sequence
+    sensor_rng: SimRng,
+    /// Movement commands written but not yet carried out, oldest first
+    actuator_queue: VecDeque<[u8; 4]>,
+    /// Input from the user's keyboard while they steer this lifeform; offspring start without
+    pub keyboard: Option<Keyboard>,
+    /// Came from a genome shared from outside, or descends from one; held to
+    /// `WorldConfig::sandbox`
+    pub sandboxed: bool,
+    /// `Species::id` assigned at the first ecology update after birth
+    pub species: Option<u32>,
+}
+
+impl Lifeform {
+    /// A lifeform with a random program, drawing all randomness from `rng`
+    pub fn new_with_rng(x: f32, y: f32, rng: &mut impl Rng) -> Self {
+        let mut vm = VM::new();
+        vm.randomize(rng);
+        Self::from_vm_with_rng(vm, x, y, rng)
+    }
+
+    /// Wrap `vm` in a new lifeform, seeding its RND generator from `rng`
+    pub fn from_vm_with_rng(mut vm: VM, x: f32, y: f32, rng: &mut impl Rng) -> Self {
+        let seed = rng.random();
+        vm.seed_rng(seed);
+        Self {
+            vm,
+            x,
+            y,
+            color: Self::random_color(rng),
+            energy: 100.0,
+            age: 0,
+            telemetry: Telemetry::new(),
+            pending_ticks: 0,
+            waste: 0.0,
+            tag: None,
+            rate_limits: RateLimitState::default(),
+            // Derived from the VM seed so sensing noise draws nothing from `rng`
+            sensor_rng: SimRng::new(seed.rotate_left(32)),
+            actuator_queue: VecDeque::new(),
+            keyboard: None,
+            sandboxed: false,
+            species: None,
+        }
+    }
+
+    fn random_color(rng: &mut impl Rng) -> [f32; 3] {
+        [
+            rng.random_range(0.0..1.0),
+            rng.random_range(0.0..1.0),
+            rng.random_range(0.0..1.0),
+        ]
+    }
+
+    /// One world tick for this lifeform, honoring throttling and coarsening
+    fn tick(&mut self, context: TickContext<'_>) {
+        if context
+            .skip_outside
+            .is_some_and(|v| !v.contains(self.x, self.y))
+        {
+            return;
+        }
+        let TickContext {
+            food,
+            config,
+            difficulty,
+            steps,
+            ..
+        } = context;
+        if let Some((period, area)) = context.coarse_area
+            && !area.contains(self.x, self.y)
+        {
+            self.pending_ticks += 1;
+            if self.pending_ticks >= period {
+                let ticks = std::mem::take(&mut self.pending_ticks);
+                self.update_batched(food, config, difficulty, steps, ticks);
+            }
+            return;
+        }
+        if self.pending_ticks > 0 {
+            // Back near the camera: settle the backlog before resuming exact updates
+            let ticks = std::mem::take(&mut self.pending_ticks);
+            self.update_batched(food, config, difficulty, steps, ticks);
+        }
+        self.update(food, config, difficulty, steps);
+    }
+
+    /// Update the lifeform - run VM step and process movement commands
+    pub fn update(
+        &mut self,
+        food_items: &[Food],
+        config: &WorldConfig,
+        difficulty: Difficulty,
+        steps: usize,
+    ) {
+        self.update_batched(food_items, config, difficulty, steps, 1);
+    }
+
+    /// Advance `ticks` ticks at once, sensing and running the VM only once
+    pub fn update_batched(
+        &mut self,
+        food_items: &[Food],
+        config: &WorldConfig,
+        difficulty: Difficulty,
+        steps: usize,
+        ticks: u32,
+    ) {
+        // Restart before sensing so a cold restart cannot wipe fresh sensor values
+        self.restart_vm_if_halted(config.restart_mode);
+        self.vm.interrupt = config.timer_interrupt;
+        self.vm.set_coroutine(config.coroutine.map(usize::from));
+        self.vm.set_harvard(config.harvard);
+        self.vm.loop_detector = config.loop_detector;
+        let detection_range = MAX_FOOD_DETECTION_RANGE * difficulty.detection_range;
+        self.update_sensory_input(food_items, detection_range, config.sensor_noise);
+        if let Some(keyboard) = &mut self.keyboard {
+            keyboard.apply(&mut self.vm);
+        }
+        if let Some(costs) = &config.instruction_costs
+            && self.vm.costs != *costs
+        {
+            self.vm.costs = costs.clone();
+        }
+        let sandbox = config.sandbox.as_ref().filter(|_| self.sandboxed);
+        match sandbox {
+            Some(sandbox) => sandbox.apply(&mut self.vm),
+            None if self.sandboxed => Sandbox::release(&mut self.vm),
+            None => {}
+        }
+        let steps = sandbox.map_or(steps, |sandbox| sandbox.steps(steps));
+        if self.vm.cycle_table != config.cycle_timing {
+            self.vm.cycle_table = config.cycle_timing.clone();
+        }
+        let steps_before = self.vm.total_steps_count;
+        let consumed_before = self.vm.consumed_energy;
+        if let Some(senses) = self.senses() {
+            senses.start_tick(steps);
+        }
+        // Steps run one by one either way, so actuator limits see every store
+        let timed = config.cycle_timing.is_some();
+        if timed {
+            self.vm.grant_cycles(steps as u64);
+        }
+        for ran in 0.. {
+            let done = if timed {
+                !self.vm.has_cycle_credit()
+            } else {
+                ran == steps
+            };
+            if done {
+                break;
+            }
+            self.vm.step();
+            if let WriteVerdict::Charged(cost) =
+                self.rate_limits
+                    .check(&config.actuator_limits, &mut self.vm, self.age)
+            {
+                self.spend_energy(cost);
+            }
+        }
+        self.spend_energy(self.vm.consumed_energy - consumed_before);
+        let movement = self.delayed_movement(config.actuator_latency);
+        let dt = config.tick_seconds as f32;
+        for _ in 0..ticks {
+            self.process_movement_commands(movement, dt);
+            self.age_and_consume_energy(dt);
+        }
+
+        // Loop detection may zero the step counter, so saturate instead of underflowing
+        let executed = self.vm.total_steps_count.saturating_sub(steps_before);
+        self.telemetry.record(
+            self.energy,
+            (self.x, self.y),
+            executed.min(u16::MAX as usize) as u16,
+        );
+    }
+
+    /// Update sensory input by finding the nearest food, for the sensors to store before the next step
+    fn update_sensory_input(
+        &mut self,
+        food_items: &[Food],
+        detection_range: f32,
+        noise: Option<SensorNoise>,
+    ) {
+        // Report how much the program computed during the previous tick
+        let last_tick_instructions = self.telemetry.instructions.back().copied().unwrap_or(0);
+
+        let mut nearest = self.find_nearest_food_distance(food_items, detection_range);
+        if let Some(noise) = &noise
+            && self.sensor_dropped(noise)
+        {
+            nearest = None;
+        }
+        let (mut memory_x, mut memory_y) = match nearest {
+            // Convert world coordinates to memory values (scaled and clamped to u8 range)
+            Some((distance_x, distance_y)) => (
+                self.distance_to_memory_value(distance_x),
+                self.distance_to_memory_value(distance_y),
+            ),
+            // No food detected within range - write neutral (middle) values
+            None => (128, 128),
+        };
+        if let Some(noise) = &noise {
+            memory_x = self.perturb_sensor(memory_x, noise);
+            memory_y = self.perturb_sensor(memory_y, noise);
+        }
+        if let Some(senses) = self.senses() {
+            senses.sense(
+                last_tick_instructions.min(u8::MAX as u16) as u8,
+                memory_x,
+                memory_y,
+            );
+        }
+    }
+
+    /// The sensors mapped into this lifeform's VM; `None` if another handler holds their cells
+    fn senses(&mut self) -> Option<&mut Senses> {
+        Senses::attach(&mut self.vm)
+            .inspect_err(|e| tracing::warn!("Cannot map sensors: {}", e))
+            .ok()
+    }
+
+    /// Find the nearest food within detection range and return relative distance
+    fn find_nearest_food_distance(
+        &self,
+        food_items: &[Food],
+        detection_range: f32,
+    ) -> Option<(f32, f32)> {
+        let mut nearest_distance_squared = detection_range * detection_range;
+        let mut nearest_food_pos: Option<(f32, f32)> = None;
+
+        for food in food_items {
+            let dx = food.x - self.x;
+            let dy = food.y - self.y;
+            let distance_squared = dx * dx + dy * dy;
+
+            if distance_squared < nearest_distance_squared {
+                nearest_distance_squared = distance_squared;
+                nearest_food_pos = Some((dx, dy));
+            }
+        }
+
+        nearest_food_pos
+    }
+
+    /// Convert a world distance to a memory value (0-255)
+    /// Positive distances map to 128-255, negative to 0-127, with 128 being neutral
+    fn distance_to_memory_value(&self, distance: f32) -> u8 {
+        let scaled_distance = distance * SENSORY_SCALE_FACTOR;
+        let clamped = scaled_distance.clamp(-128.0, 127.0);
+        (clamped + 128.0) as u8
+    }
+
+    fn restart_vm_if_halted(&mut self, mode: RestartMode) {
+        if self.vm.halted {
+            self.vm.restart(mode);
+        }
+    }
+
+    fn age_and_consume_energy(&mut self, dt: f32) {
+        self.age += 1;
+        self.spend_energy(ENERGY_DRAIN_PER_SECOND * dt);
+    }
+
+    /// Drain energy, remembering how much actually existed to be spent
+    fn spend_energy(&mut self, cost: f32) {
+        self.waste += cost.min(self.energy.max(0.0));
+        self.energy -= cost;
+    }
+
+    /// Movement command to carry out now: the actuators as the program wrote them
+    /// `latency` updates ago, or nothing while the first commands are still on the way
+    fn delayed_movement(&mut self, latency: u32) -> [u8; 4] {
+        let current = [
+            self.vm.data()[MOVE_LEFT_ADDR],
+            self.vm.data()[MOVE_RIGHT_ADDR],
+            self.vm.data()[MOVE_UP_ADDR],
+            self.vm.data()[MOVE_DOWN_ADDR],
+        ];
+        if latency == 0 {
+            self.actuator_queue.clear();
+            return current;
+        }
+        self.actuator_queue.push_back(current);
+        let mut due = [0; 4];
+        while self.actuator_queue.len() > latency as usize {
+            due = self.actuator_queue.pop_front().unwrap_or_default();
+        }
+        due
+    }
+
+    fn process_movement_commands(&mut self, movement_values: [u8; 4], dt: f32) {
+        // Compare values to determine movement direction
+        let step = MOVEMENT_SPEED * dt;
+
+        // Horizontal movement: move in direction of larger value
+        if movement_values[0] > movement_values[1] {
+            self.move_and_consume_energy(-step, 0.0, dt);
+        } else if movement_values[1] > movement_values[0] {
+            self.move_and_consume_energy(step, 0.0, dt);
+        }
+
+        // Vertical movement: move in direction of larger value
+        if movement_values[2] > movement_values[3] {
+            self.move_and_consume_energy(0.0, -step, dt);
+        } else if movement_values[3] > movement_values[2] {
+            self.move_and_consume_energy(0.0, step, dt);
+        }
+    }
+
+    fn move_and_consume_energy(&mut self, dx: f32, dy: f32, dt: f32) {
+        self.x += dx;
+        self.y += dy;
+        self.spend_energy(MOVEMENT_ENERGY_COST_PER_SECOND * dt);
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.energy > 0.0 // Only check energy, not VM halt status
+    }
+
+    /// Check if this lifeform collides with food (within eating distance)
+    pub fn can_eat_food(&self, food: &Food) -> bool {
+        let distance_squared = (self.x - food.x).powi(2) + (self.y - food.y).powi(2);
+        distance_squared <= EATING_RADIUS * EATING_RADIUS
+    }
+
+    /// Consume food, returning the energy actually gained
+    pub fn eat_food(&mut self, food: &Food) -> f32 {
+        if food.toxic {
+            self.spend_energy(TOXIN_DAMAGE);
+            return 0.0;
+        }
+        let energy_before = self.energy;
+        self.energy = (self.energy + food.energy_value).min(MAX_ENERGY);
+        self.energy - energy_before
+    }
+}
+
+/// The whole simulated ecosystem, independent of any rendering
+#[derive(Debug)]
+pub struct World {
+    pub config: WorldConfig,
+    pub lifeforms: Vec<Lifeform>,
+    pub food_items: Vec<Food>,
+    pub generation: u32,
+    /// Every random draw of the world; `rng.seed()` reproduces the run
+    pub rng: SimRng,
+    /// Area currently being watched; lifeforms outside it may be deferred when throttled
+    pub viewport: Option<Viewport>,
+    pub reproduction_stats: ReproductionStats,
+    /// Nutrients available for food growth under `FoodModel::NutrientCycling`
+    pub nutrients: NutrientField,
+    /// Energy that entered or left the world so far, by cause
+    pub energy_flow: EnergyFlow,
+    /// Leak detection, enabled with `enable_audit`
+    pub audit: Option<EnergyAudit>,
+    /// Genomes invasions are drawn from, e.g. loaded with `load_invaders`
+    pub invasion_pool: Vec<Program>,
+    /// Recent invasions, oldest first
+    pub invasion_log: Vec<InvasionEvent>,
+    /// Recent food rate changes by the controller, oldest first
+    pub food_rate_log: Vec<FoodRateAdjustment>,
+    /// Recorded species extinctions, oldest first
+    pub extinction_log: Vec<SpeciesExtinction>,
+    /// Population, fitness, food, energy and births over the run, in constant memory
+    pub metrics: WorldMetrics,
+    /// Living species, oldest first
+    species: Vec<Species>,
+    next_species_id: u32,
+    /// Chunks frozen on disk by streaming
+    stored_chunks: BTreeSet<ChunkCoord>,
+    throttle: TickThrottle,
+    tick_count: u64,
+    /// Simulated seconds elapsed, advanced by `tick_seconds` every tick
+    sim_time: f64,
+    last_ecology_time: f64,
+    /// Tick the ecology last ran for; it runs at most once per tick
+    last_ecology_tick: Option<u64>,
+    curriculum_stage: usize,
+    /// When fitness last reached the next curriculum stage's threshold
+    curriculum_held_since: Option<f64>,
+    food_rate: f64,
+    last_balance_time: f64,
+    observations: VecDeque<WorldSnapshot>,
+    last_observation_time: Option<f64>,
+    last_spawn_time: f64,
+    last_food_spawn_time: f64,
+    map_center_x: f32,
+    map_center_y: f32,
+}
+
+impl World {
+    /// Create a world with the initial population and food
+    pub fn new(config: WorldConfig) -> Self {
+        let throttle = TickThrottle {
+            steps: config.steps_per_tick,
+            defer_offscreen: false,
+        };
+        let rng = config.seed.map_or_else(SimRng::from_entropy, SimRng::new);
+        let metrics = WorldMetrics::new(rng.seed());
+        let mut world = Self {
+            config,
+            viewport: None,
+            reproduction_stats: ReproductionStats::default(),
+            nutrients: NutrientField::new(),
+            energy_flow: EnergyFlow::default(),
+            audit: None,
+            invasion_pool: Vec::new(),
+            invasion_log: Vec::new(),
+            food_rate_log: Vec::new(),
+            extinction_log: Vec::new(),
+            metrics,
+            species: Vec::new(),
+            next_species_id: 0,
+            stored_chunks: BTreeSet::new(),
+            throttle,
+            tick_count: 0,
+            lifeforms: Vec::new(),
+            food_items: Vec::new(),
+            generation: 0,
+            rng,
+            sim_time: 0.0,
+            last_ecology_time: 0.0,
+            last_ecology_tick: None,
+            curriculum_stage: 0,
+            curriculum_held_since: None,
+            food_rate: 1.0,
+            last_balance_time: 0.0,
+            observations: VecDeque::new(),
+            last_observation_time: None,
+            last_spawn_time: 0.0,
+            last_food_spawn_time: 0.0,
+            map_center_x: 0.0,
+            map_center_y: 0.0,
+        };
+
+        // Spawn initial population
+        for _ in 0..INITIAL_POPULATION {
+            let x = world.rng.random_range(-200.0..200.0);
+            let y = world.rng.random_range(-200.0..200.0);
+            let lifeform = Lifeform::new_with_rng(x, y, &mut world.rng);
+            world.lifeforms.push(lifeform);
+        }
+
+        // Spawn initial food to ensure minimum count
+        for _ in 0..INITIAL_FOOD_COUNT {
+            world.spawn_food();
+        }
+
+        world
+    }
+
+    /// Indices of the `count` oldest lifeforms, oldest first
+    pub fn leaderboard(&self, count: usize) -> Vec<usize> {
+        let mut indices: Vec<usize> = (0..self.lifeforms.len()).collect();
+        indices.sort_by_key(|&i| std::cmp::Reverse(self.lifeforms[i].age));
+        indices.truncate(count);
+        indices
+    }
+
+    /// Remove up to `count` random lifeforms, returning them as bundle records
+    pub fn take_emigrants(&mut self, count: usize) -> Vec<CreatureRecord> {
+        let mut emigrants = Vec::new();
+        for _ in 0..count.min(self.lifeforms.len()) {
+            let idx = self.rng.random_range(0..self.lifeforms.len());
+            let lifeform = self.lifeforms.swap_remove(idx);
+            self.energy_flow.emigrated += audit::held_energy(&lifeform);
+            emigrants.push(CreatureRecord::from_lifeform(&lifeform));
+        }
+        emigrants
+    }
+
+    /// Add creatures arriving from another world, keeping them inside the map
+    pub fn admit_immigrants(&mut self, immigrants: &[CreatureRecord]) {
+        for record in immigrants {
+            let mut lifeform = record.to_lifeform(&mut self.rng);
+            lifeform.sandboxed = true;
+            // Species ids are only meaningful in the world that assigned them
+            lifeform.species = None;
+            lifeform.x = clamp_to_map_bounds(lifeform.x);
+            lifeform.y = clamp_to_map_bounds(lifeform.y);
+            self.energy_flow.immigrated += audit::held_energy(&lifeform);
+            self.lifeforms.push(lifeform);
+        }
+    }
+
+    /// Run one VM step for every lifeform
+    pub fn tick(&mut self) {
+        let started = std::time::Instant::now();
+        let steps = if self.config.tick_budget_ms.is_some() {
+            self.throttle.steps
+        } else {
+            self.config.steps_per_tick
+        };
+        let deferring = self.config.tick_budget_ms.is_some() && self.throttle.defer_offscreen;
+        let deferred_turn = self.tick_count.is_multiple_of(DEFERRED_UPDATE_PERIOD);
+
+        let context = TickContext {
+            food: &self.food_items,
+            config: &self.config,
+            difficulty: self.difficulty(),
+            steps,
+            skip_outside: self.viewport.filter(|_| deferring && !deferred_turn),
+            coarse_area: self
+                .config
+                .coarsening
+                .zip(self.viewport)
+                .map(|(c, v)| (c.period.max(1), v.expanded(c.margin))),
+        };
+        match &self.config.chunking {
+            Some(chunking) => chunks::tick_chunks(&mut self.lifeforms, chunking, context),
+            None => self.lifeforms.iter_mut().for_each(|l| l.tick(context)),
+        }
+
+        self.tick_count += 1;
+        self.sim_time += self.config.tick_seconds;
+        if let Some(budget_ms) = self.config.tick_budget_ms {
+            let elapsed_ms = started.elapsed().as_secs_f64() * 1000.0;
+            self.adapt_throttle(elapsed_ms, budget_ms);
+        }
+        self.audit_energy();
+        self.record_metrics();
+    }
+
+    /// Simulated seconds since the world was created
+    pub fn sim_time(&self) -> f64 {
+        self.sim_time
+    }
+
+    /// Ticks run since the world was created
+    pub fn tick_count(&self) -> u64 {
+        self.tick_count
+    }
+
+    /// Whether `update_ecology` already ran since the last tick
+    pub fn ecology_done(&self) -> bool {
+        self.last_ecology_tick == Some(self.tick_count)
+    }
+
+    /// Current throttling state (only meaningful when a tick budget is set)
+    pub fn throttle(&self) -> TickThrottle {
+        self.throttle
+    }
+
+    /// Shed work when a tick overran its budget and restore it once there is headroom.
+    ///
+    /// Steps per lifeform are reduced first; off-screen deferral is the last resort
+    /// and the first thing undone when the world catches up.
+    fn adapt_throttle(&mut self, elapsed_ms: f64, budget_ms: f64) {
+        let max_steps = self.config.steps_per_tick.max(1);
+        let before = self.throttle;
+        if elapsed_ms > budget_ms {
+            if self.throttle.steps > 1 {
+                self.throttle.steps = (self.throttle.steps / 2).max(1);
+            } else {
+                self.throttle.defer_offscreen = true;
+            }
+        } else if elapsed_ms < budget_ms * BUDGET_RECOVERY_RATIO {
+            if self.throttle.defer_offscreen {
+                self.throttle.defer_offscreen = false;
+            } else if self.throttle.steps < max_steps {
+                self.throttle.steps += 1;
+            }
+        }
+        self.throttle.steps = self.throttle.steps.clamp(1, max_steps);
+
+        if self.throttle != before {
+            tracing::debug!(
+                "Tick took {:.2} ms (budget {:.2} ms), throttle now {:?}",
+                elapsed_ms,
+                budget_ms,
+                self.throttle
+            );
+        }
+    }
+
+    /// Spawn food, resolve eating, remove the dead and replenish the population.
+    ///
+    /// Spawning is paced by simulated time, so nothing new appears while no ticks run.
+    /// Runs at most once per tick; further calls before the next tick do nothing, so
+    /// a frontend may call it every frame without affecting the outcome of the run.
+    pub fn update_ecology(&mut self) {
+        if self.ecology_done() {
+            return;
+        }
+        self.last_ecology_tick = Some(self.tick_count);
+        let elapsed = self.sim_time - self.last_ecology_time;
+        self.last_ecology_time = self.sim_time;
+
+        self.collect_waste();
+        if self.config.subsystems.food_spawning {
+            match self.config.food_model {
+                FoodModel::GaussianSpawner => self.update_food_spawning(),
+                FoodModel::NutrientCycling => self.grow_food(elapsed),
+            }
+        }
+        self.resolve_eating();
+        if self.config.subsystems.reproduction {
+            self.reproduce(elapsed);
+        }
+        self.remove_dead();
+        self.replenish_population();
+        self.invade(elapsed);
+        self.stream_chunks();
+        self.update_species();
+        self.update_curriculum();
+        self.balance_food();
+        self.record_observation();
+        self.audit_energy();
+    }
+
+    fn spawn_food(&mut self) {
+        let food_x = clamp_to_map_bounds(normal_random(
+            self.map_center_x,
+            FOOD_DISTRIBUTION_STD,
+            &mut self.rng,
+        ));
+        let food_y = clamp_to_map_bounds(normal_random(
+            self.map_center_y,
+            FOOD_DISTRIBUTION_STD,
+            &mut self.rng,
+        ));
+        let mut food = Food::new_random(food_x, food_y, &mut self.rng);
+        food.toxic = self.roll_toxic();
+        self.energy_flow.food_spawned += food.energy_value as f64;
+        self.food_items.push(food);
+    }
+
+    /// Whether new food should be toxic under the current difficulty
+    pub(super) fn roll_toxic(&mut self) -> bool {
+        // Only draw when toxins are possible, so runs without them keep their random sequence
+        let chance = self.difficulty().toxin_chance;
+        chance > 0.0 && self.rng.random_bool(chance.min(1.0))
+    }
+
+    /// Food spawning (ensure minimum food count and spawn periodically using normal distribution)
+    fn update_food_spawning(&mut self) {
+        let now = self.sim_time;
+        let scarcity = self.food_scarcity();
+        let interval = FOOD_SPAWN_INTERVAL * scarcity;
+        let min_food_count = ((MIN_FOOD_COUNT as f64 / scarcity).round() as usize).max(1);
+        // Check if we need to spawn food (either time-based or to maintain minimum count)
+        let should_spawn_food = (now - self.last_food_spawn_time >= interval)
+            || (self.food_items.len() < min_food_count);
+
+        if should_spawn_food {
+            // Calculate how many food items to spawn
+            let food_count = if self.food_items.len() < min_food_count {
+                // Spawn enough to reach minimum count, plus 1-3 extra
+                (min_food_count - self.food_items.len()) + self.rng.random_range(1..=3)
+            } else {
+                // Regular spawning: 1-3 food items
+                self.rng.random_range(1..=3)
+            };
+
+            for _ in 0..food_count {
+                self.spawn_food();
+            }
+            self.last_food_spawn_time = now;
+        }
+    }
+
+    /// Food consumption (check collisions between lifeforms and food)
+    fn resolve_eating(&mut self) {
+        for lifeform in &mut self.lifeforms {
+            let mut eaten_food_indices = Vec::new();
+
+            for (i, food) in self.food_items.iter().enumerate() {
+                if lifeform.can_eat_food(food) {
+                    let gained = lifeform.eat_food(food);
+                    eaten_food_indices.push(i);
+
+                    // Energy above MAX_ENERGY, or in toxic food, is not lost when nutrients cycle
+                    let excess = food.energy_value - gained;
+                    match self.config.food_model {
+                        FoodModel::GaussianSpawner => self.energy_flow.overflow += excess as f64,
+                        FoodModel::NutrientCycling => {
+                            self.nutrients.deposit(food.x, food.y, excess)
+                        }
+                    }
+                }
+            }
+
+            // Remove eaten food (in reverse order to maintain indices)
+            for &i in eaten_food_indices.iter().rev() {
+                self.food_items.remove(i);
+            }
+        }
+    }
+
+    fn remove_dead(&mut self) {
+        let alive_count = self.lifeforms.len();
+        let flow = &mut self.energy_flow;
+        self.lifeforms.retain(|l| {
+            if !l.is_alive() {
+                flow.deaths += audit::held_energy(l);
+            }
+            l.is_alive()
+        });
+        let died_count = alive_count - self.lifeforms.len();
+
+        if died_count > 0 {
+            tracing::info!(
+                "Generation {}: {} lifeforms died",
+                self.generation,
+                died_count
+            );
+        }
+    }
+
+    /// Spawn new lifeforms periodically or when population is low
+    fn replenish_population(&mut self) {
+        let now = self.sim_time;
+        if (now - self.last_spawn_time > REPLENISH_INTERVAL && self.lifeforms.len() < 10)
+            || self.lifeforms.is_empty()
+        {
+            if self.lifeforms.is_empty() {
+                self.generation += 1;
+                tracing::info!("Starting generation {}", self.generation);
+            }
+
+            // Spawn new random lifeforms
+            for _ in 0..5 {
+                let x = self.rng.random_range(-MAP_BOUNDARY..MAP_BOUNDARY);
+                let y = self.rng.random_range(-MAP_BOUNDARY..MAP_BOUNDARY);
+                let mut lifeform = Lifeform::new_with_rng(x, y, &mut self.rng);
+                // A closed nutrient budget has to pay for newcomers too
+                match self.config.food_model {
+                    FoodModel::GaussianSpawner => {
+                        self.energy_flow.lifeforms_spawned += lifeform.energy as f64
+                    }
+                    FoodModel::NutrientCycling => {
+                        lifeform.energy = self.nutrients.withdraw(lifeform.energy)
+                    }
+                }
+                self.lifeforms.push(lifeform);
+            }
+
+            self.last_spawn_time = now;
+        }
+    }
+}
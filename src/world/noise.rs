@@ -0,0 +1,60 @@
+// noise.rs
+
+// Imperfect sensing: noise and dropouts applied to sensor values before the VM sees them
+
+use rand::Rng;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::{Lifeform, normal_random};
+
+/// Shape of the noise added to a sensor reading
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum NoiseKind {
+    /// `amount` is the standard deviation
+    #[default]
+    Gaussian,
+    /// Spread evenly over `-amount..=amount`
+    Uniform,
+}
+
+/// Noise and unreliability of the food sensors, in raw sensor units (0-255)
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct SensorNoise {
+    pub kind: NoiseKind,
+    pub amount: f32,
+    /// Chance per tick that the sensors report nothing, as when no food is in range
+    pub dropout: f64,
+}
+
+impl Default for SensorNoise {
+    fn default() -> Self {
+        Self {
+            kind: NoiseKind::Gaussian,
+            amount: 4.0,
+            dropout: 0.05,
+        }
+    }
+}
+
+impl Lifeform {
+    /// Whether this tick's sensor reading is lost
+    pub(super) fn sensor_dropped(&mut self, noise: &SensorNoise) -> bool {
+        noise.dropout > 0.0 && self.sensor_rng.random_bool(noise.dropout.min(1.0))
+    }
+
+    /// `value` as a noisy sensor would report it
+    pub(super) fn perturb_sensor(&mut self, value: u8, noise: &SensorNoise) -> u8 {
+        if noise.amount <= 0.0 {
+            return value;
+        }
+        let offset = match noise.kind {
+            NoiseKind::Gaussian => normal_random(0.0, noise.amount, &mut self.sensor_rng),
+            NoiseKind::Uniform => self.sensor_rng.random_range(-noise.amount..=noise.amount),
+        };
+        (value as f32 + offset).round().clamp(0.0, 255.0) as u8
+    }
+}
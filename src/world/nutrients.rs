@@ -0,0 +1,131 @@
+// nutrients.rs
+
+// Closed nutrient cycle: spent energy becomes waste that regrows food where it lands
+
+use rand::Rng;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::{Food, MAP_BOUNDARY, World};
+
+// Nutrient field constants
+pub const NUTRIENT_CELL_SIZE: f32 = 50.0; // World units covered by one field cell
+pub const NUTRIENT_INITIAL_PER_CELL: f32 = 10.0; // Starting reservoir in every cell
+pub const FOOD_GROWTH_THRESHOLD: f32 = 30.0; // Nutrients a cell needs before it can sprout food
+pub const FOOD_GROWTH_RATE: f64 = 0.12; // Sprouts per simulated second for an eligible cell
+pub const FOOD_GROWTH_MAX_ENERGY: f32 = 50.0; // Largest food item a cell grows at once
+
+/// Where food comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum FoodModel {
+    /// Food appears from nowhere around the map center on a timer
+    #[default]
+    GaussianSpawner,
+    /// Food only grows from nutrients that lifeforms excrete, so energy is conserved
+    NutrientCycling,
+}
+
+/// Grid of nutrient amounts covering the map
+#[derive(Debug, Clone)]
+pub struct NutrientField {
+    pub cells_per_side: usize,
+    pub cells: Vec<f32>,
+}
+
+impl Default for NutrientField {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NutrientField {
+    pub fn new() -> Self {
+        let cells_per_side = (MAP_BOUNDARY * 2.0 / NUTRIENT_CELL_SIZE).ceil() as usize;
+        Self {
+            cells_per_side,
+            cells: vec![NUTRIENT_INITIAL_PER_CELL; cells_per_side * cells_per_side],
+        }
+    }
+
+    /// Index of the cell containing a world position, clamping to the map edge
+    pub fn cell_index(&self, x: f32, y: f32) -> usize {
+        let to_cell = |v: f32| {
+            (((v + MAP_BOUNDARY) / NUTRIENT_CELL_SIZE) as usize).min(self.cells_per_side - 1)
+        };
+        to_cell(y) * self.cells_per_side + to_cell(x)
+    }
+
+    /// World coordinates of a cell's top-left corner
+    pub fn cell_origin(&self, index: usize) -> (f32, f32) {
+        let col = index % self.cells_per_side;
+        let row = index / self.cells_per_side;
+        (
+            col as f32 * NUTRIENT_CELL_SIZE - MAP_BOUNDARY,
+            row as f32 * NUTRIENT_CELL_SIZE - MAP_BOUNDARY,
+        )
+    }
+
+    pub fn deposit(&mut self, x: f32, y: f32, amount: f32) {
+        let index = self.cell_index(x, y);
+        self.cells[index] += amount;
+    }
+
+    /// Take up to `amount` from the richest cells, returning how much was available
+    pub fn withdraw(&mut self, amount: f32) -> f32 {
+        let mut remaining = amount;
+        while remaining > 0.0 {
+            let Some(richest) = self
+                .cells
+                .iter_mut()
+                .filter(|c| **c > 0.0)
+                .max_by(|a, b| a.total_cmp(b))
+            else {
+                break;
+            };
+            let taken = richest.min(remaining);
+            *richest -= taken;
+            remaining -= taken;
+        }
+        amount - remaining
+    }
+
+    pub fn total(&self) -> f32 {
+        self.cells.iter().sum()
+    }
+}
+
+impl World {
+    /// Move energy lifeforms spent since the last update into the field beneath them
+    pub(super) fn collect_waste(&mut self) {
+        let cycling = self.config.food_model == FoodModel::NutrientCycling;
+        for lifeform in &mut self.lifeforms {
+            let waste = std::mem::take(&mut lifeform.waste);
+            if cycling {
+                self.nutrients.deposit(lifeform.x, lifeform.y, waste);
+            } else {
+                self.energy_flow.metabolism += waste as f64;
+            }
+        }
+    }
+
+    /// Let nutrient-rich cells sprout food inside their own area, `elapsed` seconds' worth
+    pub(super) fn grow_food(&mut self, elapsed: f64) {
+        let chance = (FOOD_GROWTH_RATE * elapsed / self.food_scarcity()).min(1.0);
+        for index in 0..self.nutrients.cells.len() {
+            let available = self.nutrients.cells[index];
+            if available < FOOD_GROWTH_THRESHOLD || !self.rng.random_bool(chance) {
+                continue;
+            }
+            let energy = available.min(FOOD_GROWTH_MAX_ENERGY);
+            self.nutrients.cells[index] -= energy;
+
+            let (origin_x, origin_y) = self.nutrients.cell_origin(index);
+            let x = origin_x + self.rng.random_range(0.0..NUTRIENT_CELL_SIZE);
+            let y = origin_y + self.rng.random_range(0.0..NUTRIENT_CELL_SIZE);
+            let mut food = Food::new(x, y, energy);
+            food.toxic = self.roll_toxic();
+            self.food_items.push(food);
+        }
+    }
+}
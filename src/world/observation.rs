@@ -0,0 +1,126 @@
+// observation.rs
+
+// Compact world snapshots for external analysis and ML consumers
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::World;
+use crate::provenance::Fnv1a;
+
+// Observation constants
+pub const MAX_BUFFERED_OBSERVATIONS: usize = 256; // Oldest snapshots are dropped past this
+
+/// Identifier shared by every lifeform carrying the same genome
+pub fn species_id(genome: &[u8]) -> u64 {
+    let mut hash = Fnv1a::new();
+    hash.write(genome);
+    hash.finish()
+}
+
+/// One lifeform as seen by an observer
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LifeformObservation {
+    pub x: f32,
+    pub y: f32,
+    pub energy: f32,
+    pub age: u32,
+    /// `species_id` of the genome the lifeform was born with
+    pub species: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FoodObservation {
+    pub x: f32,
+    pub y: f32,
+    pub energy: f32,
+    pub toxic: bool,
+}
+
+/// Aggregate view of the nutrient field instead of every cell
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FieldSummary {
+    pub total: f32,
+    pub max: f32,
+    /// Cells holding any nutrients at all
+    pub occupied_cells: usize,
+}
+
+/// The observable state of a world at one tick
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct WorldSnapshot {
+    pub tick: u64,
+    pub sim_time: f64,
+    pub generation: u32,
+    pub curriculum_stage: usize,
+    pub lifeforms: Vec<LifeformObservation>,
+    pub food: Vec<FoodObservation>,
+    pub nutrients: FieldSummary,
+}
+
+impl World {
+    /// Snapshot of the world as it is now
+    pub fn observe(&self) -> WorldSnapshot {
+        let cells = &self.nutrients.cells;
+        WorldSnapshot {
+            tick: self.tick_count,
+            sim_time: self.sim_time,
+            generation: self.generation,
+            curriculum_stage: self.curriculum_stage,
+            lifeforms: self
+                .lifeforms
+                .iter()
+                .map(|l| LifeformObservation {
+                    x: l.x,
+                    y: l.y,
+                    energy: l.energy,
+                    age: l.age,
+                    species: species_id(&l.vm.initial_state),
+                })
+                .collect(),
+            food: self
+                .food_items
+                .iter()
+                .map(|f| FoodObservation {
+                    x: f.x,
+                    y: f.y,
+                    energy: f.energy_value,
+                    toxic: f.toxic,
+                })
+                .collect(),
+            nutrients: FieldSummary {
+                total: self.nutrients.total(),
+                max: cells.iter().copied().fold(0.0, f32::max),
+                occupied_cells: cells.iter().filter(|&&c| c > 0.0).count(),
+            },
+        }
+    }
+
+    /// Take the snapshots recorded every `WorldConfig::observation_interval`, oldest first
+    pub fn drain_observations(&mut self) -> Vec<WorldSnapshot> {
+        self.observations.drain(..).collect()
+    }
+
+    /// Record a snapshot if the observation interval has passed since the last one
+    pub(super) fn record_observation(&mut self) {
+        let Some(interval) = self.config.observation_interval else {
+            return;
+        };
+        if self
+            .last_observation_time
+            .is_some_and(|last| self.sim_time - last < interval)
+        {
+            return;
+        }
+        self.last_observation_time = Some(self.sim_time);
+        if self.observations.len() == MAX_BUFFERED_OBSERVATIONS {
+            self.observations.pop_front();
+        }
+        let snapshot = self.observe();
+        self.observations.push_back(snapshot);
+    }
+}
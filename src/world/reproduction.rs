@@ -0,0 +1,257 @@
+// reproduction.rs
+
+// Fission, conjugation, mating and self-copying, selectable per world for comparing strategies
+
+use rand::Rng;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::{Lifeform, World, clamp_to_map_bounds};
+use crate::compute::{MEM_SIZE, VM, mutation};
+
+// Reproduction constants
+pub const REPRODUCTION_ENERGY_THRESHOLD: f32 = 150.0; // Energy needed to reproduce
+pub const OFFSPRING_SPAWN_DISTANCE: f32 = 10.0; // How far from a parent offspring appear
+pub const CONJUGATION_RADIUS: f32 = 16.0; // Max distance for gene transfer
+pub const CONJUGATION_RATE: f64 = 0.6; // Transfers per simulated second for a nearby pair
+pub const CONJUGATION_SEGMENT_MAX: usize = 32; // Longest genome segment transferred at once
+pub const MATING_RADIUS: f32 = 16.0; // Max distance between mates
+pub const DAUGHTER_START_ADDR: usize = 112; // Where self-copying programs write their daughter
+pub const DAUGHTER_LEN: usize = 104; // Bytes of the daughter region, ending below the skin bytes
+
+/// How lifeforms produce offspring
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ReproductionMode {
+    /// No reproduction; the population is only replenished with random lifeforms
+    #[default]
+    Off,
+    /// Asexual splitting into a mutated copy once energy is high enough
+    Fission,
+    /// Fission plus horizontal transfer of genome segments between neighbours
+    FissionConjugation,
+    /// Offspring require two nearby, well-fed parents whose genomes are crossed over
+    Mating,
+    /// Programs copy themselves into the daughter region with COPY; a well-fed
+    /// lifeform whose daughter region holds anything releases it as a child genome
+    SelfCopy,
+}
+
+impl std::fmt::Display for ReproductionMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ReproductionMode::Off => "off",
+            ReproductionMode::Fission => "fission",
+            ReproductionMode::FissionConjugation => "fission+conjugation",
+            ReproductionMode::Mating => "mating",
+            ReproductionMode::SelfCopy => "self-copy",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Reproductive events counted since the world was created
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ReproductionStats {
+    pub fissions: u64,
+    pub conjugations: u64,
+    pub matings: u64,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub replications: u64,
+}
+
+impl ReproductionStats {
+    pub fn births(&self) -> u64 {
+        self.fissions + self.matings + self.replications
+    }
+}
+
+/// A child carrying `genome`, placed near `(x, y)`.
+///
+/// Unless `mutate` is off, `mutation_rate` of its bytes are rewritten, or a random
+/// 1-10% without a rate.
+fn offspring(
+    genome: &[u8; MEM_SIZE],
+    x: f32,
+    y: f32,
+    energy: f32,
+    mutate: bool,
+    mutation_rate: Option<f64>,
+    rng: &mut impl Rng,
+) -> Lifeform {
+    let mut vm = VM::new();
+    vm.load_program(genome);
+    match mutation_rate {
+        _ if !mutate => {}
+        Some(rate) => vm.mutate(mutation::byte_count(MEM_SIZE, rate), rng),
+        None => vm.partial_randomize(rng),
+    }
+
+    let angle = rng.random_range(0.0..std::f32::consts::TAU);
+    let child_x = clamp_to_map_bounds(x + angle.cos() * OFFSPRING_SPAWN_DISTANCE);
+    let child_y = clamp_to_map_bounds(y + angle.sin() * OFFSPRING_SPAWN_DISTANCE);
+    let mut child = Lifeform::from_vm_with_rng(vm, child_x, child_y, rng);
+    child.energy = energy;
+    child
+}
+
+fn within(a: &Lifeform, b: &Lifeform, radius: f32) -> bool {
+    (a.x - b.x).powi(2) + (a.y - b.y).powi(2) <= radius * radius
+}
+
+impl World {
+    /// Run the configured reproduction mode covering `elapsed` simulated seconds
+    pub(super) fn reproduce(&mut self, elapsed: f64) {
+        match self.config.reproduction {
+            ReproductionMode::Off => {}
+            ReproductionMode::Fission => self.fission(),
+            ReproductionMode::FissionConjugation => {
+                self.fission();
+                self.conjugation(elapsed);
+            }
+            ReproductionMode::Mating => self.mating(),
+            ReproductionMode::SelfCopy => self.self_copy(),
+        }
+    }
+
+    /// Split every lifeform above the energy threshold into two halves
+    fn fission(&mut self) {
+        let mut children = Vec::new();
+        for parent in &mut self.lifeforms {
+            if parent.energy < REPRODUCTION_ENERGY_THRESHOLD {
+                continue;
+            }
+            parent.energy /= 2.0;
+            let mut child = offspring(
+                &parent.vm.initial_state,
+                parent.x,
+                parent.y,
+                parent.energy,
+                self.config.subsystems.mutation,
+                self.config.mutation_rate,
+                &mut self.rng,
+            );
+            child.vm.isa = parent.vm.isa;
+            child.sandboxed = parent.sandboxed;
+            children.push(child);
+        }
+        self.reproduction_stats.fissions += children.len() as u64;
+        self.lifeforms.extend(children);
+    }
+
+    /// Copy a random genome segment from a donor into a nearby recipient
+    fn conjugation(&mut self, elapsed: f64) {
+        let chance = (CONJUGATION_RATE * elapsed).min(1.0);
+        let count = self.lifeforms.len();
+        for donor in 0..count {
+            for recipient in 0..count {
+                if donor == recipient
+                    || !within(
+                        &self.lifeforms[donor],
+                        &self.lifeforms[recipient],
+                        CONJUGATION_RADIUS,
+                    )
+                    || !self.rng.random_bool(chance)
+                {
+                    continue;
+                }
+
+                let len = self.rng.random_range(1..=CONJUGATION_SEGMENT_MAX);
+                let start = self.rng.random_range(0..=MEM_SIZE - len);
+                let segment = self.lifeforms[donor].vm.initial_state[start..start + len].to_vec();
+                // Foreign code makes the recipient foreign too
+                self.lifeforms[recipient].sandboxed |= self.lifeforms[donor].sandboxed;
+                let vm = &mut self.lifeforms[recipient].vm;
+                vm.initial_state[start..start + len].copy_from_slice(&segment);
+                vm.memory[start..start + len].copy_from_slice(&segment);
+                self.reproduction_stats.conjugations += 1;
+            }
+        }
+    }
+
+    /// Pair up nearby well-fed lifeforms and give each pair one crossed-over child
+    fn mating(&mut self) {
+        let count = self.lifeforms.len();
+        let mut mated = vec![false; count];
+        let mut children = Vec::new();
+        for a in 0..count {
+            if mated[a] || self.lifeforms[a].energy < REPRODUCTION_ENERGY_THRESHOLD {
+                continue;
+            }
+            let partner = (a + 1..count).find(|&b| {
+                !mated[b]
+                    && self.lifeforms[b].energy >= REPRODUCTION_ENERGY_THRESHOLD
+                    && within(&self.lifeforms[a], &self.lifeforms[b], MATING_RADIUS)
+            });
+            let Some(b) = partner else {
+                continue;
+            };
+            mated[a] = true;
+            mated[b] = true;
+
+            // Single-point crossover of the two parents' genomes
+            let cut = self.rng.random_range(1..MEM_SIZE);
+            let mut genome = self.lifeforms[a].vm.initial_state;
+            genome[cut..].copy_from_slice(&self.lifeforms[b].vm.initial_state[cut..]);
+
+            // Each parent gives a quarter of its energy to the child
+            let gift_a = self.lifeforms[a].energy / 4.0;
+            let gift_b = self.lifeforms[b].energy / 4.0;
+            self.lifeforms[a].energy -= gift_a;
+            self.lifeforms[b].energy -= gift_b;
+
+            let (x, y) = (self.lifeforms[a].x, self.lifeforms[a].y);
+            let mutate = self.config.subsystems.mutation;
+            let mut child = offspring(
+                &genome,
+                x,
+                y,
+                gift_a + gift_b,
+                mutate,
+                self.config.mutation_rate,
+                &mut self.rng,
+            );
+            // The child decodes the crossed genome like the parent that gave its first half
+            child.vm.isa = self.lifeforms[a].vm.isa;
+            child.sandboxed = self.lifeforms[a].sandboxed || self.lifeforms[b].sandboxed;
+            children.push(child);
+        }
+        self.reproduction_stats.matings += children.len() as u64;
+        self.lifeforms.extend(children);
+    }
+
+    /// Release the daughter region of every well-fed lifeform that wrote one.
+    ///
+    /// The child's genome is the daughter region moved to address 0, padded with
+    /// zeros; the region is cleared so the parent has to copy itself again.
+    fn self_copy(&mut self) {
+        let daughter = DAUGHTER_START_ADDR..DAUGHTER_START_ADDR + DAUGHTER_LEN;
+        let mut children = Vec::new();
+        for parent in &mut self.lifeforms {
+            if parent.energy < REPRODUCTION_ENERGY_THRESHOLD
+                || parent.vm.data()[daughter.clone()].iter().all(|&b| b == 0)
+            {
+                continue;
+            }
+            let mut genome = [0; MEM_SIZE];
+            genome[..DAUGHTER_LEN].copy_from_slice(&parent.vm.data()[daughter.clone()]);
+            parent.vm.data_mut()[daughter.clone()].fill(0);
+            parent.energy /= 2.0;
+            let mut child = offspring(
+                &genome,
+                parent.x,
+                parent.y,
+                parent.energy,
+                self.config.subsystems.mutation,
+                self.config.mutation_rate,
+                &mut self.rng,
+            );
+            child.vm.isa = parent.vm.isa;
+            child.sandboxed = parent.sandboxed;
+            children.push(child);
+        }
+        self.reproduction_stats.replications += children.len() as u64;
+        self.lifeforms.extend(children);
+    }
+}
@@ -0,0 +1,59 @@
+// senses.rs
+
+// A lifeform's sensor cells, mapped into its VM so each step sees the readings meant for it
+
+use std::ops::RangeInclusive;
+
+use super::{FOOD_DISTANCE_Y_ADDR, SELF_INSTRUCTIONS_ADDR};
+use crate::compute::{AddressWidth, VM};
+use crate::mmio::{MmioError, MmioHandler};
+
+/// Memory-mapped handler over `SELF_INSTRUCTIONS_ADDR..=FOOD_DISTANCE_Y_ADDR`.
+///
+/// Readings set with `sense` land before the next step; the step budget cell is
+/// rewritten before every step, counting down from the budget set with `start_tick`.
+#[derive(Debug, Clone, Default)]
+pub struct Senses {
+    /// Instructions executed last tick, food X and food Y, not yet stored
+    pending: Option<[u8; 3]>,
+    /// Steps left in the tick, the coming one included
+    budget: usize,
+}
+
+impl Senses {
+    pub const RANGE: RangeInclusive<usize> = SELF_INSTRUCTIONS_ADDR..=FOOD_DISTANCE_Y_ADDR;
+
+    /// The sensors mapped into `vm`, mapping fresh ones on first use
+    pub fn attach<const N: usize, A: AddressWidth>(
+        vm: &mut VM<N, A>,
+    ) -> Result<&mut Senses, MmioError> {
+        if vm.io_handler_mut::<Senses>().is_none() {
+            vm.map_io(Self::RANGE, Senses::default())?;
+        }
+        Ok(vm
+            .io_handler_mut::<Senses>()
+            .expect("sensors were just mapped"))
+    }
+
+    /// Readings the program sees from its next step on
+    pub fn sense(&mut self, last_tick_instructions: u8, food_x: u8, food_y: u8) {
+        self.pending = Some([last_tick_instructions, food_x, food_y]);
+    }
+
+    /// Start a tick of `steps` steps, so the budget cell reads `steps` down to 1
+    pub fn start_tick(&mut self, steps: usize) {
+        self.budget = steps;
+    }
+}
+
+impl MmioHandler for Senses {
+    fn before_step(&mut self, cells: &mut [u8]) {
+        if let Some([instructions, food_x, food_y]) = self.pending.take() {
+            cells[0] = instructions;
+            cells[2] = food_x;
+            cells[3] = food_y;
+        }
+        cells[1] = self.budget.min(u8::MAX as usize) as u8;
+        self.budget = self.budget.saturating_sub(1);
+    }
+}
@@ -0,0 +1,55 @@
+// skin.rs
+
+// Appearance read from designated genome bytes, so related lineages look alike
+
+use super::{Lifeform, STACK_START_ADDR};
+
+// Skin constants
+pub const SKIN_PATTERN_ADDR: usize = STACK_START_ADDR - 3; // 221-222: Bits of the 3x3 pattern
+pub const SKIN_ACCENT_ADDR: usize = STACK_START_ADDR - 1; // 223: Hue of the accent color
+
+/// A 3x3 pixel pattern and the color it is drawn in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Skin {
+    /// Bit `row * 3 + col` is set for each lit pixel
+    pub pattern: u16,
+    /// RGB components in 0.0..=1.0
+    pub accent: [f32; 3],
+}
+
+impl Skin {
+    /// Skin encoded in `genome`; bytes missing from a short genome read as 0
+    pub fn from_genome(genome: &[u8]) -> Self {
+        let byte = |addr: usize| genome.get(addr).copied().unwrap_or(0);
+        let pattern = u16::from_le_bytes([byte(SKIN_PATTERN_ADDR), byte(SKIN_PATTERN_ADDR + 1)]);
+        Self {
+            pattern: pattern & 0x1FF,
+            accent: hue_to_rgb(byte(SKIN_ACCENT_ADDR) as f32 / 256.0),
+        }
+    }
+
+    pub fn is_lit(&self, row: usize, col: usize) -> bool {
+        self.pattern & (1 << (row * 3 + col)) != 0
+    }
+}
+
+impl Lifeform {
+    /// Skin of the genome the lifeform was born with
+    pub fn skin(&self) -> Skin {
+        Skin::from_genome(&self.vm.initial_state)
+    }
+}
+
+/// Fully saturated, full-brightness color at `hue` turns around the color wheel
+fn hue_to_rgb(hue: f32) -> [f32; 3] {
+    let h = hue.fract() * 6.0;
+    let x = 1.0 - (h % 2.0 - 1.0).abs();
+    match h as u32 {
+        0 => [1.0, x, 0.0],
+        1 => [x, 1.0, 0.0],
+        2 => [0.0, 1.0, x],
+        3 => [0.0, x, 1.0],
+        4 => [x, 0.0, 1.0],
+        _ => [1.0, 0.0, x],
+    }
+}
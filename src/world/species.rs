@@ -0,0 +1,213 @@
+// species.rs
+
+// Species: lifeforms grouped by genome similarity, followed from founding to extinction
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::World;
+use crate::compute::MEM_SIZE;
+
+// Species constants
+pub const DEFAULT_SPECIES_DISTANCE: f64 = 0.15; // Share of differing genome bytes that founds a new species
+pub const DEFAULT_SPECIES_MIN_POPULATION: usize = 2; // Species smaller than this die out unrecorded
+pub const SPECIES_HISTORY: usize = 120; // Population samples kept per species, one per ecology update
+pub const MAX_RECORDED_EXTINCTIONS: usize = 256; // Older extinctions are dropped once this many are kept
+
+/// How lifeforms are grouped into species.
+///
+/// A newborn joins the living species whose founder genome is closest to its own,
+/// unless even that one differs in more than `distance` of the bytes; then it
+/// founds a species of its own. Membership is settled at birth, so genes picked up
+/// later through conjugation do not move a lifeform to another species.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Speciation {
+    /// Share of genome bytes, 0.0 to 1.0, that may differ from a species' founder
+    pub distance: f64,
+    /// Peak population a species must reach for its extinction to be recorded;
+    /// lone random spawns dying out are not worth reporting
+    pub min_population: usize,
+}
+
+impl Default for Speciation {
+    fn default() -> Self {
+        Self {
+            distance: DEFAULT_SPECIES_DISTANCE,
+            min_population: DEFAULT_SPECIES_MIN_POPULATION,
+        }
+    }
+}
+
+/// A species with at least one living member
+#[derive(Debug, Clone, PartialEq)]
+pub struct Species {
+    pub id: u32,
+    /// Genome of the first member, which newborns are compared against
+    pub founder: [u8; MEM_SIZE],
+    pub founding_generation: u32,
+    pub founding_tick: u64,
+    /// Living members as of the last ecology update
+    pub population: usize,
+    pub peak_population: usize,
+    /// Mean age of the members in simulated seconds, like `World::fitness`
+    pub mean_fitness: f64,
+    /// Population at each of the last `SPECIES_HISTORY` ecology updates, oldest first
+    pub history: VecDeque<usize>,
+}
+
+/// A species that lost its last member, kept in `World::extinction_log`
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SpeciesExtinction {
+    pub species: u32,
+    pub founding_generation: u32,
+    pub founding_tick: u64,
+    /// Tick the last member was found gone
+    pub tick: u64,
+    pub peak_population: usize,
+}
+
+impl fmt::Display for SpeciesExtinction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Species #{} went extinct at tick {} after {} ticks (founded in generation {}, peak population {})",
+            self.species,
+            self.tick,
+            self.tick - self.founding_tick,
+            self.founding_generation,
+            self.peak_population
+        )
+    }
+}
+
+/// Share of bytes at which two genomes differ, from 0.0 for identical genomes to 1.0
+pub fn genome_distance(a: &[u8], b: &[u8]) -> f64 {
+    let len = a.len().max(b.len());
+    if len == 0 {
+        return 0.0;
+    }
+    let shared = a.len().min(b.len());
+    let differing = a.iter().zip(b).filter(|(x, y)| x != y).count() + (len - shared);
+    differing as f64 / len as f64
+}
+
+impl World {
+    /// Living species, oldest first
+    pub fn species(&self) -> &[Species] {
+        &self.species
+    }
+
+    /// Forget every species, e.g. after the population was replaced wholesale;
+    /// the lifeforms are classified afresh at the next ecology update
+    pub fn reset_species(&mut self) {
+        self.species.clear();
+        for lifeform in &mut self.lifeforms {
+            lifeform.species = None;
+        }
+    }
+
+    /// Classify newborns, refresh every species' statistics and record extinctions.
+    ///
+    /// Members frozen in streamed-out chunks do not count as living.
+    pub(super) fn update_species(&mut self) {
+        let Some(speciation) = self.config.speciation else {
+            return;
+        };
+
+        // Lifeforms carrying the id of a species that is gone, such as ones thawed
+        // from a chunk, are classified again like newborns
+        let mut living: HashSet<u32> = self.species.iter().map(|s| s.id).collect();
+        for i in 0..self.lifeforms.len() {
+            if !self.lifeforms[i]
+                .species
+                .is_some_and(|id| living.contains(&id))
+            {
+                let id = self.classify(i, speciation.distance);
+                living.insert(id);
+                self.lifeforms[i].species = Some(id);
+            }
+        }
+
+        // Members and their total age, by species
+        let mut members: HashMap<u32, (usize, f64)> = HashMap::new();
+        for lifeform in &self.lifeforms {
+            if let Some(id) = lifeform.species {
+                let entry = members.entry(id).or_default();
+                entry.0 += 1;
+                entry.1 += lifeform.age as f64;
+            }
+        }
+        let tick_seconds = self.config.tick_seconds;
+        for species in &mut self.species {
+            let (population, total_age) = members.get(&species.id).copied().unwrap_or_default();
+            species.population = population;
+            species.peak_population = species.peak_population.max(population);
+            species.mean_fitness = if population == 0 {
+                0.0
+            } else {
+                total_age / population as f64 * tick_seconds
+            };
+            if species.history.len() == SPECIES_HISTORY {
+                species.history.pop_front();
+            }
+            species.history.push_back(population);
+        }
+
+        let tick = self.tick_count;
+        let log = &mut self.extinction_log;
+        self.species.retain(|species| {
+            if species.population > 0 {
+                return true;
+            }
+            if species.peak_population >= speciation.min_population {
+                let extinction = SpeciesExtinction {
+                    species: species.id,
+                    founding_generation: species.founding_generation,
+                    founding_tick: species.founding_tick,
+                    tick,
+                    peak_population: species.peak_population,
+                };
+                tracing::info!("{}", extinction);
+                if log.len() == MAX_RECORDED_EXTINCTIONS {
+                    log.remove(0);
+                }
+                log.push(extinction);
+            }
+            false
+        });
+    }
+
+    /// The species lifeform `index` belongs to, founding a new one if none is close enough
+    fn classify(&mut self, index: usize, distance: f64) -> u32 {
+        let genome = &self.lifeforms[index].vm.initial_state;
+        let closest = self
+            .species
+            .iter()
+            .map(|s| (s.id, genome_distance(genome, &s.founder)))
+            .filter(|&(_, d)| d <= distance)
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((id, _)) = closest {
+            return id;
+        }
+
+        let id = self.next_species_id;
+        self.next_species_id += 1;
+        self.species.push(Species {
+            id,
+            founder: *genome,
+            founding_generation: self.generation,
+            founding_tick: self.tick_count,
+            population: 0,
+            peak_population: 0,
+            mean_fitness: 0.0,
+            history: VecDeque::new(),
+        });
+        id
+    }
+}